@@ -27,7 +27,8 @@ use crate::{
 use communication::{
     actor::{
         CommunicationActor, CommunicationActorConfig, CommunicationRequest, CommunicationResults,
-        EstablishedConnection, FirewallPermission, FirewallRule, KeepAlive, RequestDirection, VariantPermission,
+        EstablishedConnection, FirewallPermission, FirewallRule, KeepAlive, RequestDirection, RequestMsgBuilder,
+        VariantPermission,
     },
     behaviour::BehaviourConfig,
     libp2p::{Keypair, Multiaddr, PeerId},
@@ -630,11 +631,7 @@ impl Stronghold {
 
         let local_keys = Keypair::generate_ed25519();
         let behaviour_config = BehaviourConfig::default();
-        let actor_config = CommunicationActorConfig {
-            client,
-            firewall_default_in: FirewallPermission::all(),
-            firewall_default_out: FirewallPermission::all(),
-        };
+        let actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
 
         let communication_actor = self
             .system
@@ -697,7 +694,9 @@ impl Stronghold {
             })
             .await
         {
-            Ok(CommunicationResults::EstablishConnectionResult(Ok(peer_id))) => ResultMessage::Ok(peer_id),
+            Ok(CommunicationResults::EstablishConnectionResult(Ok(connection))) => {
+                ResultMessage::Ok(connection.peer_id)
+            }
             Ok(CommunicationResults::EstablishConnectionResult(Err(err))) => {
                 ResultMessage::Error(format!("Error connecting peer: {:?}", err))
             }
@@ -936,7 +935,7 @@ impl Stronghold {
     // was returned from the communication actor,
     async fn ask_remote(&self, peer_id: PeerId, request: SHRequest) -> Result<SHResults, String> {
         match self
-            .ask_communication_actor(CommunicationRequest::RequestMsg { peer_id, request })
+            .ask_communication_actor(RequestMsgBuilder::new(peer_id, request).build())
             .await
         {
             Ok(CommunicationResults::RequestMsgResult(Ok(ok))) => Ok(ok),
@@ -18,7 +18,7 @@ use async_std::task;
 use communication::{
     actor::{
         CommunicationActor, CommunicationActorConfig, CommunicationRequest, CommunicationResults, FirewallPermission,
-        KeepAlive, PermissionValue, RequestPermissions, VariantPermission,
+        KeepAlive, PermissionValue, RequestMsgBuilder, RequestPermissions, VariantPermission,
     },
     behaviour::BehaviourConfig,
     libp2p::{Keypair, PeerId},
@@ -83,11 +83,7 @@ fn init_system(sys: &ActorSystem) -> Result<(PeerId, ActorRef<CommunicationReque
         .map_err(|e| format!("Failed to create client actor: {:?}", e))?;
 
     // Configure the firewall to allow all requests
-    let actor_config = CommunicationActorConfig {
-        client,
-        firewall_default_in: FirewallPermission::all(),
-        firewall_default_out: FirewallPermission::all(),
-    };
+    let actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
 
     // Spawn communication actor
     let communication_actor = sys
@@ -121,8 +117,8 @@ fn main() -> Result<(), String> {
         .await
         {
             CommunicationResults::<Answer>::StartListeningResult(Ok(addr)) => Ok(addr),
-            CommunicationResults::<Answer>::StartListeningResult(Err(())) => {
-                Err("Failed to start listening".to_string())
+            CommunicationResults::<Answer>::StartListeningResult(Err(err)) => {
+                Err(format!("Failed to start listening: {:?}", err))
             }
             _ => unreachable!("StartListening always returns StartListeningResult."),
         }?;
@@ -151,10 +147,7 @@ fn main() -> Result<(), String> {
         let answer = match ask(
             &sys_a,
             &communication_actor_a,
-            CommunicationRequest::RequestMsg {
-                peer_id: peer_b,
-                request: Question(question),
-            },
+            RequestMsgBuilder::new(peer_b, Question(question)).build(),
         )
         .await
         {
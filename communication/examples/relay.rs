@@ -53,13 +53,18 @@ async fn run_relay() {
                     P2PReqResEvent::Req {
                         peer_id,
                         request_id,
-                        request,
+                        mut request,
                     } => {
                         // verify that the claimed source is the actual peer that send the request
                         if peer_id.to_string() != request.source {
                             continue;
                         }
 
+                        // drop stale requests
+                        if request.is_expired() || !request.decrement_hops() {
+                            continue;
+                        }
+
                         // forward request to the target
                         if let Ok(target) = PeerId::from_str(&request.target) {
                             let forward_request = swarm.send_request(&target, request);
@@ -195,6 +200,7 @@ fn handle_event(
                     source,
                     message,
                     target,
+                    ..
                 },
         } => {
             // Verify that the request correctly targets the local peer.
@@ -249,6 +255,8 @@ fn handle_input_line(
             source: Swarm::local_peer_id(swarm).to_string(),
             message: line.to_string(),
             target: remote.to_string(),
+            expires_at: None,
+            hops_remaining: None,
         };
         swarm.send_request(&relay_peer, request);
     } else {
@@ -65,11 +65,7 @@
 //! let client = sys
 //!     .actor_of::<ClientActor>("client")
 //!     .expect("Init client actor failed.");
-//! let actor_config = CommunicationActorConfig {
-//!     client,
-//!     firewall_default_in: FirewallPermission::all(),
-//!     firewall_default_out: FirewallPermission::none(),
-//! };
+//! let actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::none());
 //! let behaviour_config = BehaviourConfig::default();
 //! let comms_actor = sys
 //!     .actor_of_args::<CommunicationActor<Request, Response, _, _>, _>(
@@ -79,43 +75,312 @@
 //!     .expect("Init communication actor failed.");
 //! ```
 
+mod breaker;
+mod cache;
 mod connections;
+mod event_log;
+mod failure_ban;
 mod firewall;
+mod handle;
+mod maintained;
+mod metrics;
+mod scoring;
+mod state;
 mod swarm_task;
 mod types;
-use crate::behaviour::{BehaviourConfig, MessageEvent};
+use crate::behaviour::{BehaviourConfig, EnvelopeTtl, MessageEvent};
 use async_std::task;
+pub use breaker::ClientBreakerConfig;
+pub use cache::{RequestNonce, ResponseCacheConfig};
+pub use connections::RetainedAddrsConfig;
 use core::{
+    fmt,
     marker::PhantomData,
     task::{Context as TaskContext, Poll},
+    time::Duration,
 };
+pub use failure_ban::FailureBanConfig;
 use firewall::*;
 pub use firewall::{
-    FirewallPermission, FirewallRule, PermissionValue, RequestDirection, RequestPermissions, ToPermissionVariants,
-    VariantPermission,
+    FirewallDecision, FirewallMode, FirewallPermission, FirewallRule, MatchedRule, PermissionRate, PermissionValue,
+    RequestDirection, RequestPermissions, ToPermissionVariants, VariantPermission,
 };
 use futures::{
     channel::mpsc::{unbounded, SendError, UnboundedSender},
-    future,
+    future::{self, BoxFuture},
 };
-use libp2p::identity::Keypair;
+pub use handle::{CommunicationHandle, SwarmInfo};
+use libp2p::{core::ConnectedPoint, identity::Keypair, PeerId};
+pub use maintained::{MaintainBackoff, MaintainedState};
+pub use metrics::SwarmMetrics;
 use riker::actors::*;
+pub use scoring::PeerScoringConfig;
+pub use state::{CommunicationState, KeepAliveState, PeerState};
+use std::sync::Arc;
 use stronghold_utils::ask;
 use swarm_task::SwarmTask;
 pub use types::*;
 
-#[derive(Debug, Clone)]
+/// Hook that is called with a request and the remote peer that the request is sent to, or was received from.
+/// The hook can mutate the request, e.g. to stamp a correlation id, or only inspect it e.g. for logging.
+pub type RequestHook<Req> = Arc<dyn Fn(&mut Req, PeerId) + Send + Sync>;
+
+/// Callback that decides whether an inbound connection of a remote peer is accepted, e.g. by consulting a revocation
+/// list in a database. The returned future is awaited in a separate task, so that it does not block the swarm.
+pub type ConnectionAuthorizer = Arc<dyn Fn(PeerId, ConnectedPoint) -> BoxFuture<'static, bool> + Send + Sync>;
+
+/// Hook that is called with an incoming request and its [`RequestProvenance`], right before it is forwarded to the
+/// client. The hook can e.g. stamp the provenance into the request, so that the client can base trust decisions on it.
+pub type ProvenanceHook<Req> = Arc<dyn Fn(&mut Req, RequestProvenance) + Send + Sync>;
+
+/// Routes an incoming request that passed the firewall to the client actor that handles it, e.g. to split the
+/// handling of different request categories into separate actors. If no route matches, the request is forwarded to
+/// the default `client`.
+pub type ClientRouter<Req, ClientMsg> = Arc<dyn Fn(&Req) -> Option<ActorRef<ClientMsg>> + Send + Sync>;
+
+/// Hook that is called with the response of the client to an incoming request and the peer id of the source, right
+/// before the response is sent back. The hook can e.g. redact fields for untrusted peers.
+pub type ResponseHook<Res> = Arc<dyn Fn(&mut Res, PeerId) + Send + Sync>;
+
+#[derive(Clone)]
 /// The actor configuration
-pub struct CommunicationActorConfig<ClientMsg>
+pub struct CommunicationActorConfig<Req, Res, ClientMsg>
 where
+    Req: Message,
     ClientMsg: Message,
 {
     /// Target client for incoming request
     pub client: ActorRef<ClientMsg>,
+    /// Select the client for each incoming request, instead of forwarding all requests to the `client`.
+    /// If none is specified, or no route matches, the request is forwarded to the `client`.
+    pub client_router: Option<ClientRouter<Req, ClientMsg>>,
     /// Default restriction for incoming requests.
     pub firewall_default_in: FirewallPermission,
     /// Default restriction for outgoing requests.
     pub firewall_default_out: FirewallPermission,
+    /// Hook that is called for each outgoing request, right before it is wrapped into the envelope that is sent to
+    /// the remote peer. Requests are checked by the firewall before the hook is called.
+    pub outgoing_request_hook: Option<RequestHook<Req>>,
+    /// Hook that is called for each incoming request with the peer id of the source, before the request is checked
+    /// by the firewall and forwarded to the client.
+    pub incoming_request_hook: Option<RequestHook<Req>>,
+    /// Hook that is called with the provenance of each incoming request that passed the firewall, before the request is
+    /// forwarded to the client.
+    pub provenance_hook: Option<ProvenanceHook<Req>>,
+    /// Hook that is called for each response of the client, before it is sent to the remote peer. Responses from the
+    /// response cache pass the hook again.
+    pub response_hook: Option<ResponseHook<Res>>,
+    /// Score remote peers based on their behaviour, and automatically ban peers whose score drops below the
+    /// configured threshold. If none is specified, peers are not scored.
+    pub peer_scoring: Option<PeerScoringConfig>,
+    /// Ban peers for a cooldown once they caused too many protocol failures or malformed envelopes within a window,
+    /// which is emitted as [`CommunicationEvent::PeerAutoBanned`]. Unlike the `peer_scoring`, this only targets peers
+    /// that send garbage that breaks the protocol. If none is specified, peers are not banned due to failures.
+    pub failure_ban: Option<FailureBanConfig>,
+    /// Cache the responses to idempotent inbound requests, so that retries of a request are answered without asking
+    /// the client again. If none is specified, no responses are cached.
+    pub response_cache: Option<ResponseCacheConfig<Req>>,
+    /// Actor that is notified about [`CommunicationEvent`]s of the swarm, e.g. new connections.
+    /// If none is specified, the events are not emitted.
+    pub observer: Option<ActorRef<CommunicationEvent>>,
+    /// Send a copy of each request that passed the firewall to the audit actor, with the direction, peer and matched
+    /// rule. If none is specified, no audit records are sent.
+    pub audit: Option<AuditConfig<Req>>,
+    /// Try to establish a direct connection to peers that communicate via the relay, and send future requests
+    /// directly once it was established. The result is emitted as [`CommunicationEvent::DirectConnectionUpgrade`].
+    /// No hole punching is done, so the upgrade only succeeds if one of the known addresses of the peer is reachable.
+    pub direct_upgrade: bool,
+    /// Duration after which a failed direct connection upgrade to a relayed peer is attempted again.
+    /// If none is specified, it defaults to 30s.
+    pub direct_upgrade_backoff: Option<Duration>,
+    /// Only consider a new connection as established once it survived the grace period, to suppress connections that
+    /// are closed right away. Until then, no [`CommunicationEvent::ConnectionEstablished`] is emitted and requests from
+    /// the peer are not accepted, unless the connection was explicitly established with
+    /// [`CommunicationRequest::EstablishConnection`]. If none is specified, connections are confirmed immediately.
+    pub connection_grace_period: Option<Duration>,
+    /// With [`RelayConfig::RelayBackup`], additionally send a request that has `hedge` set via the relay if no direct
+    /// connection to the peer was established within the delay, instead of waiting until dialing the peer failed. The
+    /// first response of either path is returned. The request on the other path can not be aborted, so the remote
+    /// may receive and handle the request twice; only idempotent requests should therefore be hedged.
+    /// If none is specified, or for requests without `hedge`, the relay is only used after the direct dial failed.
+    pub relay_fallback_delay: Option<Duration>,
+    /// Time-to-live that is set in the envelope of each outgoing request. Incoming envelopes whose `expires_at`
+    /// timestamp passed are dropped before they are forwarded to the client, and reported as
+    /// [`CommunicationEvent::EnvelopeExpired`]. If none is specified, outgoing envelopes don't expire.
+    pub envelope_ttl: Option<EnvelopeTtl>,
+    /// Remove an address of a peer from the address book once dialing it failed the configured number of consecutive
+    /// times, which is emitted as [`CommunicationEvent::AddressPruned`]. A successful dial resets the count, and the
+    /// count is forgotten if the address did not fail for an hour. A value of 0 is treated as 1.
+    /// If none is specified, addresses are never removed.
+    pub prune_failed_addrs: Option<u32>,
+    /// Limits for the addresses of previous connections that are retained to dial a peer again, if the peer has no
+    /// addresses in the address book. Defaults to the 8 most recently successful addresses per peer, that were
+    /// successful within the last 24h.
+    pub retained_addrs: RetainedAddrsConfig,
+    /// Stop forwarding inbound requests to the client for a cooldown if it repeatedly timed out, instead the requests
+    /// are rejected right away, so that the remote fails with [`RequestMessageError::Unavailable`], and counted in the
+    /// `inbound_rejected_breaker` metric. Remote peers with an older version of the protocol fail to read the
+    /// rejection. Opening and closing the breaker is emitted as
+    /// [`CommunicationEvent::ClientBreakerOpened`] and [`CommunicationEvent::ClientBreakerClosed`].
+    /// If none is specified, all permitted requests are forwarded to the client.
+    pub client_breaker: Option<ClientBreakerConfig>,
+    /// Duration to wait for a new listener to start listening, before starting the listener is considered as
+    /// failed. If none is specified, it defaults to 3s.
+    pub listen_timeout: Option<Duration>,
+    /// Authorize each inbound connection of a peer that is not connected yet, before requests from the connection are
+    /// accepted. If the connection is denied, it is closed and [`CommunicationEvent::ConnectionDenied`] is emitted.
+    /// If none is specified, all inbound connections are accepted.
+    pub connection_authorizer: Option<ConnectionAuthorizer>,
+    /// Interval in which expired state, e.g. temporary bans of peers and expired cached responses, is cleaned up.
+    /// A short interval removes the state more timely, but on a node with many connections each sweep has a
+    /// noticeable overhead. Intervals shorter than 10ms are raised to 10ms. If none is specified, it defaults to 1s.
+    pub sweep_interval: Option<Duration>,
+    /// Maximum number of inbound requests that are queued while the actor is blocked waiting on an outbound request,
+    /// dial or listener, or while the client already handles `max_inbound_in_flight` requests. Queued requests are
+    /// forwarded in order once the actor is not blocked anymore and the client finished a request. Requests that
+    /// exceed the limit are rejected right away with a busy response, so that the remote fails with
+    /// [`RequestMessageError::Unavailable`], and are counted in the `inbound_rejected_busy` metric.
+    /// If none is specified, the queue is unbounded.
+    pub inbound_queue_limit: Option<usize>,
+    /// Maximum number of inbound requests that the client handles at the same time. The actor does not wait for the
+    /// responses of the client, the number of requests that are currently handled is reported in the
+    /// `inbound_in_flight` metric. A value of 0 is treated as 1. If none is specified, the client is asked for one
+    /// inbound request at a time.
+    pub max_inbound_in_flight: Option<usize>,
+    /// Maximum number of outbound requests in flight, including requests that timed out locally while the remote is
+    /// still processing them, e.g. to bound the substreams and memory that an aggressive client can occupy. The
+    /// current number is reported in the `outbound_in_flight` metric. If none is specified, the number is not
+    /// limited.
+    pub max_outbound_in_flight: Option<usize>,
+    /// How a request is handled once `max_outbound_in_flight` is reached. Defaults to [`OutboundOverflow::Reject`].
+    pub outbound_overflow: OutboundOverflow,
+    /// Emit [`CommunicationEvent::HighLatency`] if the round-trip time of a ping to a connected peer exceeds the
+    /// threshold, e.g. to detect degraded links. This requires that probes are enabled in the [`BehaviourConfig`].
+    /// If none is specified, the event is not emitted.
+    pub rtt_threshold: Option<Duration>,
+    /// Whether the actor waits for the re-dial of a closed keep-alive connection, or handles the result of the dial
+    /// once it finished. Defaults to [`ReconnectMode::Sync`].
+    pub reconnect_mode: ReconnectMode,
+    /// Log each event of the swarm at the level, e.g. to troubleshoot connectivity issues. Only the type of the
+    /// event, peer ids, addresses and the kind of errors are logged, but never payloads or keys.
+    /// If none is specified, the events are not logged.
+    pub log_swarm_events: Option<log::Level>,
+    /// Periodically check whether the node still has a live listener, and restart listening on the configured
+    /// addresses once all listeners closed. If none is specified, closed listeners are not restarted.
+    pub listener_watchdog: Option<ListenerWatchdogConfig>,
+    /// Backoff between the dials of connections that are maintained with
+    /// [`CommunicationRequest::MaintainConnection`]. Defaults to 1s, doubling up to 60s.
+    pub maintain_backoff: MaintainBackoff,
+    /// Emit [`CommunicationEvent::ResponseSent`] once the response to an inbound request was transmitted to the
+    /// remote peer, e.g. to confirm the delivery of responses for at-least-once semantics. Defaults to false.
+    pub report_responses_sent: bool,
+    /// Respond to inbound requests that are blocked by the firewall with an explicit rejection, so that the remote
+    /// fails right away with [`RequestMessageError::Rejected`] instead of waiting until the request timed out.
+    /// Remote peers with an older version of the protocol fail to read the rejection. Defaults to false.
+    pub send_firewall_rejections: bool,
+}
+
+impl<Req, Res, ClientMsg> CommunicationActorConfig<Req, Res, ClientMsg>
+where
+    Req: Message,
+    ClientMsg: Message,
+{
+    /// Create a new configuration with the client and the default firewall restrictions, without any hooks.
+    pub fn new(
+        client: ActorRef<ClientMsg>,
+        firewall_default_in: FirewallPermission,
+        firewall_default_out: FirewallPermission,
+    ) -> Self {
+        CommunicationActorConfig {
+            client,
+            client_router: None,
+            firewall_default_in,
+            firewall_default_out,
+            outgoing_request_hook: None,
+            incoming_request_hook: None,
+            provenance_hook: None,
+            response_hook: None,
+            peer_scoring: None,
+            failure_ban: None,
+            response_cache: None,
+            observer: None,
+            audit: None,
+            direct_upgrade: false,
+            direct_upgrade_backoff: None,
+            connection_grace_period: None,
+            relay_fallback_delay: None,
+            envelope_ttl: None,
+            prune_failed_addrs: None,
+            retained_addrs: RetainedAddrsConfig::default(),
+            client_breaker: None,
+            listen_timeout: None,
+            connection_authorizer: None,
+            sweep_interval: None,
+            inbound_queue_limit: None,
+            max_inbound_in_flight: None,
+            max_outbound_in_flight: None,
+            outbound_overflow: OutboundOverflow::default(),
+            rtt_threshold: None,
+            reconnect_mode: ReconnectMode::default(),
+            log_swarm_events: None,
+            listener_watchdog: None,
+            maintain_backoff: MaintainBackoff::default(),
+            report_responses_sent: false,
+            send_firewall_rejections: false,
+        }
+    }
+}
+
+impl<Req, Res, ClientMsg> fmt::Debug for CommunicationActorConfig<Req, Res, ClientMsg>
+where
+    Req: Message,
+    ClientMsg: Message,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommunicationActorConfig")
+            .field("client", &self.client)
+            .field("client_router", &self.client_router.is_some())
+            .field("firewall_default_in", &self.firewall_default_in)
+            .field("firewall_default_out", &self.firewall_default_out)
+            .field("outgoing_request_hook", &self.outgoing_request_hook.is_some())
+            .field("incoming_request_hook", &self.incoming_request_hook.is_some())
+            .field("provenance_hook", &self.provenance_hook.is_some())
+            .field("response_hook", &self.response_hook.is_some())
+            .field("peer_scoring", &self.peer_scoring)
+            .field("failure_ban", &self.failure_ban)
+            .field(
+                "response_cache",
+                &self.response_cache.as_ref().map(|config| (config.capacity, config.ttl)),
+            )
+            .field("observer", &self.observer)
+            .field(
+                "audit",
+                &self.audit.as_ref().map(|audit| (&audit.actor, audit.include_payload)),
+            )
+            .field("direct_upgrade", &self.direct_upgrade)
+            .field("direct_upgrade_backoff", &self.direct_upgrade_backoff)
+            .field("connection_grace_period", &self.connection_grace_period)
+            .field("relay_fallback_delay", &self.relay_fallback_delay)
+            .field("envelope_ttl", &self.envelope_ttl)
+            .field("prune_failed_addrs", &self.prune_failed_addrs)
+            .field("retained_addrs", &self.retained_addrs)
+            .field("client_breaker", &self.client_breaker)
+            .field("listen_timeout", &self.listen_timeout)
+            .field("connection_authorizer", &self.connection_authorizer.is_some())
+            .field("sweep_interval", &self.sweep_interval)
+            .field("inbound_queue_limit", &self.inbound_queue_limit)
+            .field("max_inbound_in_flight", &self.max_inbound_in_flight)
+            .field("max_outbound_in_flight", &self.max_outbound_in_flight)
+            .field("outbound_overflow", &self.outbound_overflow)
+            .field("rtt_threshold", &self.rtt_threshold)
+            .field("reconnect_mode", &self.reconnect_mode)
+            .field("log_swarm_events", &self.log_swarm_events)
+            .field("listener_watchdog", &self.listener_watchdog)
+            .field("maintain_backoff", &self.maintain_backoff)
+            .field("report_responses_sent", &self.report_responses_sent)
+            .field("send_firewall_rejections", &self.send_firewall_rejections)
+            .finish()
+    }
 }
 
 /// Actor responsible for creating a [`P2PNetworkBehaviour`] and handling all interaction with the Swarm.
@@ -129,13 +394,13 @@ where
 {
     // Channel for messages to the swarm task.
     swarm_tx: Option<UnboundedSender<(CommunicationRequest<Req, ClientMsg>, Sender)>>,
-    swarm_task_config: Option<(Keypair, CommunicationActorConfig<ClientMsg>, BehaviourConfig)>,
+    swarm_task_config: Option<(Keypair, CommunicationActorConfig<Req, Res, ClientMsg>, BehaviourConfig)>,
     // Handle of the running swarm task.
     poll_swarm_handle: Option<future::RemoteHandle<()>>,
     _marker: (PhantomData<Res>, PhantomData<P>),
 }
 
-impl<Req, Res, ClientMsg, P> ActorFactoryArgs<(Keypair, CommunicationActorConfig<ClientMsg>, BehaviourConfig)>
+impl<Req, Res, ClientMsg, P> ActorFactoryArgs<(Keypair, CommunicationActorConfig<Req, Res, ClientMsg>, BehaviourConfig)>
     for CommunicationActor<Req, Res, ClientMsg, P>
 where
     Req: MessageEvent + ToPermissionVariants<P> + Into<ClientMsg>,
@@ -146,7 +411,7 @@ where
     // Create a CommunicationActor that spawns a task to poll from the swarm.
     // The provided keypair is used to authenticate the swarm communication.
     // The client actor ref is used to forward incoming requests from the swarm to it.
-    fn create_args(config: (Keypair, CommunicationActorConfig<ClientMsg>, BehaviourConfig)) -> Self {
+    fn create_args(config: (Keypair, CommunicationActorConfig<Req, Res, ClientMsg>, BehaviourConfig)) -> Self {
         Self {
             swarm_tx: None,
             swarm_task_config: Some(config),
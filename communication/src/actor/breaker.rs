@@ -0,0 +1,92 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use std::time::{Duration, Instant};
+
+/// Configuration for a circuit breaker that protects an overloaded client.
+/// After `max_timeouts` consecutive timeouts of the client, inbound requests are not forwarded to the client anymore
+/// but rejected for the `cooldown`. Once the cooldown passed, the next inbound request is forwarded again as probe:
+/// if the client responds, the breaker is closed, otherwise it is opened for another cooldown.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientBreakerConfig {
+    /// Number of consecutive timeouts of the client after which the breaker opens.
+    pub max_timeouts: u32,
+    /// Duration for which inbound requests are rejected once the breaker opened.
+    pub cooldown: Duration,
+}
+
+// State of the circuit breaker for the client.
+pub(super) struct ClientBreaker {
+    config: ClientBreakerConfig,
+    // Consecutive timeouts of the client.
+    timeouts: u32,
+    // Point in time at which the breaker opened, if it is not closed.
+    opened: Option<Instant>,
+}
+
+impl ClientBreaker {
+    pub fn new(config: ClientBreakerConfig) -> Self {
+        ClientBreaker {
+            config,
+            timeouts: 0,
+            opened: None,
+        }
+    }
+
+    // Check if inbound requests should be dropped, which is the case until the cooldown of an open breaker passed.
+    pub fn is_open(&self) -> bool {
+        self.opened
+            .map(|opened| opened.elapsed() < self.config.cooldown)
+            .unwrap_or(false)
+    }
+
+    // Record a response of the client, returns true if this closed the breaker.
+    pub fn record_success(&mut self) -> bool {
+        self.timeouts = 0;
+        self.opened.take().is_some()
+    }
+
+    // Record a timeout of the client, returns true if this opened the breaker.
+    // A timeout of the probe after the cooldown opens the breaker again right away.
+    pub fn record_timeout(&mut self) -> bool {
+        self.timeouts += 1;
+        if self.opened.is_some() || self.timeouts >= self.config.max_timeouts {
+            self.opened = Some(Instant::now());
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn open_after_timeouts() {
+        let mut breaker = ClientBreaker::new(ClientBreakerConfig {
+            max_timeouts: 2,
+            cooldown: Duration::from_secs(60),
+        });
+        assert!(!breaker.record_timeout());
+        assert!(!breaker.record_success());
+        assert!(!breaker.record_timeout());
+        assert!(!breaker.is_open());
+        assert!(breaker.record_timeout());
+        assert!(breaker.is_open());
+    }
+
+    #[test]
+    fn probe_after_cooldown() {
+        let mut breaker = ClientBreaker::new(ClientBreakerConfig {
+            max_timeouts: 1,
+            cooldown: Duration::from_secs(0),
+        });
+        assert!(breaker.record_timeout());
+        assert!(!breaker.is_open());
+        // failed probe opens the breaker again
+        assert!(breaker.record_timeout());
+        assert!(breaker.record_success());
+        assert!(!breaker.record_success());
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use libp2p::PeerId;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Function that returns the nonce of an idempotent request, or `None` if the request is not idempotent.
+/// Retries of a request are expected to use the same nonce.
+pub type RequestNonce<Req> = Arc<dyn Fn(&Req) -> Option<u64> + Send + Sync>;
+
+/// Configuration for caching the responses to idempotent inbound requests.
+/// If a request from the same source peer with the same nonce is received again, the cached response is returned
+/// without forwarding the request to the client.
+#[derive(Clone)]
+pub struct ResponseCacheConfig<Req> {
+    /// Maximum number of cached responses, the least recently used response is evicted first.
+    pub capacity: usize,
+    /// Duration after which a cached response expires.
+    pub ttl: Duration,
+    /// Marks requests as idempotent by returning their nonce.
+    pub nonce: RequestNonce<Req>,
+}
+
+// Bounded LRU cache of responses, keyed by the source peer and the nonce of the request.
+// Each use of an entry assigns it a new generation and appends it to the order, so that promoting an entry does not
+// require searching the order. Positions of previous generations are skipped on eviction and removed when the order
+// grows beyond twice the capacity.
+pub(super) struct ResponseCache<Res> {
+    capacity: usize,
+    ttl: Duration,
+    entries: HashMap<(PeerId, u64), CacheEntry<Res>>,
+    // Keys with the generation of their use, the most recently used key is at the back.
+    order: VecDeque<(u64, (PeerId, u64))>,
+    next_generation: u64,
+}
+
+struct CacheEntry<Res> {
+    response: Res,
+    inserted: Instant,
+    generation: u64,
+}
+
+impl<Res: Clone> ResponseCache<Res> {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        ResponseCache {
+            capacity,
+            ttl,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            next_generation: 0,
+        }
+    }
+
+    pub fn get(&mut self, source: PeerId, nonce: u64) -> Option<Res> {
+        let key = (source, nonce);
+        let is_expired = self.entries.get(&key)?.inserted.elapsed() > self.ttl;
+        if is_expired {
+            self.entries.remove(&key);
+            return None;
+        }
+        self.touch(key);
+        self.entries.get(&key).map(|entry| entry.response.clone())
+    }
+
+    pub fn insert(&mut self, source: PeerId, nonce: u64, response: Res) {
+        if self.capacity == 0 {
+            return;
+        }
+        let key = (source, nonce);
+        let entry = CacheEntry {
+            response,
+            inserted: Instant::now(),
+            generation: 0,
+        };
+        self.entries.insert(key, entry);
+        self.touch(key);
+        while self.entries.len() > self.capacity {
+            match self.order.pop_front() {
+                Some((generation, evicted)) if self.is_current(generation, &evicted) => {
+                    self.entries.remove(&evicted);
+                }
+                Some(_) => {}
+                None => break,
+            }
+        }
+    }
+
+    // Remove all responses for which the ttl expired.
+    pub fn remove_expired(&mut self) {
+        let ttl = self.ttl;
+        self.entries.retain(|_, entry| entry.inserted.elapsed() <= ttl);
+        self.compact();
+    }
+
+    // Mark the entry as most recently used.
+    fn touch(&mut self, key: (PeerId, u64)) {
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.generation = generation;
+            self.order.push_back((generation, key));
+        }
+        if self.order.len() > 2 * self.capacity {
+            self.compact();
+        }
+    }
+
+    fn is_current(&self, generation: u64, key: &(PeerId, u64)) -> bool {
+        self.entries
+            .get(key)
+            .map(|entry| entry.generation == generation)
+            .unwrap_or(false)
+    }
+
+    // Remove the positions of previous generations and of removed entries from the order.
+    fn compact(&mut self) {
+        let entries = &self.entries;
+        self.order.retain(|(generation, key)| {
+            entries
+                .get(key)
+                .map(|entry| entry.generation == *generation)
+                .unwrap_or(false)
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn evict_least_recently_used() {
+        let mut cache = ResponseCache::new(2, Duration::from_secs(60));
+        let peer_id = PeerId::random();
+        cache.insert(peer_id, 1, "a");
+        cache.insert(peer_id, 2, "b");
+        assert_eq!(cache.get(peer_id, 1), Some("a"));
+        cache.insert(peer_id, 3, "c");
+        assert_eq!(cache.get(peer_id, 2), None);
+        assert_eq!(cache.get(peer_id, 1), Some("a"));
+        assert_eq!(cache.get(peer_id, 3), Some("c"));
+        assert_eq!(cache.get(PeerId::random(), 3), None);
+    }
+
+    #[test]
+    fn bound_order_on_repeated_use() {
+        let mut cache = ResponseCache::new(2, Duration::from_secs(60));
+        let peer_id = PeerId::random();
+        cache.insert(peer_id, 1, "a");
+        cache.insert(peer_id, 2, "b");
+        for _ in 0..100 {
+            assert_eq!(cache.get(peer_id, 1), Some("a"));
+        }
+        assert!(cache.order.len() <= 4);
+        cache.insert(peer_id, 3, "c");
+        assert_eq!(cache.get(peer_id, 2), None);
+        assert_eq!(cache.get(peer_id, 1), Some("a"));
+        assert_eq!(cache.entries.len(), 2);
+    }
+
+    #[test]
+    fn expire_ttl() {
+        let mut cache = ResponseCache::new(2, Duration::from_secs(0));
+        let peer_id = PeerId::random();
+        cache.insert(peer_id, 1, "a");
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(cache.get(peer_id, 1), None);
+        cache.insert(peer_id, 2, "b");
+        std::thread::sleep(Duration::from_millis(1));
+        cache.remove_expired();
+        assert!(cache.entries.is_empty() && cache.order.is_empty());
+    }
+}
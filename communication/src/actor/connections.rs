@@ -2,8 +2,35 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::{EstablishedConnection, KeepAlive};
-use libp2p::{core::ConnectedPoint, PeerId};
-use std::collections::HashMap;
+use crate::behaviour::P2PIdentifyInfo;
+use libp2p::{core::ConnectedPoint, Multiaddr, PeerId};
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+// Duration after the latest failed dial of an address, after which its count of consecutive failures is forgotten.
+pub(super) const DIAL_FAILURE_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Limits for the addresses of previous connections that are retained to dial a peer again, if it has no addresses
+/// in the address book of the swarm. Addresses whose last successful connection is older than `max_age` are removed,
+/// and of each peer only the `max_count` most recently successful addresses are kept.
+#[derive(Debug, Clone, Copy)]
+pub struct RetainedAddrsConfig {
+    /// Duration after the last successful connection via an address, after which the address is removed.
+    pub max_age: Duration,
+    /// Max number of retained addresses per peer.
+    pub max_count: usize,
+}
+
+impl Default for RetainedAddrsConfig {
+    fn default() -> Self {
+        RetainedAddrsConfig {
+            max_age: Duration::from_secs(24 * 60 * 60),
+            max_count: 8,
+        }
+    }
+}
 
 // Maintain the current connection state to remote peers.
 // If a connection is closed in the ConnectionManager, no request from that peer will be forwarded anymore, but the
@@ -11,13 +38,43 @@ use std::collections::HashMap;
 // peer, otherwise it closes on timeout.
 //
 // If multiple connections to a peer exist, the ConnectionManager will keep the properties of the first connection.
+//
+// Additionally, the peers that are currently dialed are tracked, until the dial either succeeded or failed, and the
+// identifying information of connected peers, e.g. the protocols that they support, according to the identify
+// protocol.
+//
+// If a grace period is configured, new connections are unconfirmed until they survived the grace period.
+//
+// For pruning the address book, the consecutive failed dials of each address of a peer are counted. Counts whose
+// latest failure is older than `DIAL_FAILURE_MAX_AGE` are forgotten, so that they don't accumulate for addresses
+// that are never pruned.
+//
+// The addresses of connections that the local peer dialed are retained after the connections closed, so that they
+// can be dialed again if the peer has no addresses in the address book of the swarm. They are bounded by the
+// RetainedAddrsConfig.
 pub(super) struct ConnectionManager {
     map: HashMap<PeerId, EstablishedConnection>,
+    pending_dials: HashSet<PeerId>,
+    identify_info: HashMap<PeerId, P2PIdentifyInfo>,
+    unconfirmed: HashMap<PeerId, (ConnectedPoint, Instant)>,
+    // consecutive failed dials of an address with the time of the latest failure
+    dial_failures: HashMap<(PeerId, Multiaddr), (u32, Instant)>,
+    // retained addresses with the time of their last successful connection, newest first
+    retained_addrs: HashMap<PeerId, Vec<(Multiaddr, Instant)>>,
+    retained_addrs_config: RetainedAddrsConfig,
 }
 
 impl ConnectionManager {
-    pub fn new() -> Self {
-        ConnectionManager { map: HashMap::new() }
+    pub fn new(retained_addrs_config: RetainedAddrsConfig) -> Self {
+        ConnectionManager {
+            map: HashMap::new(),
+            pending_dials: HashSet::new(),
+            identify_info: HashMap::new(),
+            unconfirmed: HashMap::new(),
+            dial_failures: HashMap::new(),
+            retained_addrs: HashMap::new(),
+            retained_addrs_config,
+        }
     }
 
     // Returns all the currently active connections
@@ -51,7 +108,210 @@ impl ConnectionManager {
         }
     }
 
-    pub fn remove_connection(&mut self, peer_id: &PeerId) {
-        self.map.remove(peer_id);
+    pub fn set_rtt(&mut self, peer_id: &PeerId, rtt: Duration) {
+        if let Some(connection) = self.map.get_mut(peer_id) {
+            connection.set_rtt(rtt)
+        }
+    }
+
+    pub fn remove_connection(&mut self, peer_id: &PeerId) -> Option<EstablishedConnection> {
+        self.map.remove(peer_id)
+    }
+
+    // Mark that the swarm is currently dialing the peer.
+    pub fn insert_pending_dial(&mut self, peer_id: PeerId) {
+        self.pending_dials.insert(peer_id);
+    }
+
+    // Remove the peer from the pending dials once a connection was established, or the dial failed.
+    pub fn remove_pending_dial(&mut self, peer_id: &PeerId) {
+        self.pending_dials.remove(peer_id);
+    }
+
+    pub fn is_pending_dial(&self, peer_id: &PeerId) -> bool {
+        self.pending_dials.contains(peer_id)
+    }
+
+    pub fn pending_dials(&self) -> Vec<PeerId> {
+        self.pending_dials.iter().copied().collect()
+    }
+
+    // Set the identifying information of the peer, returns true if the protocols that the peer supports differ from
+    // the previous ones.
+    pub fn set_identify_info(&mut self, peer_id: PeerId, info: P2PIdentifyInfo) -> bool {
+        let protocols = info.protocols.clone();
+        self.identify_info.insert(peer_id, info).map(|info| info.protocols) != Some(protocols)
+    }
+
+    pub fn get_identify_info(&self, peer_id: &PeerId) -> Option<&P2PIdentifyInfo> {
+        self.identify_info.get(peer_id)
+    }
+
+    pub fn get_protocols(&self, peer_id: &PeerId) -> Option<Vec<String>> {
+        self.identify_info.get(peer_id).map(|info| info.protocols.clone())
+    }
+
+    // Forget the identifying information once all connections to the peer are closed, since it may change until the
+    // next connection.
+    pub fn remove_identify_info(&mut self, peer_id: &PeerId) {
+        self.identify_info.remove(peer_id);
+    }
+
+    // Track a new connection until it survived the grace period, if the peer is not connected yet.
+    pub fn insert_unconfirmed(&mut self, peer_id: PeerId, connected_point: ConnectedPoint) {
+        if !self.map.contains_key(&peer_id) && !self.unconfirmed.contains_key(&peer_id) {
+            self.unconfirmed.insert(peer_id, (connected_point, Instant::now()));
+        }
+    }
+
+    // Remove an unconfirmed connection, returns true if the connection was still unconfirmed.
+    pub fn remove_unconfirmed(&mut self, peer_id: &PeerId) -> bool {
+        self.unconfirmed.remove(peer_id).is_some()
+    }
+
+    // Remove and return the connections that survived the grace period.
+    pub fn take_confirmed(&mut self, grace_period: Duration) -> Vec<(PeerId, ConnectedPoint)> {
+        let confirmed: Vec<PeerId> = self
+            .unconfirmed
+            .iter()
+            .filter(|(_, (_, start))| start.elapsed() >= grace_period)
+            .map(|(peer_id, _)| *peer_id)
+            .collect();
+        confirmed
+            .into_iter()
+            .filter_map(|peer_id| {
+                self.unconfirmed
+                    .remove(&peer_id)
+                    .map(|(connected_point, _)| (peer_id, connected_point))
+            })
+            .collect()
+    }
+
+    // The point in time at which the next unconfirmed connection survived the grace period.
+    pub fn next_confirmation(&self, grace_period: Duration) -> Option<Instant> {
+        self.unconfirmed.values().map(|(_, start)| *start + grace_period).min()
+    }
+
+    // Count a failed dial of the address, returns the number of consecutive failures.
+    pub fn record_dial_failure(&mut self, peer_id: PeerId, addr: Multiaddr) -> u32 {
+        let (failures, latest) = self.dial_failures.entry((peer_id, addr)).or_insert((0, Instant::now()));
+        *failures += 1;
+        *latest = Instant::now();
+        *failures
+    }
+
+    // Forget the failures of addresses whose latest failed dial is older than the max age.
+    pub fn prune_dial_failures(&mut self, max_age: Duration) {
+        self.dial_failures.retain(|_, (_, latest)| latest.elapsed() < max_age);
+    }
+
+    // Retain the address of a dialed connection, the most recent address is the first one.
+    // If the peer exceeds the max count, the least recently successful addresses are removed.
+    pub fn retain_addr(&mut self, peer_id: PeerId, addr: Multiaddr) {
+        let addrs = self.retained_addrs.entry(peer_id).or_default();
+        addrs.retain(|(a, _)| a != &addr);
+        addrs.insert(0, (addr, Instant::now()));
+        addrs.truncate(self.retained_addrs_config.max_count);
+    }
+
+    // Addresses of previous connections to the peer that were dialed by the local peer, ordered from newest to oldest.
+    // Addresses that exceeded the max age are skipped, even if they were not pruned yet.
+    pub fn get_retained_addrs(&self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        let max_age = self.retained_addrs_config.max_age;
+        self.retained_addrs
+            .get(peer_id)
+            .map(|addrs| {
+                addrs
+                    .iter()
+                    .filter(|(_, last_success)| last_success.elapsed() < max_age)
+                    .map(|(addr, _)| addr.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // Forget a retained address, e.g. if dialing it failed.
+    pub fn remove_retained_addr(&mut self, peer_id: &PeerId, addr: &Multiaddr) {
+        if let Some(addrs) = self.retained_addrs.get_mut(peer_id) {
+            addrs.retain(|(a, _)| a != addr);
+        }
+    }
+
+    // Remove the retained addresses that exceeded the max age, and the peers that have no retained addresses left.
+    pub fn prune_retained_addrs(&mut self) {
+        let max_age = self.retained_addrs_config.max_age;
+        self.retained_addrs.retain(|_, addrs| {
+            addrs.retain(|(_, last_success)| last_success.elapsed() < max_age);
+            !addrs.is_empty()
+        });
+    }
+
+    // Forget the retained addresses and dial failures of the peer, or of all peers if none is specified.
+    pub fn clear_addrs(&mut self, peer_id: Option<&PeerId>) {
+        match peer_id {
+            Some(peer_id) => {
+                self.retained_addrs.remove(peer_id);
+                self.dial_failures.retain(|(failed_peer, _), _| failed_peer != peer_id);
+            }
+            None => {
+                self.retained_addrs.clear();
+                self.dial_failures.clear();
+            }
+        }
+    }
+
+    // Reset the failures once the address was dialed successfully, or it was removed from the address book.
+    pub fn reset_dial_failures(&mut self, peer_id: PeerId, addr: Multiaddr) {
+        self.dial_failures.remove(&(peer_id, addr));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn retained_addrs_are_bounded() {
+        let mut manager = ConnectionManager::new(RetainedAddrsConfig {
+            max_age: Duration::from_secs(60),
+            max_count: 2,
+        });
+        let peer_id = PeerId::random();
+        let addrs: Vec<Multiaddr> = (1..=3)
+            .map(|port| format!("/ip4/127.0.0.1/tcp/{}", port).parse().unwrap())
+            .collect();
+        for addr in &addrs {
+            manager.retain_addr(peer_id, addr.clone());
+        }
+        // the least recently successful address is evicted
+        assert_eq!(
+            manager.get_retained_addrs(&peer_id),
+            vec![addrs[2].clone(), addrs[1].clone()]
+        );
+        manager.retain_addr(peer_id, addrs[1].clone());
+        assert_eq!(
+            manager.get_retained_addrs(&peer_id),
+            vec![addrs[1].clone(), addrs[2].clone()]
+        );
+
+        manager.retained_addrs_config.max_age = Duration::from_secs(0);
+        assert!(manager.get_retained_addrs(&peer_id).is_empty());
+        manager.prune_retained_addrs();
+        assert!(manager.retained_addrs.is_empty());
+    }
+
+    #[test]
+    fn dial_failures_age_out() {
+        let mut manager = ConnectionManager::new(RetainedAddrsConfig::default());
+        let peer_id = PeerId::random();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/1".parse().unwrap();
+        assert_eq!(manager.record_dial_failure(peer_id, addr.clone()), 1);
+        assert_eq!(manager.record_dial_failure(peer_id, addr.clone()), 2);
+        manager.prune_dial_failures(DIAL_FAILURE_MAX_AGE);
+        assert_eq!(manager.dial_failures.len(), 1);
+
+        manager.prune_dial_failures(Duration::from_secs(0));
+        assert!(manager.dial_failures.is_empty());
+        assert_eq!(manager.record_dial_failure(peer_id, addr), 1);
     }
 }
@@ -0,0 +1,230 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::behaviour::{P2PEvent, P2PIdentifyEvent, P2PMdnsEvent, P2PPingEvent, P2PReqResEvent};
+use libp2p::{
+    core::connection::{ConnectionError, PendingConnectionError},
+    swarm::SwarmEvent,
+    Multiaddr, PeerId,
+};
+use std::io;
+
+// Describe a swarm event for the log.
+// Only the type of the event, peer ids, addresses and the kind of errors are included, the payload of requests and
+// responses as well as the identify info with the public key of a peer are never written to the log.
+pub(super) fn describe_swarm_event<Req, Res, HandleErr>(event: &SwarmEvent<P2PEvent<Req, Res>, HandleErr>) -> String {
+    match event {
+        SwarmEvent::Behaviour(behaviour_event) => describe_behaviour_event(behaviour_event),
+        SwarmEvent::ConnectionEstablished {
+            peer_id,
+            endpoint,
+            num_established,
+        } => format!(
+            "ConnectionEstablished {{ peer_id: {}, endpoint: {:?}, num_established: {} }}",
+            peer_id, endpoint, num_established
+        ),
+        SwarmEvent::ConnectionClosed {
+            peer_id,
+            endpoint,
+            num_established,
+            cause,
+        } => format!(
+            "ConnectionClosed {{ peer_id: {}, endpoint: {:?}, num_established: {}, cause: {} }}",
+            peer_id,
+            endpoint,
+            num_established,
+            cause.as_ref().map(connection_error_kind).unwrap_or("none")
+        ),
+        SwarmEvent::IncomingConnection {
+            local_addr,
+            send_back_addr,
+        } => format!(
+            "IncomingConnection {{ local_addr: {}, send_back_addr: {} }}",
+            local_addr, send_back_addr
+        ),
+        SwarmEvent::IncomingConnectionError {
+            local_addr,
+            send_back_addr,
+            error,
+        } => format!(
+            "IncomingConnectionError {{ local_addr: {}, send_back_addr: {}, error: {} }}",
+            local_addr,
+            send_back_addr,
+            pending_error_kind(error)
+        ),
+        SwarmEvent::BannedPeer { peer_id, endpoint } => {
+            format!("BannedPeer {{ peer_id: {}, endpoint: {:?} }}", peer_id, endpoint)
+        }
+        SwarmEvent::UnreachableAddr {
+            peer_id,
+            address,
+            error,
+            attempts_remaining,
+        } => format!(
+            "UnreachableAddr {{ peer_id: {}, address: {}, error: {}, attempts_remaining: {} }}",
+            peer_id,
+            address,
+            pending_error_kind(error),
+            attempts_remaining
+        ),
+        SwarmEvent::UnknownPeerUnreachableAddr { address, error } => format!(
+            "UnknownPeerUnreachableAddr {{ address: {}, error: {} }}",
+            address,
+            pending_error_kind(error)
+        ),
+        SwarmEvent::NewListenAddr(addr) => format!("NewListenAddr {{ address: {} }}", addr),
+        SwarmEvent::ExpiredListenAddr(addr) => format!("ExpiredListenAddr {{ address: {} }}", addr),
+        SwarmEvent::ListenerClosed { addresses, reason } => format!(
+            "ListenerClosed {{ addresses: {}, error: {} }}",
+            format_addrs(addresses.iter()),
+            reason
+                .as_ref()
+                .err()
+                .map(io_error_kind)
+                .unwrap_or_else(|| "none".into())
+        ),
+        SwarmEvent::ListenerError { error } => format!("ListenerError {{ error: {} }}", io_error_kind(error)),
+        SwarmEvent::Dialing(peer_id) => format!("Dialing {{ peer_id: {} }}", peer_id),
+    }
+}
+
+fn describe_behaviour_event<Req, Res>(event: &P2PEvent<Req, Res>) -> String {
+    match event {
+        P2PEvent::RequestResponse(boxed_event) => match boxed_event.as_ref() {
+            P2PReqResEvent::Req {
+                peer_id, request_id, ..
+            } => format!("Request {{ peer_id: {}, request_id: {} }}", peer_id, request_id),
+            P2PReqResEvent::Res {
+                peer_id, request_id, ..
+            } => format!("Response {{ peer_id: {}, request_id: {} }}", peer_id, request_id),
+            P2PReqResEvent::InboundFailure {
+                peer_id,
+                request_id,
+                error,
+            } => format!(
+                "InboundFailure {{ peer_id: {}, request_id: {}, error: {:?} }}",
+                peer_id, request_id, error
+            ),
+            P2PReqResEvent::OutboundFailure {
+                peer_id,
+                request_id,
+                error,
+            } => format!(
+                "OutboundFailure {{ peer_id: {}, request_id: {}, error: {:?} }}",
+                peer_id, request_id, error
+            ),
+            P2PReqResEvent::ResSent { peer_id, request_id } => {
+                format!("ResponseSent {{ peer_id: {}, request_id: {} }}", peer_id, request_id)
+            }
+        },
+        P2PEvent::Identify(boxed_event) => match boxed_event.as_ref() {
+            P2PIdentifyEvent::Received {
+                peer_id, observed_addr, ..
+            } => format!(
+                "IdentifyReceived {{ peer_id: {}, observed_addr: {} }}",
+                peer_id, observed_addr
+            ),
+            P2PIdentifyEvent::Sent { peer_id } => format!("IdentifySent {{ peer_id: {} }}", peer_id),
+            P2PIdentifyEvent::Error { peer_id, error } => {
+                format!("IdentifyError {{ peer_id: {}, error: {:?} }}", peer_id, error)
+            }
+        },
+        P2PEvent::Ping(P2PPingEvent::Ping { peer_id, rtt }) => {
+            format!("Ping {{ peer_id: {}, rtt: {:?} }}", peer_id, rtt)
+        }
+        P2PEvent::Ping(P2PPingEvent::Pong { peer_id }) => format!("Pong {{ peer_id: {} }}", peer_id),
+        P2PEvent::Ping(P2PPingEvent::Failure { peer_id }) => format!("PingFailure {{ peer_id: {} }}", peer_id),
+        P2PEvent::Mdns(P2PMdnsEvent::Discovered(peers)) => {
+            format!("MdnsDiscovered {{ peers: {} }}", format_peers(peers))
+        }
+        P2PEvent::Mdns(P2PMdnsEvent::Expired(peers)) => format!("MdnsExpired {{ peers: {} }}", format_peers(peers)),
+    }
+}
+
+fn connection_error_kind<HandleErr>(error: &ConnectionError<HandleErr>) -> &'static str {
+    match error {
+        ConnectionError::IO(_) => "io",
+        ConnectionError::Handler(_) => "handler",
+    }
+}
+
+fn pending_error_kind<TTransErr>(error: &PendingConnectionError<TTransErr>) -> String {
+    match error {
+        PendingConnectionError::Transport(_) => "transport".into(),
+        PendingConnectionError::InvalidPeerId => "invalid peer id".into(),
+        PendingConnectionError::ConnectionLimit(limit) => format!("connection limit {}", limit.limit),
+        PendingConnectionError::IO(err) => io_error_kind(err),
+    }
+}
+
+fn io_error_kind(error: &io::Error) -> String {
+    format!("io {:?}", error.kind())
+}
+
+fn format_addrs<'a>(addrs: impl Iterator<Item = &'a Multiaddr>) -> String {
+    let addrs: Vec<String> = addrs.map(|addr| addr.to_string()).collect();
+    format!("[{}]", addrs.join(", "))
+}
+
+fn format_peers(peers: &[(PeerId, Multiaddr)]) -> String {
+    let peers: Vec<String> = peers
+        .iter()
+        .map(|(peer_id, addr)| format!("{} {}", peer_id, addr))
+        .collect();
+    format!("[{}]", peers.join(", "))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::behaviour::P2PIdentifyInfo;
+    use libp2p::{core::ConnectedPoint, identity::Keypair};
+
+    type Event = SwarmEvent<P2PEvent<String, String>, io::Error>;
+
+    #[test]
+    fn describe_without_identify_info() {
+        let keys = Keypair::generate_ed25519();
+        let peer_id = PeerId::from(keys.public());
+        let observed_addr: Multiaddr = "/ip4/127.0.0.1/tcp/8080".parse().unwrap();
+        let info = P2PIdentifyInfo {
+            public_key: keys.public(),
+            protocol_version: "ipfs/0.1.0".into(),
+            agent_version: "secret-agent".into(),
+            listen_addrs: Vec::new(),
+            protocols: vec!["/stronghold/1.0.0".into()],
+        };
+        let event: Event = SwarmEvent::Behaviour(P2PEvent::Identify(Box::new(P2PIdentifyEvent::Received {
+            peer_id,
+            info,
+            observed_addr: observed_addr.clone(),
+        })));
+        let description = describe_swarm_event(&event);
+        assert!(description.contains(&peer_id.to_string()));
+        assert!(description.contains(&observed_addr.to_string()));
+        assert!(!description.contains("secret-agent"));
+        assert!(!description.contains("/stronghold/1.0.0"));
+    }
+
+    #[test]
+    fn describe_error_kinds() {
+        let peer_id = PeerId::random();
+        let address: Multiaddr = "/ip4/127.0.0.1/tcp/8080".parse().unwrap();
+        let event: Event = SwarmEvent::UnreachableAddr {
+            peer_id,
+            address: address.clone(),
+            error: PendingConnectionError::IO(io::Error::new(io::ErrorKind::ConnectionRefused, "details")),
+            attempts_remaining: 0,
+        };
+        let description = describe_swarm_event(&event);
+        assert!(description.contains("ConnectionRefused"));
+        assert!(!description.contains("details"));
+
+        let event: Event = SwarmEvent::ConnectionEstablished {
+            peer_id,
+            endpoint: ConnectedPoint::Dialer { address },
+            num_established: std::num::NonZeroU32::new(1).unwrap(),
+        };
+        assert!(describe_swarm_event(&event).starts_with("ConnectionEstablished"));
+    }
+}
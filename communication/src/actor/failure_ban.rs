@@ -0,0 +1,146 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use libp2p::PeerId;
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+/// Configuration for automatically banning peers that break the protocol, independently of the
+/// [`PeerScoringConfig`](super::PeerScoringConfig).
+/// A peer that causes more than `max_failures` protocol failures, i.e. inbound requests with unsupported protocols, or
+/// malformed envelopes within the `window` is banned for the `cooldown`. Other failures, e.g. timeouts of the client
+/// or closed connections, are not counted.
+#[derive(Debug, Clone, Copy)]
+pub struct FailureBanConfig {
+    /// Number of failures within the window that a peer may cause without being banned.
+    pub max_failures: u32,
+    /// Duration in which the failures of a peer are counted.
+    pub window: Duration,
+    /// Duration after which a banned peer is unbanned again.
+    pub cooldown: Duration,
+}
+
+// Track the recent failures of remote peers, and the peers that are currently banned due to them.
+pub(super) struct FailureBans {
+    config: FailureBanConfig,
+    // Points in time of the failures of each peer within the window, oldest first.
+    failures: HashMap<PeerId, VecDeque<Instant>>,
+    // Peers that were banned due to their failures, with the time at which they should be unbanned again.
+    banned: HashMap<PeerId, Instant>,
+}
+
+impl FailureBans {
+    pub fn new(config: FailureBanConfig) -> Self {
+        FailureBans {
+            config,
+            failures: HashMap::new(),
+            banned: HashMap::new(),
+        }
+    }
+
+    // Record a failure of the peer.
+    // Returns true if the peer exceeded the max failures within the window and should be banned now.
+    pub fn record_failure(&mut self, peer_id: PeerId) -> bool {
+        if self.banned.contains_key(&peer_id) {
+            return false;
+        }
+        let now = Instant::now();
+        let window = self.config.window;
+        let failures = self.failures.entry(peer_id).or_default();
+        while failures
+            .front()
+            .map(|at| now.duration_since(*at) >= window)
+            .unwrap_or(false)
+        {
+            failures.pop_front();
+        }
+        failures.push_back(now);
+        if failures.len() > self.config.max_failures as usize {
+            self.failures.remove(&peer_id);
+            self.banned.insert(peer_id, now + self.config.cooldown);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn cooldown(&self) -> Duration {
+        self.config.cooldown
+    }
+
+    pub fn is_banned(&self, peer_id: &PeerId) -> bool {
+        self.banned.contains_key(peer_id)
+    }
+
+    // Remove the peers for which the cooldown expired, and the failures that are outside of the window.
+    pub fn take_expired_bans(&mut self) -> Vec<PeerId> {
+        let now = Instant::now();
+        let window = self.config.window;
+        self.failures.retain(|_, failures| {
+            failures
+                .back()
+                .map(|at| now.duration_since(*at) < window)
+                .unwrap_or(false)
+        });
+        let expired: Vec<PeerId> = self
+            .banned
+            .iter()
+            .filter(|(_, until)| **until <= now)
+            .map(|(peer_id, _)| *peer_id)
+            .collect();
+        for peer_id in &expired {
+            self.banned.remove(peer_id);
+        }
+        expired
+    }
+
+    // Stop tracking the ban and the failures of the peer, e.g. because it was explicitly banned or unbanned.
+    pub fn remove_ban(&mut self, peer_id: &PeerId) {
+        self.banned.remove(peer_id);
+        self.failures.remove(peer_id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ban_above_max_failures() {
+        let mut bans = FailureBans::new(FailureBanConfig {
+            max_failures: 2,
+            window: Duration::from_secs(60),
+            cooldown: Duration::from_secs(0),
+        });
+        let peer_id = PeerId::random();
+        assert!(!bans.record_failure(peer_id));
+        assert!(!bans.record_failure(peer_id));
+        assert!(!bans.record_failure(PeerId::random()));
+        assert!(bans.record_failure(peer_id));
+        assert!(bans.is_banned(&peer_id));
+        // already banned
+        assert!(!bans.record_failure(peer_id));
+
+        assert_eq!(bans.take_expired_bans(), vec![peer_id]);
+        assert!(!bans.is_banned(&peer_id));
+        assert!(!bans.record_failure(peer_id));
+    }
+
+    #[test]
+    fn failures_expire_after_window() {
+        let mut bans = FailureBans::new(FailureBanConfig {
+            max_failures: 1,
+            window: Duration::from_millis(50),
+            cooldown: Duration::from_secs(60),
+        });
+        let peer_id = PeerId::random();
+        assert!(!bans.record_failure(peer_id));
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(!bans.record_failure(peer_id));
+        assert!(bans.record_failure(peer_id));
+        assert!(bans.take_expired_bans().is_empty());
+        assert!(bans.is_banned(&peer_id));
+    }
+}
@@ -1,13 +1,20 @@
 // Copyright 2020-2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
+use super::state::serde_peer_id;
 pub use communication_macros::RequestPermissions;
+use core::convert::TryFrom;
 use libp2p::PeerId;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
 
 /// The permission value for request variants.
 /// It is a  bit that is set at a certain index, therefore the value is always a power of 2.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "u32")]
 pub struct PermissionValue(u32);
 
 impl PermissionValue {
@@ -27,6 +34,18 @@ impl PermissionValue {
     }
 }
 
+impl TryFrom<u32> for PermissionValue {
+    type Error = String;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        if value.is_power_of_two() {
+            Ok(PermissionValue(value))
+        } else {
+            Err(format!("Permission value {} is not a power of 2", value))
+        }
+    }
+}
+
 impl PartialEq<u32> for PermissionValue {
     fn eq(&self, other: &u32) -> bool {
         self.value() == *other
@@ -35,7 +54,7 @@ impl PartialEq<u32> for PermissionValue {
 
 /// The sum of allowed permissions.
 /// This is using the same concepts as e.g. permission values in Unix systems.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct FirewallPermission(u32);
 
 impl FirewallPermission {
@@ -66,6 +85,16 @@ impl FirewallPermission {
         self.value() & v.value() != 0
     }
 
+    /// Check if no value is allowed, i.e. if all requests are rejected.
+    pub fn is_none(&self) -> bool {
+        self.value() == 0
+    }
+
+    /// Check if all values are allowed, i.e. if all requests are permitted.
+    pub fn is_all(&self) -> bool {
+        self.value() == u32::MAX
+    }
+
     fn value(&self) -> u32 {
         self.0
     }
@@ -101,7 +130,7 @@ impl<T: VariantPermission + Clone> ToPermissionVariants<T> for T {
 }
 
 /// The direction of a [`CommunicationRequest::RequestMsg`] that firewall receives.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum RequestDirection {
     /// Incoming request from a remote peer to the local system.
     In,
@@ -109,12 +138,59 @@ pub enum RequestDirection {
     Out,
 }
 
+/// Maximum frequency with which requests of a permission are permitted per peer.
+/// Each peer may send, respectively receive, at most `max_requests` requests that require the permission within a
+/// sliding window of the duration `window`, requests that exceed the rate are rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PermissionRate {
+    /// Maximum number of requests within the window.
+    pub max_requests: u32,
+    /// Duration of the sliding window.
+    pub window: Duration,
+}
+
+/// The rule of the firewall that applies to a peer.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum MatchedRule {
+    /// The rule that was set specifically for the peer.
+    Specific(FirewallPermission),
+    /// The default rule, since no specific rule is set for the peer.
+    Default(FirewallPermission),
+}
+
+/// Decision of the firewall for a request, as returned for [`CommunicationRequest::CheckPermission`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FirewallDecision {
+    /// Whether the request would be permitted, which requires that the matched rule permits it and that the
+    /// [`PermissionRate`] of its permission is not exceeded. In [`FirewallMode::Audit`], requests that are not
+    /// permitted are still sent, respectively forwarded, but reported to the observer.
+    pub is_permitted: bool,
+    /// The rule that was matched for the peer.
+    pub rule: MatchedRule,
+    /// Whether the request would exceed the rate of its permission.
+    pub is_rate_limited: bool,
+}
+
+/// Determines if the firewall rules are enforced.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FirewallMode {
+    /// Requests that are not permitted by the rules are rejected.
+    Enforce,
+    /// All requests are allowed, but for each request that the rules would reject, a
+    /// [`CommunicationEvent::FirewallWouldBlock`] is emitted. This allows testing new rules with real traffic.
+    Audit,
+}
+
 /// Configure the firewall.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum FirewallRule {
     /// Set new rules either for specific peers, or the default rule.
+    /// The permission replaces the prior rule of the peers, respectively the prior default rule. Contrary to
+    /// [`FirewallRule::AddPermissions`] and [`FirewallRule::RemovePermissions`], the resulting rule therefore does
+    /// not depend on the prior rule, so that it is deterministic even if rules are changed concurrently.
     SetRules {
         direction: RequestDirection,
+        #[serde(with = "serde_peer_id::vec")]
         peers: Vec<PeerId>,
         set_default: bool,
         permission: FirewallPermission,
@@ -122,6 +198,7 @@ pub enum FirewallRule {
     /// Add specific permissions for certain peers and optionally also to the default rule.
     AddPermissions {
         direction: RequestDirection,
+        #[serde(with = "serde_peer_id::vec")]
         peers: Vec<PeerId>,
         change_default: bool,
         permissions: Vec<PermissionValue>,
@@ -129,15 +206,37 @@ pub enum FirewallRule {
     /// Remove specific permissions from certain peers and optionally also from the default rule.
     RemovePermissions {
         direction: RequestDirection,
+        #[serde(with = "serde_peer_id::vec")]
         peers: Vec<PeerId>,
         change_default: bool,
         permissions: Vec<PermissionValue>,
     },
     /// Remove a rule for a specific peer, which results in using the default rule for that peer.
     RemoveRule {
+        #[serde(with = "serde_peer_id::vec")]
         peers: Vec<PeerId>,
         direction: RequestDirection,
     },
+    /// Limit the frequency with which each peer may send or receive requests that require the permission, in addition
+    /// to the permission rules. If the rate is none, the limit for the permission is removed.
+    SetPermissionRate {
+        direction: RequestDirection,
+        permission: PermissionValue,
+        rate: Option<PermissionRate>,
+    },
+    /// Set the permission for requests that are forwarded by a relay, which is checked in addition to the rules of
+    /// their source, e.g. to only trust the relay to forward a subset of the requests. Relayed requests are only
+    /// permitted if both the relay permission and the rule of the source permit them. If the permission is none, a
+    /// relay may forward all requests that the rule of the source permits.
+    SetRelayPermission(Option<FirewallPermission>),
+    /// Set whether the rules are enforced or only audited.
+    SetMode(FirewallMode),
+    /// Set the default rule so that all requests in that direction are rejected.
+    /// Rules for specific peers are not affected.
+    DenyAll { direction: RequestDirection },
+    /// Set the default rule so that all requests in that direction are permitted.
+    /// Rules for specific peers are not affected.
+    AllowAll { direction: RequestDirection },
 }
 
 // Configuration of the firewall in the Swarm Task
@@ -151,6 +250,86 @@ pub(super) struct FirewallConfiguration {
     rules_in: HashMap<PeerId, FirewallPermission>,
     // Rules for outgoing request to specific peers.
     rules_out: HashMap<PeerId, FirewallPermission>,
+    // Whether the rules are enforced or only audited.
+    mode: FirewallMode,
+    // Rate limits of permissions for incoming and outgoing requests.
+    rates_in: PermissionRates,
+    rates_out: PermissionRates,
+    // Optional permission for incoming requests that are forwarded by a relay.
+    relay_permission: Option<FirewallPermission>,
+}
+
+// Rate limits of permissions, and the recent requests of each peer that required a rate limited permission.
+#[derive(Debug, Clone, Default)]
+struct PermissionRates {
+    limits: HashMap<PermissionValue, PermissionRate>,
+    requests: HashMap<(PeerId, PermissionValue), VecDeque<Instant>>,
+}
+
+impl PermissionRates {
+    fn set_rate(&mut self, permission: PermissionValue, rate: Option<PermissionRate>) {
+        match rate {
+            Some(rate) => {
+                self.limits.insert(permission, rate);
+            }
+            None => {
+                self.limits.remove(&permission);
+                self.requests.retain(|(_, value), _| *value != permission);
+            }
+        }
+    }
+
+    // Record the request if it is within the rate of the permission, otherwise the request is rejected.
+    fn try_record(&mut self, peer_id: PeerId, permission: PermissionValue) -> bool {
+        let rate = match self.limits.get(&permission) {
+            Some(rate) => *rate,
+            None => return true,
+        };
+        let now = Instant::now();
+        let requests = self.requests.entry((peer_id, permission)).or_default();
+        while matches!(requests.front(), Some(instant) if now.duration_since(*instant) >= rate.window) {
+            requests.pop_front();
+        }
+        if requests.len() >= rate.max_requests as usize {
+            return false;
+        }
+        requests.push_back(now);
+        true
+    }
+
+    // Check whether another request would be within the rate of the permission, without recording it.
+    fn is_within_rate(&self, peer_id: &PeerId, permission: &PermissionValue) -> bool {
+        let rate = match self.limits.get(permission) {
+            Some(rate) => rate,
+            None => return true,
+        };
+        let recent = self
+            .requests
+            .get(&(*peer_id, *permission))
+            .map(|requests| {
+                requests
+                    .iter()
+                    .filter(|instant| instant.elapsed() < rate.window)
+                    .count()
+            })
+            .unwrap_or(0);
+        recent < rate.max_requests as usize
+    }
+
+    // Remove the recorded requests that are outside of the window of their permission.
+    fn remove_expired(&mut self) {
+        let limits = &self.limits;
+        self.requests.retain(|(_, permission), requests| {
+            let window = match limits.get(permission) {
+                Some(rate) => rate.window,
+                None => return false,
+            };
+            while matches!(requests.front(), Some(instant) if instant.elapsed() >= window) {
+                requests.pop_front();
+            }
+            !requests.is_empty()
+        });
+    }
 }
 
 impl Default for FirewallConfiguration {
@@ -160,6 +339,10 @@ impl Default for FirewallConfiguration {
             default_out: FirewallPermission::all(),
             rules_in: HashMap::new(),
             rules_out: HashMap::new(),
+            mode: FirewallMode::Enforce,
+            rates_in: PermissionRates::default(),
+            rates_out: PermissionRates::default(),
+            relay_permission: None,
         }
     }
 }
@@ -171,6 +354,10 @@ impl FirewallConfiguration {
             default_out,
             rules_in: HashMap::new(),
             rules_out: HashMap::new(),
+            mode: FirewallMode::Enforce,
+            rates_in: PermissionRates::default(),
+            rates_out: PermissionRates::default(),
+            relay_permission: None,
         }
     }
 
@@ -181,6 +368,14 @@ impl FirewallConfiguration {
         }
     }
 
+    pub fn get_mode(&self) -> FirewallMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: FirewallMode) {
+        self.mode = mode;
+    }
+
     pub fn set_default(&mut self, direction: &RequestDirection, default: FirewallPermission) {
         match direction {
             RequestDirection::In => self.default_in = default,
@@ -206,6 +401,13 @@ impl FirewallConfiguration {
         }
     }
 
+    pub fn get_rules(&self, direction: &RequestDirection) -> &HashMap<PeerId, FirewallPermission> {
+        match direction {
+            RequestDirection::In => &self.rules_in,
+            RequestDirection::Out => &self.rules_out,
+        }
+    }
+
     pub fn remove_rule(&mut self, peer_id: &PeerId, direction: &RequestDirection) {
         match direction {
             RequestDirection::In => {
@@ -217,6 +419,30 @@ impl FirewallConfiguration {
         }
     }
 
+    pub fn set_rate(
+        &mut self,
+        direction: &RequestDirection,
+        permission: PermissionValue,
+        rate: Option<PermissionRate>,
+    ) {
+        match direction {
+            RequestDirection::In => self.rates_in.set_rate(permission, rate),
+            RequestDirection::Out => self.rates_out.set_rate(permission, rate),
+        }
+    }
+
+    pub fn set_relay_permission(&mut self, permission: Option<FirewallPermission>) {
+        self.relay_permission = permission;
+    }
+
+    // Check whether a relay may forward the request, independently of the rule for the source of the request.
+    pub fn is_relay_permitted<Req: ToPermissionVariants<P>, P: VariantPermission>(&self, variant: Req) -> bool {
+        match self.relay_permission {
+            Some(permission) => permission.permits(&variant.to_permissioned().permission()),
+            None => true,
+        }
+    }
+
     // Uses a rule if one is specified for that peer, otherwise use default.
     // The firewall permission is checked for the required permissions of the specific request variant.
     pub fn is_permitted<Req: ToPermissionVariants<P>, P: VariantPermission>(
@@ -231,4 +457,60 @@ impl FirewallConfiguration {
         };
         permissions.permits(&variant.to_permissioned().permission())
     }
+
+    // The rule for the peer, or the default rule if no specific one is set.
+    pub fn matched_rule(&self, peer_id: &PeerId, direction: &RequestDirection) -> MatchedRule {
+        let (rules, default) = match direction {
+            RequestDirection::In => (&self.rules_in, self.default_in),
+            RequestDirection::Out => (&self.rules_out, self.default_out),
+        };
+        match rules.get(peer_id) {
+            Some(permission) => MatchedRule::Specific(*permission),
+            None => MatchedRule::Default(default),
+        }
+    }
+
+    // Check the request against the rules and rates of the firewall, without recording it for the rate.
+    pub fn check_permission<Req: ToPermissionVariants<P>, P: VariantPermission>(
+        &self,
+        variant: Req,
+        peer_id: PeerId,
+        direction: &RequestDirection,
+    ) -> FirewallDecision {
+        let rates = match direction {
+            RequestDirection::In => &self.rates_in,
+            RequestDirection::Out => &self.rates_out,
+        };
+        let rule = self.matched_rule(&peer_id, direction);
+        let permission = variant.to_permissioned().permission();
+        let is_rate_limited = !rates.is_within_rate(&peer_id, &permission);
+        let is_permitted = match rule {
+            MatchedRule::Specific(rule) | MatchedRule::Default(rule) => rule.permits(&permission) && !is_rate_limited,
+        };
+        FirewallDecision {
+            is_permitted,
+            rule,
+            is_rate_limited,
+        }
+    }
+
+    // Check whether the permitted request is within the rate of its permission, and record it if so.
+    pub fn try_record_rate<Req: ToPermissionVariants<P>, P: VariantPermission>(
+        &mut self,
+        variant: Req,
+        peer_id: PeerId,
+        direction: &RequestDirection,
+    ) -> bool {
+        let permission = variant.to_permissioned().permission();
+        match direction {
+            RequestDirection::In => self.rates_in.try_record(peer_id, permission),
+            RequestDirection::Out => self.rates_out.try_record(peer_id, permission),
+        }
+    }
+
+    // Remove recorded requests that are outside of the window of their rate.
+    pub fn remove_expired_rates(&mut self) {
+        self.rates_in.remove_expired();
+        self.rates_out.remove_expired();
+    }
 }
@@ -0,0 +1,122 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::types::{
+    CommunicationRequest, CommunicationResults, ConnectPeerError, ConnectionInfo, EstablishedConnection, KeepAlive,
+    RequestMessageError, RequestMsgBuilder, StartListeningError,
+};
+use core::marker::PhantomData;
+use libp2p::{Multiaddr, PeerId};
+use riker::actors::*;
+use stronghold_utils::ask;
+
+/// Information about the local swarm, as returned by [`CommunicationHandle::swarm_info`].
+#[derive(Debug, Clone)]
+pub struct SwarmInfo {
+    /// The local peer id.
+    pub peer_id: PeerId,
+    /// The listening addresses of the local system.
+    pub listeners: Vec<Multiaddr>,
+    /// Established connections.
+    pub connections: Vec<(PeerId, EstablishedConnection)>,
+}
+
+/// Async interface for a running [`CommunicationActor`].
+/// Each method sends the according [`CommunicationRequest`] to the actor and awaits the [`CommunicationResults`], so
+/// that the actor can be used without implementing the ask pattern. Requests that don't have a dedicated method can
+/// be sent with [`CommunicationHandle::ask`].
+#[derive(Clone)]
+pub struct CommunicationHandle<Req, Res, ClientMsg>
+where
+    Req: Message,
+    Res: Message,
+    ClientMsg: Message,
+{
+    system: ActorSystem,
+    actor: ActorRef<CommunicationRequest<Req, ClientMsg>>,
+    _marker: PhantomData<Res>,
+}
+
+impl<Req, Res, ClientMsg> CommunicationHandle<Req, Res, ClientMsg>
+where
+    Req: Message,
+    Res: Message,
+    ClientMsg: Message,
+{
+    /// Create a handle for the communication actor that was spawned in the actor system.
+    pub fn new(system: ActorSystem, actor: ActorRef<CommunicationRequest<Req, ClientMsg>>) -> Self {
+        CommunicationHandle {
+            system,
+            actor,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Send a request to the actor and await the result.
+    pub async fn ask(&self, request: CommunicationRequest<Req, ClientMsg>) -> CommunicationResults<Res> {
+        ask(&self.system, &self.actor, request).await
+    }
+
+    /// Send a request to a remote peer and await the response.
+    pub async fn send_request(&self, peer_id: PeerId, request: Req) -> Result<Res, RequestMessageError> {
+        self.send(RequestMsgBuilder::new(peer_id, request)).await
+    }
+
+    /// Send a request with optional parameters to a remote peer and await the response.
+    pub async fn send(&self, request: RequestMsgBuilder<Req>) -> Result<Res, RequestMessageError> {
+        match self.ask(request.build()).await {
+            CommunicationResults::RequestMsgResult(res) => res,
+            _ => unreachable!("Invalid result of the communication actor."),
+        }
+    }
+
+    /// Connect to a remote peer, which is dialed via `addr` if no address for it is known.
+    pub async fn connect(
+        &self,
+        peer_id: PeerId,
+        addr: Multiaddr,
+        keep_alive: KeepAlive,
+    ) -> Result<ConnectionInfo, ConnectPeerError> {
+        let request = CommunicationRequest::EstablishConnection {
+            addr,
+            peer_id,
+            keep_alive,
+        };
+        match self.ask(request).await {
+            CommunicationResults::EstablishConnectionResult(res) => res,
+            _ => unreachable!("Invalid result of the communication actor."),
+        }
+    }
+
+    /// Start listening on the address, or on an OS assigned port if none is provided.
+    pub async fn start_listening(&self, addr: Option<Multiaddr>) -> Result<Multiaddr, StartListeningError> {
+        match self.ask(CommunicationRequest::StartListening(addr)).await {
+            CommunicationResults::StartListeningResult(res) => res,
+            _ => unreachable!("Invalid result of the communication actor."),
+        }
+    }
+
+    /// Get the TCP ports that the swarm is listening on, e.g. to register the local peer at a service discovery.
+    pub async fn listening_ports(&self) -> Vec<u16> {
+        match self.ask(CommunicationRequest::GetListeningPorts).await {
+            CommunicationResults::ListeningPorts(ports) => ports,
+            _ => unreachable!("Invalid result of the communication actor."),
+        }
+    }
+
+    /// Get the local peer id, listeners and established connections of the swarm.
+    pub async fn swarm_info(&self) -> SwarmInfo {
+        match self.ask(CommunicationRequest::GetSwarmInfo).await {
+            CommunicationResults::SwarmInfo {
+                peer_id,
+                listeners,
+                connections,
+            } => SwarmInfo {
+                peer_id,
+                listeners,
+                connections,
+            },
+            _ => unreachable!("Invalid result of the communication actor."),
+        }
+    }
+}
@@ -0,0 +1,197 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use libp2p::{Multiaddr, PeerId};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Backoff between the dials of a connection that is maintained with
+/// [`CommunicationRequest::MaintainConnection`](super::CommunicationRequest::MaintainConnection).
+/// The delay after the first failed dial is `initial`, and it doubles with each further failure up to `max`.
+#[derive(Debug, Clone, Copy)]
+pub struct MaintainBackoff {
+    /// Delay after the first failed dial.
+    pub initial: Duration,
+    /// Max delay between two dials.
+    pub max: Duration,
+}
+
+impl Default for MaintainBackoff {
+    fn default() -> Self {
+        MaintainBackoff {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(60),
+        }
+    }
+}
+
+/// State of a maintained connection, as emitted in
+/// [`CommunicationEvent::MaintainedConnection`](super::CommunicationEvent::MaintainedConnection).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaintainedState {
+    /// A connection to the peer was established.
+    Connected,
+    /// The addresses of the peer are dialed, `attempt` counts the dials since the connection was lost.
+    Dialing { attempt: u32 },
+    /// Dialing failed, the peer is dialed again after the delay.
+    Backoff { delay: Duration },
+}
+
+struct MaintainedPeer {
+    addrs: Vec<Multiaddr>,
+    is_connected: bool,
+    // number of dials since the connection was lost
+    attempts: u32,
+    // point in time at which the peer should be dialed next, none while it is connected or currently dialed
+    next_dial: Option<Instant>,
+}
+
+// Peers to which a connection should be kept until the user stops maintaining it, with the state of re-dialing them.
+pub(super) struct MaintainedConnections {
+    backoff: MaintainBackoff,
+    peers: HashMap<PeerId, MaintainedPeer>,
+}
+
+impl MaintainedConnections {
+    pub fn new(backoff: MaintainBackoff) -> Self {
+        MaintainedConnections {
+            backoff,
+            peers: HashMap::new(),
+        }
+    }
+
+    // Start maintaining the connection to the peer, a peer that is not connected is dialed right away.
+    // If the peer is maintained already, only the addresses are replaced.
+    pub fn insert(&mut self, peer_id: PeerId, addrs: Vec<Multiaddr>, is_connected: bool) {
+        if let Some(peer) = self.peers.get_mut(&peer_id) {
+            peer.addrs = addrs;
+            return;
+        }
+        let peer = MaintainedPeer {
+            addrs,
+            is_connected,
+            attempts: 0,
+            next_dial: if is_connected { None } else { Some(Instant::now()) },
+        };
+        self.peers.insert(peer_id, peer);
+    }
+
+    // Stop maintaining the connection, returns false if the peer was not maintained.
+    pub fn remove(&mut self, peer_id: &PeerId) -> bool {
+        self.peers.remove(peer_id).is_some()
+    }
+
+    pub fn addrs(&self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.peers
+            .get(peer_id)
+            .map(|peer| peer.addrs.clone())
+            .unwrap_or_default()
+    }
+
+    // The point in time at which the next peer should be dialed.
+    pub fn next_dial(&self) -> Option<Instant> {
+        self.peers.values().filter_map(|peer| peer.next_dial).min()
+    }
+
+    // Take the peers that should be dialed now, with the number of the attempt.
+    pub fn take_due(&mut self) -> Vec<(PeerId, u32)> {
+        let now = Instant::now();
+        let mut due = Vec::new();
+        for (peer_id, peer) in self.peers.iter_mut() {
+            if peer.next_dial.map(|at| at <= now).unwrap_or(false) {
+                peer.next_dial = None;
+                peer.attempts += 1;
+                due.push((*peer_id, peer.attempts));
+            }
+        }
+        due
+    }
+
+    // Mark the peer as connected, returns true if it is maintained and was not connected before.
+    pub fn set_connected(&mut self, peer_id: &PeerId) -> bool {
+        match self.peers.get_mut(peer_id) {
+            Some(peer) if !peer.is_connected => {
+                peer.is_connected = true;
+                peer.attempts = 0;
+                peer.next_dial = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Dial the peer right away once all connections to it closed, returns true if it is maintained.
+    pub fn set_disconnected(&mut self, peer_id: &PeerId) -> bool {
+        match self.peers.get_mut(peer_id) {
+            Some(peer) if peer.is_connected => {
+                peer.is_connected = false;
+                peer.next_dial = Some(Instant::now());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Schedule the next dial after dialing all addresses of the peer failed.
+    // Returns the delay if the peer is maintained and was currently dialed.
+    pub fn dial_failed(&mut self, peer_id: &PeerId) -> Option<Duration> {
+        let peer = self.peers.get_mut(peer_id)?;
+        if peer.is_connected || peer.next_dial.is_some() {
+            return None;
+        }
+        let factor = 2u32.saturating_pow(peer.attempts.saturating_sub(1));
+        let delay = self
+            .backoff
+            .initial
+            .checked_mul(factor)
+            .map(|delay| delay.min(self.backoff.max))
+            .unwrap_or(self.backoff.max);
+        peer.next_dial = Some(Instant::now() + delay);
+        Some(delay)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let mut maintained = MaintainedConnections::new(MaintainBackoff {
+            initial: Duration::from_millis(0),
+            max: Duration::from_secs(1),
+        });
+        let peer_id = PeerId::random();
+        maintained.insert(peer_id, Vec::new(), false);
+        assert_eq!(maintained.take_due(), vec![(peer_id, 1)]);
+        assert_eq!(maintained.dial_failed(&peer_id), Some(Duration::from_millis(0)));
+        // not dialed at the moment
+        assert_eq!(maintained.dial_failed(&peer_id), None);
+        assert_eq!(maintained.take_due(), vec![(peer_id, 2)]);
+
+        maintained.backoff.initial = Duration::from_millis(400);
+        assert_eq!(maintained.dial_failed(&peer_id), Some(Duration::from_millis(800)));
+        maintained.peers.get_mut(&peer_id).unwrap().attempts = 40;
+        maintained.peers.get_mut(&peer_id).unwrap().next_dial = None;
+        assert_eq!(maintained.dial_failed(&peer_id), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn redial_after_disconnect() {
+        let mut maintained = MaintainedConnections::new(MaintainBackoff::default());
+        let peer_id = PeerId::random();
+        maintained.insert(peer_id, Vec::new(), true);
+        assert!(maintained.next_dial().is_none());
+        assert!(!maintained.set_connected(&peer_id));
+
+        assert!(maintained.set_disconnected(&peer_id));
+        assert_eq!(maintained.take_due(), vec![(peer_id, 1)]);
+        assert!(maintained.set_connected(&peer_id));
+        assert!(maintained.dial_failed(&peer_id).is_none());
+
+        assert!(maintained.remove(&peer_id));
+        assert!(!maintained.set_disconnected(&peer_id));
+    }
+}
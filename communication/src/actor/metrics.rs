@@ -0,0 +1,360 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::state::serde_peer_id;
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, time::Duration};
+
+#[cfg(feature = "prometheus")]
+use core::fmt::{Display, Write};
+
+/// Metrics about the requests and connections of the [`CommunicationActor`], as returned for
+/// [`CommunicationRequest::GetMetrics`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SwarmMetrics {
+    /// Number of outbound requests that passed the local firewall and were sent to a remote peer.
+    pub outbound_requests: u64,
+    /// Number of outbound requests for which no response was received.
+    pub outbound_failures: u64,
+    /// Number of outbound requests that were answered with the result of an identical request that was in flight,
+    /// instead of being sent.
+    pub outbound_coalesced: u64,
+    /// Number of outbound requests that are currently waiting for a response.
+    pub outbound_in_flight: usize,
+    /// Accumulated duration between sending an outbound request and receiving the response.
+    pub outbound_latency_sum: Duration,
+    /// Maximum duration between sending an outbound request and receiving the response.
+    pub outbound_latency_max: Duration,
+    /// Number of inbound requests that targeted the local peer and were accepted by the firewall.
+    pub inbound_requests: u64,
+    /// Number of inbound requests that were rejected because the queue of requests that arrived while the actor was
+    /// blocked was full.
+    pub inbound_rejected_busy: u64,
+    /// Maximum number of inbound requests that were queued at the same time while the actor was blocked.
+    pub inbound_queued_max: usize,
+    /// Number of inbound requests that are currently handled by the client.
+    pub inbound_in_flight: usize,
+    /// Number of inbound requests that were rejected because the circuit breaker of the client was open.
+    pub inbound_rejected_breaker: u64,
+    /// Number of inbound requests that were dropped because the target of the envelope was not the local peer.
+    pub inbound_misrouted: u64,
+    /// Number of outbound requests that were rejected by the local firewall.
+    pub firewall_blocked_out: u64,
+    /// Number of inbound requests that were rejected by the local firewall.
+    pub firewall_blocked_in: u64,
+    /// Number of connections that were established since the actor started.
+    pub connections_established: u64,
+    /// Number of connections that were closed since the actor started.
+    pub connections_closed: u64,
+    /// Number of connections that were refused because a limit of the `ConnectionLimitsConfig` was reached.
+    pub connections_refused_limit: u64,
+    /// Number of peers that the swarm is currently connected to.
+    pub connected_peers: usize,
+    /// Number of outbound connections that are currently being dialed or upgraded, which can be limited with the
+    /// `max_pending_outgoing` of the `ConnectionLimitsConfig`.
+    pub dials_in_progress: usize,
+    /// Number of outbound requests per remote peer.
+    #[serde(with = "serde_peer_id::map")]
+    pub outbound_requests_per_peer: HashMap<PeerId, u64>,
+    /// Number of inbound requests per source peer that were accepted by the firewall.
+    #[serde(with = "serde_peer_id::map")]
+    pub inbound_requests_per_peer: HashMap<PeerId, u64>,
+    /// Number of iterations of the event loop of the swarm task in which an event was handled.
+    pub loop_iterations: u64,
+    /// Accumulated duration of handling the events in the event loop, which blocks the loop from handling other
+    /// events.
+    pub loop_busy_sum: Duration,
+    /// Maximum duration of handling an event in the event loop.
+    pub loop_busy_max: Duration,
+}
+
+impl SwarmMetrics {
+    // Record the outcome of an outbound request and the duration until it finished.
+    pub(super) fn record_outbound(&mut self, peer_id: PeerId, duration: Duration, is_success: bool) {
+        self.outbound_requests += 1;
+        *self.outbound_requests_per_peer.entry(peer_id).or_insert(0) += 1;
+        if is_success {
+            self.outbound_latency_sum += duration;
+            if duration > self.outbound_latency_max {
+                self.outbound_latency_max = duration;
+            }
+        } else {
+            self.outbound_failures += 1;
+        }
+    }
+
+    // Record an inbound request from a source peer that is forwarded to the client.
+    pub(super) fn record_inbound(&mut self, source: PeerId) {
+        self.inbound_requests += 1;
+        *self.inbound_requests_per_peer.entry(source).or_insert(0) += 1;
+    }
+
+    // Record the duration of an iteration of the event loop.
+    pub(super) fn record_loop_iteration(&mut self, duration: Duration) {
+        self.loop_iterations += 1;
+        self.loop_busy_sum += duration;
+        if duration > self.loop_busy_max {
+            self.loop_busy_max = duration;
+        }
+    }
+
+    /// Average duration of handling an event in the event loop.
+    pub fn loop_busy_avg(&self) -> Duration {
+        if self.loop_iterations == 0 {
+            return Duration::from_secs(0);
+        }
+        Duration::from_secs_f64(self.loop_busy_sum.as_secs_f64() / self.loop_iterations as f64)
+    }
+
+    /// Render the metrics in the Prometheus text exposition format.
+    /// The per-peer metrics are only included if `per_peer_labels` is set, since a label for each peer may result in
+    /// a high cardinality of the metrics.
+    #[cfg(feature = "prometheus")]
+    pub fn to_prometheus(&self, per_peer_labels: bool) -> String {
+        let mut out = String::new();
+        write_metric(
+            &mut out,
+            "outbound_requests_total",
+            "counter",
+            "Number of outbound requests that were sent to a remote peer.",
+            self.outbound_requests,
+        );
+        write_metric(
+            &mut out,
+            "outbound_failures_total",
+            "counter",
+            "Number of outbound requests for which no response was received.",
+            self.outbound_failures,
+        );
+        write_metric(
+            &mut out,
+            "outbound_coalesced_total",
+            "counter",
+            "Number of outbound requests that were answered with the result of an identical request.",
+            self.outbound_coalesced,
+        );
+        write_metric(
+            &mut out,
+            "outbound_latency_seconds_sum",
+            "counter",
+            "Accumulated duration of successful outbound requests.",
+            self.outbound_latency_sum.as_secs_f64(),
+        );
+        write_metric(
+            &mut out,
+            "outbound_latency_seconds_max",
+            "gauge",
+            "Maximum duration of a successful outbound request.",
+            self.outbound_latency_max.as_secs_f64(),
+        );
+        write_metric(
+            &mut out,
+            "inbound_requests_total",
+            "counter",
+            "Number of inbound requests that targeted the local peer and were accepted by the firewall.",
+            self.inbound_requests,
+        );
+        write_metric(
+            &mut out,
+            "inbound_rejected_busy_total",
+            "counter",
+            "Number of inbound requests that were rejected because the queue was full.",
+            self.inbound_rejected_busy,
+        );
+        write_metric(
+            &mut out,
+            "inbound_queued_max",
+            "gauge",
+            "Maximum number of inbound requests that were queued at the same time.",
+            self.inbound_queued_max,
+        );
+        write_metric(
+            &mut out,
+            "inbound_rejected_breaker_total",
+            "counter",
+            "Number of inbound requests that were rejected because the client breaker was open.",
+            self.inbound_rejected_breaker,
+        );
+        write_metric(
+            &mut out,
+            "inbound_misrouted_total",
+            "counter",
+            "Number of inbound requests that were dropped because they targeted another peer.",
+            self.inbound_misrouted,
+        );
+        write_metric(
+            &mut out,
+            "firewall_blocked_out_total",
+            "counter",
+            "Number of outbound requests that were rejected by the local firewall.",
+            self.firewall_blocked_out,
+        );
+        write_metric(
+            &mut out,
+            "firewall_blocked_in_total",
+            "counter",
+            "Number of inbound requests that were rejected by the local firewall.",
+            self.firewall_blocked_in,
+        );
+        write_metric(
+            &mut out,
+            "connections_established_total",
+            "counter",
+            "Number of connections that were established.",
+            self.connections_established,
+        );
+        write_metric(
+            &mut out,
+            "connections_closed_total",
+            "counter",
+            "Number of connections that were closed.",
+            self.connections_closed,
+        );
+        write_metric(
+            &mut out,
+            "connections_refused_limit_total",
+            "counter",
+            "Number of connections that were refused because a connection limit was reached.",
+            self.connections_refused_limit,
+        );
+        write_metric(
+            &mut out,
+            "inbound_in_flight",
+            "gauge",
+            "Number of inbound requests that are currently handled by the client.",
+            self.inbound_in_flight,
+        );
+        write_metric(
+            &mut out,
+            "outbound_in_flight",
+            "gauge",
+            "Number of outbound requests that are waiting for a response.",
+            self.outbound_in_flight,
+        );
+        write_metric(
+            &mut out,
+            "connected_peers",
+            "gauge",
+            "Number of currently connected peers.",
+            self.connected_peers,
+        );
+        write_metric(
+            &mut out,
+            "dials_in_progress",
+            "gauge",
+            "Number of outbound connections that are currently being dialed.",
+            self.dials_in_progress,
+        );
+        write_metric(
+            &mut out,
+            "loop_iterations_total",
+            "counter",
+            "Number of handled events in the event loop.",
+            self.loop_iterations,
+        );
+        write_metric(
+            &mut out,
+            "loop_busy_seconds_sum",
+            "counter",
+            "Accumulated duration of handling events in the event loop.",
+            self.loop_busy_sum.as_secs_f64(),
+        );
+        write_metric(
+            &mut out,
+            "loop_busy_seconds_max",
+            "gauge",
+            "Maximum duration of handling an event in the event loop.",
+            self.loop_busy_max.as_secs_f64(),
+        );
+        if per_peer_labels {
+            write_peer_metric(
+                &mut out,
+                "outbound_peer_requests_total",
+                "Number of outbound requests per remote peer.",
+                &self.outbound_requests_per_peer,
+            );
+            write_peer_metric(
+                &mut out,
+                "inbound_peer_requests_total",
+                "Number of inbound requests per source peer that were accepted by the firewall.",
+                &self.inbound_requests_per_peer,
+            );
+        }
+        out
+    }
+}
+
+#[cfg(feature = "prometheus")]
+const PROMETHEUS_PREFIX: &str = "stronghold_communication";
+
+#[cfg(feature = "prometheus")]
+fn write_metric(out: &mut String, name: &str, metric_type: &str, help: &str, value: impl Display) {
+    let _ = writeln!(out, "# HELP {}_{} {}", PROMETHEUS_PREFIX, name, help);
+    let _ = writeln!(out, "# TYPE {}_{} {}", PROMETHEUS_PREFIX, name, metric_type);
+    let _ = writeln!(out, "{}_{} {}", PROMETHEUS_PREFIX, name, value);
+}
+
+#[cfg(feature = "prometheus")]
+fn write_peer_metric(out: &mut String, name: &str, help: &str, values: &HashMap<PeerId, u64>) {
+    let _ = writeln!(out, "# HELP {}_{} {}", PROMETHEUS_PREFIX, name, help);
+    let _ = writeln!(out, "# TYPE {}_{} counter", PROMETHEUS_PREFIX, name);
+    for (peer_id, value) in values {
+        let _ = writeln!(
+            out,
+            "{}_{}{{peer_id=\"{}\"}} {}",
+            PROMETHEUS_PREFIX, name, peer_id, value
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn record_requests() {
+        let mut metrics = SwarmMetrics::default();
+        let peer_id = PeerId::random();
+        metrics.record_outbound(peer_id, Duration::from_millis(20), true);
+        metrics.record_outbound(peer_id, Duration::from_millis(10), true);
+        metrics.record_outbound(peer_id, Duration::from_secs(3), false);
+        metrics.record_inbound(peer_id);
+        assert_eq!(metrics.outbound_requests, 3);
+        assert_eq!(metrics.outbound_failures, 1);
+        assert_eq!(metrics.outbound_latency_sum, Duration::from_millis(30));
+        assert_eq!(metrics.outbound_latency_max, Duration::from_millis(20));
+        assert_eq!(metrics.outbound_requests_per_peer.get(&peer_id), Some(&3));
+        assert_eq!(metrics.inbound_requests, 1);
+    }
+
+    #[test]
+    fn record_loop_iterations() {
+        let mut metrics = SwarmMetrics::default();
+        assert_eq!(metrics.loop_busy_avg(), Duration::from_secs(0));
+        metrics.record_loop_iteration(Duration::from_millis(10));
+        metrics.record_loop_iteration(Duration::from_millis(30));
+        assert_eq!(metrics.loop_iterations, 2);
+        assert_eq!(metrics.loop_busy_max, Duration::from_millis(30));
+        assert_eq!(metrics.loop_busy_avg(), Duration::from_millis(20));
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn prometheus_peer_labels() {
+        let mut metrics = SwarmMetrics::default();
+        let peer_id = PeerId::random();
+        metrics.record_inbound(peer_id);
+
+        let rendered = metrics.to_prometheus(false);
+        assert!(rendered.contains("stronghold_communication_inbound_requests_total 1\n"));
+        assert!(!rendered.contains(&peer_id.to_string()));
+
+        let rendered = metrics.to_prometheus(true);
+        let line = format!(
+            "stronghold_communication_inbound_peer_requests_total{{peer_id=\"{}\"}} 1\n",
+            peer_id
+        );
+        assert!(rendered.contains(&line));
+    }
+}
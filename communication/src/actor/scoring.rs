@@ -0,0 +1,144 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use libp2p::PeerId;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Configuration for scoring remote peers based on their behaviour.
+/// Each peer starts with a score of 0, and the penalties are subtracted from the score upon misbehaviour.
+/// Once the score drops below the `ban_threshold`, the peer is automatically banned for the `ban_cooldown`.
+#[derive(Debug, Clone)]
+pub struct PeerScoringConfig {
+    /// Penalty for an incoming envelope with an invalid source peer id.
+    pub malformed_envelope_penalty: i32,
+    /// Penalty for an incoming request that was rejected by the firewall. Relayed requests are accounted to the relay
+    /// if the relay was not permitted to forward them, and are not penalized otherwise since their source can not be
+    /// verified.
+    pub firewall_block_penalty: i32,
+    /// Penalty for a failure on an inbound request, e.g. if the peer does not support the protocol.
+    /// Failures that were caused by the local system, e.g. timeouts of the client, and closed connections are not
+    /// penalized.
+    pub inbound_failure_penalty: i32,
+    /// Peers are banned if their score is below this threshold.
+    pub ban_threshold: i32,
+    /// Duration after which a banned peer is unbanned and its score is reset.
+    pub ban_cooldown: Duration,
+}
+
+impl Default for PeerScoringConfig {
+    fn default() -> Self {
+        PeerScoringConfig {
+            malformed_envelope_penalty: 20,
+            firewall_block_penalty: 5,
+            inbound_failure_penalty: 10,
+            ban_threshold: -100,
+            ban_cooldown: Duration::from_secs(600),
+        }
+    }
+}
+
+// Misbehaviour of a remote peer that results in a penalty.
+pub(super) enum Misbehaviour {
+    MalformedEnvelope,
+    FirewallBlock,
+    InboundFailure,
+}
+
+// Maintain the scores of remote peers, and the peers that are currently banned due to their score.
+pub(super) struct PeerScoring {
+    config: PeerScoringConfig,
+    scores: HashMap<PeerId, i32>,
+    // Peers that were banned due to their score, with the time at which they should be unbanned again.
+    banned: HashMap<PeerId, Instant>,
+}
+
+impl PeerScoring {
+    pub fn new(config: PeerScoringConfig) -> Self {
+        PeerScoring {
+            config,
+            scores: HashMap::new(),
+            banned: HashMap::new(),
+        }
+    }
+
+    // Subtract the penalty for the misbehaviour from the peer's score.
+    // Returns true if the peer dropped below the threshold and should be banned now.
+    pub fn penalize(&mut self, peer_id: PeerId, misbehaviour: Misbehaviour) -> bool {
+        let penalty = match misbehaviour {
+            Misbehaviour::MalformedEnvelope => self.config.malformed_envelope_penalty,
+            Misbehaviour::FirewallBlock => self.config.firewall_block_penalty,
+            Misbehaviour::InboundFailure => self.config.inbound_failure_penalty,
+        };
+        let score = self.scores.entry(peer_id).or_insert(0);
+        *score = score.saturating_sub(penalty);
+        if *score < self.config.ban_threshold && !self.banned.contains_key(&peer_id) {
+            self.banned.insert(peer_id, Instant::now() + self.config.ban_cooldown);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn is_banned(&self, peer_id: &PeerId) -> bool {
+        self.banned.contains_key(peer_id)
+    }
+
+    // Remove the peers for which the ban cooldown expired and reset their score.
+    pub fn take_expired_bans(&mut self) -> Vec<PeerId> {
+        let now = Instant::now();
+        let expired: Vec<PeerId> = self
+            .banned
+            .iter()
+            .filter(|(_, until)| **until <= now)
+            .map(|(peer_id, _)| *peer_id)
+            .collect();
+        for peer_id in &expired {
+            self.banned.remove(peer_id);
+            self.scores.remove(peer_id);
+        }
+        expired
+    }
+
+    // Stop tracking the ban of the peer, e.g. because it was explicitly banned or unbanned.
+    // If `reset_score` is set, the score of the peer is reset as well.
+    pub fn remove_ban(&mut self, peer_id: &PeerId, reset_score: bool) {
+        self.banned.remove(peer_id);
+        if reset_score {
+            self.scores.remove(peer_id);
+        }
+    }
+
+    pub fn scores(&self) -> Vec<(PeerId, i32)> {
+        self.scores.iter().map(|(peer_id, score)| (*peer_id, *score)).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ban_below_threshold() {
+        let config = PeerScoringConfig {
+            firewall_block_penalty: 40,
+            ban_threshold: -100,
+            ban_cooldown: Duration::from_secs(0),
+            ..PeerScoringConfig::default()
+        };
+        let mut scoring = PeerScoring::new(config);
+        let peer_id = PeerId::random();
+        assert!(!scoring.penalize(peer_id, Misbehaviour::FirewallBlock));
+        assert!(!scoring.penalize(peer_id, Misbehaviour::FirewallBlock));
+        assert!(scoring.penalize(peer_id, Misbehaviour::FirewallBlock));
+        // already banned
+        assert!(!scoring.penalize(peer_id, Misbehaviour::FirewallBlock));
+        assert_eq!(scoring.scores(), vec![(peer_id, -160)]);
+
+        assert_eq!(scoring.take_expired_bans(), vec![peer_id]);
+        assert!(scoring.scores().is_empty());
+        assert!(scoring.take_expired_bans().is_empty());
+    }
+}
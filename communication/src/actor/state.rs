@@ -0,0 +1,388 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{
+    firewall::{FirewallMode, FirewallPermission},
+    types::{KeepAlive, RelayConfig},
+};
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Serializable snapshot of the firewall, relay configuration, keep-alive connections and known addresses of a
+/// [`CommunicationActor`], as returned for [`CommunicationRequest::ExportState`].
+/// Importing the state with [`CommunicationRequest::ImportState`] brings another actor, e.g. a hot standby, into the
+/// same logical state. The local peer id is not part of the state, since it is derived from the keypair of the actor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommunicationState {
+    /// Default permission for inbound requests of peers without a specific rule.
+    pub firewall_default_in: FirewallPermission,
+    /// Default permission for outbound requests to peers without a specific rule.
+    pub firewall_default_out: FirewallPermission,
+    /// Whether the firewall rules are enforced or only audited.
+    pub firewall_mode: FirewallMode,
+    /// Relay that is used for requests to remote peers.
+    pub relay: RelayConfig,
+    /// Known addresses, keep-alive connections and firewall rules of remote peers.
+    pub peers: Vec<PeerState>,
+}
+
+/// State of a remote peer in the [`CommunicationState`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerState {
+    /// The remote peer.
+    #[serde(with = "serde_peer_id")]
+    pub peer_id: PeerId,
+    /// Known addresses of the peer.
+    pub addrs: Vec<Multiaddr>,
+    /// Keep-alive of the connection to the peer, if it is actively kept alive.
+    pub keep_alive: Option<KeepAliveState>,
+    /// Rule for inbound requests from the peer, if the default rule is not used for it.
+    pub firewall_in: Option<FirewallPermission>,
+    /// Rule for outbound requests to the peer, if the default rule is not used for it.
+    pub firewall_out: Option<FirewallPermission>,
+}
+
+impl PeerState {
+    /// State of a peer without addresses, keep-alive connection and firewall rules.
+    pub fn new(peer_id: PeerId) -> Self {
+        PeerState {
+            peer_id,
+            addrs: Vec::new(),
+            keep_alive: None,
+            firewall_in: None,
+            firewall_out: None,
+        }
+    }
+}
+
+/// Serializable [`KeepAlive`] of a connection.
+/// Limited keep-alives are stored as duration relative to the point in time at which the state was exported.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum KeepAliveState {
+    /// Keep alive for the remaining duration.
+    Limited { remaining: Duration },
+    /// Keep alive until one of the peers close the connection.
+    Unlimited,
+}
+
+impl KeepAliveState {
+    // Get the state of a keep-alive, returns none if the connection is not actively kept alive (anymore).
+    pub(super) fn from_keep_alive(keep_alive: &KeepAlive) -> Option<Self> {
+        match keep_alive {
+            KeepAlive::None => None,
+            KeepAlive::Limited { end } => {
+                let remaining = end.checked_duration_since(Instant::now())?;
+                Some(KeepAliveState::Limited { remaining })
+            }
+            KeepAlive::Unlimited => Some(KeepAliveState::Unlimited),
+        }
+    }
+}
+
+impl From<Option<KeepAliveState>> for KeepAlive {
+    fn from(state: Option<KeepAliveState>) -> Self {
+        state.map(KeepAlive::from).unwrap_or(KeepAlive::None)
+    }
+}
+
+impl From<KeepAlive> for Option<KeepAliveState> {
+    fn from(keep_alive: KeepAlive) -> Self {
+        KeepAliveState::from_keep_alive(&keep_alive)
+    }
+}
+
+impl From<KeepAliveState> for KeepAlive {
+    fn from(state: KeepAliveState) -> Self {
+        match state {
+            KeepAliveState::Limited { remaining } => KeepAlive::Limited {
+                end: Instant::now() + remaining,
+            },
+            KeepAliveState::Unlimited => KeepAlive::Unlimited,
+        }
+    }
+}
+
+// Serialize peer ids in their base58 string representation.
+pub(super) mod serde_peer_id {
+    use core::str::FromStr;
+    use libp2p::PeerId;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(peer_id: &PeerId, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&peer_id.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PeerId, D::Error> {
+        let peer_id = String::deserialize(deserializer)?;
+        PeerId::from_str(&peer_id).map_err(D::Error::custom)
+    }
+
+    fn parse<E: Error>(peer_id: &str) -> Result<PeerId, E> {
+        PeerId::from_str(peer_id).map_err(E::custom)
+    }
+
+    pub mod option {
+        use super::*;
+        use serde::Serialize;
+
+        pub fn serialize<S: Serializer>(peer_id: &Option<PeerId>, serializer: S) -> Result<S::Ok, S::Error> {
+            peer_id.map(|peer_id| peer_id.to_string()).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<PeerId>, D::Error> {
+            Option::<String>::deserialize(deserializer)?
+                .map(|peer_id| parse(&peer_id))
+                .transpose()
+        }
+    }
+
+    pub mod vec {
+        use super::*;
+        use serde::Serialize;
+
+        pub fn serialize<S: Serializer>(peers: &[PeerId], serializer: S) -> Result<S::Ok, S::Error> {
+            let peers: Vec<String> = peers.iter().map(|peer_id| peer_id.to_string()).collect();
+            peers.serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<PeerId>, D::Error> {
+            Vec::<String>::deserialize(deserializer)?
+                .iter()
+                .map(|peer_id| parse(peer_id))
+                .collect()
+        }
+    }
+
+    // Peer ids that are paired with a value, e.g. the score of a peer.
+    pub mod pairs {
+        use super::*;
+        use serde::Serialize;
+
+        pub fn serialize<S: Serializer, T: Serialize>(pairs: &[(PeerId, T)], serializer: S) -> Result<S::Ok, S::Error> {
+            let pairs: Vec<(String, &T)> = pairs
+                .iter()
+                .map(|(peer_id, value)| (peer_id.to_string(), value))
+                .collect();
+            pairs.serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+            deserializer: D,
+        ) -> Result<Vec<(PeerId, T)>, D::Error> {
+            Vec::<(String, T)>::deserialize(deserializer)?
+                .into_iter()
+                .map(|(peer_id, value)| Ok((parse(&peer_id)?, value)))
+                .collect()
+        }
+    }
+
+    // Optional pairs of peer ids and values, e.g. the requested connections of each peer.
+    pub mod option_pairs {
+        use super::*;
+        use serde::Serialize;
+
+        pub fn serialize<S: Serializer, T: Serialize>(
+            pairs: &Option<Vec<(PeerId, T)>>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            let pairs: Option<Vec<(String, &T)>> = pairs.as_ref().map(|pairs| {
+                pairs
+                    .iter()
+                    .map(|(peer_id, value)| (peer_id.to_string(), value))
+                    .collect()
+            });
+            pairs.serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Vec<(PeerId, T)>>, D::Error> {
+            Option::<Vec<(String, T)>>::deserialize(deserializer)?
+                .map(|pairs| {
+                    pairs
+                        .into_iter()
+                        .map(|(peer_id, value)| Ok((parse(&peer_id)?, value)))
+                        .collect()
+                })
+                .transpose()
+        }
+    }
+
+    // Peer ids with two values, e.g. the addresses and connection state of a known peer.
+    pub mod triples {
+        use super::*;
+        use serde::Serialize;
+
+        pub fn serialize<S: Serializer, T: Serialize, U: Serialize>(
+            triples: &[(PeerId, T, U)],
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            let triples: Vec<(String, &T, &U)> = triples
+                .iter()
+                .map(|(peer_id, first, second)| (peer_id.to_string(), first, second))
+                .collect();
+            triples.serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>, U: Deserialize<'de>>(
+            deserializer: D,
+        ) -> Result<Vec<(PeerId, T, U)>, D::Error> {
+            Vec::<(String, T, U)>::deserialize(deserializer)?
+                .into_iter()
+                .map(|(peer_id, first, second)| Ok((parse(&peer_id)?, first, second)))
+                .collect()
+        }
+    }
+
+    // Maps with peer ids as keys, e.g. the addresses of each peer.
+    pub mod map {
+        use super::*;
+        use serde::Serialize;
+        use std::collections::HashMap;
+
+        pub fn serialize<S: Serializer, T: Serialize>(
+            map: &HashMap<PeerId, T>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            let map: HashMap<String, &T> = map
+                .iter()
+                .map(|(peer_id, value)| (peer_id.to_string(), value))
+                .collect();
+            map.serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+            deserializer: D,
+        ) -> Result<HashMap<PeerId, T>, D::Error> {
+            HashMap::<String, T>::deserialize(deserializer)?
+                .into_iter()
+                .map(|(peer_id, value)| Ok((parse(&peer_id)?, value)))
+                .collect()
+        }
+    }
+
+    // Results that contain a peer id on success.
+    pub mod result {
+        use super::*;
+        use serde::Serialize;
+
+        pub fn serialize<S: Serializer, E: Serialize>(
+            result: &Result<PeerId, E>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            result.as_ref().map(|peer_id| peer_id.to_string()).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>, E: Deserialize<'de>>(
+            deserializer: D,
+        ) -> Result<Result<PeerId, E>, D::Error> {
+            match Result::<String, E>::deserialize(deserializer)? {
+                Ok(peer_id) => parse(&peer_id).map(Ok),
+                Err(err) => Ok(Err(err)),
+            }
+        }
+    }
+}
+
+// Serialize an optional deadline as the duration that remains until it, relative to the point in time of the
+// serialization. Deadlines that already passed are serialized as zero duration.
+pub(super) mod serde_deadline {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::time::{Duration, Instant};
+
+    pub fn serialize<S: Serializer>(deadline: &Option<Instant>, serializer: S) -> Result<S::Ok, S::Error> {
+        deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Instant>, D::Error> {
+        let remaining = Option::<Duration>::deserialize(deserializer)?;
+        Ok(remaining.map(|remaining| Instant::now() + remaining))
+    }
+}
+
+// Serialize a point in time in the past as the duration that elapsed since it, relative to the point in time of
+// the serialization.
+pub(super) mod serde_elapsed {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::time::{Duration, Instant};
+
+    pub fn serialize<S: Serializer>(instant: &Instant, serializer: S) -> Result<S::Ok, S::Error> {
+        instant.elapsed().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Instant, D::Error> {
+        let elapsed = Duration::deserialize(deserializer)?;
+        let now = Instant::now();
+        Ok(now.checked_sub(elapsed).unwrap_or(now))
+    }
+}
+
+// Serialize the request ids of libp2p in their decimal representation. The ids are only valid within the local swarm,
+// therefore they are not deserialized.
+pub(super) mod serde_request_id {
+    use libp2p::{request_response::RequestId, PeerId};
+    use serde::{Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(request_id: &RequestId, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(request_id)
+    }
+
+    // Request ids that are paired with the peer that sent the request.
+    pub mod pairs {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(pairs: &[(PeerId, RequestId)], serializer: S) -> Result<S::Ok, S::Error> {
+            let pairs: Vec<(String, String)> = pairs
+                .iter()
+                .map(|(peer_id, request_id)| (peer_id.to_string(), request_id.to_string()))
+                .collect();
+            pairs.serialize(serializer)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn serde_state() {
+        let relay_id = PeerId::random();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/8080".parse().unwrap();
+        let mut peer = PeerState::new(PeerId::random());
+        peer.addrs.push(addr.clone());
+        peer.keep_alive = Some(KeepAliveState::Unlimited);
+        peer.firewall_in = Some(FirewallPermission::none());
+        let state = CommunicationState {
+            firewall_default_in: FirewallPermission::all(),
+            firewall_default_out: FirewallPermission::none(),
+            firewall_mode: FirewallMode::Audit,
+            relay: RelayConfig::RelayBackup {
+                peer_id: relay_id,
+                addr,
+            },
+            peers: vec![peer.clone()],
+        };
+        let bytes = serde_json::to_vec(&state).unwrap();
+        let state: CommunicationState = serde_json::from_slice(&bytes).unwrap();
+
+        assert_eq!(state.firewall_default_out, FirewallPermission::none());
+        assert_eq!(state.firewall_mode, FirewallMode::Audit);
+        assert!(matches!(state.relay, RelayConfig::RelayBackup { peer_id, .. } if peer_id == relay_id));
+        assert_eq!(state.peers[0].peer_id, peer.peer_id);
+        assert_eq!(state.peers[0].addrs, peer.addrs);
+        assert_eq!(state.peers[0].keep_alive, Some(KeepAliveState::Unlimited));
+        assert_eq!(state.peers[0].firewall_out, None);
+    }
+
+    #[test]
+    fn expired_keep_alive() {
+        let expired = KeepAlive::Limited { end: Instant::now() };
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(KeepAliveState::from_keep_alive(&expired), None);
+        assert_eq!(KeepAliveState::from_keep_alive(&KeepAlive::None), None);
+    }
+}
@@ -1,14 +1,38 @@
 // Copyright 2020-2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
-use super::{connections::ConnectionManager, *};
+mod outbound;
+
+use super::{
+    breaker::ClientBreaker,
+    cache::ResponseCache,
+    connections::{ConnectionManager, DIAL_FAILURE_MAX_AGE},
+    event_log::describe_swarm_event,
+    failure_ban::FailureBans,
+    maintained::{MaintainedConnections, MaintainedState},
+    metrics::SwarmMetrics,
+    scoring::{Misbehaviour, PeerScoring},
+    state::{CommunicationState, KeepAliveState, PeerState},
+    types::message_too_large,
+    *,
+};
 use crate::behaviour::{
-    BehaviourError, MessageEvent, P2PEvent, P2PNetworkBehaviour, P2POutboundFailure, P2PReqResEvent, RequestEnvelope,
+    addr_peer_id, addr_transport, socket_addr_to_multiaddr, tcp_ports, BehaviourError, ConnectionLimitKind,
+    ConnectionLimitsConfig, EnvelopeTtl, MessageEvent, MessageTooLarge, P2PEvent, P2PIdentifyEvent, P2PInboundFailure,
+    P2PNetworkBehaviour, P2PPingEvent, P2PReqResEvent, RejectReason, RequestEnvelope, TransportSupport,
 };
 use core::{ops::Deref, str::FromStr, time::Duration};
-use futures::{channel::mpsc::UnboundedReceiver, future, prelude::*, select};
+use futures::{
+    channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+    future,
+    prelude::*,
+    select, stream,
+};
 use libp2p::{
-    core::{connection::ListenerId, multiaddr::Protocol, ConnectedPoint},
+    core::{
+        connection::{ConnectionLimit, ListenerId, PendingConnectionError},
+        ConnectedPoint,
+    },
     identity::Keypair,
     request_response::RequestId,
     swarm::{DialError, Swarm, SwarmEvent},
@@ -16,11 +40,45 @@ use libp2p::{
 };
 use riker::{actors::*, Message};
 use std::{
-    net::Ipv4Addr,
-    task::{Context, Poll},
-    time::Instant,
+    collections::{HashMap, HashSet, VecDeque},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    ops::RangeInclusive,
+    time::{Instant, SystemTime},
 };
 
+// Default interval in which expired state, e.g. temporary bans of peers, is cleaned up.
+const DEFAULT_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+// Shortest sweep interval, shorter intervals would keep the event loop busy with sweeping.
+const MIN_SWEEP_INTERVAL: Duration = Duration::from_millis(10);
+// Default duration to wait for the response of an outbound request, and for a dialed connection to be established.
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+const DEFAULT_CONNECTION_TIMEOUT: Duration = Duration::from_secs(3);
+// Default duration to wait for the client to respond to an inbound request.
+const DEFAULT_CLIENT_TIMEOUT: Duration = Duration::from_secs(3);
+// Default duration after which a failed direct connection upgrade is attempted again.
+const DEFAULT_UPGRADE_BACKOFF: Duration = Duration::from_secs(30);
+// Default duration to wait for a new listener to start listening.
+const DEFAULT_LISTEN_TIMEOUT: Duration = Duration::from_secs(3);
+// Shortest interval of the listener watchdog, shorter intervals would keep the event loop busy with checking the
+// listeners.
+const MIN_WATCHDOG_INTERVAL: Duration = Duration::from_millis(100);
+// Maximum number of listener failures that are kept for `CommunicationRequest::GetListenerErrors`.
+const MAX_LISTENER_FAILURES: usize = 32;
+// Utilization of the event loop above which it is reported as saturated in the health summary.
+const SATURATION_THRESHOLD: f64 = 0.9;
+// Duration over which the utilization of the event loop is measured for the health summary.
+const UTILIZATION_WINDOW: Duration = Duration::from_secs(10);
+
+// The configured sweep interval, raised to the minimum interval.
+fn sweep_interval(interval: Option<Duration>) -> Duration {
+    interval.unwrap_or(DEFAULT_SWEEP_INTERVAL).max(MIN_SWEEP_INTERVAL)
+}
+
+// The configured interval of the listener watchdog, raised to the minimum interval.
+fn watchdog_interval(interval: Duration) -> Duration {
+    interval.max(MIN_WATCHDOG_INTERVAL)
+}
+
 // Separate task that manages the swarm communication.
 pub(super) struct SwarmTask<Req, Res, ClientMsg, P>
 where
@@ -30,20 +88,141 @@ where
     P: Message + VariantPermission,
 {
     system: ActorSystem,
-    // client to receive incoming requests
+    // client to receive incoming requests, and the optional router that selects another client per request
     client: ActorRef<ClientMsg>,
+    client_router: Option<ClientRouter<Req, ClientMsg>>,
     // firewall configuration to check and validate all outgoing and incoming requests
     firewall: FirewallConfiguration,
     // the expanded swarm that is used to poll for incoming requests and interact
     swarm: Swarm<P2PNetworkBehaviour<RequestEnvelope<Req>, Res>>,
     // channel from the communication actor to this task
     swarm_rx: UnboundedReceiver<(CommunicationRequest<Req, ClientMsg>, Sender)>,
-    // current listener in the swarm
-    listener: Option<ListenerId>,
+    // current listeners in the swarm with the address that was returned when they started
+    listeners: Vec<(ListenerId, Multiaddr)>,
+    // optional watchdog that restarts listening once all listeners closed, and whether listeners should be restored,
+    // which is the case if the node started listening and did not remove its listeners
+    listener_watchdog: Option<ListenerWatchdogConfig>,
+    restore_listeners: bool,
+    // whether transmitted responses to inbound requests are emitted as event, and the inbound requests that were
+    // answered by the client or from the cache, for which the transmission is reported
+    report_responses_sent: bool,
+    reported_responses: HashSet<RequestId>,
+    // whether inbound requests that are blocked by the firewall are explicitly rejected instead of dropped
+    send_firewall_rejections: bool,
+    // optional actor that receives a copy of each request that passed the firewall
+    audit: Option<AuditConfig<Req>>,
+    // connections that are kept until the user stops maintaining them
+    maintained: MaintainedConnections,
+    // listeners that closed or reported an error, limited to the latest `MAX_LISTENER_FAILURES`
+    listener_failures: VecDeque<ListenerFailure>,
+    // outbound requests that were sent and are awaiting a response, with the peer they were sent to and the time at
+    // which they were sent
+    pending_requests: HashMap<RequestId, (PeerId, Instant)>,
     // configuration to use optionally use a relay peer if a peer in a remote network can not be reached directly.
     relay: RelayConfig,
+    // peers that are explicitly reached via a specific relay instead of the configured one
+    peer_relays: HashMap<PeerId, PeerId>,
+    // peers for which the relayed path was closed, so that requests are only exchanged directly
+    unrelayed_peers: HashSet<PeerId>,
+    // attempt to establish a direct connection to peers that communicate via the relay
+    direct_upgrade: bool,
+    // peers for which a direct connection was attempted with the time of the latest attempt, and the ones for which
+    // the attempt is still pending
+    upgrade_attempts: HashMap<PeerId, Instant>,
+    upgrade_backoff: Duration,
+    pending_upgrades: HashSet<PeerId>,
+    // duration that new connections have to survive until they are considered as established
+    connection_grace_period: Option<Duration>,
+    // optional delay after which a request is additionally sent via the backup relay, if the peer is not connected
+    relay_fallback_delay: Option<Duration>,
+    // optional level at which the events of the swarm are logged
+    log_swarm_events: Option<log::Level>,
+    // optional time-to-live of outgoing envelopes
+    envelope_ttl: Option<EnvelopeTtl>,
+    // number of consecutive failed dials after which an address is removed from the address book
+    prune_failed_addrs: Option<u32>,
+    // duration to wait for the response of an outbound request
+    request_timeout: Duration,
+    // duration to wait for the client to respond to an inbound request
+    client_timeout: Duration,
+    // duration to wait for a dialed connection to be established
+    connection_timeout: Duration,
+    // duration to wait for a new listener to start listening
+    listen_timeout: Duration,
+    // interval in which expired state is cleaned up
+    sweep_interval: Duration,
     // maintain the current state of connections and keep-alive configuration
     connection_manager: ConnectionManager,
+    // optional hook that is called for each outgoing request before it is sent
+    outgoing_request_hook: Option<RequestHook<Req>>,
+    // optional hook that is called for each incoming request before the firewall check
+    incoming_request_hook: Option<RequestHook<Req>>,
+    // optional hook that is called with the provenance of each permitted incoming request
+    provenance_hook: Option<ProvenanceHook<Req>>,
+    // optional hook that is called for each response before it is sent
+    response_hook: Option<ResponseHook<Res>>,
+    // optional scoring of remote peers to automatically ban misbehaving peers
+    peer_scoring: Option<PeerScoring>,
+    // optional policy that bans peers which repeatedly caused inbound failures or malformed requests
+    failure_bans: Option<FailureBans>,
+    // metrics about requests and connections
+    metrics: SwarmMetrics,
+    // number of nested blocking waits for swarm events, and the inbound requests that were received during the wait
+    wait_depth: usize,
+    deferred_requests: Vec<(PeerId, RequestId, RequestEnvelope<Req>)>,
+    // whether handling requests is paused, and if outbound requests are queued instead of rejected while paused
+    is_paused: bool,
+    queue_outbound: bool,
+    // outbound requests that were queued while paused
+    paused_requests: Vec<(CommunicationRequest<Req, ClientMsg>, Sender)>,
+    // requests of the actor that were received while an outbound request was awaited
+    deferred_actor_requests: VecDeque<(CommunicationRequest<Req, ClientMsg>, Sender)>,
+    // optional threshold for the round-trip time of pings, above which an event is emitted
+    rtt_threshold: Option<Duration>,
+    // whether closed keep-alive connections are re-established synchronously
+    reconnect_mode: ReconnectMode,
+    // peers that are re-dialed in deferred mode, with the address of the closed connection
+    pending_reconnects: HashMap<PeerId, Multiaddr>,
+    // addresses of concurrent dials to a peer that lost the race against another address, whose failures are not
+    // reported
+    superseded_dials: HashSet<Multiaddr>,
+    // optional limit for the number of deferred inbound requests
+    inbound_queue_limit: Option<usize>,
+    // limit for the number of inbound requests that the client handles at the same time, the requests that it
+    // currently handles with their source and the nonce of idempotent requests, and the channel for its responses
+    max_inbound_in_flight: usize,
+    inbound_in_flight: HashMap<RequestId, (PeerId, Option<u64>)>,
+    client_response_tx: UnboundedSender<(RequestId, Option<Res>)>,
+    client_response_rx: UnboundedReceiver<(RequestId, Option<Res>)>,
+    // optional limit for the number of outbound requests in flight, and how requests beyond it are handled
+    max_outbound_in_flight: Option<usize>,
+    outbound_overflow: OutboundOverflow,
+    // optional circuit breaker that stops forwarding requests to an overloaded client
+    client_breaker: Option<ClientBreaker>,
+    // optional cache for the responses to idempotent inbound requests
+    response_cache: Option<(RequestNonce<Req>, ResponseCache<Res>)>,
+    // optional actor that is notified about events of the swarm
+    observer: Option<ActorRef<CommunicationEvent>>,
+    // optional callback to authorize inbound connections, the inbound connections that are currently authorized, and
+    // the channel for the results of the authorization tasks
+    connection_authorizer: Option<ConnectionAuthorizer>,
+    pending_authorizations: HashMap<PeerId, ConnectedPoint>,
+    authorization_tx: UnboundedSender<(PeerId, bool)>,
+    authorization_rx: UnboundedReceiver<(PeerId, bool)>,
+    // supported versions of the request-response protocol, ordered from newest to oldest
+    protocol_versions: Vec<String>,
+    // limits for the number of connections, to determine which limit was hit when a connection is refused
+    connection_limits: ConnectionLimitsConfig,
+    // transports of the swarm, to validate addresses without dialing them
+    transport_support: TransportSupport,
+    // named groups of peers, with the members in the order in which they were added
+    groups: HashMap<String, Vec<PeerId>>,
+    // points in time and accumulated busy duration of the event loop, sampled on each sweep over the utilization
+    // window
+    utilization_samples: VecDeque<(Instant, Duration)>,
+    // point in time at which the task started, measured with the monotonic and the system clock
+    started: Instant,
+    started_at: SystemTime,
     _marker: PhantomData<P>,
 }
 
@@ -57,22 +236,94 @@ where
     pub async fn new(
         system: ActorSystem,
         swarm_rx: UnboundedReceiver<(CommunicationRequest<Req, ClientMsg>, Sender)>,
-        actor_config: CommunicationActorConfig<ClientMsg>,
+        actor_config: CommunicationActorConfig<Req, Res, ClientMsg>,
         keypair: Keypair,
         behaviour: BehaviourConfig,
     ) -> Result<Self, BehaviourError> {
+        let protocol_versions = behaviour.protocol_names();
+        let connection_limits = behaviour.connection_limits();
+        let transport_support = behaviour.transport_support();
         // Create a P2PNetworkBehaviour for the swarm communication.
         let swarm = P2PNetworkBehaviour::<RequestEnvelope<Req>, Res>::init_swarm(keypair, behaviour).await?;
         let firewall = FirewallConfiguration::new(actor_config.firewall_default_in, actor_config.firewall_default_out);
+        let (authorization_tx, authorization_rx) = unbounded();
+        let (client_response_tx, client_response_rx) = unbounded();
         Ok(SwarmTask {
             system,
             client: actor_config.client,
+            client_router: actor_config.client_router,
             firewall,
             swarm,
             swarm_rx,
-            listener: None,
+            listeners: Vec::new(),
+            listener_watchdog: actor_config.listener_watchdog,
+            restore_listeners: false,
+            report_responses_sent: actor_config.report_responses_sent,
+            reported_responses: HashSet::new(),
+            send_firewall_rejections: actor_config.send_firewall_rejections,
+            audit: actor_config.audit,
+            maintained: MaintainedConnections::new(actor_config.maintain_backoff),
+            listener_failures: VecDeque::new(),
+            pending_requests: HashMap::new(),
             relay: RelayConfig::NoRelay,
-            connection_manager: ConnectionManager::new(),
+            peer_relays: HashMap::new(),
+            unrelayed_peers: HashSet::new(),
+            direct_upgrade: actor_config.direct_upgrade,
+            upgrade_attempts: HashMap::new(),
+            upgrade_backoff: actor_config.direct_upgrade_backoff.unwrap_or(DEFAULT_UPGRADE_BACKOFF),
+            pending_upgrades: HashSet::new(),
+            connection_grace_period: actor_config.connection_grace_period,
+            relay_fallback_delay: actor_config.relay_fallback_delay,
+            log_swarm_events: actor_config.log_swarm_events,
+            envelope_ttl: actor_config.envelope_ttl,
+            // an address is pruned at the earliest on its first failure
+            prune_failed_addrs: actor_config.prune_failed_addrs.map(|max_failures| max_failures.max(1)),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            client_timeout: DEFAULT_CLIENT_TIMEOUT,
+            connection_timeout: DEFAULT_CONNECTION_TIMEOUT,
+            listen_timeout: actor_config.listen_timeout.unwrap_or(DEFAULT_LISTEN_TIMEOUT),
+            sweep_interval: sweep_interval(actor_config.sweep_interval),
+            connection_manager: ConnectionManager::new(actor_config.retained_addrs),
+            outgoing_request_hook: actor_config.outgoing_request_hook,
+            incoming_request_hook: actor_config.incoming_request_hook,
+            provenance_hook: actor_config.provenance_hook,
+            response_hook: actor_config.response_hook,
+            peer_scoring: actor_config.peer_scoring.map(PeerScoring::new),
+            failure_bans: actor_config.failure_ban.map(FailureBans::new),
+            metrics: SwarmMetrics::default(),
+            wait_depth: 0,
+            deferred_requests: Vec::new(),
+            is_paused: false,
+            queue_outbound: false,
+            paused_requests: Vec::new(),
+            deferred_actor_requests: VecDeque::new(),
+            rtt_threshold: actor_config.rtt_threshold,
+            reconnect_mode: actor_config.reconnect_mode,
+            pending_reconnects: HashMap::new(),
+            superseded_dials: HashSet::new(),
+            inbound_queue_limit: actor_config.inbound_queue_limit,
+            max_inbound_in_flight: actor_config.max_inbound_in_flight.unwrap_or(1).max(1),
+            inbound_in_flight: HashMap::new(),
+            client_response_tx,
+            client_response_rx,
+            max_outbound_in_flight: actor_config.max_outbound_in_flight,
+            outbound_overflow: actor_config.outbound_overflow,
+            client_breaker: actor_config.client_breaker.map(ClientBreaker::new),
+            response_cache: actor_config
+                .response_cache
+                .map(|config| (config.nonce, ResponseCache::new(config.capacity, config.ttl))),
+            observer: actor_config.observer,
+            connection_authorizer: actor_config.connection_authorizer,
+            pending_authorizations: HashMap::new(),
+            authorization_tx,
+            authorization_rx,
+            protocol_versions,
+            connection_limits,
+            transport_support,
+            groups: HashMap::new(),
+            utilization_samples: vec![(Instant::now(), Duration::from_secs(0))].into_iter().collect(),
+            started: Instant::now(),
+            started_at: SystemTime::now(),
             _marker: PhantomData,
         })
     }
@@ -80,32 +331,225 @@ where
     // Poll from the swarm for events from remote peers, and from the `swarm_tx` channel for events from the local
     // actor, and forward them.
     pub async fn poll_swarm(mut self) {
+        let mut sweep_interval = stream::unfold(self.sweep_interval, |interval| async move {
+            task::sleep(interval).await;
+            Some(((), interval))
+        })
+        .boxed();
+        let mut watchdog_interval = match self.listener_watchdog.as_ref() {
+            Some(config) => stream::unfold(watchdog_interval(config.interval), |interval| async move {
+                task::sleep(interval).await;
+                Some(((), interval))
+            })
+            .boxed(),
+            None => stream::pending().boxed(),
+        };
         loop {
+            // Timer for the next connection that survived the grace period.
+            let next_confirmation = self
+                .connection_grace_period
+                .and_then(|grace_period| self.connection_manager.next_confirmation(grace_period));
+            let confirmation_timer = match next_confirmation {
+                Some(instant) => task::sleep(instant.saturating_duration_since(Instant::now())).boxed(),
+                None => future::pending::<()>().boxed(),
+            };
+            // Timer for the next dial of a maintained connection.
+            let maintain_timer = match self.maintained.next_dial() {
+                Some(instant) => task::sleep(instant.saturating_duration_since(Instant::now())).boxed(),
+                None => future::pending::<()>().boxed(),
+            };
             select! {
-                swarm_event = self.swarm.next_event().fuse() => self.handle_swarm_event(swarm_event),
+                swarm_event = self.swarm.next_event().fuse() => self.timed(|task| task.handle_swarm_event(swarm_event)),
+                _ = sweep_interval.next().fuse() => self.sweep(),
+                _ = watchdog_interval.next().fuse() => self.timed(|task| task.check_listeners()),
+                _ = confirmation_timer.fuse() => self.confirm_connections(),
+                _ = maintain_timer.fuse() => self.timed(|task| task.dial_maintained()),
+                client_response = self.client_response_rx.next().fuse() => {
+                    if let Some((request_id, res)) = client_response {
+                        self.timed(|task| task.handle_client_response(request_id, res))
+                    }
+                },
+                authorization = self.authorization_rx.next().fuse() => {
+                    if let Some((peer_id, is_allowed)) = authorization {
+                        self.timed(|task| task.handle_authorization(peer_id, is_allowed))
+                    }
+                },
                 actor_event = self.swarm_rx.next().fuse() => {
                     if let Some((message, sender)) = actor_event {
                         if let CommunicationRequest::Shutdown = message {
                             break;
                         } else {
-                            self.handle_actor_request(message, sender)
+                            self.timed(|task| task.handle_actor_request(message, sender))
                         }
                     } else {
                         break
                     }
                 },
             };
+            // Actor requests that were received while an outbound request was awaited.
+            while let Some((message, sender)) = self.deferred_actor_requests.pop_front() {
+                if let CommunicationRequest::Shutdown = message {
+                    self.shutdown();
+                    return;
+                }
+                self.timed(|task| task.handle_actor_request(message, sender))
+            }
         }
         self.shutdown();
     }
 
+    // Handle an event and record the duration for which the event loop was blocked.
+    fn timed(&mut self, handle: impl FnOnce(&mut Self)) {
+        let start = Instant::now();
+        handle(self);
+        self.metrics.record_loop_iteration(start.elapsed());
+    }
+
     fn shutdown(mut self) {
-        if let Some(listener_id) = self.listener.take() {
+        for (listener_id, _) in self.listeners.drain(..) {
             let _ = Swarm::remove_listener(&mut self.swarm, listener_id);
         }
         self.swarm_rx.close();
     }
 
+    // Periodically clean up expired state.
+    fn sweep(&mut self) {
+        let mut expired = Vec::new();
+        if let Some(scoring) = self.peer_scoring.as_mut() {
+            expired.extend(scoring.take_expired_bans());
+        }
+        if let Some(bans) = self.failure_bans.as_mut() {
+            expired.extend(bans.take_expired_bans());
+        }
+        // Peers stay banned as long as one of the policies still bans them.
+        for peer_id in expired {
+            if !self.is_auto_banned(&peer_id) {
+                Swarm::unban_peer_id(&mut self.swarm, peer_id);
+            }
+        }
+        if let Some((_, cache)) = self.response_cache.as_mut() {
+            cache.remove_expired();
+        }
+        self.firewall.remove_expired_rates();
+        self.connection_manager.prune_retained_addrs();
+        self.connection_manager.prune_dial_failures(DIAL_FAILURE_MAX_AGE);
+        let backoff = self.upgrade_backoff;
+        self.upgrade_attempts.retain(|_, attempt| attempt.elapsed() < backoff);
+        self.sample_utilization();
+    }
+
+    // Record the busy duration of the event loop, and drop samples that are not needed to cover the utilization
+    // window anymore. The oldest sample is the latest one that is at least as old as the window.
+    fn sample_utilization(&mut self) {
+        self.utilization_samples
+            .push_back((Instant::now(), self.metrics.loop_busy_sum));
+        while matches!(self.utilization_samples.get(1), Some((at, _)) if at.elapsed() >= UTILIZATION_WINDOW) {
+            self.utilization_samples.pop_front();
+        }
+    }
+
+    // Restart listening on the addresses of the watchdog if all listeners closed.
+    // Addresses on which listening fails are tried again in the next interval.
+    fn check_listeners(&mut self) {
+        if !self.restore_listeners || !self.listeners.is_empty() {
+            return;
+        }
+        let addrs = match self.listener_watchdog.as_ref() {
+            Some(config) => config.addrs.clone(),
+            None => return,
+        };
+        for addr in addrs {
+            if let Ok(addr) = self.start_listening(Some(addr)) {
+                self.emit_event(CommunicationEvent::ListenerRestarted { addr });
+            }
+        }
+    }
+
+    // Consider the connections that survived the grace period as established.
+    fn confirm_connections(&mut self) {
+        if let Some(grace_period) = self.connection_grace_period {
+            for (peer_id, endpoint) in self.connection_manager.take_confirmed(grace_period) {
+                self.confirm_connection(peer_id, endpoint);
+            }
+        }
+    }
+
+    fn record_listener_failure(&mut self, failure: ListenerFailure) {
+        if self.listener_failures.len() == MAX_LISTENER_FAILURES {
+            self.listener_failures.pop_front();
+        }
+        self.listener_failures.push_back(failure.clone());
+        self.emit_event(CommunicationEvent::ListenerFailed(failure));
+    }
+
+    // Accept a new connection, which is either confirmed immediately or once it survived the grace period.
+    fn accept_connection(&mut self, peer_id: PeerId, endpoint: ConnectedPoint) {
+        if self.connection_grace_period.is_some() {
+            self.connection_manager.insert_unconfirmed(peer_id, endpoint);
+        } else {
+            self.confirm_connection(peer_id, endpoint);
+        }
+    }
+
+    // Accept the inbound connection if it was authorized, otherwise close it.
+    // Results for connections that closed while they were authorized are ignored.
+    fn handle_authorization(&mut self, peer_id: PeerId, is_allowed: bool) {
+        if let Some(endpoint) = self.pending_authorizations.remove(&peer_id) {
+            if is_allowed {
+                self.accept_connection(peer_id, endpoint);
+            } else {
+                // Connections in the swarm can only actively be closed by banning the peer.
+                Swarm::ban_peer_id(&mut self.swarm, peer_id);
+                Swarm::unban_peer_id(&mut self.swarm, peer_id);
+                self.emit_event(CommunicationEvent::ConnectionDenied { peer_id });
+            }
+        }
+    }
+
+    // Mark the connection as active and notify the observer.
+    fn confirm_connection(&mut self, peer_id: PeerId, endpoint: ConnectedPoint) {
+        self.emit_event(CommunicationEvent::ConnectionEstablished {
+            peer_id,
+            endpoint: endpoint.clone(),
+            protocols: self.connection_manager.get_protocols(&peer_id),
+        });
+        self.connection_manager.insert(peer_id, endpoint, KeepAlive::None);
+    }
+
+    // Reduce the score of a misbehaving peer, and ban it if the score dropped below the threshold.
+    fn penalize_peer(&mut self, peer_id: PeerId, misbehaviour: Misbehaviour) {
+        if let Some(scoring) = self.peer_scoring.as_mut() {
+            if scoring.penalize(peer_id, misbehaviour) {
+                Swarm::ban_peer_id(&mut self.swarm, peer_id);
+            }
+        }
+    }
+
+    // Whether the peer is currently banned due to its score or its failures.
+    fn is_auto_banned(&self, peer_id: &PeerId) -> bool {
+        let is_scoring_banned = self.peer_scoring.as_ref().map(|scoring| scoring.is_banned(peer_id));
+        let is_failure_banned = self.failure_bans.as_ref().map(|bans| bans.is_banned(peer_id));
+        is_scoring_banned.unwrap_or(false) || is_failure_banned.unwrap_or(false)
+    }
+
+    // Record a protocol failure or malformed envelope of the peer, and ban it if it caused too many failures.
+    fn record_failure(&mut self, peer_id: PeerId) {
+        if let Some(bans) = self.failure_bans.as_mut() {
+            if bans.record_failure(peer_id) {
+                Swarm::ban_peer_id(&mut self.swarm, peer_id);
+                let cooldown = bans.cooldown();
+                self.emit_event(CommunicationEvent::PeerAutoBanned { peer_id, cooldown });
+            }
+        }
+    }
+
+    // Notify the observer about an event of the swarm.
+    fn emit_event(&self, event: CommunicationEvent) {
+        if let Some(observer) = self.observer.as_ref() {
+            observer.tell(event, None);
+        }
+    }
+
     // Send a reponse to the sender of a previous [`CommunicationRequest`]
     fn send_response(result: CommunicationResults<Res>, sender: Sender) {
         if let Some(sender) = sender {
@@ -113,207 +557,995 @@ where
         }
     }
 
-    // Forward request to client actor and wait for the result, with 3s timeout.
-    fn ask_client(&mut self, request: Req) -> Option<Res> {
-        let start = Instant::now();
-        let mut ask_client = ask(&self.system, &self.client, request);
-        task::block_on(future::poll_fn(move |cx: &mut Context<'_>| {
-            match ask_client.poll_unpin(cx) {
-                Poll::Ready(res) => Poll::Ready(Some(res)),
-                Poll::Pending => {
-                    if start.elapsed() > Duration::new(3, 0) {
-                        Poll::Ready(None)
-                    } else {
-                        Poll::Pending
+    // Start a blocking wait for swarm events, e.g. while connecting a peer or waiting for a response.
+    //
+    // Re-entrancy: inbound requests that are received while the task is blocked in a wait are not handled right
+    // away, since this would block the wait on the client, and could deadlock if the client in turn depends on the
+    // result of the wait. Instead they are deferred until the outermost wait completed, and handled in the order in
+    // which they were received. All other events are handled immediately.
+    fn begin_wait(&mut self) {
+        self.wait_depth += 1;
+    }
+
+    // Queue an inbound request until the task is not blocked anymore and the client can handle it, or reject it as
+    // busy if the queue is full.
+    fn defer_request(&mut self, peer_id: PeerId, request_id: RequestId, request: RequestEnvelope<Req>) {
+        let is_full = self
+            .inbound_queue_limit
+            .map(|limit| self.deferred_requests.len() >= limit)
+            .unwrap_or(false);
+        if is_full {
+            self.metrics.inbound_rejected_busy += 1;
+            self.swarm.send_rejection(request_id, RejectReason::Busy);
+            return;
+        }
+        self.deferred_requests.push((peer_id, request_id, request));
+        if self.deferred_requests.len() > self.metrics.inbound_queued_max {
+            self.metrics.inbound_queued_max = self.deferred_requests.len();
+        }
+    }
+
+    // Complete a blocking wait, and handle the deferred requests once no wait is pending anymore.
+    fn end_wait(&mut self) {
+        self.wait_depth -= 1;
+        self.handle_deferred_requests();
+    }
+
+    // Handle the deferred requests in order, as long as the task is not blocked and the client is not saturated.
+    fn handle_deferred_requests(&mut self) {
+        while self.wait_depth == 0 && !self.is_client_saturated() && !self.deferred_requests.is_empty() {
+            let (peer_id, request_id, request) = self.deferred_requests.remove(0);
+            self.handle_incoming_envelope(peer_id, request_id, request);
+        }
+    }
+
+    // Cancel an outbound request that was queued while paused, or while another request was awaited.
+    // Returns false if no queued request has the token.
+    fn cancel_queued_request(&mut self, token: u64) -> bool {
+        let has_token = |(message, _): &(CommunicationRequest<Req, ClientMsg>, Sender)| match message {
+            CommunicationRequest::RequestMsg { cancel_token, .. } => *cancel_token == Some(token),
+            _ => false,
+        };
+        let queued = match self.paused_requests.iter().position(has_token) {
+            Some(index) => Some(self.paused_requests.remove(index)),
+            None => self
+                .deferred_actor_requests
+                .iter()
+                .position(has_token)
+                .and_then(|index| self.deferred_actor_requests.remove(index)),
+        };
+        match queued {
+            Some((_, sender)) => {
+                let res = CommunicationResults::RequestMsgResult(Err(RequestMessageError::Cancelled));
+                Self::send_response(res, sender);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Forward the request to the client actor that the router selected, or the default client, without blocking the
+    // task. The response, or none if the client did not respond within the client timeout, is sent to the
+    // `client_response_tx` channel.
+    fn ask_client(&self, request_id: RequestId, request: Req) {
+        let timeout = self.client_timeout;
+        let client = self
+            .client_router
+            .as_ref()
+            .and_then(|route| route(&request))
+            .unwrap_or_else(|| self.client.clone());
+        let ask_client = ask(&self.system, &client, request);
+        let tx = self.client_response_tx.clone();
+        task::spawn(async move {
+            let res = async_std::future::timeout(timeout, ask_client).await.ok();
+            let _ = tx.unbounded_send((request_id, res));
+        });
+    }
+
+    // Check if the client already handles the max number of inbound requests.
+    fn is_client_saturated(&self) -> bool {
+        self.inbound_in_flight.len() >= self.max_inbound_in_flight
+    }
+
+    // Respond to an inbound request, either from the response cache if the request is idempotent and was already
+    // answered before, or by forwarding it to the client. While the circuit breaker of the client is open, the request
+    // is rejected instead. Requests that are answered are recorded for the audit, with the reason for which the
+    // firewall would have blocked them in audit mode.
+    fn forward_to_client(
+        &mut self,
+        source: PeerId,
+        request_id: RequestId,
+        request: Req,
+        would_block: Option<FirewallBlocked>,
+    ) {
+        let nonce = match self.response_cache.as_mut() {
+            Some((get_nonce, cache)) => match get_nonce(&request) {
+                Some(nonce) => {
+                    if let Some(res) = cache.get(source, nonce) {
+                        self.audit_request(&request, source, RequestDirection::In, would_block);
+                        self.send_client_response(source, request_id, res);
+                        return;
                     }
+                    Some(nonce)
                 }
+                None => None,
+            },
+            None => None,
+        };
+        if self
+            .client_breaker
+            .as_ref()
+            .map(|breaker| breaker.is_open())
+            .unwrap_or(false)
+        {
+            self.metrics.inbound_rejected_breaker += 1;
+            self.swarm.send_rejection(request_id, RejectReason::Busy);
+            return;
+        }
+        self.audit_request(&request, source, RequestDirection::In, would_block);
+        self.inbound_in_flight.insert(request_id, (source, nonce));
+        self.ask_client(request_id, request);
+    }
+
+    // Handle the response of the client to an inbound request. Requests that the client did not respond to within the
+    // client timeout are dropped without response, which closes the stream so that the remote fails right away.
+    // Afterwards, the next deferred inbound request is handled.
+    fn handle_client_response(&mut self, request_id: RequestId, res: Option<Res>) {
+        let (source, nonce) = match self.inbound_in_flight.remove(&request_id) {
+            Some(in_flight) => in_flight,
+            None => return,
+        };
+        if let Some(breaker) = self.client_breaker.as_mut() {
+            let event = match res {
+                Some(_) if breaker.record_success() => Some(CommunicationEvent::ClientBreakerClosed),
+                None if breaker.record_timeout() => Some(CommunicationEvent::ClientBreakerOpened),
+                _ => None,
+            };
+            if let Some(event) = event {
+                self.emit_event(event);
+            }
+        }
+        match res {
+            Some(res) => {
+                if let (Some(nonce), Some((_, cache))) = (nonce, self.response_cache.as_mut()) {
+                    cache.insert(source, nonce, res.clone());
+                }
+                self.send_client_response(source, request_id, res);
             }
-        }))
+            None => self.swarm.reject_request(request_id),
+        }
+        self.handle_deferred_requests();
     }
 
-    // Start listening on the swarm, if not address is provided, the port will be OS assigned.
-    fn start_listening(&mut self, addr: Option<Multiaddr>) -> Result<Multiaddr, ()> {
-        let addr = addr.unwrap_or_else(|| {
-            Multiaddr::empty()
-                .with(Protocol::Ip4(Ipv4Addr::new(0, 0, 0, 0)))
-                .with(Protocol::Tcp(0u16))
+    // Send the response to an inbound request, after it was passed to the response hook.
+    fn send_client_response(&mut self, source: PeerId, request_id: RequestId, mut res: Res) {
+        if let Some(hook) = self.response_hook.as_ref() {
+            hook(&mut res, source);
+        }
+        if self.swarm.send_response(request_id, res).is_ok() && self.report_responses_sent {
+            self.reported_responses.insert(request_id);
+        }
+    }
+
+    // Store the latest round-trip time to a peer, and report it if it exceeds the threshold.
+    fn record_rtt(&mut self, peer_id: PeerId, rtt: Duration) {
+        self.connection_manager.set_rtt(&peer_id, rtt);
+        if self.rtt_threshold.map(|threshold| rtt > threshold).unwrap_or(false) {
+            self.emit_event(CommunicationEvent::HighLatency { peer_id, rtt });
+        }
+    }
+
+    // Report a failed dial of an address, and remove the address of the peer from the address book if dialing it
+    // failed too often.
+    fn handle_dial_failure(
+        &mut self,
+        peer_id: Option<PeerId>,
+        address: Multiaddr,
+        error: ConnectPeerError,
+        attempts_remaining: u32,
+    ) {
+        if let ConnectPeerError::ConnectionLimit(limit) = &error {
+            let kind = self.connection_limits.established_limit_kind(false, limit.limit);
+            self.record_connection_limit(kind, limit);
+        }
+        self.emit_event(CommunicationEvent::DialFailure {
+            peer_id,
+            address: address.clone(),
+            error,
+            attempts_remaining,
         });
-        if let Ok(listener_id) = Swarm::listen_on(&mut self.swarm, addr) {
-            let start = Instant::now();
-            task::block_on(async {
-                loop {
-                    match self.swarm.next_event().await {
-                        SwarmEvent::NewListenAddr(addr) => {
-                            self.listener = Some(listener_id);
-                            return Ok(addr);
-                        }
-                        other => self.handle_swarm_event(other),
-                    }
-                    if start.elapsed() > Duration::new(3, 0) {
-                        return Err(());
-                    }
+        if let (Some(peer_id), 0) = (peer_id, attempts_remaining) {
+            self.maintained_dial_failed(peer_id);
+        }
+        if let (Some(peer_id), Some(max_failures)) = (peer_id, self.prune_failed_addrs) {
+            let failures = self.connection_manager.record_dial_failure(peer_id, address.clone());
+            if failures >= max_failures {
+                self.connection_manager.reset_dial_failures(peer_id, address.clone());
+                self.swarm.remove_peer_addr(&peer_id, &address);
+                self.emit_event(CommunicationEvent::AddressPruned { peer_id, address });
+            }
+        }
+    }
+
+    // Dial the maintained peers that are not connected and whose backoff passed. The dials don't block the task, their
+    // results are handled once the swarm reports them.
+    fn dial_maintained(&mut self) {
+        for (peer_id, attempt) in self.maintained.take_due() {
+            for addr in self.maintained.addrs(&peer_id) {
+                self.swarm.add_peer_addr(peer_id, addr);
+            }
+            let state = MaintainedState::Dialing { attempt };
+            self.emit_event(CommunicationEvent::MaintainedConnection { peer_id, state });
+            match Swarm::dial(&mut self.swarm, &peer_id) {
+                Ok(()) => {}
+                Err(DialError::ConnectionLimit(limit)) => {
+                    self.refuse_dial(limit);
+                    self.maintained_dial_failed(peer_id);
                 }
-            })
+                Err(_) => self.maintained_dial_failed(peer_id),
+            }
+        }
+    }
+
+    // Schedule the next dial if the peer is maintained.
+    fn maintained_dial_failed(&mut self, peer_id: PeerId) {
+        if let Some(delay) = self.maintained.dial_failed(&peer_id) {
+            let state = MaintainedState::Backoff { delay };
+            self.emit_event(CommunicationEvent::MaintainedConnection { peer_id, state });
+        }
+    }
+
+    // Report a connection that was refused because the limit was reached.
+    fn record_connection_limit(&mut self, kind: ConnectionLimitKind, limit: &ConnectionLimit) {
+        self.metrics.connections_refused_limit += 1;
+        self.emit_event(CommunicationEvent::ConnectionLimitReached {
+            kind,
+            limit: limit.limit,
+            current: limit.current,
+        });
+    }
+
+    // Report a dial that was refused because the limit of pending outbound connections was reached.
+    fn refuse_dial(&mut self, limit: ConnectionLimit) -> ConnectPeerError {
+        self.record_connection_limit(ConnectionLimitKind::PendingOutgoing, &limit);
+        ConnectPeerError::ConnectionLimit(limit)
+    }
+
+    fn connection_state(&self, peer_id: &PeerId) -> ConnectionState {
+        if Swarm::is_connected(&self.swarm, peer_id) {
+            ConnectionState::Connected
+        } else if self.connection_manager.is_pending_dial(peer_id) {
+            ConnectionState::Connecting
         } else {
-            Err(())
+            ConnectionState::Disconnected
         }
     }
 
-    // Try to connect a remote peer by id, and if the peer id is not know yet the address is used.
-    fn connect_peer(&mut self, target_peer: PeerId, target_addr: Multiaddr) -> Result<PeerId, ConnectPeerError> {
-        if let Err(err) = Swarm::dial(&mut self.swarm, &target_peer) {
-            match err {
-                DialError::NoAddresses => {
-                    if let Err(err) = Swarm::dial_addr(&mut self.swarm, target_addr.clone()) {
-                        return Err(err.into());
+    // Start listening on the swarm, if not address is provided, the port will be OS assigned.
+    fn start_listening(&mut self, addr: Option<Multiaddr>) -> Result<Multiaddr, StartListeningError> {
+        let addr = addr.unwrap_or_else(|| socket_addr_to_multiaddr(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))));
+        let listener_id = Swarm::listen_on(&mut self.swarm, addr).map_err(StartListeningError::from)?;
+        let deadline = Instant::now() + self.listen_timeout;
+        self.begin_wait();
+        let res = task::block_on(async {
+            loop {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                match async_std::future::timeout(remaining, self.swarm.next_event()).await {
+                    Ok(SwarmEvent::NewListenAddr(addr)) => {
+                        self.listeners.push((listener_id, addr.clone()));
+                        self.restore_listeners = true;
+                        return Ok(addr);
+                    }
+                    Ok(other) => self.handle_swarm_event(other),
+                    Err(_) => {
+                        let _ = Swarm::remove_listener(&mut self.swarm, listener_id);
+                        return Err(StartListeningError::ReadyTimeout);
                     }
                 }
-                _ => {
-                    return Err(err.into());
-                }
             }
+        });
+        self.end_wait();
+        res
+    }
+
+    // Start listening on the first port of the range that is free.
+    // If none of the ports could be used, the error of the last port is returned.
+    fn start_listening_in_range(
+        &mut self,
+        ip: IpAddr,
+        range: RangeInclusive<u16>,
+    ) -> Result<Multiaddr, StartListeningError> {
+        let mut error = StartListeningError::Listen;
+        for port in range {
+            let addr = socket_addr_to_multiaddr(SocketAddr::new(ip, port));
+            match self.start_listening(Some(addr)) {
+                Ok(addr) => return Ok(addr),
+                Err(err) => error = err,
+            }
+        }
+        Err(error)
+    }
+
+    // Start listening only on the loopback interfaces, with an OS assigned port.
+    // Listening on the IPv6 loopback is optional, since IPv6 may not be available on the host.
+    fn start_listening_local(&mut self) -> Result<Multiaddr, StartListeningError> {
+        let ipv4_addr = socket_addr_to_multiaddr(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)));
+        let addr = self.start_listening(Some(ipv4_addr))?;
+        let ipv6_addr = socket_addr_to_multiaddr(SocketAddr::from((Ipv6Addr::LOCALHOST, 0)));
+        let _ = self.start_listening(Some(ipv6_addr));
+        Ok(addr)
+    }
+
+    // Dial the address on a new connection and wait for the first ping of the peer after it was established.
+    // Pings of other connections to the peer may be received in the meantime, which can not be distinguished from
+    // pings on the new connection.
+    fn probe(&mut self, target_peer: PeerId, target_addr: Multiaddr) -> Result<ProbeTimings, ProbeError> {
+        if !self.swarm.is_probing_enabled() {
+            return Err(ProbeError::Disabled);
         }
         let start = Instant::now();
-        task::block_on(async {
+        Swarm::dial_addr(&mut self.swarm, target_addr.clone())
+            .map_err(|limit| ProbeError::Connect(self.refuse_dial(limit)))?;
+        let deadline = start + self.connection_timeout + self.request_timeout;
+        self.begin_wait();
+        let res = task::block_on(async {
+            let mut connect = None;
             loop {
-                let event = self.swarm.next_event().await;
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let event = match async_std::future::timeout(remaining, self.swarm.next_event()).await {
+                    Ok(event) => event,
+                    Err(_) => return Err(ProbeError::Timeout),
+                };
                 match event {
                     SwarmEvent::ConnectionEstablished {
                         peer_id,
-                        endpoint: ConnectedPoint::Dialer { address: _ },
+                        endpoint: ConnectedPoint::Dialer { ref address },
                         num_established: _,
-                    } => {
-                        if peer_id == target_peer {
-                            return Ok(peer_id);
-                        } else {
-                            self.handle_swarm_event(event)
+                    } if connect.is_none() && *address == target_addr => {
+                        self.handle_swarm_event(event);
+                        if peer_id != target_peer {
+                            return Err(ProbeError::Connect(ConnectPeerError::InvalidPeerId));
                         }
+                        connect = Some(start.elapsed());
                     }
                     SwarmEvent::UnreachableAddr {
                         peer_id,
-                        address: _,
+                        address,
                         error,
-                        attempts_remaining: 0,
-                    } => {
-                        if peer_id == target_peer {
-                            return Err(ConnectPeerError::from(error));
+                        attempts_remaining,
+                    } if connect.is_none() && address == target_addr => {
+                        let error = ConnectPeerError::from(error);
+                        self.handle_dial_failure(Some(peer_id), address, error.clone(), attempts_remaining);
+                        return Err(ProbeError::Connect(error));
+                    }
+                    SwarmEvent::UnknownPeerUnreachableAddr { address, error }
+                        if connect.is_none() && address == target_addr =>
+                    {
+                        let error = ConnectPeerError::from(error);
+                        self.handle_dial_failure(None, address, error.clone(), 0);
+                        return Err(ProbeError::Connect(error));
+                    }
+                    SwarmEvent::Behaviour(P2PEvent::Ping(P2PPingEvent::Ping { peer_id, rtt }))
+                        if peer_id == target_peer =>
+                    {
+                        self.record_rtt(peer_id, rtt);
+                        if let Some(connect) = connect {
+                            return Ok(ProbeTimings { connect, rtt });
                         }
                     }
-                    SwarmEvent::UnknownPeerUnreachableAddr { address, error } => {
-                        if address == target_addr {
-                            return Err(ConnectPeerError::from(error));
+                    SwarmEvent::Behaviour(P2PEvent::Ping(P2PPingEvent::Failure { peer_id }))
+                        if peer_id == target_peer && connect.is_some() =>
+                    {
+                        return Err(ProbeError::PingFailed);
+                    }
+                    other => self.handle_swarm_event(other),
+                }
+            }
+        });
+        self.end_wait();
+        res
+    }
+
+    // Dial the peer on a new connection and wait until it sent its identifying information, which the identify
+    // protocol requests on each new connection. The new connection is closed by the swarm once it is idle.
+    // The known addresses of the peer are dialed one after another until the information was received, or the
+    // deadline passed.
+    fn refresh_identify(&mut self, target_peer: PeerId) -> Result<PeerProtocolInfo, ConnectPeerError> {
+        let mut target_addrs = self.swarm.get_peer_addr(&target_peer).cloned().unwrap_or_default();
+        for addr in self.connection_manager.get_retained_addrs(&target_peer) {
+            if !target_addrs.contains(&addr) {
+                target_addrs.push(addr);
+            }
+        }
+        let deadline = Instant::now() + self.connection_timeout + self.request_timeout;
+        let mut res = Err(ConnectPeerError::NoAddresses);
+        for target_addr in target_addrs {
+            res = self.refresh_identify_via(target_peer, target_addr, deadline);
+            // Only a failed dial is retried on the next address.
+            if matches!(
+                res,
+                Ok(_) | Err(ConnectPeerError::Timeout) | Err(ConnectPeerError::NoIdentifyInfo)
+            ) {
+                break;
+            }
+        }
+        res
+    }
+
+    // Dial the address of the peer and wait until the peer sent its identifying information on the new connection.
+    fn refresh_identify_via(
+        &mut self,
+        target_peer: PeerId,
+        target_addr: Multiaddr,
+        deadline: Instant,
+    ) -> Result<PeerProtocolInfo, ConnectPeerError> {
+        Swarm::dial_addr(&mut self.swarm, target_addr.clone()).map_err(|limit| self.refuse_dial(limit))?;
+        self.begin_wait();
+        let res = task::block_on(async {
+            loop {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let event = match async_std::future::timeout(remaining, self.swarm.next_event()).await {
+                    Ok(event) => event,
+                    Err(_) => return Err(ConnectPeerError::Timeout),
+                };
+                match event {
+                    SwarmEvent::ConnectionEstablished {
+                        peer_id,
+                        endpoint: ConnectedPoint::Dialer { ref address },
+                        num_established: _,
+                    } if *address == target_addr && peer_id != target_peer => {
+                        self.handle_swarm_event(event);
+                        return Err(ConnectPeerError::InvalidPeerId);
+                    }
+                    SwarmEvent::UnreachableAddr {
+                        peer_id,
+                        address,
+                        error,
+                        attempts_remaining,
+                    } if address == target_addr => {
+                        let error = ConnectPeerError::from(error);
+                        self.handle_dial_failure(Some(peer_id), address, error.clone(), attempts_remaining);
+                        return Err(error);
+                    }
+                    SwarmEvent::UnknownPeerUnreachableAddr { address, error } if address == target_addr => {
+                        let error = ConnectPeerError::from(error);
+                        self.handle_dial_failure(None, address, error.clone(), 0);
+                        return Err(error);
+                    }
+                    SwarmEvent::Behaviour(P2PEvent::Identify(ref boxed_event)) => {
+                        let is_target = matches!(
+                            boxed_event.deref(),
+                            P2PIdentifyEvent::Received { peer_id, .. } if *peer_id == target_peer
+                        );
+                        self.handle_swarm_event(event);
+                        if is_target {
+                            let info = self
+                                .connection_manager
+                                .get_identify_info(&target_peer)
+                                .map(|info| PeerProtocolInfo::new(info, &self.protocol_versions));
+                            return info.ok_or(ConnectPeerError::NoIdentifyInfo);
                         }
                     }
-                    _ => self.handle_swarm_event(event),
+                    other => self.handle_swarm_event(other),
                 }
-                if start.elapsed() > Duration::new(3, 0) {
-                    return Err(ConnectPeerError::Timeout);
+            }
+        });
+        self.end_wait();
+        res
+    }
+
+    // Re-establish a closed keep-alive connection, returns false if it could not be re-established.
+    // In deferred mode, only the dial is started and the result is handled once the swarm reports it.
+    fn reconnect_peer(&mut self, peer_id: PeerId, address: Multiaddr) -> bool {
+        match self.reconnect_mode {
+            ReconnectMode::Sync => self.connect_peer(peer_id, address).is_ok(),
+            ReconnectMode::Deferred => {
+                let res = match Swarm::dial(&mut self.swarm, &peer_id) {
+                    Err(DialError::NoAddresses) => {
+                        Swarm::dial_addr(&mut self.swarm, address.clone()).map_err(|limit| self.refuse_dial(limit))
+                    }
+                    Err(DialError::ConnectionLimit(limit)) => Err(self.refuse_dial(limit)),
+                    res => res.map_err(ConnectPeerError::from),
+                };
+                if res.is_ok() {
+                    self.connection_manager.insert_pending_dial(peer_id);
+                    self.pending_reconnects.insert(peer_id, address);
                 }
+                res.is_ok()
             }
-        })
+        }
     }
 
-    // Try sending a request envelope to a remote peer if it was approved by the firewall, and return the received
-    // Response. If no response is received, a RequestMessageError::Rejected will be returned.
-    fn send_envelope_to_peer(
+    // Stop keeping the connection alive, once a deferred reconnect failed.
+    fn fail_reconnect(&mut self, peer_id: &PeerId) {
+        if self.pending_reconnects.remove(peer_id).is_some() {
+            self.connection_manager.remove_connection(peer_id);
+        }
+    }
+
+    // Try to connect a remote peer by id, and if the peer id is not know yet the address is used.
+    // The peer is tracked as pending dial until the dial succeeded, failed or timed out.
+    // If the peer has no addresses in the address book, the retained addresses of previous connections to it are
+    // dialed concurrently with the `target_addr`, and the first connection to the peer is used. The connection
+    // timeout applies to the whole attempt, so stale retained addresses don't extend it.
+    // Each address that is refused due to the limit of pending dials is reported, the attempt only fails right away
+    // if all of them were refused. Dials can not be aborted in the swarm, so the dials that lost the race keep running:
+    // their failures are ignored, and a connection that they establish is handled as any other connection to the peer.
+    fn connect_peer(
         &mut self,
-        peer_id: PeerId,
-        envelope: RequestEnvelope<Req>,
-    ) -> Result<Res, RequestMessageError> {
-        let req_id = self.swarm.send_request(&peer_id, envelope);
-        let start = Instant::now();
-        task::block_on(async {
+        target_peer: PeerId,
+        target_addr: Multiaddr,
+    ) -> Result<ConnectedPoint, ConnectPeerError> {
+        let deadline = Instant::now() + self.connection_timeout;
+        match Swarm::dial(&mut self.swarm, &target_peer) {
+            Ok(()) => self.await_dial(target_peer, Vec::new(), deadline),
+            Err(DialError::NoAddresses) => {
+                let mut addrs = self.connection_manager.get_retained_addrs(&target_peer);
+                addrs.retain(|addr| *addr != target_addr);
+                addrs.push(target_addr);
+                let mut dialed = Vec::new();
+                let mut refused = None;
+                for addr in addrs {
+                    match Swarm::dial_addr(&mut self.swarm, addr.clone()) {
+                        Ok(()) => dialed.push(addr),
+                        Err(limit) => refused = Some(self.refuse_dial(limit)),
+                    }
+                }
+                match refused {
+                    Some(err) if dialed.is_empty() => Err(err),
+                    _ => self.await_dial(target_peer, dialed, deadline),
+                }
+            }
+            Err(DialError::ConnectionLimit(limit)) => Err(self.refuse_dial(limit)),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    // Wait until the dial of the target peer succeeded or failed, or the deadline passed. If addresses were dialed
+    // directly, their failures are reported as failures of an unknown peer, and the dial failed once all of them
+    // failed. Failed addresses are removed from the retained addresses of the peer.
+    // On success, the endpoint of the established connection is returned.
+    fn await_dial(
+        &mut self,
+        target_peer: PeerId,
+        mut target_addrs: Vec<Multiaddr>,
+        deadline: Instant,
+    ) -> Result<ConnectedPoint, ConnectPeerError> {
+        self.connection_manager.insert_pending_dial(target_peer);
+        self.begin_wait();
+        let res = task::block_on(async {
             loop {
-                let event = self.swarm.next_event().await;
+                // The timeout also applies if no other events occur on the swarm while the dial is pending.
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let event = match async_std::future::timeout(remaining, self.swarm.next_event()).await {
+                    Ok(event) => event,
+                    Err(_) => {
+                        // Later requests don't wait for the dial anymore, even if it is still pending in the swarm.
+                        self.connection_manager.remove_pending_dial(&target_peer);
+                        return Err(ConnectPeerError::Timeout);
+                    }
+                };
                 match event {
-                    SwarmEvent::Behaviour(P2PEvent::RequestResponse(ref boxed_event)) => {
-                        match boxed_event.clone().deref().clone() {
-                            P2PReqResEvent::Res {
-                                peer_id: _,
-                                request_id,
-                                response,
-                            } => {
-                                if request_id == req_id {
-                                    return Ok(response);
-                                }
-                            }
-                            P2PReqResEvent::InboundFailure {
-                                peer_id: _,
-                                request_id,
-                                error,
-                            } => {
-                                if request_id == req_id {
-                                    return Err(RequestMessageError::Inbound(error));
-                                }
-                            }
-                            P2PReqResEvent::OutboundFailure {
-                                peer_id: _,
-                                request_id,
-                                error,
-                            } => {
-                                if request_id == req_id {
-                                    return Err(RequestMessageError::Outbound(error));
-                                }
+                    SwarmEvent::ConnectionEstablished {
+                        peer_id,
+                        endpoint: ConnectedPoint::Dialer { ref address },
+                        num_established: _,
+                    } => {
+                        let endpoint = ConnectedPoint::Dialer {
+                            address: address.clone(),
+                        };
+                        self.handle_swarm_event(event);
+                        if peer_id == target_peer {
+                            if let ConnectedPoint::Dialer { address } = &endpoint {
+                                let superseded = target_addrs.drain(..).filter(|addr| addr != address);
+                                self.superseded_dials.extend(superseded);
                             }
-                            _ => self.handle_swarm_event(event),
+                            return Ok(endpoint);
+                        }
+                    }
+                    SwarmEvent::UnreachableAddr {
+                        peer_id,
+                        address,
+                        error,
+                        attempts_remaining: 0,
+                    } if peer_id == target_peer => {
+                        self.connection_manager.remove_pending_dial(&peer_id);
+                        let error = ConnectPeerError::from(error);
+                        self.handle_dial_failure(Some(peer_id), address, error.clone(), 0);
+                        return Err(error);
+                    }
+                    SwarmEvent::UnknownPeerUnreachableAddr { address, error } if target_addrs.contains(&address) => {
+                        target_addrs.retain(|addr| *addr != address);
+                        self.connection_manager.remove_retained_addr(&target_peer, &address);
+                        let error = ConnectPeerError::from(error);
+                        self.handle_dial_failure(None, address, error.clone(), target_addrs.len() as u32);
+                        if target_addrs.is_empty() {
+                            self.connection_manager.remove_pending_dial(&target_peer);
+                            return Err(error);
                         }
                     }
                     _ => self.handle_swarm_event(event),
                 }
-                if start.elapsed() > Duration::new(3, 0) {
-                    return Err(RequestMessageError::Rejected(FirewallBlocked::Remote));
-                }
             }
-        })
+        });
+        self.end_wait();
+        res
+    }
+
+    // Close the connections to a peer and re-establish a new one with the previous keep-alive configuration.
+    fn reconnect(&mut self, peer_id: PeerId) -> Result<PeerId, ConnectPeerError> {
+        let previous = self.connection_manager.remove_connection(&peer_id);
+        let keep_alive = previous
+            .as_ref()
+            .map(|connection| connection.keep_alive())
+            .unwrap_or(KeepAlive::None);
+        let addr = match previous.as_ref().map(|connection| connection.connected_point()) {
+            Some(ConnectedPoint::Dialer { address }) => Some(address.clone()),
+            _ => self
+                .swarm
+                .get_peer_addr(&peer_id)
+                .and_then(|addrs| addrs.first().cloned()),
+        }
+        .ok_or(ConnectPeerError::NoAddresses)?;
+        // Connections in the swarm can only actively be closed by banning the peer, the ban is lifted right away.
+        // A peer that is still connected is not banned, so this does not unban peers that were banned before.
+        if Swarm::is_connected(&self.swarm, &peer_id) {
+            Swarm::ban_peer_id(&mut self.swarm, peer_id);
+            Swarm::unban_peer_id(&mut self.swarm, peer_id);
+        }
+        let endpoint = self.connect_peer(peer_id, addr)?;
+        self.connection_manager.insert(peer_id, endpoint, keep_alive.clone());
+        self.connection_manager.set_keep_alive(&peer_id, keep_alive);
+        Ok(peer_id)
+    }
+
+    // Outstanding operations of the task, with the number of requests that were coalesced into the awaited request.
+    fn state_dump(&self, coalesced_requests: usize) -> StateDump {
+        let mut outbound_requests: Vec<PendingOutboundRequest> = self
+            .pending_requests
+            .iter()
+            .map(|(request_id, (peer_id, sent))| PendingOutboundRequest {
+                request_id: *request_id,
+                peer_id: *peer_id,
+                elapsed: sent.elapsed(),
+            })
+            .collect();
+        outbound_requests.sort_by(|a, b| b.elapsed.cmp(&a.elapsed));
+        StateDump {
+            pending_dials: self.connection_manager.pending_dials(),
+            outbound_requests,
+            queued_inbound: self
+                .deferred_requests
+                .iter()
+                .map(|(peer_id, request_id, _)| (*peer_id, *request_id))
+                .collect(),
+            inbound_in_flight: self
+                .inbound_in_flight
+                .iter()
+                .map(|(request_id, (source, _))| (*source, *request_id))
+                .collect(),
+            queued_outbound: self.paused_requests.len(),
+            deferred_actor_requests: self.deferred_actor_requests.len(),
+            coalesced_requests,
+        }
     }
 
-    // Wrap the request into an envelope, which enables using a relay peer, and send it to the remote.
-    // Depending on the config, it is ether send directly or via the relay.
-    fn send_request(&mut self, peer_id: PeerId, request: Req) -> Result<Res, RequestMessageError> {
-        let local_peer = Swarm::local_peer_id(&self.swarm);
-        let envelope = RequestEnvelope {
-            source: local_peer.to_string(),
-            message: request,
-            target: peer_id.to_string(),
+    // Determine the health of the swarm, with the utilization of the event loop since the oldest sample.
+    fn health(&self) -> HealthSummary {
+        let (start, busy) = self.utilization_samples[0];
+        let elapsed = start.elapsed().as_secs_f64();
+        let loop_utilization = if elapsed > 0.0 {
+            ((self.metrics.loop_busy_sum - busy).as_secs_f64() / elapsed).min(1.0)
+        } else {
+            0.0
         };
-        match self.relay {
-            RelayConfig::NoRelay => self.send_envelope_to_peer(peer_id, envelope),
-            RelayConfig::RelayAlways {
-                peer_id: relay_id,
-                addr: _,
-            } => self.send_envelope_to_peer(relay_id, envelope),
-            RelayConfig::RelayBackup {
-                peer_id: relay_id,
-                addr: _,
-            } => {
-                // try sending directly, otherwise use relay
-                let res = self.send_envelope_to_peer(peer_id, envelope.clone());
-                if let Err(RequestMessageError::Outbound(P2POutboundFailure::DialFailure)) = res {
-                    self.send_envelope_to_peer(relay_id, envelope)
-                } else {
-                    res
-                }
+
+        let listen_addrs = Swarm::listeners(&self.swarm).count();
+        let connected_peers = Swarm::network_info(&self.swarm).num_peers();
+        let (relay_connected, relay_required) = match &self.relay {
+            RelayConfig::NoRelay => (None, false),
+            RelayConfig::RelayAlways { peer_id, .. } => (Some(Swarm::is_connected(&self.swarm, peer_id)), true),
+            RelayConfig::RelayBackup { peer_id, .. } => (Some(Swarm::is_connected(&self.swarm, peer_id)), false),
+        };
+
+        let mut factors = Vec::new();
+        let mut status = HealthStatus::Healthy;
+        if listen_addrs == 0 {
+            factors.push(HealthFactor::NotListening);
+            status = HealthStatus::Unhealthy;
+        }
+        if relay_connected == Some(false) {
+            factors.push(HealthFactor::RelayDisconnected);
+            if relay_required {
+                status = HealthStatus::Unhealthy;
+            }
+        }
+        if connected_peers == 0 {
+            factors.push(HealthFactor::NoConnectedPeers);
+        }
+        if loop_utilization > SATURATION_THRESHOLD {
+            factors.push(HealthFactor::Saturated);
+        }
+        if status == HealthStatus::Healthy && !factors.is_empty() {
+            status = HealthStatus::Degraded;
+        }
+        HealthSummary {
+            status,
+            factors,
+            listen_addrs,
+            relay_connected,
+            connected_peers,
+            loop_utilization,
+        }
+    }
+
+    // Add the peers that are not a member yet to the group, creates the group if it does not exist.
+    fn add_to_group(&mut self, group: &str, peers: Vec<PeerId>) {
+        let members = self.groups.entry(group.to_string()).or_default();
+        for peer_id in peers {
+            if !members.contains(&peer_id) {
+                members.push(peer_id);
+            }
+        }
+    }
+
+    // Try to establish a direct connection to a peer that communicates via the relay, so that future requests can be
+    // sent directly. After a failed attempt, the peer is only dialed again once the backoff elapsed, the relay
+    // configuration changed, or a direct connection was established and closed again.
+    // Note: this does not implement hole punching, the upgrade only succeeds if one of the known addresses of the peer
+    // can be reached directly.
+    fn try_direct_upgrade(&mut self, peer_id: PeerId) {
+        if !self.direct_upgrade || self.pending_upgrades.contains(&peer_id) {
+            return;
+        }
+        let backoff = self.upgrade_backoff;
+        if matches!(self.upgrade_attempts.get(&peer_id), Some(attempt) if attempt.elapsed() < backoff) {
+            return;
+        }
+        self.upgrade_attempts.insert(peer_id, Instant::now());
+        if Swarm::is_connected(&self.swarm, &peer_id) {
+            self.unrelayed_peers.insert(peer_id);
+            let result = Ok(());
+            self.emit_event(CommunicationEvent::DirectConnectionUpgrade { peer_id, result });
+            return;
+        }
+        match Swarm::dial(&mut self.swarm, &peer_id) {
+            Ok(()) => {
+                self.connection_manager.insert_pending_dial(peer_id);
+                self.pending_upgrades.insert(peer_id);
+            }
+            Err(err) => {
+                let error = match err {
+                    DialError::ConnectionLimit(limit) => self.refuse_dial(limit),
+                    err => ConnectPeerError::from(err),
+                };
+                let result = Err(error);
+                self.emit_event(CommunicationEvent::DirectConnectionUpgrade { peer_id, result });
             }
         }
     }
 
     // Set the new relay configuration. If a relay is use, a keep-alive connection to the relay will be established.
-    fn set_relay(&mut self, config: RelayConfig) -> Result<(), ConnectPeerError> {
-        match config.clone() {
-            RelayConfig::NoRelay => Ok(()),
+    // The connection to the previous relay is not kept alive anymore, unless it is still used as relay for a specific
+    // peer. With `disconnect_previous`, the connections to the previous relay are closed right away in that case.
+    fn set_relay(&mut self, config: RelayConfig, disconnect_previous: bool) -> Result<(), ConnectPeerError> {
+        let relay_peer = match config.clone() {
+            RelayConfig::NoRelay => None,
             RelayConfig::RelayAlways { peer_id, addr } | RelayConfig::RelayBackup { peer_id, addr } => {
-                let res = self.connect_peer(peer_id, addr.clone());
-                match res {
-                    Ok(_) => {
-                        let endpoint = ConnectedPoint::Dialer { address: addr };
-                        self.connection_manager.insert(peer_id, endpoint, KeepAlive::Unlimited);
-                        self.relay = config;
-                        Ok(())
+                let endpoint = self.connect_peer(peer_id, addr)?;
+                self.connection_manager.insert(peer_id, endpoint, KeepAlive::Unlimited);
+                Some(peer_id)
+            }
+        };
+        let previous = match std::mem::replace(&mut self.relay, config) {
+            RelayConfig::NoRelay => None,
+            RelayConfig::RelayAlways { peer_id, .. } | RelayConfig::RelayBackup { peer_id, .. } => Some(peer_id),
+        };
+        if let Some(previous) = previous {
+            let is_peer_relay = self.peer_relays.values().any(|relay| *relay == previous);
+            if Some(previous) != relay_peer && !is_peer_relay {
+                self.connection_manager.set_keep_alive(&previous, KeepAlive::None);
+                // The connections are closed by banning the peer, the ban is lifted right away.
+                if disconnect_previous && Swarm::is_connected(&self.swarm, &previous) {
+                    Swarm::ban_peer_id(&mut self.swarm, previous);
+                    Swarm::unban_peer_id(&mut self.swarm, previous);
+                }
+            }
+        }
+        self.unrelayed_peers.clear();
+        self.upgrade_attempts.clear();
+        Ok(())
+    }
+
+    // Use the relay again for the peers that are reached via it, but for which the relayed path was closed.
+    fn reset_relayed_paths(&mut self, relay_id: PeerId) {
+        let configured_relay = match self.relay {
+            RelayConfig::NoRelay => None,
+            RelayConfig::RelayAlways { peer_id, .. } | RelayConfig::RelayBackup { peer_id, .. } => Some(peer_id),
+        };
+        let peer_relays = &self.peer_relays;
+        self.unrelayed_peers.retain(|peer_id| {
+            let relay = peer_relays.get(peer_id).copied().or(configured_relay);
+            relay != Some(relay_id)
+        });
+    }
+
+    // Establish a keep-alive connection to the relay, and use it for the peer instead of the configured relay.
+    // No circuit to the peer is dialed, the envelopes to the peer are sent to the relay, which forwards them.
+    fn connect_via_relay(
+        &mut self,
+        peer_id: PeerId,
+        relay_peer: PeerId,
+        relay_addr: Multiaddr,
+    ) -> Result<ConnectionInfo, ConnectPeerError> {
+        let endpoint = self.connect_peer(relay_peer, relay_addr)?;
+        self.connection_manager
+            .insert(relay_peer, endpoint.clone(), KeepAlive::Unlimited);
+        self.peer_relays.insert(peer_id, relay_peer);
+        self.unrelayed_peers.remove(&peer_id);
+        self.upgrade_attempts.remove(&peer_id);
+        Ok(ConnectionInfo::new(relay_peer, &endpoint, KeepAlive::Unlimited))
+    }
+
+    // Collect the state of the firewall, relay, keep-alive connections and known addresses.
+    fn export_state(&mut self) -> CommunicationState {
+        let mut peers: HashMap<PeerId, PeerState> = HashMap::new();
+        for peer_id in self.swarm.get_all_peers() {
+            let addrs = self.swarm.get_peer_addr(peer_id).cloned().unwrap_or_default();
+            peers.entry(*peer_id).or_insert_with(|| PeerState::new(*peer_id)).addrs = addrs;
+        }
+        for (peer_id, connection) in self.connection_manager.current_connections() {
+            if let Some(keep_alive) = KeepAliveState::from_keep_alive(&connection.keep_alive()) {
+                let peer = peers.entry(peer_id).or_insert_with(|| PeerState::new(peer_id));
+                peer.keep_alive = Some(keep_alive);
+                // The dialed address is used to re-establish the connection, even if it is not in the address book.
+                if let ConnectedPoint::Dialer { address } = connection.connected_point() {
+                    if !peer.addrs.contains(address) {
+                        peer.addrs.insert(0, address.clone());
                     }
-                    Err(err) => Err(err),
                 }
             }
         }
+        for (peer_id, permission) in self.firewall.get_rules(&RequestDirection::In) {
+            peers
+                .entry(*peer_id)
+                .or_insert_with(|| PeerState::new(*peer_id))
+                .firewall_in = Some(*permission);
+        }
+        for (peer_id, permission) in self.firewall.get_rules(&RequestDirection::Out) {
+            peers
+                .entry(*peer_id)
+                .or_insert_with(|| PeerState::new(*peer_id))
+                .firewall_out = Some(*permission);
+        }
+        CommunicationState {
+            firewall_default_in: self.firewall.get_default(&RequestDirection::In),
+            firewall_default_out: self.firewall.get_default(&RequestDirection::Out),
+            firewall_mode: self.firewall.get_mode(),
+            relay: self.relay.clone(),
+            peers: peers.into_iter().map(|(_, peer)| peer).collect(),
+        }
+    }
+
+    // Apply the state, returns the result of setting the relay and the keep-alive connections that failed.
+    // The relay is set first, so that the connection to it is not established twice if it is also a keep-alive peer.
+    fn import_state(
+        &mut self,
+        state: CommunicationState,
+    ) -> (Result<(), ConnectPeerError>, Vec<(PeerId, ConnectPeerError)>) {
+        self.firewall
+            .set_default(&RequestDirection::In, state.firewall_default_in);
+        self.firewall
+            .set_default(&RequestDirection::Out, state.firewall_default_out);
+        self.firewall.set_mode(state.firewall_mode);
+        for peer in state.peers.iter() {
+            for addr in peer.addrs.iter() {
+                self.swarm.add_peer_addr(peer.peer_id, addr.clone());
+            }
+            if let Some(permission) = peer.firewall_in {
+                self.firewall.set_rule(peer.peer_id, &RequestDirection::In, permission);
+            }
+            if let Some(permission) = peer.firewall_out {
+                self.firewall.set_rule(peer.peer_id, &RequestDirection::Out, permission);
+            }
+        }
+        let relay = self.set_relay(state.relay, false);
+        let mut failed_connections = Vec::new();
+        for peer in state.peers {
+            let keep_alive = match peer.keep_alive {
+                Some(keep_alive) if !self.connection_manager.is_active_connection(&peer.peer_id) => {
+                    KeepAlive::from(keep_alive)
+                }
+                _ => continue,
+            };
+            let addr = match peer.addrs.first() {
+                Some(addr) => addr.clone(),
+                None => {
+                    failed_connections.push((peer.peer_id, ConnectPeerError::NoAddresses));
+                    continue;
+                }
+            };
+            match self.connect_peer(peer.peer_id, addr) {
+                Ok(endpoint) => {
+                    self.connection_manager
+                        .insert(peer.peer_id, endpoint, keep_alive.clone());
+                    self.connection_manager.set_keep_alive(&peer.peer_id, keep_alive);
+                }
+                Err(err) => failed_connections.push((peer.peer_id, err)),
+            }
+        }
+        (relay, failed_connections)
+    }
+
+    // Check the request against the firewall rules and the rate of its permission. In audit mode the request is
+    // always permitted, the observer is notified if the firewall would have rejected it and the reason is returned.
+    fn check_firewall(
+        &mut self,
+        request: Req,
+        peer_id: PeerId,
+        direction: RequestDirection,
+    ) -> Result<Option<FirewallBlocked>, FirewallBlocked> {
+        let res = if !self.firewall.is_permitted(request.clone(), peer_id, direction.clone()) {
+            Err(FirewallBlocked::Local)
+        } else if !self.firewall.try_record_rate(request, peer_id, &direction) {
+            Err(FirewallBlocked::RateLimited)
+        } else {
+            Ok(())
+        };
+        match res {
+            Ok(()) => Ok(None),
+            Err(blocked) if self.firewall.get_mode() == FirewallMode::Audit => {
+                self.emit_event(CommunicationEvent::FirewallWouldBlock { peer_id, direction });
+                Ok(Some(blocked))
+            }
+            Err(blocked) => Err(blocked),
+        }
+    }
+
+    // Send the record of a request that passed the firewall and is forwarded to the client or sent to the remote, to
+    // the audit actor without waiting for it.
+    fn audit_request(
+        &self,
+        request: &Req,
+        peer_id: PeerId,
+        direction: RequestDirection,
+        would_block: Option<FirewallBlocked>,
+    ) {
+        if let Some(audit) = self.audit.as_ref() {
+            let record = AuditRecord {
+                rule: self.firewall.matched_rule(&peer_id, &direction),
+                direction,
+                peer_id,
+                timestamp: SystemTime::now(),
+                request: if audit.include_payload {
+                    Some(request.clone())
+                } else {
+                    None
+                },
+                would_block,
+            };
+            audit.actor.tell(record, None);
+        }
+    }
+
+    // Check whether the relay may forward the incoming request, in addition to the rules for its source. In audit mode
+    // the request is always permitted, and the reason is returned if the firewall would have rejected it.
+    fn check_relay_firewall(
+        &mut self,
+        request: Req,
+        relay_id: PeerId,
+    ) -> Result<Option<FirewallBlocked>, FirewallBlocked> {
+        if self.firewall.is_relay_permitted(request) {
+            return Ok(None);
+        }
+        if self.firewall.get_mode() == FirewallMode::Audit {
+            self.emit_event(CommunicationEvent::FirewallWouldBlock {
+                peer_id: relay_id,
+                direction: RequestDirection::In,
+            });
+            return Ok(Some(FirewallBlocked::Local));
+        }
+        Err(FirewallBlocked::Local)
     }
 
     fn configure_firewall(&mut self, rule: FirewallRule) {
@@ -376,23 +1608,67 @@ where
                     self.firewall.remove_rule(&peer, &direction);
                 }
             }
+            FirewallRule::SetPermissionRate {
+                direction,
+                permission,
+                rate,
+            } => self.firewall.set_rate(&direction, permission, rate),
+            FirewallRule::SetRelayPermission(permission) => self.firewall.set_relay_permission(permission),
+            FirewallRule::SetMode(mode) => self.firewall.set_mode(mode),
+            FirewallRule::DenyAll { direction } => self.firewall.set_default(&direction, FirewallPermission::none()),
+            FirewallRule::AllowAll { direction } => self.firewall.set_default(&direction, FirewallPermission::all()),
         }
     }
 
     // Handle the messages that are received from other actors in the system.
     fn handle_actor_request(&mut self, event: CommunicationRequest<Req, ClientMsg>, sender: Sender) {
+        if self.is_paused
+            && matches!(
+                event,
+                CommunicationRequest::RequestMsg { .. } | CommunicationRequest::RequestMsgGroup { .. }
+            )
+        {
+            if self.queue_outbound {
+                self.paused_requests.push((event, sender));
+            } else if let CommunicationRequest::RequestMsgGroup { group, .. } = event {
+                let members = self.groups.get(&group).cloned().unwrap_or_default();
+                let res = members
+                    .into_iter()
+                    .map(|peer_id| (peer_id, Err(RequestMessageError::Paused)))
+                    .collect();
+                Self::send_response(CommunicationResults::RequestMsgGroupResult(res), sender);
+            } else {
+                let res = CommunicationResults::RequestMsgResult(Err(RequestMessageError::Paused));
+                Self::send_response(res, sender);
+            }
+            return;
+        }
         match event {
-            CommunicationRequest::RequestMsg { peer_id, request } => {
-                let res = if self
-                    .firewall
-                    .is_permitted(request.clone(), peer_id, RequestDirection::Out)
-                {
-                    self.send_request(peer_id, request)
-                } else {
-                    Err(RequestMessageError::Rejected(FirewallBlocked::Local))
-                };
-                Self::send_response(CommunicationResults::RequestMsgResult(res), sender);
+            message @ CommunicationRequest::RequestMsg { .. } => self.handle_request_msg(message, sender),
+            CommunicationRequest::CancelRequest(token) => {
+                // The awaited request was already answered, unless it is still queued.
+                let is_cancelled = self.cancel_queued_request(token);
+                Self::send_response(CommunicationResults::CancelRequestResult(is_cancelled), sender);
+            }
+            CommunicationRequest::CreateGroup { name, peers } => {
+                self.groups.remove(&name);
+                self.add_to_group(&name, peers);
+                Self::send_response(CommunicationResults::CreateGroupAck, sender);
+            }
+            CommunicationRequest::AddToGroup { group, peers } => {
+                self.add_to_group(&group, peers);
+                Self::send_response(CommunicationResults::AddToGroupAck, sender);
+            }
+            CommunicationRequest::RemoveFromGroup { group, peers } => {
+                if let Some(members) = self.groups.get_mut(&group) {
+                    members.retain(|peer_id| !peers.contains(peer_id));
+                    if members.is_empty() {
+                        self.groups.remove(&group);
+                    }
+                }
+                Self::send_response(CommunicationResults::RemoveFromGroupAck, sender);
             }
+            CommunicationRequest::RequestMsgGroup { group, request } => self.send_to_group(group, request, sender),
             CommunicationRequest::SetClientRef(client_ref) => {
                 self.client = client_ref;
                 let res = CommunicationResults::SetClientRefAck;
@@ -403,21 +1679,42 @@ where
                 addr,
                 keep_alive,
             } => {
-                let res = self.connect_peer(peer_id, addr.clone());
-                if res.is_ok() {
-                    let endpoint = ConnectedPoint::Dialer { address: addr };
-                    self.connection_manager.insert(peer_id, endpoint, keep_alive.clone());
-                    self.connection_manager.set_keep_alive(&peer_id, keep_alive);
-                }
+                let res = self.connect_peer(peer_id, addr).map(|endpoint| {
+                    self.connection_manager
+                        .insert(peer_id, endpoint.clone(), keep_alive.clone());
+                    self.connection_manager.set_keep_alive(&peer_id, keep_alive.clone());
+                    ConnectionInfo::new(peer_id, &endpoint, keep_alive)
+                });
+                Self::send_response(CommunicationResults::EstablishConnectionResult(res), sender);
+            }
+            CommunicationRequest::EstablishConnectionViaRelay {
+                peer_id,
+                relay_peer,
+                relay_addr,
+            } => {
+                let res = self.connect_via_relay(peer_id, relay_peer, relay_addr);
                 Self::send_response(CommunicationResults::EstablishConnectionResult(res), sender);
             }
             CommunicationRequest::CloseConnection(peer_id) => {
                 self.connection_manager.remove_connection(&peer_id);
                 Self::send_response(CommunicationResults::CloseConnectionAck, sender);
             }
-            CommunicationRequest::CheckConnection(peer_id) => {
+            CommunicationRequest::Reconnect(peer_id) => {
+                let res = self.reconnect(peer_id);
+                Self::send_response(CommunicationResults::ReconnectResult(res), sender);
+            }
+            CommunicationRequest::MaintainConnection { peer_id, addrs } => {
                 let is_connected = Swarm::is_connected(&self.swarm, &peer_id);
-                let res = CommunicationResults::CheckConnectionResult { peer_id, is_connected };
+                self.maintained.insert(peer_id, addrs, is_connected);
+                Self::send_response(CommunicationResults::MaintainConnectionAck, sender);
+            }
+            CommunicationRequest::StopMaintaining(peer_id) => {
+                let res = self.maintained.remove(&peer_id);
+                Self::send_response(CommunicationResults::StopMaintainingResult(res), sender);
+            }
+            CommunicationRequest::CheckConnection(peer_id) => {
+                let state = self.connection_state(&peer_id);
+                let res = CommunicationResults::CheckConnectionResult { peer_id, state };
                 Self::send_response(res, sender);
             }
             CommunicationRequest::GetSwarmInfo => {
@@ -431,48 +1728,301 @@ where
                 };
                 Self::send_response(res, sender);
             }
+            CommunicationRequest::GetSwarmInfoPartial {
+                include_peer_id,
+                include_listeners,
+                include_connections,
+            } => {
+                let peer_id = Some(*Swarm::local_peer_id(&self.swarm)).filter(|_| include_peer_id);
+                let listeners = if include_listeners {
+                    Some(Swarm::listeners(&self.swarm).cloned().collect())
+                } else {
+                    None
+                };
+                let connections = if include_connections {
+                    Some(self.connection_manager.current_connections())
+                } else {
+                    None
+                };
+                let res = CommunicationResults::SwarmInfoPartial {
+                    peer_id,
+                    listeners,
+                    connections,
+                };
+                Self::send_response(res, sender);
+            }
+            CommunicationRequest::GetProtocolInfo(peer_id) => {
+                let info = self
+                    .connection_manager
+                    .get_identify_info(&peer_id)
+                    .map(|info| PeerProtocolInfo::new(info, &self.protocol_versions));
+                Self::send_response(CommunicationResults::ProtocolInfo(info), sender);
+            }
+            CommunicationRequest::RefreshIdentify(peer_id) => {
+                let res = self.refresh_identify(peer_id);
+                Self::send_response(CommunicationResults::RefreshIdentifyResult(res), sender);
+            }
+            CommunicationRequest::DumpState => {
+                // Requests are only coalesced while another request is awaited, in which case the dump is created
+                // when the message is intercepted.
+                let dump = self.state_dump(0);
+                Self::send_response(CommunicationResults::StateDump(dump), sender);
+            }
+            CommunicationRequest::GetHealth => {
+                let health = self.health();
+                Self::send_response(CommunicationResults::Health(health), sender);
+            }
+            CommunicationRequest::GetListeningPorts => {
+                let ports = tcp_ports(Swarm::listeners(&self.swarm));
+                Self::send_response(CommunicationResults::ListeningPorts(ports), sender);
+            }
+            CommunicationRequest::ValidateMultiaddr(addr) => {
+                let res = addr
+                    .parse::<Multiaddr>()
+                    .map(|addr| MultiaddrInfo {
+                        transport: addr_transport(&addr),
+                        is_dialable: self.transport_support.can_dial(&addr),
+                        is_listenable: self.transport_support.can_listen(&addr),
+                        peer_id: addr_peer_id(&addr),
+                        addr,
+                    })
+                    .map_err(|e| e.to_string());
+                Self::send_response(CommunicationResults::ValidateMultiaddrResult(res), sender);
+            }
+            CommunicationRequest::GetKnownPeers => {
+                let mut known_peers: HashMap<PeerId, Vec<Multiaddr>> = self
+                    .swarm
+                    .get_all_peers()
+                    .into_iter()
+                    .map(|peer_id| (*peer_id, self.swarm.get_peer_addr(peer_id).cloned().unwrap_or_default()))
+                    .collect();
+                for (peer_id, _) in self.connection_manager.current_connections() {
+                    known_peers.entry(peer_id).or_default();
+                }
+                let peers = known_peers
+                    .into_iter()
+                    .map(|(peer_id, addrs)| (peer_id, addrs, self.connection_state(&peer_id)))
+                    .collect();
+                Self::send_response(CommunicationResults::KnownPeers(peers), sender);
+            }
+            CommunicationRequest::GetAddressBook => {
+                let address_book = self
+                    .swarm
+                    .get_all_peers()
+                    .into_iter()
+                    .map(|peer_id| (*peer_id, self.swarm.get_peer_addr(peer_id).cloned().unwrap_or_default()))
+                    .collect();
+                Self::send_response(CommunicationResults::AddressBook(address_book), sender);
+            }
+            CommunicationRequest::ClearAddressBook(peer_id) => {
+                // Only the addresses are removed, the swarm keeps established connections open.
+                let peers = match peer_id {
+                    Some(peer_id) => vec![peer_id],
+                    None => self.swarm.get_all_peers().into_iter().copied().collect(),
+                };
+                for peer_id in peers {
+                    self.swarm.remove_peer(&peer_id);
+                }
+                self.connection_manager.clear_addrs(peer_id.as_ref());
+                Self::send_response(CommunicationResults::ClearAddressBookAck, sender);
+            }
             CommunicationRequest::StartListening(addr) => {
                 let res = self.start_listening(addr);
                 Self::send_response(CommunicationResults::StartListeningResult(res), sender);
             }
+            CommunicationRequest::StartListeningInRange { ip, range } => {
+                let res = self.start_listening_in_range(ip, range);
+                Self::send_response(CommunicationResults::StartListeningResult(res), sender);
+            }
+            CommunicationRequest::StartListeningLocal => {
+                let res = self.start_listening_local();
+                Self::send_response(CommunicationResults::StartListeningResult(res), sender);
+            }
             CommunicationRequest::RemoveListener => {
-                let result = if let Some(listener_id) = self.listener.take() {
-                    Swarm::remove_listener(&mut self.swarm, listener_id)
-                } else {
+                let result = if self.listeners.is_empty() {
                     Err(())
+                } else {
+                    for (listener_id, _) in self.listeners.drain(..) {
+                        let _ = Swarm::remove_listener(&mut self.swarm, listener_id);
+                    }
+                    self.restore_listeners = false;
+                    Ok(())
                 };
                 let res = CommunicationResults::RemoveListenerResult(result);
                 Self::send_response(res, sender);
             }
+            CommunicationRequest::GetListenerErrors => {
+                let failures = self.listener_failures.iter().cloned().collect();
+                Self::send_response(CommunicationResults::ListenerErrors(failures), sender);
+            }
             CommunicationRequest::BanPeer(peer_id) => {
+                // The peer should not be unbanned automatically if it was already banned due to its score or failures.
+                if let Some(scoring) = self.peer_scoring.as_mut() {
+                    scoring.remove_ban(&peer_id, false);
+                }
+                if let Some(bans) = self.failure_bans.as_mut() {
+                    bans.remove_ban(&peer_id);
+                }
                 Swarm::ban_peer_id(&mut self.swarm, peer_id);
                 let res = CommunicationResults::BannedPeerAck(peer_id);
                 Self::send_response(res, sender);
             }
             CommunicationRequest::UnbanPeer(peer_id) => {
+                if let Some(scoring) = self.peer_scoring.as_mut() {
+                    scoring.remove_ban(&peer_id, true);
+                }
+                if let Some(bans) = self.failure_bans.as_mut() {
+                    bans.remove_ban(&peer_id);
+                }
                 Swarm::unban_peer_id(&mut self.swarm, peer_id);
                 let res = CommunicationResults::UnbannedPeerAck(peer_id);
                 Self::send_response(res, sender);
             }
             CommunicationRequest::SetRelay(config) => {
-                let res = self.set_relay(config);
+                let res = self.set_relay(config, false);
                 Self::send_response(CommunicationResults::SetRelayResult(res), sender);
             }
+            CommunicationRequest::SetRelayAndDisconnect(config) => {
+                let res = self.set_relay(config, true);
+                Self::send_response(CommunicationResults::SetRelayResult(res), sender);
+            }
+            CommunicationRequest::CloseRelayedConnection(peer_id) => {
+                self.unrelayed_peers.insert(peer_id);
+                Self::send_response(CommunicationResults::CloseRelayedConnectionAck, sender);
+            }
+            CommunicationRequest::ReconfigureNetwork { relay, firewall } => {
+                // Inbound requests that are received while the relay is connected are deferred until the rules
+                // were applied as well.
+                self.begin_wait();
+                let res = relay.map(|config| self.set_relay(config, false)).unwrap_or(Ok(()));
+                if res.is_ok() {
+                    for rule in firewall {
+                        self.configure_firewall(rule);
+                    }
+                }
+                self.end_wait();
+                Self::send_response(CommunicationResults::ReconfigureNetworkResult(res), sender);
+            }
             CommunicationRequest::ConfigureFirewall(rule) => {
                 self.configure_firewall(rule);
                 Self::send_response(CommunicationResults::ConfigureFirewallAck, sender);
             }
+            CommunicationRequest::GetFirewallDefault(direction) => {
+                let default = self.firewall.get_default(&direction);
+                Self::send_response(CommunicationResults::FirewallDefault(default), sender);
+            }
+            CommunicationRequest::CheckPermission {
+                peer_id,
+                direction,
+                request_sample,
+            } => {
+                let decision = self.firewall.check_permission(request_sample, peer_id, &direction);
+                Self::send_response(CommunicationResults::CheckPermissionResult(decision), sender);
+            }
+            CommunicationRequest::Probe { peer_id, addr } => {
+                let res = self.probe(peer_id, addr);
+                Self::send_response(CommunicationResults::ProbeResult(res), sender);
+            }
+            CommunicationRequest::ExportState => {
+                let state = self.export_state();
+                Self::send_response(CommunicationResults::State(state), sender);
+            }
+            CommunicationRequest::ImportState(state) => {
+                let (relay, failed_connections) = self.import_state(state);
+                let res = CommunicationResults::ImportStateResult {
+                    relay,
+                    failed_connections,
+                };
+                Self::send_response(res, sender);
+            }
+            CommunicationRequest::SetProtocolTimeouts {
+                request_timeout,
+                connection_timeout,
+            } => {
+                if let Some(timeout) = request_timeout {
+                    self.request_timeout = timeout;
+                }
+                if let Some(timeout) = connection_timeout {
+                    self.connection_timeout = timeout;
+                }
+                Self::send_response(CommunicationResults::SetProtocolTimeoutsAck, sender);
+            }
+            CommunicationRequest::SetClientAskTimeout(timeout) => {
+                let res = if timeout > Duration::from_secs(0) {
+                    Ok(std::mem::replace(&mut self.client_timeout, timeout))
+                } else {
+                    Err(InvalidTimeout(timeout))
+                };
+                Self::send_response(CommunicationResults::SetClientAskTimeoutResult(res), sender);
+            }
+            CommunicationRequest::GetPeerScores => {
+                let scores = self
+                    .peer_scoring
+                    .as_ref()
+                    .map(|scoring| scoring.scores())
+                    .unwrap_or_default();
+                Self::send_response(CommunicationResults::PeerScores(scores), sender);
+            }
+            CommunicationRequest::GetMetrics => {
+                let mut metrics = self.metrics.clone();
+                let network_info = Swarm::network_info(&self.swarm);
+                metrics.connected_peers = network_info.num_peers();
+                metrics.dials_in_progress = network_info.connection_counters().num_pending_outgoing() as usize;
+                metrics.outbound_in_flight = self.pending_requests.len();
+                metrics.inbound_in_flight = self.inbound_in_flight.len();
+                Self::send_response(CommunicationResults::Metrics(Box::new(metrics)), sender);
+            }
+            CommunicationRequest::GetUptime => {
+                let uptime = CommunicationResults::Uptime {
+                    started_at: self.started_at,
+                    elapsed: self.started.elapsed(),
+                };
+                Self::send_response(uptime, sender);
+            }
+            CommunicationRequest::Pause { queue_outbound } => {
+                self.is_paused = true;
+                self.queue_outbound = queue_outbound;
+                Self::send_response(CommunicationResults::PauseAck, sender);
+            }
+            CommunicationRequest::Resume => {
+                self.is_paused = false;
+                Self::send_response(CommunicationResults::ResumeAck, sender);
+                for (request, sender) in std::mem::take(&mut self.paused_requests) {
+                    self.handle_actor_request(request, sender);
+                }
+            }
+            CommunicationRequest::GetPendingRequests(peer_id) => {
+                let count = match peer_id {
+                    Some(peer_id) => self.pending_requests.values().filter(|(p, _)| *p == peer_id).count(),
+                    None => self.pending_requests.len(),
+                };
+                Self::send_response(CommunicationResults::PendingRequests(count), sender);
+            }
             CommunicationRequest::Shutdown => unreachable!(),
         }
     }
 
     // Handle incoming enveloped from either a peer directly or via the relay peer.
-    fn handle_incoming_envelope(&mut self, peer_id: PeerId, request_id: RequestId, request: RequestEnvelope<Req>) {
-        if Swarm::local_peer_id(&self.swarm).to_string() != request.target {
+    fn handle_incoming_envelope(&mut self, peer_id: PeerId, request_id: RequestId, mut request: RequestEnvelope<Req>) {
+        let local_peer_id = *Swarm::local_peer_id(&self.swarm);
+        if local_peer_id.to_string() != request.target {
+            self.metrics.inbound_misrouted += 1;
+            self.emit_event(CommunicationEvent::EnvelopeMisrouted {
+                peer_id,
+                target: request.target,
+                local_peer_id,
+            });
+            return;
+        }
+        // Requests of a connection are only handled once it was authorized.
+        if self.pending_authorizations.contains_key(&peer_id) {
+            return;
+        }
+        if self.is_paused {
+            self.swarm.send_rejection(request_id, RejectReason::Paused);
             return;
         }
         if let Ok(source) = PeerId::from_str(&request.source) {
-            let is_active_direct = peer_id == source && self.connection_manager.is_active_connection(&peer_id);
             let from_relay = match self.relay {
                 RelayConfig::RelayAlways {
                     peer_id: relay_id,
@@ -483,55 +2033,315 @@ where
                     addr: _,
                 } => peer_id == relay_id,
                 RelayConfig::NoRelay => false,
+            } || self.peer_relays.get(&source) == Some(&peer_id);
+            let is_active_direct = peer_id == source && self.connection_manager.is_active_connection(&peer_id);
+            // The source of the envelope is only trusted if it is the connected peer or was forwarded by a relay,
+            // other envelopes are dropped before they are accounted to the rates of the claimed source.
+            if !is_active_direct && !from_relay {
+                return;
+            }
+            // Drop forwarded requests if the relayed path to the source was closed.
+            if from_relay && self.unrelayed_peers.contains(&source) {
+                return;
+            }
+            // Drop stale requests before they are forwarded to the client.
+            if request.is_expired() {
+                self.emit_event(CommunicationEvent::EnvelopeExpired { source });
+                return;
+            }
+            if let Some(hook) = self.incoming_request_hook.as_ref() {
+                hook(&mut request.message, source);
+            }
+            // Relayed requests additionally have to be permitted for the relay.
+            let relay_res = if from_relay {
+                self.check_relay_firewall(request.message.clone(), peer_id)
+            } else {
+                Ok(None)
+            };
+            let is_relay_permitted = relay_res.is_ok();
+            let res = match relay_res {
+                Ok(relay_would_block) => self
+                    .check_firewall(request.message.clone(), source, RequestDirection::In)
+                    .map(|would_block| relay_would_block.or(would_block)),
+                Err(blocked) => Err(blocked),
             };
-            let is_permitted = self
-                .firewall
-                .is_permitted(request.message.clone(), source, RequestDirection::In);
 
-            if (is_active_direct || from_relay) && is_permitted {
-                if let Some(res) = self.ask_client(request.message) {
-                    let _ = self.swarm.send_response(request_id, res);
+            if let Ok(would_block) = res {
+                self.metrics.record_inbound(source);
+                if let Some(hook) = self.provenance_hook.as_ref() {
+                    let relay = if from_relay { Some(peer_id) } else { None };
+                    hook(
+                        &mut request.message,
+                        RequestProvenance {
+                            source,
+                            relay,
+                            request_id,
+                        },
+                    );
+                }
+                self.forward_to_client(source, request_id, request.message, would_block);
+                if from_relay {
+                    self.try_direct_upgrade(source);
+                }
+            } else {
+                self.metrics.firewall_blocked_in += 1;
+                if self.send_firewall_rejections {
+                    self.swarm.send_rejection(request_id, RejectReason::Blocked);
+                }
+                // The source of a relayed request can not be verified, hence only the connected peer is penalized,
+                // and the relay only if it forwarded a request that it was not permitted to forward.
+                if !from_relay || !is_relay_permitted {
+                    self.penalize_peer(peer_id, Misbehaviour::FirewallBlock);
                 }
             }
+        } else {
+            self.penalize_peer(peer_id, Misbehaviour::MalformedEnvelope);
+            self.record_failure(peer_id);
         }
     }
 
     // Send incoming request to the client.
     // Eventually other swarm events lik e.g. incoming connection should also be send to some top level actor.
-    fn handle_swarm_event<HandleErr>(&mut self, event: SwarmEvent<P2PEvent<RequestEnvelope<Req>, Res>, HandleErr>) {
+    fn handle_swarm_event<HandleErr: std::error::Error + 'static>(
+        &mut self,
+        event: SwarmEvent<P2PEvent<RequestEnvelope<Req>, Res>, HandleErr>,
+    ) {
+        if let Some(level) = self.log_swarm_events {
+            log::log!(level, "Swarm event: {}", describe_swarm_event(&event));
+        }
         match event {
             SwarmEvent::Behaviour(behaviour_event) => match behaviour_event {
-                P2PEvent::RequestResponse(boxed_event) => {
-                    if let P2PReqResEvent::Req {
+                P2PEvent::RequestResponse(boxed_event) => match boxed_event.deref().clone() {
+                    P2PReqResEvent::Req {
                         peer_id,
                         request_id,
                         request,
-                    } = boxed_event.deref().clone()
+                    } => {
+                        // Requests are queued behind the deferred ones to keep their order.
+                        let is_blocked = self.wait_depth > 0 || self.is_client_saturated();
+                        if is_blocked || !self.deferred_requests.is_empty() {
+                            self.defer_request(peer_id, request_id, request);
+                        } else {
+                            self.handle_incoming_envelope(peer_id, request_id, request)
+                        }
+                    }
+                    P2PReqResEvent::InboundFailure {
+                        peer_id,
+                        request_id,
+                        error,
+                    } => {
+                        self.reported_responses.remove(&request_id);
+                        // Timeouts and omitted responses are caused by the local system, and closed connections
+                        // are no misbehaviour of the peer.
+                        if !matches!(
+                            error,
+                            P2PInboundFailure::Timeout
+                                | P2PInboundFailure::ResponseOmission
+                                | P2PInboundFailure::ConnectionClosed
+                        ) {
+                            self.penalize_peer(peer_id, Misbehaviour::InboundFailure);
+                        }
+                        // Only failures to speak the protocol count towards the failure ban.
+                        if let P2PInboundFailure::UnsupportedProtocols = error {
+                            self.record_failure(peer_id);
+                        }
+                    }
+                    // Late responses and failures of requests that timed out locally.
+                    P2PReqResEvent::Res { request_id, .. } | P2PReqResEvent::OutboundFailure { request_id, .. } => {
+                        self.pending_requests.remove(&request_id);
+                    }
+                    P2PReqResEvent::ResSent { peer_id, request_id } => {
+                        if self.reported_responses.remove(&request_id) {
+                            self.emit_event(CommunicationEvent::ResponseSent { peer_id, request_id });
+                        }
+                    }
+                },
+                P2PEvent::Identify(boxed_event) => {
+                    if let P2PIdentifyEvent::Received {
+                        peer_id,
+                        info,
+                        observed_addr: _,
+                    } = *boxed_event
                     {
-                        self.handle_incoming_envelope(peer_id, request_id, request);
+                        let protocols = info.protocols.clone();
+                        if self.connection_manager.set_identify_info(peer_id, info) {
+                            self.emit_event(CommunicationEvent::ProtocolsUpdated { peer_id, protocols });
+                        }
+                    }
+                    // Connections to mDNS peers of a different service can only be closed by banning the peer, a
+                    // peer that is still connected is not banned, so this does not unban peers that were banned before.
+                    #[cfg(feature = "mdns")]
+                    for peer_id in self.swarm.take_mdns_mismatches() {
+                        if Swarm::is_connected(&self.swarm, &peer_id) {
+                            Swarm::ban_peer_id(&mut self.swarm, peer_id);
+                            Swarm::unban_peer_id(&mut self.swarm, peer_id);
+                        }
                     }
                 }
-                P2PEvent::Identify(_) | P2PEvent::Mdns(_) => {}
+                P2PEvent::Ping(P2PPingEvent::Ping { peer_id, rtt }) => self.record_rtt(peer_id, rtt),
+                P2PEvent::Mdns(_) | P2PEvent::Ping(_) => {}
             },
             SwarmEvent::ConnectionEstablished {
                 peer_id,
                 endpoint,
                 num_established: _,
             } => {
-                self.connection_manager.insert(peer_id, endpoint, KeepAlive::None);
+                self.metrics.connections_established += 1;
+                self.connection_manager.remove_pending_dial(&peer_id);
+                self.pending_reconnects.remove(&peer_id);
+                if self.maintained.set_connected(&peer_id) {
+                    let state = MaintainedState::Connected;
+                    self.emit_event(CommunicationEvent::MaintainedConnection { peer_id, state });
+                }
+                if let ConnectedPoint::Dialer { address } = &endpoint {
+                    self.superseded_dials.remove(address);
+                    self.connection_manager.reset_dial_failures(peer_id, address.clone());
+                    self.connection_manager.retain_addr(peer_id, address.clone());
+                }
+                if self.pending_upgrades.remove(&peer_id) {
+                    self.unrelayed_peers.insert(peer_id);
+                    let result = Ok(());
+                    self.emit_event(CommunicationEvent::DirectConnectionUpgrade { peer_id, result });
+                }
+                let is_new_inbound = matches!(endpoint, ConnectedPoint::Listener { .. })
+                    && !self.connection_manager.is_active_connection(&peer_id);
+                match self.connection_authorizer.clone() {
+                    Some(authorizer) if is_new_inbound => {
+                        if !self.pending_authorizations.contains_key(&peer_id) {
+                            let authorization = authorizer(peer_id, endpoint.clone());
+                            let tx = self.authorization_tx.clone();
+                            task::spawn(async move {
+                                let is_allowed = authorization.await;
+                                let _ = tx.unbounded_send((peer_id, is_allowed));
+                            });
+                            self.pending_authorizations.insert(peer_id, endpoint);
+                        }
+                    }
+                    _ => self.accept_connection(peer_id, endpoint),
+                }
             }
             SwarmEvent::ConnectionClosed {
                 peer_id,
-                endpoint: ConnectedPoint::Dialer { address },
-                num_established: 0,
-                cause: _,
+                endpoint,
+                num_established,
+                cause,
             } => {
+                self.metrics.connections_closed += 1;
+                if let Some(MessageTooLarge { size, max }) = message_too_large(&cause) {
+                    self.emit_event(CommunicationEvent::MessageTooLarge { peer_id, size, max });
+                }
+                self.emit_event(CommunicationEvent::ConnectionClosed {
+                    peer_id,
+                    endpoint: endpoint.clone(),
+                    num_established,
+                    reason: DisconnectReason::from(cause),
+                });
+                if num_established == 0 {
+                    // Connections that closed within the grace period are treated as failed connections.
+                    self.connection_manager.remove_unconfirmed(&peer_id);
+                    self.pending_authorizations.remove(&peer_id);
+                    self.connection_manager.remove_identify_info(&peer_id);
+                    // Without a direct connection, the relay can be used for the peer again.
+                    self.unrelayed_peers.remove(&peer_id);
+                    self.upgrade_attempts.remove(&peer_id);
+                    // Without a connection to the relay, the relayed paths via it are reset as well.
+                    self.reset_relayed_paths(peer_id);
+                    // Maintained connections are re-dialed with the next iteration of the event loop.
+                    self.maintained.set_disconnected(&peer_id);
+                }
                 // Re-establish the connection if it was configured.
-                if !self.connection_manager.is_keep_alive(&peer_id) || self.connect_peer(peer_id, address).is_err() {
-                    self.connection_manager.remove_connection(&peer_id);
+                if let (ConnectedPoint::Dialer { address }, 0) = (endpoint, num_established) {
+                    if !self.connection_manager.is_keep_alive(&peer_id) || !self.reconnect_peer(peer_id, address) {
+                        self.connection_manager.remove_connection(&peer_id);
+                    }
                 }
             }
+            SwarmEvent::Dialing(peer_id) => {
+                self.connection_manager.insert_pending_dial(peer_id);
+                self.emit_event(CommunicationEvent::Dialing { peer_id });
+            }
+            SwarmEvent::UnreachableAddr {
+                peer_id,
+                address,
+                error,
+                attempts_remaining,
+            } => {
+                let error = ConnectPeerError::from(error);
+                self.handle_dial_failure(Some(peer_id), address, error.clone(), attempts_remaining);
+                if attempts_remaining == 0 {
+                    self.connection_manager.remove_pending_dial(&peer_id);
+                    self.fail_reconnect(&peer_id);
+                    if self.pending_upgrades.remove(&peer_id) {
+                        let result = Err(error);
+                        self.emit_event(CommunicationEvent::DirectConnectionUpgrade { peer_id, result });
+                    }
+                }
+            }
+            SwarmEvent::UnknownPeerUnreachableAddr { address, error: _ }
+                if self.superseded_dials.contains(&address) =>
+            {
+                self.superseded_dials.remove(&address);
+            }
+            SwarmEvent::UnknownPeerUnreachableAddr { address, error } => {
+                let reconnect = self
+                    .pending_reconnects
+                    .iter()
+                    .find(|(_, addr)| **addr == address)
+                    .map(|(peer_id, _)| *peer_id);
+                if let Some(peer_id) = reconnect {
+                    self.connection_manager.remove_pending_dial(&peer_id);
+                    self.fail_reconnect(&peer_id);
+                }
+                self.handle_dial_failure(None, address, ConnectPeerError::from(error), 0);
+            }
+            SwarmEvent::IncomingConnectionError {
+                error: PendingConnectionError::ConnectionLimit(limit),
+                ..
+            } => {
+                let kind = self.connection_limits.established_limit_kind(true, limit.limit);
+                self.record_connection_limit(kind, &limit);
+            }
+            SwarmEvent::ListenerClosed { addresses, reason } => {
+                // The event does not include the listener id, so the listener is determined by its addresses.
+                let index = self.listeners.iter().position(|(_, addr)| addresses.contains(addr));
+                let listener_id = index.map(|index| self.listeners.remove(index).0);
+                // Listeners that were removed locally are closed without error.
+                if listener_id.is_some() || reason.is_err() {
+                    self.record_listener_failure(ListenerFailure {
+                        listener_id,
+                        addresses,
+                        error: reason.err().map(|err| err.to_string()),
+                        is_closed: true,
+                    });
+                }
+            }
+            SwarmEvent::ListenerError { error } => {
+                self.record_listener_failure(ListenerFailure {
+                    listener_id: None,
+                    addresses: Vec::new(),
+                    error: Some(error.to_string()),
+                    is_closed: false,
+                });
+            }
             _ => {}
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sweep_interval_is_bounded() {
+        assert_eq!(sweep_interval(None), DEFAULT_SWEEP_INTERVAL);
+        assert_eq!(sweep_interval(Some(Duration::from_secs(5))), Duration::from_secs(5));
+        assert_eq!(sweep_interval(Some(Duration::from_secs(0))), MIN_SWEEP_INTERVAL);
+    }
+
+    #[test]
+    fn watchdog_interval_is_bounded() {
+        assert_eq!(watchdog_interval(Duration::from_secs(5)), Duration::from_secs(5));
+        assert_eq!(watchdog_interval(Duration::from_secs(0)), MIN_WATCHDOG_INTERVAL);
+    }
+}
@@ -0,0 +1,643 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::behaviour::P2POutboundFailure;
+use futures::stream::FusedStream;
+
+// Options of the outbound request that is currently awaited, and the requests of the actor that were attached to it
+// in the meantime. They are passed along the send path instead of being stored in the task, so that they never
+// outlive the request.
+struct RequestOptions<Req, ClientMsg: Message> {
+    // token with which the request can be cancelled while it is awaited
+    cancel_token: Option<u64>,
+    // deadline of the request, which overrides the request timeout
+    deadline: Option<Instant>,
+    // whether the request may be sent via both the direct path and the relay
+    hedge: bool,
+    // whether the request bypassed the local firewall, and the reason for which the firewall would have blocked it in
+    // audit mode, for the record of the audit
+    bypass_firewall: bool,
+    would_block: Option<FirewallBlocked>,
+    // peer, source override and key of the caller by which identical requests are coalesced with this one, and the
+    // requests that are answered with its result
+    coalesce_key: Option<(PeerId, Option<PeerId>, u64)>,
+    coalesced: Vec<(CommunicationRequest<Req, ClientMsg>, Sender)>,
+}
+
+impl<Req, ClientMsg: Message> Default for RequestOptions<Req, ClientMsg> {
+    fn default() -> Self {
+        RequestOptions {
+            cancel_token: None,
+            deadline: None,
+            hedge: false,
+            bypass_firewall: false,
+            would_block: None,
+            coalesce_key: None,
+            coalesced: Vec::new(),
+        }
+    }
+}
+
+// The next message of the actor, or a future that never completes once the channel was closed, so that a wait is not
+// interrupted by the closed channel again.
+fn next_actor_request<T>(swarm_rx: &mut UnboundedReceiver<T>) -> impl Future<Output = Option<T>> + '_ {
+    if swarm_rx.is_terminated() {
+        future::pending().left_future()
+    } else {
+        swarm_rx.next().right_future()
+    }
+}
+
+impl<Req, Res, ClientMsg, P> SwarmTask<Req, Res, ClientMsg, P>
+where
+    Req: MessageEvent + ToPermissionVariants<P> + Into<ClientMsg>,
+    Res: MessageEvent,
+    ClientMsg: Message,
+    P: Message + VariantPermission,
+{
+    // Check the request against the local firewall, send it with its options and answer the sender with the result.
+    pub(super) fn handle_request_msg(&mut self, message: CommunicationRequest<Req, ClientMsg>, sender: Sender) {
+        if let CommunicationRequest::RequestMsg {
+            peer_id,
+            request,
+            fallback_addrs,
+            bypass_firewall,
+            source_override,
+            cancel_token,
+            deadline,
+            coalesce,
+            hedge,
+        } = message
+        {
+            let permitted = if bypass_firewall {
+                Ok(None)
+            } else {
+                self.check_firewall(request.clone(), peer_id, RequestDirection::Out)
+            };
+            let res = match permitted {
+                Ok(_) if deadline.map(|deadline| deadline <= Instant::now()).unwrap_or(false) => {
+                    Err(RequestMessageError::DeadlineExceeded)
+                }
+                Ok(would_block) => {
+                    for addr in fallback_addrs {
+                        self.swarm.add_peer_addr(peer_id, addr);
+                    }
+                    let mut options = RequestOptions {
+                        cancel_token,
+                        deadline,
+                        hedge,
+                        bypass_firewall,
+                        would_block,
+                        ..Default::default()
+                    };
+                    if let Some(key) = coalesce {
+                        self.start_coalescing(&mut options, (peer_id, source_override, key));
+                    }
+                    let res = self.send_permitted_request(peer_id, request, source_override, &mut options);
+                    self.finish_coalescing(options, &res);
+                    res
+                }
+                Err(blocked) => {
+                    self.metrics.firewall_blocked_out += 1;
+                    Err(RequestMessageError::Rejected(blocked))
+                }
+            };
+            Self::send_response(CommunicationResults::RequestMsgResult(res), sender);
+        }
+    }
+
+    // Send the request to each member of the group one after another, and answer the sender with all results.
+    // Each member is sent like a separate request, including the limit of outbound requests in flight.
+    pub(super) fn send_to_group(&mut self, group: String, request: Req, sender: Sender) {
+        let members = self.groups.get(&group).cloned().unwrap_or_default();
+        let mut res = Vec::with_capacity(members.len());
+        for peer_id in members {
+            let peer_res = match self.check_firewall(request.clone(), peer_id, RequestDirection::Out) {
+                Ok(would_block) => {
+                    let mut options = RequestOptions {
+                        would_block,
+                        ..Default::default()
+                    };
+                    self.send_permitted_request(peer_id, request.clone(), None, &mut options)
+                }
+                Err(blocked) => {
+                    self.metrics.firewall_blocked_out += 1;
+                    Err(RequestMessageError::Rejected(blocked))
+                }
+            };
+            res.push((peer_id, peer_res));
+        }
+        Self::send_response(CommunicationResults::RequestMsgGroupResult(res), sender);
+    }
+
+    // Handle a message of the actor that was received while an outbound request is awaited. Returns true if the
+    // message cancels the awaited request. Cancellations of other requests and read-only queries are handled right
+    // away, requests that are identical to a coalesced request are attached to it, and all other messages are
+    // deferred until the current actor request was handled.
+    fn intercept_actor_request(
+        &mut self,
+        options: &mut RequestOptions<Req, ClientMsg>,
+        actor_event: Option<(CommunicationRequest<Req, ClientMsg>, Sender)>,
+    ) -> bool {
+        match actor_event {
+            Some((CommunicationRequest::CancelRequest(cancel), sender)) => {
+                let is_awaited = options.cancel_token == Some(cancel);
+                let is_cancelled =
+                    is_awaited || Self::cancel_coalesced_request(options, cancel) || self.cancel_queued_request(cancel);
+                Self::send_response(CommunicationResults::CancelRequestResult(is_cancelled), sender);
+                is_awaited
+            }
+            Some((CommunicationRequest::DumpState, sender)) => {
+                let dump = self.state_dump(options.coalesced.len());
+                Self::send_response(CommunicationResults::StateDump(dump), sender);
+                false
+            }
+            Some((message, sender)) if Self::is_query(&message) => {
+                self.handle_actor_request(message, sender);
+                false
+            }
+            Some((message, sender)) if Self::is_coalesced(options, &message) => {
+                options.coalesced.push((message, sender));
+                false
+            }
+            Some((message, sender)) => {
+                self.deferred_actor_requests.push_back((message, sender));
+                false
+            }
+            None => {
+                // The channel was closed, the task shuts down once the current request was handled.
+                self.deferred_actor_requests
+                    .push_back((CommunicationRequest::Shutdown, None));
+                false
+            }
+        }
+    }
+
+    // Whether the message only reads the state of the task, so that it can be answered while a request is awaited.
+    fn is_query(message: &CommunicationRequest<Req, ClientMsg>) -> bool {
+        matches!(
+            message,
+            CommunicationRequest::DumpState
+                | CommunicationRequest::GetHealth
+                | CommunicationRequest::GetMetrics
+                | CommunicationRequest::GetUptime
+                | CommunicationRequest::GetPendingRequests(_)
+        )
+    }
+
+    // Check if the message is a request with coalescing that has the same key as the awaited one. Requests on behalf
+    // of different sources are answered differently, so the source override is part of the key.
+    fn is_coalesced(options: &RequestOptions<Req, ClientMsg>, message: &CommunicationRequest<Req, ClientMsg>) -> bool {
+        match message {
+            CommunicationRequest::RequestMsg {
+                peer_id,
+                source_override,
+                coalesce: Some(key),
+                ..
+            } => options.coalesce_key == Some((*peer_id, *source_override, *key)),
+            _ => false,
+        }
+    }
+
+    // Start coalescing requests with the same key with the outbound request, including those that are already queued.
+    fn start_coalescing(&mut self, options: &mut RequestOptions<Req, ClientMsg>, key: (PeerId, Option<PeerId>, u64)) {
+        options.coalesce_key = Some(key);
+        let mut queued = VecDeque::with_capacity(self.deferred_actor_requests.len());
+        while let Some((message, sender)) = self.deferred_actor_requests.pop_front() {
+            if Self::is_coalesced(options, &message) {
+                options.coalesced.push((message, sender));
+            } else {
+                queued.push_back((message, sender));
+            }
+        }
+        self.deferred_actor_requests = queued;
+    }
+
+    // Answer the requests that were attached to the coalesced request with its result. If the coalesced request was
+    // cancelled, the attached requests are handled regularly instead.
+    fn finish_coalescing(&mut self, options: RequestOptions<Req, ClientMsg>, res: &Result<Res, RequestMessageError>) {
+        if let Err(RequestMessageError::Cancelled) = res {
+            for entry in options.coalesced.into_iter().rev() {
+                self.deferred_actor_requests.push_front(entry);
+            }
+            return;
+        }
+        for (message, sender) in options.coalesced {
+            if let CommunicationRequest::RequestMsg {
+                peer_id,
+                request,
+                bypass_firewall,
+                ..
+            } = message
+            {
+                let permitted = if bypass_firewall {
+                    Ok(None)
+                } else {
+                    self.check_firewall(request, peer_id, RequestDirection::Out)
+                };
+                let res = match permitted {
+                    Ok(_) => {
+                        self.metrics.outbound_coalesced += 1;
+                        res.clone()
+                    }
+                    Err(blocked) => {
+                        self.metrics.firewall_blocked_out += 1;
+                        Err(RequestMessageError::Rejected(blocked))
+                    }
+                };
+                Self::send_response(CommunicationResults::RequestMsgResult(res), sender);
+            }
+        }
+    }
+
+    // Cancel a request that was attached to the coalesced request, returns false if no attached request has the
+    // token.
+    fn cancel_coalesced_request(options: &mut RequestOptions<Req, ClientMsg>, token: u64) -> bool {
+        let index = options.coalesced.iter().position(|(message, _)| match message {
+            CommunicationRequest::RequestMsg { cancel_token, .. } => *cancel_token == Some(token),
+            _ => false,
+        });
+        match index {
+            Some(index) => {
+                let (_, sender) = options.coalesced.remove(index);
+                let res = CommunicationResults::RequestMsgResult(Err(RequestMessageError::Cancelled));
+                Self::send_response(res, sender);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Only let the request-response protocol dial the peer if it is neither connected nor already being dialed.
+    // The protocol dials a peer for a new request if it is not connected, even if a dial to it is still pending, so
+    // established connections are reused and a pending dial is awaited before the request is sent.
+    // The dial is awaited at most for the connection timeout, or until the deadline of the request if it passes
+    // earlier. Returns an error if the deadline passed, in which case the request is not sent.
+    fn await_pending_dial(
+        &mut self,
+        peer_id: PeerId,
+        options: &RequestOptions<Req, ClientMsg>,
+    ) -> Result<(), RequestMessageError> {
+        if !Swarm::is_connected(&self.swarm, &peer_id) && self.connection_manager.is_pending_dial(&peer_id) {
+            let timeout = Instant::now() + self.connection_timeout;
+            let deadline = options
+                .deadline
+                .map(|deadline| deadline.min(timeout))
+                .unwrap_or(timeout);
+            let _ = self.await_dial(peer_id, Vec::new(), deadline);
+        }
+        match options.deadline {
+            Some(deadline) if deadline <= Instant::now() => Err(RequestMessageError::DeadlineExceeded),
+            _ => Ok(()),
+        }
+    }
+
+    // Send a request that passed the local firewall and record it in the metrics.
+    fn send_permitted_request(
+        &mut self,
+        peer_id: PeerId,
+        request: Req,
+        source: Option<PeerId>,
+        options: &mut RequestOptions<Req, ClientMsg>,
+    ) -> Result<Res, RequestMessageError> {
+        self.await_outbound_capacity(options)?;
+        let start = Instant::now();
+        let res = self.send_request(peer_id, request, source, options);
+        self.metrics.record_outbound(peer_id, start.elapsed(), res.is_ok());
+        res
+    }
+
+    // Check whether another outbound request can be sent without exceeding the limit of requests in flight. Depending
+    // on the overflow config, the swarm is driven until a request in flight finished, or at most until the request
+    // timeout. Messages of the actor are intercepted in the meantime, so that the request can be cancelled.
+    fn await_outbound_capacity(
+        &mut self,
+        options: &mut RequestOptions<Req, ClientMsg>,
+    ) -> Result<(), RequestMessageError> {
+        let max = match self.max_outbound_in_flight {
+            Some(max) => max,
+            None => return Ok(()),
+        };
+        if self.pending_requests.len() < max {
+            return Ok(());
+        }
+        if self.outbound_overflow == OutboundOverflow::Reject {
+            return Err(RequestMessageError::TooManyInFlight);
+        }
+        let deadline = self.request_deadline(options, Instant::now());
+        self.begin_wait();
+        let res = task::block_on(async {
+            while self.pending_requests.len() >= max {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let next_event = async_std::future::timeout(remaining, self.swarm.next_event());
+                select! {
+                    event = next_event.fuse() => match event {
+                        Ok(event) => self.handle_swarm_event(event),
+                        Err(_) => return Err(RequestMessageError::TooManyInFlight),
+                    },
+                    actor_event = next_actor_request(&mut self.swarm_rx).fuse() => {
+                        if self.intercept_actor_request(options, actor_event) {
+                            return Err(RequestMessageError::Cancelled);
+                        }
+                    }
+                }
+            }
+            Ok(())
+        });
+        self.end_wait();
+        res
+    }
+
+    // Wrap the request into an envelope, which enables using a relay peer, and send it to the remote.
+    // Depending on the config, it is ether send directly or via the relay, unless the relayed path to the peer was
+    // closed. The source of the envelope is the local peer, unless another source is set.
+    fn send_request(
+        &mut self,
+        peer_id: PeerId,
+        mut request: Req,
+        source: Option<PeerId>,
+        options: &mut RequestOptions<Req, ClientMsg>,
+    ) -> Result<Res, RequestMessageError> {
+        if let Some(hook) = self.outgoing_request_hook.as_ref() {
+            hook(&mut request, peer_id);
+        }
+        if !options.bypass_firewall {
+            self.audit_request(&request, peer_id, RequestDirection::Out, options.would_block.clone());
+        }
+        let source = source.unwrap_or(*Swarm::local_peer_id(&self.swarm));
+        let mut envelope = RequestEnvelope {
+            source: source.to_string(),
+            message: request,
+            target: peer_id.to_string(),
+            expires_at: None,
+            hops_remaining: None,
+        };
+        if let Some(ttl) = self.envelope_ttl {
+            envelope.set_ttl(ttl);
+        }
+        let is_unrelayed = self.unrelayed_peers.contains(&peer_id);
+        // A specific relay for the peer takes precedence over the configured one.
+        if let (Some(relay_id), false) = (self.peer_relays.get(&peer_id).copied(), is_unrelayed) {
+            let res = self.send_envelope_to_peer(relay_id, envelope, options);
+            self.try_direct_upgrade(peer_id);
+            return res;
+        }
+        let relay = if is_unrelayed {
+            RelayConfig::NoRelay
+        } else {
+            self.relay.clone()
+        };
+        match relay {
+            RelayConfig::NoRelay => self.send_envelope_to_peer(peer_id, envelope, options),
+            RelayConfig::RelayAlways {
+                peer_id: relay_id,
+                addr: _,
+            } => {
+                let res = self.send_envelope_to_peer(relay_id, envelope, options);
+                self.try_direct_upgrade(peer_id);
+                res
+            }
+            RelayConfig::RelayBackup {
+                peer_id: relay_id,
+                addr: _,
+            } => {
+                if let (Some(delay), true) = (self.relay_fallback_delay, options.hedge) {
+                    return self.send_hedged(peer_id, relay_id, envelope, delay, options);
+                }
+                // try sending directly, otherwise use relay
+                let res = self.send_envelope_to_peer(peer_id, envelope.clone(), options);
+                if let Err(RequestMessageError::Outbound(P2POutboundFailure::DialFailure)) = res {
+                    let res = self.send_envelope_to_peer(relay_id, envelope, options);
+                    self.try_direct_upgrade(peer_id);
+                    res
+                } else {
+                    res
+                }
+            }
+        }
+    }
+
+    // Try sending a request envelope to a remote peer if it was approved by the firewall, and return the received
+    // Response. If no response is received, a RequestMessageError::Rejected will be returned.
+    fn send_envelope_to_peer(
+        &mut self,
+        peer_id: PeerId,
+        envelope: RequestEnvelope<Req>,
+        options: &mut RequestOptions<Req, ClientMsg>,
+    ) -> Result<Res, RequestMessageError> {
+        self.await_pending_dial(peer_id, options)?;
+        let req_id = self.swarm.send_request(&peer_id, envelope);
+        self.pending_requests.insert(req_id, (peer_id, Instant::now()));
+        let deadline = self.request_deadline(options, Instant::now());
+        self.begin_wait();
+        let res = task::block_on(async {
+            loop {
+                // The deadline also applies if no other events occur on the swarm while the response is awaited.
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let next_event = async_std::future::timeout(remaining, self.swarm.next_event());
+                let event = select! {
+                    event = next_event.fuse() => event,
+                    actor_event = next_actor_request(&mut self.swarm_rx).fuse() => {
+                        if self.intercept_actor_request(options, actor_event) {
+                            self.pending_requests.remove(&req_id);
+                            return Err(RequestMessageError::Cancelled);
+                        }
+                        continue;
+                    }
+                };
+                let event = match event {
+                    Ok(event) => event,
+                    Err(_) => return Err(Self::timeout_error(options)),
+                };
+                match event {
+                    SwarmEvent::Behaviour(P2PEvent::RequestResponse(ref boxed_event)) => {
+                        match boxed_event.clone().deref().clone() {
+                            P2PReqResEvent::Res {
+                                peer_id: _,
+                                request_id,
+                                response,
+                            } => {
+                                self.pending_requests.remove(&request_id);
+                                if request_id == req_id {
+                                    return Ok(response);
+                                }
+                            }
+                            P2PReqResEvent::InboundFailure {
+                                peer_id: _,
+                                request_id,
+                                error,
+                            } => {
+                                if request_id == req_id {
+                                    return Err(RequestMessageError::Inbound(error));
+                                }
+                            }
+                            P2PReqResEvent::OutboundFailure {
+                                peer_id: _,
+                                request_id,
+                                error,
+                            } => {
+                                self.pending_requests.remove(&request_id);
+                                if request_id == req_id {
+                                    return Err(Self::outbound_error(error));
+                                }
+                            }
+                            _ => self.handle_swarm_event(event),
+                        }
+                    }
+                    _ => self.handle_swarm_event(event),
+                }
+                if Instant::now() >= deadline {
+                    return Err(Self::timeout_error(options));
+                }
+            }
+        });
+        self.end_wait();
+        res
+    }
+
+    // The point in time at which an outbound request that was sent at `start` fails, which is the deadline of the
+    // request if one is set, and otherwise determined by the request timeout.
+    fn request_deadline(&self, options: &RequestOptions<Req, ClientMsg>, start: Instant) -> Instant {
+        options.deadline.unwrap_or(start + self.request_timeout)
+    }
+
+    // The error of an outbound request for which no response was received in time.
+    fn timeout_error(options: &RequestOptions<Req, ClientMsg>) -> RequestMessageError {
+        match options.deadline {
+            Some(_) => RequestMessageError::DeadlineExceeded,
+            None => RequestMessageError::Rejected(FirewallBlocked::Remote),
+        }
+    }
+
+    // The error of an outbound request that failed. A rejection by the firewall of the remote is reported as
+    // rejected request, and a remote that is busy or paused as unavailable with its reason.
+    fn outbound_error(error: P2POutboundFailure) -> RequestMessageError {
+        match error {
+            P2POutboundFailure::Rejected(RejectReason::Blocked) => {
+                RequestMessageError::Rejected(FirewallBlocked::Remote)
+            }
+            P2POutboundFailure::Rejected(reason) => RequestMessageError::Unavailable(reason),
+            error => RequestMessageError::Outbound(error),
+        }
+    }
+
+    // Send the envelope directly to the peer, and additionally via the relay if dialing the peer failed or no
+    // connection to it was established within the delay. The first response of either path is returned.
+    // Outbound requests can not be aborted in the swarm, so the request that lost the race stays pending until its
+    // response or failure is received, which is then dropped. The remote may therefore handle the request twice, which
+    // is why only requests that opted in are hedged.
+    fn send_hedged(
+        &mut self,
+        peer_id: PeerId,
+        relay_id: PeerId,
+        envelope: RequestEnvelope<Req>,
+        delay: Duration,
+        options: &mut RequestOptions<Req, ClientMsg>,
+    ) -> Result<Res, RequestMessageError> {
+        self.await_pending_dial(peer_id, options)?;
+        let direct_id = self.swarm.send_request(&peer_id, envelope.clone());
+        self.pending_requests.insert(direct_id, (peer_id, Instant::now()));
+        let start = Instant::now();
+        let fallback_at = start + delay;
+        let deadline = self.request_deadline(options, start);
+        let mut relayed_id = None;
+        let mut direct_failed = false;
+        let mut relay_failed = false;
+        self.begin_wait();
+        let res = task::block_on(async {
+            loop {
+                let use_relay =
+                    direct_failed || (Instant::now() >= fallback_at && !Swarm::is_connected(&self.swarm, &peer_id));
+                if relayed_id.is_none() && use_relay {
+                    let req_id = self.swarm.send_request(&relay_id, envelope.clone());
+                    self.pending_requests.insert(req_id, (relay_id, Instant::now()));
+                    relayed_id = Some(req_id);
+                }
+                // wake up once the delay passed, so that the relay is used even if no event is received
+                let wake_at = match relayed_id {
+                    None if fallback_at > Instant::now() => fallback_at,
+                    _ => deadline,
+                };
+                let remaining = wake_at.saturating_duration_since(Instant::now());
+                let next_event = async_std::future::timeout(remaining, self.swarm.next_event());
+                let event = select! {
+                    event = next_event.fuse() => event,
+                    actor_event = next_actor_request(&mut self.swarm_rx).fuse() => {
+                        if self.intercept_actor_request(options, actor_event) {
+                            self.pending_requests.remove(&direct_id);
+                            if let Some(relayed_id) = relayed_id {
+                                self.pending_requests.remove(&relayed_id);
+                            }
+                            return Err(RequestMessageError::Cancelled);
+                        }
+                        continue;
+                    }
+                };
+                let event = match event {
+                    Ok(event) => event,
+                    Err(_) if Instant::now() >= deadline => return Err(Self::timeout_error(options)),
+                    Err(_) => continue,
+                };
+                let is_own = |request_id: &RequestId| *request_id == direct_id || Some(*request_id) == relayed_id;
+                let error = match event {
+                    SwarmEvent::Behaviour(P2PEvent::RequestResponse(ref boxed_event)) => {
+                        match boxed_event.clone().deref().clone() {
+                            P2PReqResEvent::Res {
+                                peer_id: _,
+                                request_id,
+                                response,
+                            } if is_own(&request_id) => {
+                                self.pending_requests.remove(&request_id);
+                                if request_id != direct_id {
+                                    self.try_direct_upgrade(peer_id);
+                                }
+                                return Ok(response);
+                            }
+                            P2PReqResEvent::InboundFailure {
+                                peer_id: _,
+                                request_id,
+                                error,
+                            } if is_own(&request_id) => (request_id, RequestMessageError::Inbound(error)),
+                            P2PReqResEvent::OutboundFailure {
+                                peer_id: _,
+                                request_id,
+                                error,
+                            } if is_own(&request_id) => {
+                                self.pending_requests.remove(&request_id);
+                                (request_id, Self::outbound_error(error))
+                            }
+                            _ => {
+                                self.handle_swarm_event(event);
+                                continue;
+                            }
+                        }
+                    }
+                    _ => {
+                        self.handle_swarm_event(event);
+                        continue;
+                    }
+                };
+                // only fail once both paths failed, or if the direct path failed for another reason than the dial
+                match error {
+                    (request_id, err) if request_id == direct_id => {
+                        let is_dial_failure =
+                            matches!(err, RequestMessageError::Outbound(P2POutboundFailure::DialFailure));
+                        if relay_failed || (relayed_id.is_none() && !is_dial_failure) {
+                            return Err(err);
+                        }
+                        direct_failed = true;
+                    }
+                    (_, err) => {
+                        if direct_failed {
+                            return Err(err);
+                        }
+                        relay_failed = true;
+                    }
+                }
+            }
+        });
+        self.end_wait();
+        res
+    }
+}
@@ -1,32 +1,70 @@
 // Copyright 2020-2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::behaviour::{P2PInboundFailure, P2POutboundFailure};
+use crate::behaviour::{
+    parse_agent_version, AddrTransport, ConnectionLimitKind, MessageTooLarge, P2PIdentifyInfo, P2PInboundFailure,
+    P2POutboundFailure, RejectReason,
+};
 use libp2p::{
     core::{
-        connection::{ConnectedPoint, ConnectionError, ConnectionLimit, PendingConnectionError},
+        connection::{ConnectedPoint, ConnectionError, ConnectionLimit, ListenerId, PendingConnectionError},
+        transport::TransportError,
         Multiaddr, PeerId,
     },
-    swarm::DialError,
+    request_response::RequestId,
+    swarm::{protocols_handler::NodeHandlerWrapperError, DialError},
 };
 use riker::{actors::ActorRef, Message};
+use serde::{Deserialize, Serialize};
 
-use crate::actor::firewall::FirewallRule;
-use std::time::Instant;
+use crate::actor::{
+    firewall::{FirewallDecision, FirewallMode, FirewallPermission, FirewallRule, MatchedRule, RequestDirection},
+    maintained::MaintainedState,
+    metrics::SwarmMetrics,
+    state::{serde_deadline, serde_elapsed, serde_peer_id, serde_request_id, CommunicationState, KeepAliveState},
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fmt, io,
+    net::IpAddr,
+    ops::RangeInclusive,
+    time::{Duration, Instant, SystemTime},
+};
+use thiserror::Error as DeriveError;
 
 /// Relay peer for outgoing request.
-#[derive(Debug, Clone)]
+///
+/// Only a single relay is configured, and a relay for a specific peer can be set with
+/// [`CommunicationRequest::EstablishConnectionViaRelay`]. The relay of a request is therefore always determined by
+/// its target peer, there is no random selection among multiple relays that would need a seed to be reproducible, and
+/// no selector for the relay of a request. Sticky routing through a specific relay is configured per peer instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum RelayConfig {
     /// No relay should be used, peers can only be dialed directly.
     NoRelay,
     /// Always send requests to remote peers via the relay.
-    RelayAlways { peer_id: PeerId, addr: Multiaddr },
+    RelayAlways {
+        #[serde(with = "serde_peer_id")]
+        peer_id: PeerId,
+        addr: Multiaddr,
+    },
     /// Use relay peer if sending the request directly failed,
-    RelayBackup { peer_id: PeerId, addr: Multiaddr },
+    RelayBackup {
+        #[serde(with = "serde_peer_id")]
+        peer_id: PeerId,
+        addr: Multiaddr,
+    },
 }
 
 /// Determines if the local system should actively keep the connection alive
-#[derive(Debug, Clone)]
+///
+/// The connection is not kept alive by periodic pings, instead it is re-established by the local system when it was
+/// closed. Therefore the keep-alive itself sends no traffic on idle connections, and has no ping interval that could
+/// be jittered, e.g. to spread the load on a shared relay. If probes are enabled in the [`BehaviourConfig`], each
+/// connection, including idle keep-alive connections, is nonetheless pinged every 15s.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "Option<KeepAliveState>", into = "Option<KeepAliveState>")]
 pub enum KeepAlive {
     /// No keep-alive.
     None,
@@ -44,70 +82,608 @@ pub enum KeepAlive {
     Unlimited,
 }
 
+/// How a closed keep-alive connection is re-established.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReconnectMode {
+    /// The peer is re-dialed right away, and the actor waits until the connection was established or the dial
+    /// failed, which blocks handling other requests for up to the connection timeout.
+    Sync,
+    /// The re-dial is started without waiting for it, and its result is handled once the swarm reports it.
+    /// If the dial fails, the connection is not kept alive anymore.
+    Deferred,
+}
+
+impl Default for ReconnectMode {
+    fn default() -> Self {
+        ReconnectMode::Sync
+    }
+}
+
+/// How an outbound request is handled if the number of outbound requests in flight reached the limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutboundOverflow {
+    /// The request fails right away with [`RequestMessageError::TooManyInFlight`].
+    Reject,
+    /// The actor waits until one of the requests in flight finished, and fails the request with
+    /// [`RequestMessageError::TooManyInFlight`] if none finished within the request timeout, or its deadline.
+    /// Meanwhile, the waiting request can be cancelled with its cancel token, and further requests to the actor are
+    /// handled like while a response is awaited.
+    Wait,
+}
+
+impl Default for OutboundOverflow {
+    fn default() -> Self {
+        OutboundOverflow::Reject
+    }
+}
+
 /// Requests for the [`CommunicationActor`].
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum CommunicationRequest<Req, ClientMsg: Message> {
     /// Send a request to a remote peer.
     /// This requires that a connection to the targeted peer has been established and is active.
-    RequestMsg { peer_id: PeerId, request: Req },
+    ///
+    /// The `fallback_addrs` are added to the known addresses of the peer before the request is sent, so that the
+    /// peer can be dialed by its id if no connection exists yet.
+    ///
+    /// If `bypass_firewall` is set, the request is sent without checking the rules of the local firewall, e.g. for
+    /// trusted control requests of the local system. This only affects the local check of the outgoing request, the
+    /// request is still checked by the inbound firewall of the remote peer.
+    ///
+    /// If `source_override` is set, it is used as source of the envelope instead of the local peer id, e.g. if the
+    /// local peer is a proxy that forwards the request on behalf of another peer. The remote peer then evaluates its
+    /// firewall rules for the original requester, but only accepts the request if the local peer is trusted as relay
+    /// for it, i.e. it is the configured relay of the remote, or its relay for the requester as set with
+    /// [`CommunicationRequest::EstablishConnectionViaRelay`]. Envelopes are not signed, so the remote can not verify
+    /// the source, which is why an override should only be used in deployments where the proxy is trusted.
+    ///
+    /// If a `cancel_token` is set, the request can be cancelled with [`CommunicationRequest::CancelRequest`] while
+    /// its response is still awaited or while it is queued, in which case it results in
+    /// [`RequestMessageError::Cancelled`]. While the response is awaited, queries of the state, e.g.
+    /// [`CommunicationRequest::DumpState`] or [`CommunicationRequest::GetMetrics`], are answered right away, all other
+    /// requests to the actor that are received in the meantime are handled once the request completed.
+    ///
+    /// If a `deadline` is set, it overrides the relative request timeout, so that the request fails with
+    /// [`RequestMessageError::DeadlineExceeded`] exactly when the deadline passed, e.g. for requests that are part of
+    /// a larger operation with an overall deadline. Requests whose deadline already passed when they are handled,
+    /// e.g. because they were queued, are not sent. The time that is spent on fallbacks to the relay, and on waiting
+    /// for a pending dial to the peer, is included. The timeout of the request-response protocol in the
+    /// [`BehaviourConfig`] still applies, so a request fails earlier with [`RequestMessageError::Outbound`] if that
+    /// timeout is shorter than the time until the deadline.
+    ///
+    /// If a `coalesce` key is set, requests to the same peer with the same key and source override that are received
+    /// while the request is awaited or queued, are not sent again. Instead they are answered with the result of the
+    /// awaited request, e.g. for idempotent reads that are requested by multiple callers at the same time. The key is
+    /// provided by the caller, who has to ensure that requests with the same key are identical, e.g. by hashing the
+    /// read that they request. Attached requests are still checked by the local firewall, but inherit the deadline,
+    /// the hedging and the fallback addresses of the awaited request, and their own are ignored. They can be cancelled
+    /// with their own cancel token. If the awaited request is cancelled, the attached requests are sent regularly.
+    ///
+    /// If `hedge` is set and a `relay_fallback_delay` is configured, the request is additionally sent via the backup
+    /// relay if no direct connection to the peer was established within the delay. Both copies may reach the remote,
+    /// which then handles the request twice, so only idempotent requests should be hedged. Without `hedge`, the relay
+    /// is only used once the direct dial failed.
+    ///
+    /// Each request results in exactly one response, since the request-response protocol of libp2p 0.36 closes the
+    /// substream after the response. There is no request for subscriptions in which the remote pushes multiple
+    /// updates over one substream; instead the remote has to send each update as separate request to the subscriber.
+    ///
+    /// The actor waits for the response or the timeout of a request before it handles the next one, but requests to
+    /// the same peer can still overlap: a request that timed out locally stays in flight until the remote responds or
+    /// the protocol times out, and a hedged request may be delivered both directly and via the relay. The number of
+    /// requests in flight can be limited with the `max_outbound_in_flight` of the `CommunicationActorConfig`, the
+    /// current number is reported in the [`SwarmMetrics`]. There is no queue that serializes the requests per peer;
+    /// overlapping requests are ruled out by a `max_outbound_in_flight` of 1 with [`OutboundOverflow::Wait`], and by
+    /// not hedging requests.
+    RequestMsg {
+        #[serde(with = "serde_peer_id")]
+        peer_id: PeerId,
+        request: Req,
+        fallback_addrs: Vec<Multiaddr>,
+        bypass_firewall: bool,
+        #[serde(with = "serde_peer_id::option")]
+        source_override: Option<PeerId>,
+        cancel_token: Option<u64>,
+        #[serde(with = "serde_deadline")]
+        deadline: Option<Instant>,
+        coalesce: Option<u64>,
+        hedge: bool,
+    },
+    /// Cancel the outbound request with the cancel token, if it is still outstanding. The local actor stops waiting
+    /// for the response, and the cancelled request is answered with [`RequestMessageError::Cancelled`]. A late
+    /// response of the remote is dropped.
+    /// The result is false if no outstanding request has the token, e.g. because the response already arrived.
+    CancelRequest(u64),
+    /// Create a named group of peers, or replace the members of an existing group, so that requests can be sent to
+    /// all members with [`CommunicationRequest::RequestMsgGroup`].
+    CreateGroup {
+        name: String,
+        #[serde(with = "serde_peer_id::vec")]
+        peers: Vec<PeerId>,
+    },
+    /// Add peers to a group, the group is created if it does not exist yet.
+    AddToGroup {
+        group: String,
+        #[serde(with = "serde_peer_id::vec")]
+        peers: Vec<PeerId>,
+    },
+    /// Remove peers from a group, the group is removed once it has no members anymore.
+    RemoveFromGroup {
+        group: String,
+        #[serde(with = "serde_peer_id::vec")]
+        peers: Vec<PeerId>,
+    },
+    /// Send a request to each current member of the group, in the same way as a [`CommunicationRequest::RequestMsg`]
+    /// without fallback addresses. The members are requested one after another, in the order in which they were
+    /// added to the group, and each member is awaited before the next one is requested. A group request therefore
+    /// takes up to the number of members times the request timeout if the members don't respond, during which the
+    /// actor only answers queries of its state. Each member request is subject to the `max_outbound_in_flight` of the
+    /// `CommunicationActorConfig`, so with [`OutboundOverflow::Reject`] the remaining members fail with
+    /// [`RequestMessageError::TooManyInFlight`] once the limit is reached.
+    RequestMsgGroup { group: String, request: Req },
     /// Set the actor reference that incoming request are forwarded to.
+    /// It is not serialized, since actor references are only valid within the local actor system.
+    #[serde(skip)]
     SetClientRef(ActorRef<ClientMsg>),
     /// Connect to a remote peer.
     /// If the peer id is know it will attempt to use a know address of it, otherwise the `addr` will be dialed.
+    /// The known addresses of a peer are dialed sequentially until one of them succeeds.
     EstablishConnection {
         addr: Multiaddr,
+        #[serde(with = "serde_peer_id")]
         peer_id: PeerId,
         keep_alive: KeepAlive,
     },
+    /// Communicate with a remote peer via a specific relay, independently of the [`RelayConfig`] that is used for
+    /// other peers. A keep-alive connection to the relay is established, and subsequent requests to the peer are sent
+    /// via the relay, which is also accepted as relay for requests from the peer. The relay is used for the peer until
+    /// the relayed path is closed with [`CommunicationRequest::CloseRelayedConnection`].
+    /// No relay circuit (`/p2p/<relay>/p2p-circuit/p2p/<peer>`) is dialed, since the transport does not support the
+    /// libp2p relay protocol; instead the requests are sent to the relay wrapped in an envelope with the peer as
+    /// target, and the relay has to forward them on application level.
+    /// The result is returned as [`CommunicationResults::EstablishConnectionResult`] with the connection to the relay
+    /// on success.
+    EstablishConnectionViaRelay {
+        #[serde(with = "serde_peer_id")]
+        peer_id: PeerId,
+        #[serde(with = "serde_peer_id")]
+        relay_peer: PeerId,
+        relay_addr: Multiaddr,
+    },
     /// Close the connection to a remote peer so that no more requests from that peer will be allowed.
     /// This does not directly close the underlying transport connection, which will close on timeout instead.
-    CloseConnection(PeerId),
-    /// Check if a connection to that peer is currently active.
-    CheckConnection(PeerId),
+    CloseConnection(#[serde(with = "serde_peer_id")] PeerId),
+    /// Close all connections to a peer and establish a new one, e.g. if the current connection is in a bad state.
+    /// The previous keep-alive configuration of the connection is preserved. The peer is dialed via the address of
+    /// the previous connection, or one of its known addresses.
+    Reconnect(#[serde(with = "serde_peer_id")] PeerId),
+    /// Keep a connection to the peer until [`CommunicationRequest::StopMaintaining`], independently of the
+    /// [`KeepAlive`] of connections. Whenever the peer is not connected, all `addrs` and the known addresses of the
+    /// peer are dialed, and failed dials are retried with the [`MaintainBackoff`] of the
+    /// [`CommunicationActorConfig`]. Dials don't block the actor, the changes of the state are emitted as
+    /// [`CommunicationEvent::MaintainedConnection`].
+    ///
+    /// [`MaintainBackoff`]: crate::actor::MaintainBackoff
+    MaintainConnection {
+        #[serde(with = "serde_peer_id")]
+        peer_id: PeerId,
+        addrs: Vec<Multiaddr>,
+    },
+    /// Stop re-dialing the peer of a [`CommunicationRequest::MaintainConnection`]. A current connection to the peer is
+    /// not closed.
+    StopMaintaining(#[serde(with = "serde_peer_id")] PeerId),
+    /// Check if a connection to that peer is currently active, or if the peer is currently being dialed.
+    CheckConnection(#[serde(with = "serde_peer_id")] PeerId),
     /// Obtain information about the swarm.
     GetSwarmInfo,
+    /// Obtain only the requested sections of the information about the swarm, e.g. to frequently poll the listeners
+    /// of a node with many connections without collecting all connections each time.
+    GetSwarmInfoPartial {
+        include_peer_id: bool,
+        include_listeners: bool,
+        include_connections: bool,
+    },
+    /// Get the identifying information and the supported versions of the request-response protocol of a connected
+    /// peer, e.g. to verify the negotiated versions during a rolling upgrade.
+    GetProtocolInfo(#[serde(with = "serde_peer_id")] PeerId),
+    /// Dial the peer on an additional connection to receive its current identifying information, and update the
+    /// cached information that is returned for [`CommunicationRequest::GetProtocolInfo`].
+    /// This allows re-learning the protocols of a peer that was upgraded while the connection to it was open, because
+    /// the identify protocol only requests the information once a new connection was established.
+    RefreshIdentify(#[serde(with = "serde_peer_id")] PeerId),
+    /// Get a summary of the listeners, the relay connection, the connected peers and the load of the event loop, which
+    /// are all determined at the same time.
+    GetHealth,
+    /// Dump the outstanding operations of the actor for debugging, e.g. after a stall was detected.
+    /// The dump is taken right away while an outbound request is awaited, otherwise the actor handles one request
+    /// after another, so the dump is taken once a blocking operation of a previous request finished. Operations that
+    /// timed out locally but are still outstanding in the swarm, e.g. requests for which the remote never responded,
+    /// are included.
+    DumpState,
+    /// Get the TCP ports that the swarm is listening on, e.g. the OS assigned port of a `/tcp/0` listener.
+    GetListeningPorts,
+    /// Parse the address and check it against the transports of the local system, e.g. to validate the input of a
+    /// user before it is used for dialing or listening. Nothing is dialed or resolved.
+    ValidateMultiaddr(String),
+    /// Get all peers that are known to the local system, either from the address book, e.g. via mDNS or previous
+    /// connections, or because a connection to them is currently established.
+    GetKnownPeers,
+    /// Get the addresses in the address book of the swarm, which are dialed when connecting to a peer.
+    /// Peers that are only connected without a known address are not included, unlike for
+    /// [`CommunicationRequest::GetKnownPeers`].
+    GetAddressBook,
+    /// Remove the addresses of a peer, or of all peers if none is specified, from the address book and the addresses
+    /// of previous connections, e.g. because the peer permanently moved to a new address. Established connections are
+    /// not closed. Addresses that were discovered by mDNS are still dialed until they expire.
+    ClearAddressBook(#[serde(with = "serde_peer_id::option")] Option<PeerId>),
     /// Ban a peer, which prevents any connection to that peer.
-    BanPeer(PeerId),
+    BanPeer(#[serde(with = "serde_peer_id")] PeerId),
     /// Unban a peer to allow future communication.
-    UnbanPeer(PeerId),
+    UnbanPeer(#[serde(with = "serde_peer_id")] PeerId),
     /// Start listening to a port on the swarm. If no `Multiaddr` is provided, the address will be OS assigned.
+    /// The socket is always bound by the swarm, since the tcp transport of libp2p 0.36 can not adopt a pre-bound
+    /// listener, e.g. from socket activation.
     StartListening(Option<Multiaddr>),
-    /// Stop listening to the swarm. Without a listener, the local peer can not be dialed from remote.
+    /// Start listening only on the loopback interfaces `127.0.0.1` and `[::1]` with an OS assigned port, so that
+    /// the local peer can only be reached by processes on the same host.
+    /// The returned address is the one of the IPv4 listener, listening on IPv6 is skipped if it is not available.
+    StartListeningLocal,
+    /// Start listening on the first port of the range that is not taken yet, e.g. if only a specific range of ports
+    /// is forwarded to the local system. Each port is tried for the `listen_timeout` of the
+    /// [`CommunicationActorConfig`]. Fails if the listener could not be started on any port of the range.
+    StartListeningInRange { ip: IpAddr, range: RangeInclusive<u16> },
+    /// Stop listening on all listeners of the swarm. Without a listener, the local peer can not be dialed from
+    /// remote.
     RemoveListener,
+    /// Get the latest listeners that closed unexpectedly or reported an error. Closed listeners are removed from the
+    /// listeners of the swarm. Each failure is also emitted as [`CommunicationEvent::ListenerFailed`].
+    GetListenerErrors,
     /// Configured if a relay peer should be used for requests
     SetRelay(RelayConfig),
+    /// Configure the relay like [`CommunicationRequest::SetRelay`], but close the connections to the previous relay
+    /// right away instead of once they are idle. The previous relay stays connected if it is the new relay, or if it
+    /// is still the relay for a specific peer.
+    SetRelayAndDisconnect(RelayConfig),
+    /// Stop using the relay for a peer, e.g. once a direct connection to it was established, without affecting the
+    /// connection to the relay or other relayed peers. Requests to the peer are sent directly, and requests from it
+    /// that are forwarded by the relay are dropped.
+    /// The relay is used for the peer again once all direct connections to it closed, the connection to the relay
+    /// closed, or the relay for the peer is changed with [`CommunicationRequest::SetRelay`] or
+    /// [`CommunicationRequest::EstablishConnectionViaRelay`].
+    CloseRelayedConnection(#[serde(with = "serde_peer_id")] PeerId),
+    /// Change the timeouts for subsequent requests and dials, a timeout that is none remains unchanged.
+    /// The `request_timeout` is the duration to wait for the response of an outbound request, and the
+    /// `connection_timeout` the duration to wait for a connection when a peer is dialed. Both default to 3s.
+    /// Requests still fail earlier if the timeout of the request-response protocol in the [`BehaviourConfig`] passed,
+    /// which can not be changed at runtime.
+    SetProtocolTimeouts {
+        request_timeout: Option<Duration>,
+        connection_timeout: Option<Duration>,
+    },
+    /// Change the duration to wait for the client to respond to subsequent inbound requests, which defaults to 3s.
+    /// Inbound requests that the client does not respond to within the timeout are dropped without response.
+    SetClientAskTimeout(Duration),
+    /// Get the current scores of remote peers.
+    /// Peers are only scored if the scoring has been enabled in the [`CommunicationActorConfig`].
+    GetPeerScores,
+    /// Get the metrics about requests and connections since the actor started.
+    GetMetrics,
+    /// Get the point in time at which the swarm task started, and how long it has been running since.
+    GetUptime,
+    /// Get the number of outbound requests that were sent but not answered yet, either to a specific peer or in
+    /// total. Outbound requests are sent one after another, but a request that timed out locally is still counted
+    /// until the remote responds or the request-response protocol reports a failure for it.
+    GetPendingRequests(#[serde(with = "serde_peer_id::option")] Option<PeerId>),
     /// Add or remove a rule of the firewall.
     /// If a rule for a peer & direction combination already exists, it is overwritten.
     ConfigureFirewall(FirewallRule),
+    /// Set the relay and the firewall rules together, so that no request is handled with only part of the new
+    /// configuration applied. The relay is left unchanged if it is `None`, the rules are applied in the given order.
+    /// If the new relay could not be connected, neither the relay nor the rules are changed.
+    ///
+    /// There is no connection allowlist that could be set together with them, since the actor has no allowlist of
+    /// peers. Connections from specific peers can instead be limited with the firewall rules, or denied through the
+    /// [`ConnectionAuthorizer`].
+    ReconfigureNetwork {
+        relay: Option<RelayConfig>,
+        firewall: Vec<FirewallRule>,
+    },
+    /// Get the default permission of the firewall for a direction, which is used for peers without a specific rule.
+    GetFirewallDefault(RequestDirection),
+    /// Check whether the firewall would permit the request from or to the peer, without sending it or recording it
+    /// for the rate of its permission. The result includes the rule that was matched for the peer.
+    CheckPermission {
+        #[serde(with = "serde_peer_id")]
+        peer_id: PeerId,
+        direction: RequestDirection,
+        request_sample: Req,
+    },
+    /// Probe the reachability and round-trip time of a peer on a fresh connection to `addr`, independently of the
+    /// firewall rules. This requires that probes are enabled in the [`BehaviourConfig`] of both peers.
+    /// The fresh connection is not closed after the probe, but handled as any other connection.
+    Probe {
+        #[serde(with = "serde_peer_id")]
+        peer_id: PeerId,
+        addr: Multiaddr,
+    },
+    /// Export the firewall, relay configuration, keep-alive connections and known addresses as serializable
+    /// [`CommunicationState`].
+    ExportState,
+    /// Apply a [`CommunicationState`] that was exported from another actor: the firewall defaults, mode and rules of
+    /// the peers in the state are set, the addresses are added, and the relay and keep-alive connections are
+    /// re-established. Rules of peers that are not included in the state remain unchanged.
+    ImportState(CommunicationState),
+    /// Stop handling requests, e.g. for a maintenance window, while connections and keep-alives remain unchanged.
+    /// Inbound requests are rejected right away while paused, so that the remote fails with
+    /// [`RequestMessageError::Unavailable`]. Outbound [`CommunicationRequest::RequestMsg`]s are either queued until
+    /// [`CommunicationRequest::Resume`] if `queue_outbound` is set, or rejected with [`RequestMessageError::Paused`].
+    Pause { queue_outbound: bool },
+    /// Resume handling requests after [`CommunicationRequest::Pause`], and send the queued outbound requests.
+    Resume,
     /// Shutdown communication actor.
     Shutdown,
 }
 
-/// The firewall that rejected or dropped the request
+/// Builder for a [`CommunicationRequest::RequestMsg`] that only requires the target peer and the request, and uses
+/// defaults for all optional fields: no fallback addresses, and the request is checked by the local firewall.
+///
+/// ```
+/// # use communication::{actor::{CommunicationRequest, RequestMsgBuilder}, libp2p::PeerId};
+/// let request: CommunicationRequest<String, String> = RequestMsgBuilder::new(PeerId::random(), "ping".into())
+///     .bypass_firewall(true)
+///     .build();
+/// ```
 #[derive(Debug, Clone)]
+pub struct RequestMsgBuilder<Req> {
+    peer_id: PeerId,
+    request: Req,
+    fallback_addrs: Vec<Multiaddr>,
+    bypass_firewall: bool,
+    source_override: Option<PeerId>,
+    cancel_token: Option<u64>,
+    deadline: Option<Instant>,
+    coalesce: Option<u64>,
+    hedge: bool,
+}
+
+impl<Req> RequestMsgBuilder<Req> {
+    pub fn new(peer_id: PeerId, request: Req) -> Self {
+        RequestMsgBuilder {
+            peer_id,
+            request,
+            fallback_addrs: Vec::new(),
+            bypass_firewall: false,
+            source_override: None,
+            cancel_token: None,
+            deadline: None,
+            coalesce: None,
+            hedge: false,
+        }
+    }
+
+    /// Add an address that the peer is dialed on if no connection exists yet.
+    pub fn fallback_addr(mut self, addr: Multiaddr) -> Self {
+        self.fallback_addrs.push(addr);
+        self
+    }
+
+    /// Set the addresses that the peer is dialed on if no connection exists yet.
+    pub fn fallback_addrs(mut self, addrs: Vec<Multiaddr>) -> Self {
+        self.fallback_addrs = addrs;
+        self
+    }
+
+    /// Send the request without checking the rules of the local firewall.
+    pub fn bypass_firewall(mut self, bypass_firewall: bool) -> Self {
+        self.bypass_firewall = bypass_firewall;
+        self
+    }
+
+    /// Use the peer as source of the envelope instead of the local peer id, to forward the request on behalf of it.
+    /// This should only be used if the remote peer trusts the local peer as relay for the source.
+    pub fn source_override(mut self, source: PeerId) -> Self {
+        self.source_override = Some(source);
+        self
+    }
+
+    /// Set a token with which the request can be cancelled while it is outstanding.
+    pub fn cancel_token(mut self, token: u64) -> Self {
+        self.cancel_token = Some(token);
+        self
+    }
+
+    /// Set a point in time at which the request fails, instead of the relative request timeout.
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Answer requests to the peer with the same key that are received while this request is in flight with its
+    /// result, instead of sending them again. Requests with the same key have to be identical.
+    pub fn coalesce(mut self, key: u64) -> Self {
+        self.coalesce = Some(key);
+        self
+    }
+
+    /// Additionally send the request via the backup relay if the peer was not connected within the
+    /// `relay_fallback_delay`. The remote may receive the request twice, so this should only be set for idempotent
+    /// requests.
+    pub fn hedge(mut self, hedge: bool) -> Self {
+        self.hedge = hedge;
+        self
+    }
+
+    pub fn build<ClientMsg: Message>(self) -> CommunicationRequest<Req, ClientMsg> {
+        CommunicationRequest::RequestMsg {
+            peer_id: self.peer_id,
+            request: self.request,
+            fallback_addrs: self.fallback_addrs,
+            bypass_firewall: self.bypass_firewall,
+            source_override: self.source_override,
+            cancel_token: self.cancel_token,
+            deadline: self.deadline,
+            coalesce: self.coalesce,
+            hedge: self.hedge,
+        }
+    }
+}
+
+impl<Req, ClientMsg: Message> From<RequestMsgBuilder<Req>> for CommunicationRequest<Req, ClientMsg> {
+    fn from(builder: RequestMsgBuilder<Req>) -> Self {
+        builder.build()
+    }
+}
+
+/// Copy of a request that passed the firewall and was forwarded to the client or sent to the remote, as sent to the
+/// actor of the [`AuditConfig`].
+#[derive(Debug, Clone)]
+pub struct AuditRecord<Req> {
+    /// Whether the request was received from or sent to the peer.
+    pub direction: RequestDirection,
+    /// The remote peer, which is the source for inbound requests that were forwarded by a relay.
+    pub peer_id: PeerId,
+    /// Point in time at which the request was forwarded to the client or sent to the remote.
+    pub timestamp: SystemTime,
+    /// The rule of the firewall that was matched for the peer.
+    pub rule: MatchedRule,
+    /// The request, or none if the payload is not included.
+    pub request: Option<Req>,
+    /// The reason for which the firewall would have blocked the request if it is in [`FirewallMode::Audit`], or none
+    /// if the request was permitted by the rules.
+    pub would_block: Option<FirewallBlocked>,
+}
+
+/// Audit trail of the requests that passed the firewall, independently of the observer of [`CommunicationEvent`]s.
+/// Inbound requests are recorded once they are forwarded to the client or answered from the response cache, and
+/// outbound requests once they are sent. Requests that are sent with `bypass_firewall`, and requests that are answered
+/// with the result of a coalesced request are not included.
+#[derive(Debug, Clone)]
+pub struct AuditConfig<Req: Message> {
+    /// Actor that receives an [`AuditRecord`] for each inbound and outbound request that passed the firewall. The
+    /// records are sent without waiting for the actor, so that it never blocks the requests.
+    pub actor: ActorRef<AuditRecord<Req>>,
+    /// Include the requests in the records, otherwise only the metadata is sent, e.g. for privacy.
+    pub include_payload: bool,
+}
+
+/// Provenance of an incoming request, as passed to the [`ProvenanceHook`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RequestProvenance {
+    /// The peer that sent the request.
+    pub source: PeerId,
+    /// The relay peer that forwarded the request, or none if the request was received directly from the source.
+    pub relay: Option<PeerId>,
+    /// The id of the inbound request, which is included in the [`CommunicationEvent::ResponseSent`] once the response
+    /// was transmitted. The hook can attach it to the request, so that the client can correlate the event with the
+    /// request that it answered.
+    pub request_id: RequestId,
+}
+
+impl RequestProvenance {
+    pub fn is_relayed(&self) -> bool {
+        self.relay.is_some()
+    }
+}
+
+/// The firewall that rejected or dropped the request
+#[derive(Debug, Clone, DeriveError, Serialize, Deserialize)]
 pub enum FirewallBlocked {
     /// The local firewall block between the request was forwarded to the swarm.
+    #[error("Blocked by the local firewall")]
     Local,
     /// The remote peer did not response.
+    #[error("No response from the remote peer")]
     Remote,
+    /// The local firewall blocked the request because it exceeded the
+    /// [`PermissionRate`](crate::actor::PermissionRate) of its permission.
+    #[error("Rate limit of the local firewall exceeded")]
+    RateLimited,
 }
 
-#[derive(Debug, Clone)]
+/// Errors that can occur when sending a request to a remote peer.
+#[derive(Debug, Clone, DeriveError, Serialize, Deserialize)]
 pub enum RequestMessageError {
     /// Possible failures occurring in the context of sending an outbound request and receiving the response.
-    Outbound(P2POutboundFailure),
+    #[error("Outbound failure")]
+    Outbound(#[source] P2POutboundFailure),
     /// Possible failures occurring in the context of receiving an inbound request and sending a response.
-    Inbound(P2PInboundFailure),
+    #[error("Inbound failure")]
+    Inbound(#[source] P2PInboundFailure),
     /// The request was rejected or dropped by the local or remote firewall.
-    Rejected(FirewallBlocked),
+    #[error("Request rejected")]
+    Rejected(#[source] FirewallBlocked),
+    /// The request was not sent because the local actor is paused.
+    #[error("Requests are paused")]
+    Paused,
+    /// The remote peer rejected the request without handling it, because it was busy or paused.
+    #[error("Remote peer is unavailable: `{0:?}`")]
+    Unavailable(RejectReason),
+    /// The request was cancelled with [`CommunicationRequest::CancelRequest`].
+    #[error("Request cancelled")]
+    Cancelled,
+    /// The deadline of the request passed before a response was received.
+    #[error("Deadline exceeded")]
+    DeadlineExceeded,
+    /// The request was not sent because the number of outbound requests in flight reached the
+    /// `max_outbound_in_flight` of the `CommunicationActorConfig`.
+    #[error("Too many outbound requests in flight")]
+    TooManyInFlight,
+}
+
+/// Information about a connection that was established with [`CommunicationRequest::EstablishConnection`] or
+/// [`CommunicationRequest::EstablishConnectionViaRelay`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionInfo {
+    /// The connected peer, which is the relay for connections via a relay.
+    #[serde(with = "serde_peer_id")]
+    pub peer_id: PeerId,
+    /// The remote address of the connection. This is not necessarily the address of the request, since known
+    /// addresses of the peer are dialed first.
+    pub addr: Multiaddr,
+    /// Whether the connection was established by the remote peer, otherwise it was dialed by the local peer.
+    pub is_inbound: bool,
+    /// The keep-alive that was applied to the connection.
+    pub keep_alive: KeepAlive,
+}
+
+impl ConnectionInfo {
+    pub(super) fn new(peer_id: PeerId, endpoint: &ConnectedPoint, keep_alive: KeepAlive) -> Self {
+        let (addr, is_inbound) = match endpoint {
+            ConnectedPoint::Dialer { address } => (address.clone(), false),
+            ConnectedPoint::Listener { send_back_addr, .. } => (send_back_addr.clone(), true),
+        };
+        ConnectionInfo {
+            peer_id,
+            addr,
+            is_inbound,
+            keep_alive,
+        }
+    }
 }
 
 /// Information about the connection with a remote peer as maintained in the ConnectionManager.
-#[derive(Clone, Debug)]
+/// The start of the connection is serialized as the duration that elapsed since it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct EstablishedConnection {
+    #[serde(with = "serde_elapsed")]
     start: Instant,
     keep_alive: KeepAlive,
+    #[serde(with = "ConnectedPointDef")]
     connected_point: ConnectedPoint,
+    rtt: Option<Duration>,
+}
+
+// Serializable mirror of the `ConnectedPoint` of libp2p.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ConnectedPoint")]
+enum ConnectedPointDef {
+    Dialer {
+        address: Multiaddr,
+    },
+    Listener {
+        local_addr: Multiaddr,
+        send_back_addr: Multiaddr,
+    },
 }
 
 impl EstablishedConnection {
@@ -116,8 +692,19 @@ impl EstablishedConnection {
             start: Instant::now(),
             keep_alive,
             connected_point,
+            rtt: None,
         }
     }
+
+    /// Latest round-trip time to the peer that was measured by the ping protocol. The peers are only pinged if
+    /// probes are enabled in the [`BehaviourConfig`] of both peers.
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+
+    pub(super) fn set_rtt(&mut self, rtt: Duration) {
+        self.rtt = Some(rtt);
+    }
     pub(super) fn is_keep_alive(&self) -> bool {
         match self.keep_alive {
             KeepAlive::Unlimited => true,
@@ -129,81 +716,413 @@ impl EstablishedConnection {
     pub(super) fn set_keep_alive(&mut self, keep_alive: KeepAlive) {
         self.keep_alive = keep_alive;
     }
+
+    pub(super) fn keep_alive(&self) -> KeepAlive {
+        self.keep_alive.clone()
+    }
+
+    pub(super) fn connected_point(&self) -> &ConnectedPoint {
+        &self.connected_point
+    }
+}
+
+/// Overall health of the [`CommunicationActor`], as part of the [`HealthSummary`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum HealthStatus {
+    /// None of the [`HealthFactor`]s apply.
+    Healthy,
+    /// The actor is operational, but peers may not be reachable or requests may be delayed.
+    Degraded,
+    /// The local peer can not be reached by remote peers, or requests can not be sent.
+    Unhealthy,
+}
+
+/// Factor that contributes to a [`HealthStatus`] other than [`HealthStatus::Healthy`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum HealthFactor {
+    /// The swarm is not listening on any address. Results in [`HealthStatus::Unhealthy`].
+    NotListening,
+    /// The configured relay is not connected. Results in [`HealthStatus::Unhealthy`] for
+    /// [`RelayConfig::RelayAlways`], and in [`HealthStatus::Degraded`] for [`RelayConfig::RelayBackup`].
+    RelayDisconnected,
+    /// The swarm is not connected to any peer. Results in [`HealthStatus::Degraded`].
+    NoConnectedPeers,
+    /// The event loop was busy for more than 90% of the time since the previous health request, so that new events
+    /// are delayed. Results in [`HealthStatus::Degraded`].
+    Saturated,
+}
+
+/// Health of the [`CommunicationActor`], as returned for [`CommunicationRequest::GetHealth`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthSummary {
+    /// The overall status, which is the worst status of the `factors`.
+    pub status: HealthStatus,
+    /// The factors that contribute to a degraded or unhealthy status.
+    pub factors: Vec<HealthFactor>,
+    /// Number of addresses that the swarm is listening on.
+    pub listen_addrs: usize,
+    /// Whether the configured relay is connected, `None` if no relay is configured.
+    pub relay_connected: Option<bool>,
+    /// Number of peers that the swarm is currently connected to.
+    pub connected_peers: usize,
+    /// Share of the time in which the event loop was busy handling events, between 0 and 1. It is measured over a
+    /// rolling window of about the last 10s, or since the start of the actor, and does not depend on previous health
+    /// requests.
+    pub loop_utilization: f64,
+}
+
+/// Outstanding operations of the [`CommunicationActor`], as returned for [`CommunicationRequest::DumpState`].
+/// The request ids of libp2p are serialized in their decimal representation, but since they are only valid within the
+/// local swarm, the dump can not be deserialized.
+#[derive(Debug, Clone, Serialize)]
+pub struct StateDump {
+    /// Peers that are currently being dialed.
+    #[serde(with = "serde_peer_id::vec")]
+    pub pending_dials: Vec<PeerId>,
+    /// Outbound requests that were sent but not answered yet, ordered from oldest to newest.
+    pub outbound_requests: Vec<PendingOutboundRequest>,
+    /// Inbound requests that were received while the actor was blocked, and are queued until they are forwarded to
+    /// the client, with the peer from which they were received.
+    #[serde(serialize_with = "serde_request_id::pairs::serialize")]
+    pub queued_inbound: Vec<(PeerId, RequestId)>,
+    /// Inbound requests that were forwarded to the client and are currently being serviced by it, with the source of
+    /// the request.
+    #[serde(serialize_with = "serde_request_id::pairs::serialize")]
+    pub inbound_in_flight: Vec<(PeerId, RequestId)>,
+    /// Number of outbound requests that were queued while handling requests is paused.
+    pub queued_outbound: usize,
+    /// Number of messages to the actor that were received while a request was awaited, and are handled once it
+    /// finished.
+    pub deferred_actor_requests: usize,
+    /// Number of requests that were coalesced into the currently awaited request, and receive its response.
+    pub coalesced_requests: usize,
+}
+
+/// Outbound request that awaits a response, as part of the [`StateDump`].
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingOutboundRequest {
+    /// Id of the request in the request-response protocol.
+    #[serde(serialize_with = "serde_request_id::serialize")]
+    pub request_id: RequestId,
+    /// The peer that the request was sent to, which is the relay if the request was relayed.
+    #[serde(serialize_with = "serde_peer_id::serialize")]
+    pub peer_id: PeerId,
+    /// Time since the request was sent.
+    pub elapsed: Duration,
+}
+
+/// State of the connection to a remote peer.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ConnectionState {
+    /// At least one connection to the peer is established.
+    Connected,
+    /// No connection is established yet, but the peer is currently being dialed.
+    Connecting,
+    /// There is neither an established connection nor a pending dial.
+    Disconnected,
 }
 
 /// Returned results from the [`CommunicationActor`]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum CommunicationResults<Res> {
     /// Response or Error for an [`RequestMsg`] to a remote peer
     RequestMsgResult(Result<Res, RequestMessageError>),
+    /// Whether an outstanding request with the cancel token was cancelled.
+    CancelRequestResult(bool),
+    /// The group was created.
+    CreateGroupAck,
+    /// The peers were added to the group.
+    AddToGroupAck,
+    /// The peers were removed from the group.
+    RemoveFromGroupAck,
+    /// Response or error for each member of the group, empty if the group does not exist.
+    RequestMsgGroupResult(#[serde(with = "serde_peer_id::pairs")] Vec<(PeerId, Result<Res, RequestMessageError>)>),
     /// New client actor reference was set.
     SetClientRefAck,
-    /// Result of trying to connect a peer.
-    EstablishConnectionResult(Result<PeerId, ConnectPeerError>),
+    /// Result of trying to connect a peer, with the information about the established connection on success.
+    EstablishConnectionResult(Result<ConnectionInfo, ConnectPeerError>),
     /// Closed connection to peer.
     CloseConnectionAck,
-    /// Check if the connection exists.
+    /// Result of re-establishing the connection to a peer.
+    ReconnectResult(#[serde(with = "serde_peer_id::result")] Result<PeerId, ConnectPeerError>),
+    /// Started maintaining the connection to the peer.
+    MaintainConnectionAck,
+    /// Stopped maintaining the connection, false if it was not maintained.
+    StopMaintainingResult(bool),
+    /// Current state of the connection to a peer.
     CheckConnectionResult {
+        #[serde(with = "serde_peer_id")]
         peer_id: PeerId,
-        is_connected: bool,
+        state: ConnectionState,
     },
     /// Information about the local swarm.
     SwarmInfo {
         /// The local peer id.
+        #[serde(with = "serde_peer_id")]
         peer_id: PeerId,
         /// The listening addresses of the local system.
         /// Not all of theses addresses can be reached from outside of the network since they might be localhost or
         /// private IPs.
         listeners: Vec<Multiaddr>,
         /// Established connections.
+        #[serde(with = "serde_peer_id::pairs")]
         connections: Vec<(PeerId, EstablishedConnection)>,
     },
-    BannedPeerAck(PeerId),
-    UnbannedPeerAck(PeerId),
+    /// The requested sections of the information about the local swarm, sections that were not requested are `None`.
+    SwarmInfoPartial {
+        /// The local peer id.
+        #[serde(with = "serde_peer_id::option")]
+        peer_id: Option<PeerId>,
+        /// The listening addresses of the local system.
+        listeners: Option<Vec<Multiaddr>>,
+        /// Established connections.
+        #[serde(with = "serde_peer_id::option_pairs")]
+        connections: Option<Vec<(PeerId, EstablishedConnection)>>,
+    },
+    /// Protocol information of the peer, or `None` if the peer is not connected or did not send its identifying
+    /// information yet.
+    ProtocolInfo(Option<PeerProtocolInfo>),
+    /// Refreshed protocol information of the peer, or the error if the peer could not be connected or did not send
+    /// its information within the timeout.
+    RefreshIdentifyResult(Result<PeerProtocolInfo, ConnectPeerError>),
+    /// Health of the actor.
+    Health(HealthSummary),
+    /// Outstanding operations of the actor.
+    /// It is only serialized, since the request ids of libp2p can not be deserialized.
+    #[serde(skip_deserializing)]
+    StateDump(StateDump),
+    /// TCP ports of the active listeners, without duplicates and in ascending order.
+    ListeningPorts(Vec<u16>),
+    /// Breakdown of the address, or the error if it is not a valid multiaddr.
+    ValidateMultiaddrResult(Result<MultiaddrInfo, String>),
+    /// The known peers with their known addresses, and the state of the connection to them.
+    #[serde(with = "serde_peer_id::triples")]
+    KnownPeers(Vec<(PeerId, Vec<Multiaddr>, ConnectionState)>),
+    /// The addresses of each peer in the address book.
+    AddressBook(#[serde(with = "serde_peer_id::map")] HashMap<PeerId, Vec<Multiaddr>>),
+    /// Removed the addresses from the address book.
+    ClearAddressBookAck,
+    BannedPeerAck(#[serde(with = "serde_peer_id")] PeerId),
+    UnbannedPeerAck(#[serde(with = "serde_peer_id")] PeerId),
     /// Result of starting a new listener on the swarm.
     /// If it was successful, one of the listening addresses is returned, which will show the listening port.
-    StartListeningResult(Result<Multiaddr, ()>),
+    StartListeningResult(Result<Multiaddr, StartListeningError>),
     /// Stopped listening to the swarm for incoming connections.
     RemoveListenerResult(Result<(), ()>),
+    /// Latest listener failures, ordered from oldest to newest.
+    ListenerErrors(Vec<ListenerFailure>),
     /// Setting relay result.
     /// Error if the relay peer could not be connected.
     SetRelayResult(Result<(), ConnectPeerError>),
+    /// Stopped using the relay for the peer.
+    CloseRelayedConnectionAck,
     /// Successfully set firewall rule.
     ConfigureFirewallAck,
+    /// Result of reconfiguring the network.
+    /// Error if the new relay could not be connected, in which case no change was applied.
+    ReconfigureNetworkResult(Result<(), ConnectPeerError>),
+    /// Current default permission of the firewall for the requested direction.
+    FirewallDefault(FirewallPermission),
+    /// Decision of the firewall for the checked request.
+    CheckPermissionResult(FirewallDecision),
+    /// Updated the timeouts for subsequent requests.
+    SetProtocolTimeoutsAck,
+    /// Changed the client timeout, the previous timeout is returned.
+    /// Error if the new timeout is zero.
+    SetClientAskTimeoutResult(Result<Duration, InvalidTimeout>),
+    /// Paused handling requests.
+    PauseAck,
+    /// Resumed handling requests.
+    ResumeAck,
+    /// The current score of each peer that has misbehaved.
+    PeerScores(#[serde(with = "serde_peer_id::pairs")] Vec<(PeerId, i32)>),
+    /// Current metrics of the swarm.
+    Metrics(Box<SwarmMetrics>),
+    /// Number of outbound requests that are awaiting a response.
+    PendingRequests(usize),
+    /// Start time of the swarm task, and the elapsed time since, which is measured with a monotonic clock and
+    /// therefore not affected by changes of the system time.
+    Uptime {
+        started_at: SystemTime,
+        elapsed: Duration,
+    },
+    /// Timings of the probe.
+    ProbeResult(Result<ProbeTimings, ProbeError>),
+    /// Current state of the actor.
+    State(CommunicationState),
+    /// Applied the state. The connections that could not be re-established are returned with the error.
+    ImportStateResult {
+        relay: Result<(), ConnectPeerError>,
+        #[serde(with = "serde_peer_id::pairs")]
+        failed_connections: Vec<(PeerId, ConnectPeerError)>,
+    },
 }
 
-/// Errors that can occur in the context of a pending `Connection`.
+/// Events of the swarm that are sent to the observer configured in the [`CommunicationActorConfig`].
 #[derive(Debug, Clone)]
+pub enum CommunicationEvent {
+    /// A connection to a remote peer was established.
+    /// The protocols that the remote supports are only included if they are already known from a previous identify
+    /// exchange on an existing connection, otherwise they are emitted later with
+    /// [`CommunicationEvent::ProtocolsUpdated`].
+    ConnectionEstablished {
+        peer_id: PeerId,
+        endpoint: ConnectedPoint,
+        protocols: Option<Vec<String>>,
+    },
+    /// A connection to a remote peer was closed. The number of remaining connections to the peer is
+    /// `num_established`.
+    ConnectionClosed {
+        peer_id: PeerId,
+        endpoint: ConnectedPoint,
+        num_established: u32,
+        reason: DisconnectReason,
+    },
+    /// An inbound connection was closed because the [`ConnectionAuthorizer`] denied it.
+    ConnectionDenied { peer_id: PeerId },
+    /// The list of protocols that a remote peer supports was received or changed.
+    ProtocolsUpdated { peer_id: PeerId, protocols: Vec<String> },
+    /// A request was allowed in [`FirewallMode::Audit`], but would have been rejected by the firewall rules.
+    /// For incoming requests, the `peer_id` is the source of the request.
+    FirewallWouldBlock {
+        peer_id: PeerId,
+        direction: RequestDirection,
+    },
+    /// Result of trying to establish a direct connection to a peer that communicated via the relay.
+    /// On success, future requests to the peer are sent directly instead of via the relay.
+    DirectConnectionUpgrade {
+        peer_id: PeerId,
+        result: Result<(), ConnectPeerError>,
+    },
+    /// The swarm started dialing a peer, either explicitly or e.g. to send a request to it.
+    Dialing { peer_id: PeerId },
+    /// Dialing an address failed. The `peer_id` is none if the address was dialed without a known peer id.
+    /// The dial attempt to the peer failed completely if no attempts are remaining.
+    DialFailure {
+        peer_id: Option<PeerId>,
+        address: Multiaddr,
+        error: ConnectPeerError,
+        attempts_remaining: u32,
+    },
+    /// An address of a peer was removed from the address book because dialing it failed too often.
+    AddressPruned { peer_id: PeerId, address: Multiaddr },
+    /// The client timed out too often, inbound requests are rejected until the cooldown of the breaker passed.
+    ClientBreakerOpened,
+    /// The client responded again after the breaker was opened, inbound requests are forwarded to it again.
+    ClientBreakerClosed,
+    /// A listener closed unexpectedly or reported an error.
+    ListenerFailed(ListenerFailure),
+    /// An incoming request was dropped because the ttl of its envelope expired.
+    EnvelopeExpired { source: PeerId },
+    /// An incoming request was dropped because the target of its envelope is not the local peer, e.g. because a relay
+    /// misrouted it. The `target` is the claimed target of the envelope, which is not necessarily a valid peer id.
+    EnvelopeMisrouted {
+        peer_id: PeerId,
+        target: String,
+        local_peer_id: PeerId,
+    },
+    /// A ping to a connected peer measured a round-trip time above the `rtt_threshold` of the
+    /// [`CommunicationActorConfig`]. It is emitted for each ping that exceeds the threshold.
+    HighLatency { peer_id: PeerId, rtt: Duration },
+    /// All listeners closed, and the [`ListenerWatchdogConfig`] restarted listening on the address.
+    ListenerRestarted { addr: Multiaddr },
+    /// A remote peer declared a request or response larger than the `max_message_size` of the [`BehaviourConfig`].
+    /// The message was rejected before reading it, and the connection to the peer was closed.
+    ///
+    /// [`BehaviourConfig`]: crate::behaviour::BehaviourConfig
+    MessageTooLarge { peer_id: PeerId, size: usize, max: usize },
+    /// The peer was banned for the `cooldown` of the [`FailureBanConfig`], because it caused too many inbound failures
+    /// or malformed requests.
+    ///
+    /// [`FailureBanConfig`]: crate::actor::FailureBanConfig
+    PeerAutoBanned { peer_id: PeerId, cooldown: Duration },
+    /// The response of the client to the inbound request was transmitted to the remote peer. This is only emitted if
+    /// `report_responses_sent` is enabled in the [`CommunicationActorConfig`], and not for rejections of the request,
+    /// e.g. by the firewall. The id of the request is passed to the [`ProvenanceHook`] as part of the
+    /// [`RequestProvenance`], and the id of a queued inbound request is included in the [`StateDump`].
+    ResponseSent { peer_id: PeerId, request_id: RequestId },
+    /// A connection was refused because the `limit` of the [`ConnectionLimitsConfig`] was reached, with the `current`
+    /// number of connections for the limit.
+    ///
+    /// [`ConnectionLimitsConfig`]: crate::behaviour::ConnectionLimitsConfig
+    ConnectionLimitReached {
+        kind: ConnectionLimitKind,
+        limit: u32,
+        current: u32,
+    },
+    /// The state of a connection that is maintained with [`CommunicationRequest::MaintainConnection`] changed.
+    MaintainedConnection { peer_id: PeerId, state: MaintainedState },
+}
+
+/// Errors that can occur in the context of a pending `Connection`.
+#[derive(Debug, Clone, DeriveError, Serialize, Deserialize)]
 pub enum ConnectPeerError {
     /// The peer is currently banned.
+    #[error("The peer is banned")]
     Banned,
     /// No addresses for the peer to dial
+    #[error("No addresses to dial the peer")]
     NoAddresses,
     /// An error occurred while negotiating the transport protocol(s).
-    Transport,
+    #[error("Negotiating the transport failed")]
+    Transport(#[source] ErrorCause),
     /// The peer identity obtained on the connection did not
     /// match the one that was expected or is otherwise invalid.
+    #[error("Invalid peer id")]
     InvalidPeerId,
     /// The connection was dropped because the connection limit
     /// for a peer has been reached.
-    ConnectionLimit(ConnectionLimit),
+    #[error("Connection limit reached")]
+    ConnectionLimit(
+        #[source]
+        #[serde(with = "ConnectionLimitDef")]
+        ConnectionLimit,
+    ),
     /// An I/O error occurred on the connection.
-    IO,
+    #[error("I/O error on the connection")]
+    IO(#[source] ErrorCause),
     /// The connection handler produced an error.
-    Handler,
+    #[error("Connection handler error")]
+    Handler(#[source] ErrorCause),
     /// Timout on connection attempt
+    #[error("Timeout while connecting")]
     Timeout,
     /// The address given for dialing is invalid.
+    #[error("Invalid address: `{0}`")]
     InvalidAddress(Multiaddr),
+    /// The peer was connected, but its identifying information was not received for
+    /// [`CommunicationRequest::RefreshIdentify`].
+    #[error("No identify info received")]
+    NoIdentifyInfo,
 }
 
-impl<TTransErr> From<PendingConnectionError<TTransErr>> for ConnectPeerError {
+/// Message of the error that caused a [`ConnectPeerError`], which is returned as its `source`. Only the message of the
+/// original error is kept, so that the error can be cloned and serialized.
+#[derive(Debug, Clone, PartialEq, Eq, DeriveError, Serialize, Deserialize)]
+#[error("{0}")]
+pub struct ErrorCause(pub String);
+
+impl ErrorCause {
+    fn new(error: impl fmt::Display) -> Self {
+        ErrorCause(error.to_string())
+    }
+}
+
+// Serializable definition of the `ConnectionLimit` of libp2p.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ConnectionLimit")]
+struct ConnectionLimitDef {
+    limit: u32,
+    current: u32,
+}
+
+impl<TTransErr: fmt::Display> From<PendingConnectionError<TTransErr>> for ConnectPeerError {
     fn from(error: PendingConnectionError<TTransErr>) -> Self {
         match error {
-            PendingConnectionError::Transport(_) => ConnectPeerError::Transport,
+            PendingConnectionError::Transport(err) => ConnectPeerError::Transport(ErrorCause::new(err)),
             PendingConnectionError::InvalidPeerId => ConnectPeerError::InvalidPeerId,
             PendingConnectionError::ConnectionLimit(limit) => ConnectPeerError::ConnectionLimit(limit),
-            PendingConnectionError::IO(_) => ConnectPeerError::IO,
+            PendingConnectionError::IO(err) => ConnectPeerError::IO(ErrorCause::new(err)),
         }
     }
 }
@@ -219,11 +1138,569 @@ impl From<DialError> for ConnectPeerError {
     }
 }
 
-impl<THandlerErr> From<ConnectionError<THandlerErr>> for ConnectPeerError {
+impl<THandlerErr: fmt::Display> From<ConnectionError<THandlerErr>> for ConnectPeerError {
     fn from(error: ConnectionError<THandlerErr>) -> Self {
         match error {
-            ConnectionError::Handler(_) => ConnectPeerError::Handler,
-            ConnectionError::IO(_) => ConnectPeerError::IO,
+            ConnectionError::Handler(err) => ConnectPeerError::Handler(ErrorCause::new(err)),
+            ConnectionError::IO(err) => ConnectPeerError::IO(ErrorCause::new(err)),
+        }
+    }
+}
+
+/// Protocol information of a connected peer, as returned for [`CommunicationRequest::GetProtocolInfo`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerProtocolInfo {
+    /// Name and version of the peer, as advertised via the identify protocol, without the metadata.
+    pub agent_version: String,
+    /// Metadata that the peer advertised as part of its agent version, see [`parse_agent_version`].
+    pub metadata: BTreeMap<String, String>,
+    /// Version of the protocol family used by the peer, e.g. `ipfs/0.1.0`.
+    pub protocol_version: String,
+    /// The versions of the request-response protocol that both peers support, ordered by the preference of the
+    /// local peer.
+    pub versions: Vec<String>,
+    /// The version that is used for outbound requests, which is the first version of the local list that the peer
+    /// supports. `None` if the peer supports none of the local versions.
+    pub outbound_version: Option<String>,
+}
+
+impl PeerProtocolInfo {
+    // Create the information from the identify info of the peer and the local protocol versions.
+    pub(super) fn new(info: &P2PIdentifyInfo, local_versions: &[String]) -> Self {
+        let versions: Vec<String> = local_versions
+            .iter()
+            .filter(|version| info.protocols.contains(version))
+            .cloned()
+            .collect();
+        let (agent_version, metadata) = parse_agent_version(&info.agent_version);
+        PeerProtocolInfo {
+            agent_version,
+            metadata,
+            protocol_version: info.protocol_version.clone(),
+            outbound_version: versions.first().cloned(),
+            versions,
         }
     }
 }
+
+/// Breakdown of an address that was checked with [`CommunicationRequest::ValidateMultiaddr`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MultiaddrInfo {
+    /// The parsed address.
+    pub addr: Multiaddr,
+    /// The transport of the address, or `None` if it does not match any transport of the local system, e.g. for
+    /// relayed addresses.
+    pub transport: Option<AddrTransport>,
+    /// The address can be dialed with the transports of the local system.
+    pub is_dialable: bool,
+    /// The local system can listen on the address. This requires an IP address instead of a DNS name, and no peer
+    /// id. Whether the port is free is not checked.
+    pub is_listenable: bool,
+    /// The peer id of a `/p2p` protocol at the end of the address.
+    #[serde(with = "serde_peer_id::option")]
+    pub peer_id: Option<PeerId>,
+}
+
+/// Timings of a [`CommunicationRequest::Probe`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ProbeTimings {
+    /// Duration from dialing the peer until the connection was established. The swarm of libp2p 0.36 reports a
+    /// connection only after the security and multiplexing handshakes, so this includes the handshakes.
+    pub connect: Duration,
+    /// Round-trip time of a ping on the connection.
+    pub rtt: Duration,
+}
+
+/// Errors that can occur when probing a peer.
+#[derive(Debug, Clone, DeriveError, Serialize, Deserialize)]
+pub enum ProbeError {
+    /// Probes are not enabled in the local [`BehaviourConfig`].
+    #[error("Probes are disabled")]
+    Disabled,
+    /// Establishing the connection failed.
+    #[error("Connecting failed")]
+    Connect(#[source] ConnectPeerError),
+    /// The ping failed, e.g. because the remote peer did not enable probes.
+    #[error("Ping failed")]
+    PingFailed,
+    /// No ping result was received within the connection and request timeout.
+    #[error("Timeout while probing")]
+    Timeout,
+}
+
+/// A listener that closed or reported an error.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListenerFailure {
+    /// Id of the listener, if it could be determined. The swarm of libp2p 0.36 does not include the listener id in
+    /// its events, therefore it is determined by the addresses of a closed listener, and it is none for errors.
+    /// The id is only valid within the local swarm and therefore not serialized.
+    #[serde(skip)]
+    pub listener_id: Option<ListenerId>,
+    /// Addresses that the listener was listening on, empty for errors of listeners that are still alive.
+    pub addresses: Vec<Multiaddr>,
+    /// The error, or none if the listener closed without error.
+    pub error: Option<String>,
+    /// Whether the listener was closed, otherwise the error was not fatal and the listener is still alive.
+    pub is_closed: bool,
+}
+
+/// Configuration for a watchdog that periodically checks whether the local node still has a live listener.
+/// If all listeners closed, e.g. because the transport ran out of file descriptors, the node is restarted to listen
+/// on the `addrs`, and each restarted listener is emitted as [`CommunicationEvent::ListenerRestarted`].
+/// Listeners are only restored if the node started listening before, and did not remove its listeners with
+/// [`CommunicationRequest::RemoveListener`].
+#[derive(Debug, Clone)]
+pub struct ListenerWatchdogConfig {
+    /// Interval in which the listeners are checked. Intervals shorter than 100ms are raised to 100ms.
+    pub interval: Duration,
+    /// Addresses on which the node listens again once all listeners closed.
+    pub addrs: Vec<Multiaddr>,
+}
+
+/// Errors that can occur when starting a new listener.
+#[derive(Debug, Clone, PartialEq, Eq, DeriveError, Serialize, Deserialize)]
+pub enum StartListeningError {
+    /// The address is not supported by the transport.
+    #[error("Address not supported: `{0}`")]
+    NotSupported(Multiaddr),
+    /// Listening on the address failed, e.g. because the address is already in use.
+    #[error("Listening failed")]
+    Listen,
+    /// The listener did not report its listening address within the `listen_timeout` of the
+    /// [`CommunicationActorConfig`].
+    #[error("Timeout before the listener was ready")]
+    ReadyTimeout,
+}
+
+impl<TErr> From<TransportError<TErr>> for StartListeningError {
+    fn from(error: TransportError<TErr>) -> Self {
+        match error {
+            TransportError::MultiaddrNotSupported(addr) => StartListeningError::NotSupported(addr),
+            TransportError::Other(_) => StartListeningError::Listen,
+        }
+    }
+}
+
+/// The timeout of [`CommunicationRequest::SetClientAskTimeout`] was zero, which would drop each inbound request before
+/// the client could respond to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DeriveError, Serialize, Deserialize)]
+#[error("Invalid timeout: `{0:?}`")]
+pub struct InvalidTimeout(pub Duration);
+
+/// Reason why a connection to a remote peer was closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// The connection was closed gracefully, e.g. because the remote peer shut down or the connection was closed
+    /// locally.
+    Graceful,
+    /// The connection was closed because it was idle and not kept alive.
+    KeepAliveTimeout,
+    /// An I/O error occurred on the transport, e.g. because the network of the remote peer dropped.
+    Transport(String),
+    /// A protocol handler of the connection produced an error.
+    Handler,
+}
+
+impl<THandlerErr> From<Option<ConnectionError<NodeHandlerWrapperError<THandlerErr>>>> for DisconnectReason {
+    fn from(cause: Option<ConnectionError<NodeHandlerWrapperError<THandlerErr>>>) -> Self {
+        match cause {
+            None => DisconnectReason::Graceful,
+            Some(ConnectionError::IO(err)) => DisconnectReason::Transport(err.to_string()),
+            Some(ConnectionError::Handler(NodeHandlerWrapperError::KeepAliveTimeout)) => {
+                DisconnectReason::KeepAliveTimeout
+            }
+            Some(ConnectionError::Handler(NodeHandlerWrapperError::Handler(_))) => DisconnectReason::Handler,
+        }
+    }
+}
+
+// The violated limit if the connection was closed because the remote peer declared a message that exceeds the max
+// message size. The error of the codec is nested in the errors of the protocols handlers.
+pub(super) fn message_too_large<THandlerErr: Error + 'static>(
+    cause: &Option<ConnectionError<NodeHandlerWrapperError<THandlerErr>>>,
+) -> Option<MessageTooLarge> {
+    let mut error: &(dyn Error + 'static) = match cause {
+        Some(ConnectionError::Handler(NodeHandlerWrapperError::Handler(err))) => err,
+        _ => return None,
+    };
+    loop {
+        let too_large = error
+            .downcast_ref::<io::Error>()
+            .and_then(|err| err.get_ref())
+            .and_then(|err| err.downcast_ref::<MessageTooLarge>());
+        if let Some(too_large) = too_large {
+            return Some(*too_large);
+        }
+        error = error.source()?;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actor::{MatchedRule, PermissionRate, PermissionValue};
+    use core::fmt::Debug;
+    use serde::de::DeserializeOwned;
+    use std::net::Ipv4Addr;
+
+    type Request = CommunicationRequest<String, String>;
+
+    // Serialize and deserialize the value, and compare the debug output since the enums don't implement PartialEq.
+    fn assert_round_trip<T: Serialize + DeserializeOwned + Debug>(value: T) {
+        let bytes = serde_json::to_vec(&value).unwrap();
+        let deserialized: T = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(format!("{:?}", deserialized), format!("{:?}", value));
+    }
+
+    #[test]
+    fn serde_requests() {
+        let peer_id = PeerId::random();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/8080".parse().unwrap();
+        let requests: Vec<Request> = vec![
+            CommunicationRequest::RequestMsg {
+                peer_id,
+                request: "request".into(),
+                fallback_addrs: vec![addr.clone()],
+                bypass_firewall: false,
+                source_override: Some(peer_id),
+                cancel_token: Some(1),
+                deadline: None,
+                coalesce: Some(3),
+                hedge: true,
+            },
+            CommunicationRequest::CancelRequest(1),
+            CommunicationRequest::CreateGroup {
+                name: "group".into(),
+                peers: vec![peer_id],
+            },
+            CommunicationRequest::AddToGroup {
+                group: "group".into(),
+                peers: vec![PeerId::random()],
+            },
+            CommunicationRequest::RemoveFromGroup {
+                group: "group".into(),
+                peers: vec![peer_id],
+            },
+            CommunicationRequest::RequestMsgGroup {
+                group: "group".into(),
+                request: "request".into(),
+            },
+            CommunicationRequest::EstablishConnection {
+                addr: addr.clone(),
+                peer_id,
+                keep_alive: KeepAlive::Unlimited,
+            },
+            CommunicationRequest::EstablishConnectionViaRelay {
+                peer_id,
+                relay_peer: PeerId::random(),
+                relay_addr: addr.clone(),
+            },
+            CommunicationRequest::CloseConnection(peer_id),
+            CommunicationRequest::Reconnect(peer_id),
+            CommunicationRequest::MaintainConnection {
+                peer_id,
+                addrs: vec![addr.clone()],
+            },
+            CommunicationRequest::StopMaintaining(peer_id),
+            CommunicationRequest::CheckConnection(peer_id),
+            CommunicationRequest::GetSwarmInfo,
+            CommunicationRequest::GetSwarmInfoPartial {
+                include_peer_id: false,
+                include_listeners: true,
+                include_connections: false,
+            },
+            CommunicationRequest::GetListeningPorts,
+            CommunicationRequest::ValidateMultiaddr("/ip4/127.0.0.1/tcp/8080".into()),
+            CommunicationRequest::GetHealth,
+            CommunicationRequest::DumpState,
+            CommunicationRequest::GetProtocolInfo(peer_id),
+            CommunicationRequest::RefreshIdentify(peer_id),
+            CommunicationRequest::GetKnownPeers,
+            CommunicationRequest::GetAddressBook,
+            CommunicationRequest::ClearAddressBook(Some(peer_id)),
+            CommunicationRequest::ClearAddressBook(None),
+            CommunicationRequest::BanPeer(peer_id),
+            CommunicationRequest::UnbanPeer(peer_id),
+            CommunicationRequest::StartListening(Some(addr.clone())),
+            CommunicationRequest::StartListeningLocal,
+            CommunicationRequest::StartListeningInRange {
+                ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+                range: 8000..=8010,
+            },
+            CommunicationRequest::RemoveListener,
+            CommunicationRequest::GetListenerErrors,
+            CommunicationRequest::SetRelay(RelayConfig::RelayAlways {
+                peer_id,
+                addr: addr.clone(),
+            }),
+            CommunicationRequest::SetRelayAndDisconnect(RelayConfig::NoRelay),
+            CommunicationRequest::CloseRelayedConnection(peer_id),
+            CommunicationRequest::ReconfigureNetwork {
+                relay: Some(RelayConfig::NoRelay),
+                firewall: vec![
+                    FirewallRule::SetMode(FirewallMode::Enforce),
+                    FirewallRule::SetRelayPermission(None),
+                ],
+            },
+            CommunicationRequest::SetProtocolTimeouts {
+                request_timeout: Some(Duration::from_secs(1)),
+                connection_timeout: None,
+            },
+            CommunicationRequest::SetClientAskTimeout(Duration::from_secs(5)),
+            CommunicationRequest::GetPeerScores,
+            CommunicationRequest::GetMetrics,
+            CommunicationRequest::GetUptime,
+            CommunicationRequest::GetPendingRequests(Some(peer_id)),
+            CommunicationRequest::GetPendingRequests(None),
+            CommunicationRequest::ConfigureFirewall(FirewallRule::AddPermissions {
+                direction: RequestDirection::In,
+                peers: vec![peer_id],
+                change_default: false,
+                permissions: vec![PermissionValue::new(3).unwrap()],
+            }),
+            CommunicationRequest::ConfigureFirewall(FirewallRule::SetRules {
+                direction: RequestDirection::Out,
+                peers: vec![peer_id],
+                set_default: true,
+                permission: FirewallPermission::from(5),
+            }),
+            CommunicationRequest::ConfigureFirewall(FirewallRule::SetPermissionRate {
+                direction: RequestDirection::In,
+                permission: PermissionValue::new(1).unwrap(),
+                rate: Some(PermissionRate {
+                    max_requests: 10,
+                    window: Duration::from_secs(60),
+                }),
+            }),
+            CommunicationRequest::ConfigureFirewall(FirewallRule::SetRelayPermission(Some(FirewallPermission::none()))),
+            CommunicationRequest::ConfigureFirewall(FirewallRule::SetRelayPermission(None)),
+            CommunicationRequest::ConfigureFirewall(FirewallRule::SetMode(FirewallMode::Audit)),
+            CommunicationRequest::GetFirewallDefault(RequestDirection::Out),
+            CommunicationRequest::CheckPermission {
+                peer_id,
+                direction: RequestDirection::In,
+                request_sample: "request".into(),
+            },
+            CommunicationRequest::Probe {
+                peer_id,
+                addr: addr.clone(),
+            },
+            CommunicationRequest::ExportState,
+            CommunicationRequest::Pause { queue_outbound: true },
+            CommunicationRequest::Resume,
+            CommunicationRequest::Shutdown,
+        ];
+        for request in requests {
+            assert_round_trip(request);
+        }
+        // expired keep-alives are deserialized as no keep-alive
+        let request: Request = CommunicationRequest::EstablishConnection {
+            addr,
+            peer_id,
+            keep_alive: KeepAlive::Limited { end: Instant::now() },
+        };
+        std::thread::sleep(Duration::from_millis(1));
+        let bytes = serde_json::to_vec(&request).unwrap();
+        let request: Request = serde_json::from_slice(&bytes).unwrap();
+        assert!(matches!(
+            request,
+            CommunicationRequest::EstablishConnection {
+                keep_alive: KeepAlive::None,
+                ..
+            }
+        ));
+        // deadlines are serialized relative to the time of the serialization
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let request: Request = RequestMsgBuilder::new(peer_id, "request".into())
+            .deadline(deadline)
+            .build();
+        let bytes = serde_json::to_vec(&request).unwrap();
+        let request: Request = serde_json::from_slice(&bytes).unwrap();
+        match request {
+            CommunicationRequest::RequestMsg {
+                deadline: Some(received),
+                ..
+            } => assert!(received >= deadline && received < deadline + Duration::from_secs(1)),
+            _ => panic!("Invalid deadline."),
+        }
+    }
+
+    #[test]
+    fn serde_results() {
+        let peer_id = PeerId::random();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/8080".parse().unwrap();
+        let results: Vec<CommunicationResults<String>> = vec![
+            CommunicationResults::RequestMsgResult(Ok("response".into())),
+            CommunicationResults::RequestMsgResult(Err(RequestMessageError::Outbound(P2POutboundFailure::Timeout))),
+            CommunicationResults::RequestMsgResult(Err(RequestMessageError::Rejected(FirewallBlocked::Local))),
+            CommunicationResults::RequestMsgResult(Err(RequestMessageError::Rejected(FirewallBlocked::RateLimited))),
+            CommunicationResults::RequestMsgResult(Err(RequestMessageError::Cancelled)),
+            CommunicationResults::RequestMsgResult(Err(RequestMessageError::DeadlineExceeded)),
+            CommunicationResults::RequestMsgResult(Err(RequestMessageError::TooManyInFlight)),
+            CommunicationResults::CancelRequestResult(true),
+            CommunicationResults::CreateGroupAck,
+            CommunicationResults::RequestMsgGroupResult(vec![(peer_id, Ok("response".into()))]),
+            CommunicationResults::SetClientRefAck,
+            CommunicationResults::EstablishConnectionResult(Ok(ConnectionInfo::new(
+                peer_id,
+                &ConnectedPoint::Dialer { address: addr.clone() },
+                KeepAlive::Unlimited,
+            ))),
+            CommunicationResults::EstablishConnectionResult(Err(ConnectPeerError::InvalidAddress(addr.clone()))),
+            CommunicationResults::CloseConnectionAck,
+            CommunicationResults::ReconnectResult(Err(ConnectPeerError::ConnectionLimit(ConnectionLimit {
+                limit: 1,
+                current: 1,
+            }))),
+            CommunicationResults::MaintainConnectionAck,
+            CommunicationResults::StopMaintainingResult(true),
+            CommunicationResults::CheckConnectionResult {
+                peer_id,
+                state: ConnectionState::Connected,
+            },
+            CommunicationResults::BannedPeerAck(peer_id),
+            CommunicationResults::UnbannedPeerAck(peer_id),
+            CommunicationResults::StartListeningResult(Ok(addr.clone())),
+            CommunicationResults::StartListeningResult(Err(StartListeningError::NotSupported(addr.clone()))),
+            CommunicationResults::RemoveListenerResult(Err(())),
+            CommunicationResults::SetRelayResult(Err(ConnectPeerError::Timeout)),
+            CommunicationResults::CloseRelayedConnectionAck,
+            CommunicationResults::ConfigureFirewallAck,
+            CommunicationResults::ReconfigureNetworkResult(Ok(())),
+            CommunicationResults::FirewallDefault(FirewallPermission::all()),
+            CommunicationResults::CheckPermissionResult(FirewallDecision {
+                is_permitted: false,
+                rule: MatchedRule::Specific(FirewallPermission::none()),
+                is_rate_limited: false,
+            }),
+            CommunicationResults::SetProtocolTimeoutsAck,
+            CommunicationResults::SetClientAskTimeoutResult(Ok(Duration::from_secs(3))),
+            CommunicationResults::SetClientAskTimeoutResult(Err(InvalidTimeout(Duration::from_secs(0)))),
+            CommunicationResults::PauseAck,
+            CommunicationResults::ResumeAck,
+            CommunicationResults::PeerScores(vec![(peer_id, -10)]),
+            CommunicationResults::PendingRequests(2),
+            CommunicationResults::Uptime {
+                started_at: SystemTime::now(),
+                elapsed: Duration::from_secs(60),
+            },
+            CommunicationResults::ListeningPorts(vec![8080]),
+            CommunicationResults::ValidateMultiaddrResult(Ok(MultiaddrInfo {
+                addr: addr.clone(),
+                transport: Some(AddrTransport::Tcp),
+                is_dialable: true,
+                is_listenable: true,
+                peer_id: None,
+            })),
+            CommunicationResults::ValidateMultiaddrResult(Err("invalid multiaddr".into())),
+            CommunicationResults::AddressBook(vec![(peer_id, vec![addr])].into_iter().collect()),
+            CommunicationResults::ClearAddressBookAck,
+            CommunicationResults::ProtocolInfo(None),
+            CommunicationResults::RefreshIdentifyResult(Err(ConnectPeerError::Timeout)),
+            CommunicationResults::Health(HealthSummary {
+                status: HealthStatus::Degraded,
+                factors: vec![HealthFactor::NoConnectedPeers],
+                listen_addrs: 1,
+                relay_connected: None,
+                connected_peers: 0,
+                loop_utilization: 0.5,
+            }),
+            CommunicationResults::ProbeResult(Ok(ProbeTimings {
+                connect: Duration::from_millis(20),
+                rtt: Duration::from_millis(5),
+            })),
+            CommunicationResults::ProbeResult(Err(ProbeError::Connect(ConnectPeerError::Banned))),
+            CommunicationResults::ImportStateResult {
+                relay: Ok(()),
+                failed_connections: vec![(peer_id, ConnectPeerError::NoAddresses)],
+            },
+            CommunicationResults::SwarmInfo {
+                peer_id,
+                listeners: vec![addr.clone()],
+                connections: Vec::new(),
+            },
+            CommunicationResults::SwarmInfoPartial {
+                peer_id: Some(peer_id),
+                listeners: None,
+                connections: Some(Vec::new()),
+            },
+            CommunicationResults::KnownPeers(vec![(peer_id, vec![addr.clone()], ConnectionState::Connecting)]),
+            CommunicationResults::ListenerErrors(vec![ListenerFailure {
+                listener_id: None,
+                addresses: vec![addr.clone()],
+                error: Some("too many open files".into()),
+                is_closed: true,
+            }]),
+            CommunicationResults::Metrics(Box::new(SwarmMetrics {
+                outbound_requests: 3,
+                outbound_latency_max: Duration::from_millis(20),
+                inbound_requests_per_peer: vec![(peer_id, 2)].into_iter().collect(),
+                ..Default::default()
+            })),
+        ];
+        for result in results {
+            assert_round_trip(result);
+        }
+
+        // the state dump is only serialized
+        let dump: CommunicationResults<String> = CommunicationResults::StateDump(StateDump {
+            pending_dials: vec![peer_id],
+            outbound_requests: Vec::new(),
+            queued_inbound: Vec::new(),
+            inbound_in_flight: Vec::new(),
+            queued_outbound: 0,
+            deferred_actor_requests: 1,
+            coalesced_requests: 2,
+        });
+        let bytes = serde_json::to_vec(&dump).unwrap();
+        assert!(serde_json::from_slice::<CommunicationResults<String>>(&bytes).is_err());
+    }
+
+    #[test]
+    fn serde_established_connection() {
+        let peer_id = PeerId::random();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/8080".parse().unwrap();
+        let mut connection = EstablishedConnection::new(
+            KeepAlive::Unlimited,
+            ConnectedPoint::Listener {
+                local_addr: addr.clone(),
+                send_back_addr: addr,
+            },
+        );
+        connection.set_rtt(Duration::from_millis(5));
+        let result: CommunicationResults<String> = CommunicationResults::SwarmInfo {
+            peer_id,
+            listeners: Vec::new(),
+            connections: vec![(peer_id, connection.clone())],
+        };
+        let bytes = serde_json::to_vec(&result).unwrap();
+        let connections = match serde_json::from_slice(&bytes).unwrap() {
+            CommunicationResults::<String>::SwarmInfo { connections, .. } => connections,
+            _ => panic!("Unexpected result"),
+        };
+        let (received_id, received) = &connections[0];
+        assert_eq!(*received_id, peer_id);
+        assert!(received.start <= Instant::now() && received.start >= connection.start);
+        assert!(matches!(received.keep_alive, KeepAlive::Unlimited));
+        assert_eq!(received.connected_point, connection.connected_point);
+        assert_eq!(received.rtt(), Some(Duration::from_millis(5)));
+    }
+
+    #[test]
+    fn connect_error_source() {
+        let error = ConnectPeerError::from(PendingConnectionError::<io::Error>::IO(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            "connection refused",
+        )));
+        assert_eq!(error.to_string(), "I/O error on the connection");
+        let source = error.source().map(|source| source.to_string());
+        assert_eq!(source.as_deref(), Some("connection refused"));
+        assert_round_trip(error);
+    }
+
+    #[test]
+    fn invalid_permission_value() {
+        assert!(serde_json::from_str::<PermissionValue>("8").is_ok());
+        assert!(serde_json::from_str::<PermissionValue>("6").is_err());
+    }
+}
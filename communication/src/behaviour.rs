@@ -53,11 +53,18 @@
 //! });
 //! ```
 
+mod addr;
+mod agent;
 mod protocol;
 mod types;
 
+pub(crate) use addr::TransportSupport;
+pub use addr::{
+    addr_peer_id, addr_transport, multiaddr_to_socket_addr, socket_addr_to_multiaddr, tcp_ports, AddrTransport,
+};
+use agent::{encode_agent_version, is_valid_entry};
+pub use agent::{parse_agent_version, DEFAULT_AGENT_VERSION, DEFAULT_IDENTIFY_PROTOCOL_VERSION, MDNS_SERVICE_KEY};
 use core::{
-    iter,
     result::Result,
     task::{Context, Poll},
     time::Duration,
@@ -65,28 +72,47 @@ use core::{
 #[cfg(feature = "mdns")]
 use libp2p::mdns::{Mdns, MdnsEvent};
 use libp2p::{
-    core::{upgrade, Multiaddr, PeerId},
-    dns::DnsConfig,
+    core::{connection::ConnectionLimits, transport::OptionalTransport, upgrade, Multiaddr, PeerId},
+    dns::{DnsConfig, ResolverConfig, ResolverOpts},
     identify::{Identify, IdentifyEvent},
     identity::Keypair,
     mdns::MdnsConfig,
     noise::{self, NoiseConfig},
+    ping::{Ping, PingConfig, PingEvent},
     request_response::{
         ProtocolSupport, RequestId, RequestResponse, RequestResponseConfig, RequestResponseEvent,
         RequestResponseMessage, ResponseChannel,
     },
-    swarm::{NetworkBehaviourAction, NetworkBehaviourEventProcess, PollParameters, Swarm},
+    swarm::{
+        toggle::Toggle, DialPeerCondition, NetworkBehaviourAction, NetworkBehaviourEventProcess, PollParameters, Swarm,
+        SwarmBuilder,
+    },
     tcp::TcpConfig,
-    websocket::WsConfig,
+    websocket::{tls, WsConfig},
     yamux::YamuxConfig,
     NetworkBehaviour, Transport,
 };
-pub use protocol::MessageEvent;
 use protocol::{MessageCodec, MessageProtocol};
-use std::collections::HashMap;
+pub use protocol::{MessageEvent, MessageResponse, MessageTooLarge, RejectReason, WireFormat, DEFAULT_PROTOCOL};
+use socket2::{SockRef, TcpKeepalive};
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    net::SocketAddr,
+    num::NonZeroU32,
+    sync::Arc,
+};
 use thiserror::Error as DeriveError;
+use trust_dns_resolver::config::NameServerConfigGroup;
+
+#[cfg(all(feature = "uds", unix))]
+use libp2p::uds::UdsConfig;
 pub use types::*;
 
+// Default timeout for upgrading a new connection.
+const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(20);
+
 /// Error upon creating a new [`P2PNetworkBehaviour`]
 #[derive(Debug, DeriveError)]
 pub enum BehaviourError {
@@ -95,12 +121,224 @@ pub enum BehaviourError {
     TransportError(String),
 
     /// Error on upgrading the transport with noise authentication
-    #[error("Noise authentic error: `{0}")]
+    #[error("Noise authentic error: `{0}`")]
     NoiseAuthenticError(String),
 
     /// Error creating new mDNS behaviour
     #[error("Mdns error: `{0}`")]
     MdnsError(String),
+
+    /// The wire format was set for other request or response types than the ones of the behaviour
+    #[error("Wire format does not match the request and response types")]
+    WireFormatMismatch,
+
+    /// The limits of the multiplexer are invalid
+    #[error("Invalid multiplex config: `{0}`")]
+    InvalidMultiplexConfig(String),
+
+    /// An entry of the identify metadata can not be encoded in the agent version
+    #[error("Invalid identify metadata: `{0}`")]
+    InvalidIdentifyMetadata(String),
+}
+
+/// Websocket transport that is used in addition to plain TCP.
+#[derive(Clone)]
+pub enum WebsocketConfig {
+    /// Only plain TCP is used.
+    Disabled,
+    /// Listening on and dialing `/ws` addresses is supported. `/wss` addresses can only be dialed, the certificate of
+    /// the remote is verified against the webpki root certificates.
+    Enabled,
+    /// Additionally to the [`WebsocketConfig::Enabled`] transport, the local peer can listen on `/wss` addresses.
+    /// The `private_key` and the certificate chain in `certificates` have to be DER-encoded, and the first
+    /// certificate has to be the end-entity certificate for the `private_key`.
+    /// Loading, storing and renewing the certificates is up to the user.
+    Secure {
+        private_key: Vec<u8>,
+        certificates: Vec<Vec<u8>>,
+    },
+}
+
+// The private key should never be written to logs.
+impl fmt::Debug for WebsocketConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WebsocketConfig::Disabled => write!(f, "Disabled"),
+            WebsocketConfig::Enabled => write!(f, "Enabled"),
+            WebsocketConfig::Secure {
+                private_key: _,
+                certificates,
+            } => f
+                .debug_struct("Secure")
+                .field("certificates", &certificates.len())
+                .finish(),
+        }
+    }
+}
+
+/// Limits of the yamux multiplexer, which are applied to each connection.
+/// If a limit is not specified, the default of yamux is used.
+#[derive(Debug, Clone, Default)]
+pub struct MultiplexConfig {
+    /// Maximum number of concurrent substreams per connection, further inbound substreams are rejected.
+    /// Defaults to 8192.
+    pub max_substreams: Option<usize>,
+    /// Initial receive window of each substream in bytes. A larger window allows the remote to send more data
+    /// without waiting for window updates, which increases the throughput of bulk transfers on high latency links.
+    /// Yamux requires a window of at least [`MIN_RECEIVE_WINDOW_SIZE`], smaller windows are rejected with a
+    /// [`BehaviourError::InvalidMultiplexConfig`] when the swarm is created.
+    /// Defaults to 256 KiB.
+    pub receive_window_size: Option<u32>,
+    /// Maximum number of bytes that are buffered for each substream. It should not be smaller than the receive
+    /// window, otherwise the substream is reset if the remote sends the full window.
+    /// Defaults to 1 MiB.
+    pub max_buffer_size: Option<usize>,
+}
+
+/// Minimum receive window of a substream in bytes, which is the default window of the yamux protocol.
+pub const MIN_RECEIVE_WINDOW_SIZE: u32 = 256 * 1024;
+
+impl MultiplexConfig {
+    // Yamux panics on a receive window below the default window of the protocol, therefore it is validated here.
+    fn yamux_config(&self) -> Result<YamuxConfig, BehaviourError> {
+        let mut yamux_config = YamuxConfig::default();
+        if let Some(max_substreams) = self.max_substreams {
+            yamux_config.set_max_num_streams(max_substreams);
+        }
+        if let Some(receive_window_size) = self.receive_window_size {
+            if receive_window_size < MIN_RECEIVE_WINDOW_SIZE {
+                return Err(BehaviourError::InvalidMultiplexConfig(format!(
+                    "Receive window of {} bytes is smaller than the minimum of {} bytes",
+                    receive_window_size, MIN_RECEIVE_WINDOW_SIZE
+                )));
+            }
+            yamux_config.set_receive_window_size(receive_window_size);
+        }
+        if let Some(max_buffer_size) = self.max_buffer_size {
+            yamux_config.set_max_buffer_size(max_buffer_size);
+        }
+        Ok(yamux_config)
+    }
+}
+
+/// Limits for the number of connections of the swarm. A connection beyond a limit is refused, which is reported as
+/// [`ConnectionLimitReached`](crate::actor::CommunicationEvent::ConnectionLimitReached) by the communication actor.
+/// If a limit is not specified, the number of connections is not limited.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConnectionLimitsConfig {
+    /// Maximum number of inbound connections that are currently being upgraded. Inbound connections beyond this
+    /// limit are dropped by the swarm without reporting them, therefore this limit is not included in the events.
+    pub max_pending_incoming: Option<u32>,
+    /// Maximum number of outbound connections that are currently being dialed and upgraded, e.g. to prevent that a
+    /// storm of dials during a mass reconnect exhausts the ephemeral ports and file descriptors. Dials beyond the
+    /// limit are rejected right away with a `ConnectPeerError::ConnectionLimit`, the current number of dials is
+    /// reported in the `dials_in_progress` metric.
+    pub max_pending_outgoing: Option<u32>,
+    /// Maximum number of established inbound connections.
+    pub max_established_incoming: Option<u32>,
+    /// Maximum number of established outbound connections.
+    pub max_established_outgoing: Option<u32>,
+    /// Maximum number of established connections to a single peer.
+    pub max_established_per_peer: Option<u32>,
+}
+
+/// The limit of the [`ConnectionLimitsConfig`] that was hit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionLimitKind {
+    /// `max_pending_outgoing`
+    PendingOutgoing,
+    /// `max_established_incoming`
+    EstablishedIncoming,
+    /// `max_established_outgoing`
+    EstablishedOutgoing,
+    /// `max_established_per_peer`
+    EstablishedPerPeer,
+}
+
+impl ConnectionLimitsConfig {
+    fn swarm_limits(&self) -> ConnectionLimits {
+        ConnectionLimits::default()
+            .with_max_pending_incoming(self.max_pending_incoming)
+            .with_max_pending_outgoing(self.max_pending_outgoing)
+            .with_max_established_incoming(self.max_established_incoming)
+            .with_max_established_outgoing(self.max_established_outgoing)
+            .with_max_established_per_peer(self.max_established_per_peer)
+    }
+
+    // Determine which limit was hit when an established connection was refused.
+    // The error of libp2p only includes the value of the limit, therefore the limit for the direction is assumed if
+    // it has the same value as the limit per peer.
+    pub(crate) fn established_limit_kind(&self, is_inbound: bool, limit: u32) -> ConnectionLimitKind {
+        let (max_direction, kind) = if is_inbound {
+            (self.max_established_incoming, ConnectionLimitKind::EstablishedIncoming)
+        } else {
+            (self.max_established_outgoing, ConnectionLimitKind::EstablishedOutgoing)
+        };
+        if max_direction != Some(limit) && self.max_established_per_peer == Some(limit) {
+            ConnectionLimitKind::EstablishedPerPeer
+        } else {
+            kind
+        }
+    }
+}
+
+/// OS-level TCP keepalive of the transport sockets, which detects half-open connections, e.g. of a peer that vanished
+/// without closing the connection, by sending probes on an idle socket. The connection is closed by the OS if the
+/// probes are not answered.
+/// This is independent of the `keep_alive` of the [`BehaviourConfig`] and the [`KeepAlive`] of connections in the
+/// communication actor, which determine how long the swarm keeps a connection open while it is not used.
+///
+/// [`KeepAlive`]: crate::actor::KeepAlive
+#[derive(Debug, Clone, Copy)]
+pub struct TcpKeepaliveConfig {
+    /// Duration for which a connection has to be idle before the first probe is sent.
+    pub idle: Duration,
+    /// Interval between unanswered probes. This is only applied on Linux and Android, otherwise and if none is
+    /// specified, the default of the OS is used.
+    pub interval: Option<Duration>,
+    /// Number of unanswered probes after which the connection is closed. This is only applied on Linux and Android,
+    /// otherwise and if none is specified, the default of the OS is used.
+    pub count: Option<u32>,
+}
+
+impl TcpKeepaliveConfig {
+    // Enable the keepalive on the socket. The connection is still usable if it fails, therefore errors are ignored.
+    fn apply(&self, stream: &std::net::TcpStream) {
+        let keepalive = TcpKeepalive::new().with_time(self.idle);
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let keepalive = match self.interval {
+            Some(interval) => keepalive.with_interval(interval),
+            None => keepalive,
+        };
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let keepalive = match self.count {
+            Some(count) => keepalive.with_retries(count),
+            None => keepalive,
+        };
+        let _ = SockRef::from(stream).set_tcp_keepalive(&keepalive);
+    }
+}
+
+/// Resolver of the transport for `/dns`, `/dns4` and `/dns6` addresses.
+#[derive(Debug, Clone)]
+pub enum DnsResolverConfig {
+    /// Use the resolver configuration of the system, e.g. `/etc/resolv.conf` on unix.
+    System,
+    /// Only query the nameservers on the addresses, independently of the configuration of the system. Each nameserver
+    /// is queried via UDP, with TCP as fallback for large responses.
+    Custom { nameservers: Vec<SocketAddr> },
+}
+
+impl DnsResolverConfig {
+    fn custom_resolver(nameservers: &[SocketAddr]) -> ResolverConfig {
+        let mut resolver_config = ResolverConfig::new();
+        for addr in nameservers {
+            for nameserver in NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true).iter() {
+                resolver_config.add_name_server(nameserver.clone());
+            }
+        }
+        resolver_config
+    }
 }
 
 /// Configuration for initiating the [`P2PNetworkBehaviour`].
@@ -116,6 +354,47 @@ pub struct BehaviourConfig {
     mdns_ttl: Option<Duration>,
     /// Frequency for new peers via mDNS
     mdns_query_interval: Option<Duration>,
+    /// Name of the service to which the peers discovered via mDNS are scoped.
+    /// If none is specified, all peers discovered via mDNS are used.
+    mdns_service_name: Option<String>,
+    /// Websocket transport in addition to TCP.
+    /// If none is specified, it defaults to [`WebsocketConfig::Enabled`].
+    websocket: Option<WebsocketConfig>,
+    /// Supported versions of the request-response protocol, ordered from newest to oldest.
+    /// If none is specified, it defaults to [`DEFAULT_PROTOCOL`].
+    protocol_versions: Option<Vec<String>>,
+    /// Limits of the multiplexer on each connection.
+    /// If none is specified, the defaults of yamux are used.
+    multiplex: Option<MultiplexConfig>,
+    /// Enable the ping protocol for probing the reachability and round-trip time of peers.
+    probes: bool,
+    /// Timeout for negotiating the security and multiplexing protocols on a new connection.
+    /// If none is specified, it defaults to 20s.
+    handshake_timeout: Option<Duration>,
+    /// Encoding of the requests and responses on the wire, as `Arc<dyn WireFormat<Req, Res>>` for the message types
+    /// with which it was set.
+    /// If none is specified, the messages are encoded as JSON.
+    wire_format: Option<Arc<dyn Any + Send + Sync>>,
+    /// Resolver for dns addresses.
+    /// If none is specified, it defaults to [`DnsResolverConfig::System`].
+    dns_resolver: Option<DnsResolverConfig>,
+    /// OS-level keepalive of the TCP sockets.
+    /// If none is specified, the keepalive is not enabled.
+    tcp_keepalive: Option<TcpKeepaliveConfig>,
+    /// Max size in bytes of a single request or response that is received from a remote peer.
+    /// If none is specified, the size of messages is not limited.
+    max_message_size: Option<usize>,
+    /// Limits for the number of connections of the swarm.
+    /// If none are specified, the number of connections is not limited.
+    connection_limits: ConnectionLimitsConfig,
+    /// Agent version that is advertised via the identify protocol.
+    /// If none is specified, it defaults to [`DEFAULT_AGENT_VERSION`].
+    agent_version: Option<String>,
+    /// Version of the protocol family that is advertised via the identify protocol.
+    /// If none is specified, it defaults to [`DEFAULT_IDENTIFY_PROTOCOL_VERSION`].
+    identify_protocol_version: Option<String>,
+    /// Metadata that is advertised as part of the agent version.
+    identify_metadata: BTreeMap<String, String>,
 }
 
 impl BehaviourConfig {
@@ -130,8 +409,179 @@ impl BehaviourConfig {
             keep_alive,
             mdns_ttl,
             mdns_query_interval,
+            mdns_service_name: None,
+            websocket: None,
+            protocol_versions: None,
+            multiplex: None,
+            probes: false,
+            handshake_timeout: None,
+            wire_format: None,
+            dns_resolver: None,
+            tcp_keepalive: None,
+            max_message_size: None,
+            connection_limits: ConnectionLimitsConfig::default(),
+            agent_version: None,
+            identify_protocol_version: None,
+            identify_metadata: BTreeMap::new(),
+        }
+    }
+
+    /// Set the TTL of the mDNS records that announce the local peer, after which peers that are not announced again
+    /// expire. This has no effect if the `mdns` feature is disabled.
+    pub fn set_mdns_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.mdns_ttl = Some(ttl);
+        self
+    }
+
+    /// Set the interval in which mDNS queries for new peers are sent. This has no effect if the `mdns` feature is
+    /// disabled.
+    pub fn set_mdns_query_interval(&mut self, query_interval: Duration) -> &mut Self {
+        self.mdns_query_interval = Some(query_interval);
+        self
+    }
+
+    /// Set the name of the service to which the peer discovery via mDNS is scoped. This has no effect if the `mdns`
+    /// feature is disabled.
+    ///
+    /// The name of the mDNS queries is fixed to `_p2p._udp.local` by libp2p, therefore the service name is advertised
+    /// as [`MDNS_SERVICE_KEY`] entry in the identify metadata instead. A peer that was discovered via mDNS is dialed
+    /// and only added to the known peers and [`P2PNetworkBehaviour::get_active_mdns_peers`] once it advertised the
+    /// same service name. Peers with a different or without a service name are ignored, and the connection that was
+    /// established to receive their identify info is closed by the communication actor, or has to be closed by the
+    /// owner of the swarm after [`P2PNetworkBehaviour::take_mdns_mismatches`]. A peer is checked again once it was
+    /// rediscovered after its mDNS record expired.
+    /// The name must not contain any of the characters `(`, `)`, `;` and `=`.
+    pub fn set_mdns_service_name(&mut self, service_name: String) -> &mut Self {
+        self.mdns_service_name = Some(service_name);
+        self
+    }
+
+    /// Set the websocket transport that is used in addition to TCP.
+    pub fn set_websocket(&mut self, websocket: WebsocketConfig) -> &mut Self {
+        self.websocket = Some(websocket);
+        self
+    }
+
+    /// Set the supported versions of the request-response protocol, ordered from newest to oldest, e.g. to support
+    /// both the old and the new version of the requests during a rolling upgrade.
+    /// Inbound requests are accepted on any of the versions. For outbound requests the protocol is negotiated with
+    /// the remote peer, which selects the first version of the list that it supports.
+    pub fn set_protocol_versions(&mut self, protocol_versions: Vec<String>) -> &mut Self {
+        self.protocol_versions = Some(protocol_versions);
+        self
+    }
+
+    // The configured versions of the request-response protocol, or the default protocol if none were set.
+    pub(crate) fn protocol_names(&self) -> Vec<String> {
+        match &self.protocol_versions {
+            Some(versions) if !versions.is_empty() => versions.clone(),
+            _ => vec![DEFAULT_PROTOCOL.to_string()],
+        }
+    }
+
+    /// Set the limits of the multiplexer on each connection, e.g. to bound the memory that is used by concurrent
+    /// substreams.
+    pub fn set_multiplex(&mut self, multiplex: MultiplexConfig) -> &mut Self {
+        self.multiplex = Some(multiplex);
+        self
+    }
+
+    /// Enable or disable probes, which use the libp2p ping protocol independently of the request-response protocol
+    /// and therefore of the firewall. Probes are only answered and sent if they are enabled, and the remote peer has
+    /// to enable them as well.
+    /// With probes enabled, each connection is pinged every 15s, without keeping the connection alive.
+    pub fn set_probes(&mut self, enabled: bool) -> &mut Self {
+        self.probes = enabled;
+        self
+    }
+
+    /// Set a custom encoding of the requests and responses on the wire, e.g. to match the byte layout of an
+    /// externally specified protocol. Both peers have to use the same format.
+    /// The types have to match the messages of the [`P2PNetworkBehaviour`], which are the
+    /// [`RequestEnvelope`]s of the requests for the [`CommunicationActor`](crate::actor::CommunicationActor),
+    /// otherwise initiating the swarm fails with [`BehaviourError::WireFormatMismatch`].
+    pub fn set_wire_format<Req: 'static, Res: 'static>(&mut self, format: Arc<dyn WireFormat<Req, Res>>) -> &mut Self {
+        self.wire_format = Some(Arc::new(format));
+        self
+    }
+
+    /// Set the resolver for `/dns`, `/dns4` and `/dns6` addresses, e.g. to use specific nameservers if the resolver of
+    /// the system is not configured correctly.
+    pub fn set_dns_resolver(&mut self, dns_resolver: DnsResolverConfig) -> &mut Self {
+        self.dns_resolver = Some(dns_resolver);
+        self
+    }
+
+    /// Enable the OS-level keepalive on the TCP sockets of all connections, including websocket connections, so that
+    /// connections to peers that vanished without closing them are detected and closed.
+    pub fn set_tcp_keepalive(&mut self, tcp_keepalive: TcpKeepaliveConfig) -> &mut Self {
+        self.tcp_keepalive = Some(tcp_keepalive);
+        self
+    }
+
+    /// Set the max size in bytes of requests and responses that are received from remote peers.
+    /// The length prefix of a message is checked before the message is read, so that a peer can not make the codec
+    /// buffer a huge message by declaring a large length. On violation the substream is dropped without reading the
+    /// message, and the connection to the peer is closed with a [`MessageTooLarge`] error.
+    pub fn set_max_message_size(&mut self, max_message_size: usize) -> &mut Self {
+        self.max_message_size = Some(max_message_size);
+        self
+    }
+
+    /// Set the limits for the number of pending and established connections of the swarm.
+    pub fn set_connection_limits(&mut self, connection_limits: ConnectionLimitsConfig) -> &mut Self {
+        self.connection_limits = connection_limits;
+        self
+    }
+
+    pub(crate) fn connection_limits(&self) -> ConnectionLimitsConfig {
+        self.connection_limits
+    }
+
+    /// Set the agent version that is advertised to remote peers via the identify protocol, e.g. the name and build of
+    /// the application, so that the operators of remote peers can identify the local node.
+    pub fn set_agent_version(&mut self, agent_version: String) -> &mut Self {
+        self.agent_version = Some(agent_version);
+        self
+    }
+
+    /// Set the version of the protocol family that is advertised via the identify protocol.
+    pub fn set_identify_protocol_version(&mut self, protocol_version: String) -> &mut Self {
+        self.identify_protocol_version = Some(protocol_version);
+        self
+    }
+
+    /// Set key-value metadata that is advertised via the identify protocol.
+    /// Identify has no field for custom data, so the entries are appended to the agent version in the format
+    /// `<agent version> (key=value; key=value)`, and can be read from the agent version of a remote peer with
+    /// [`parse_agent_version`]. Entries with an empty key, or with a key or value that contains one of the characters
+    /// `(`, `)`, `;` and `=`, are rejected with [`BehaviourError::InvalidIdentifyMetadata`] when the swarm is created.
+    pub fn set_identify_metadata(&mut self, metadata: BTreeMap<String, String>) -> &mut Self {
+        self.identify_metadata = metadata;
+        self
+    }
+
+    // The transports that the swarm is created with.
+    pub(crate) fn transport_support(&self) -> TransportSupport {
+        let (websocket, secure_websocket_listen) = match &self.websocket {
+            Some(WebsocketConfig::Disabled) => (false, false),
+            None | Some(WebsocketConfig::Enabled) => (true, false),
+            Some(WebsocketConfig::Secure { .. }) => (true, true),
+        };
+        TransportSupport {
+            websocket,
+            secure_websocket_listen,
+            unix: cfg!(all(feature = "uds", unix)),
         }
     }
+
+    /// Set the timeout for the noise handshake and the negotiation of the multiplexer on new inbound and outbound
+    /// connections. Connections that don't complete the upgrade within the timeout are dropped, so that a remote
+    /// can not tie up resources by opening connections and stalling the handshake.
+    pub fn set_handshake_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.handshake_timeout = Some(timeout);
+        self
+    }
 }
 
 impl Default for BehaviourConfig {
@@ -141,6 +591,20 @@ impl Default for BehaviourConfig {
             keep_alive: None,
             mdns_ttl: None,
             mdns_query_interval: None,
+            mdns_service_name: None,
+            websocket: None,
+            protocol_versions: None,
+            multiplex: None,
+            probes: false,
+            handshake_timeout: None,
+            wire_format: None,
+            dns_resolver: None,
+            tcp_keepalive: None,
+            max_message_size: None,
+            connection_limits: ConnectionLimitsConfig::default(),
+            agent_version: None,
+            identify_protocol_version: None,
+            identify_metadata: BTreeMap::new(),
         }
     }
 }
@@ -150,6 +614,7 @@ impl Default for BehaviourConfig {
 /// - mDNS for peer discovery within the local network
 /// - identify-protocol to receive identifying information of the remote peer
 /// - RequestResponse Protocol for sending generic request `Req` and response `Res` messages
+/// - optionally the ping-protocol for probing the round-trip time to a remote peer
 ///
 /// The P2PNetworkBehaviour itself is only effective if a new [`ExpandedSwarm`] is created for it, this
 /// swarm is the entry point for all communication to remote peers, and contains the current state.
@@ -163,12 +628,40 @@ pub struct P2PNetworkBehaviour<Req: MessageEvent, Res: MessageEvent> {
     mdns: Mdns,
     identify: Identify,
     msg_proto: RequestResponse<MessageCodec<Req, Res>>,
+    ping: Toggle<Ping>,
+    #[behaviour(ignore)]
+    probes: bool,
     #[behaviour(ignore)]
     peers: HashMap<PeerId, Vec<Multiaddr>>,
     #[behaviour(ignore)]
     events: Vec<P2PEvent<Req, Res>>,
     #[behaviour(ignore)]
-    response_channels: HashMap<RequestId, ResponseChannel<Res>>,
+    response_channels: HashMap<RequestId, ResponseChannel<MessageResponse<Res>>>,
+    // service name to which the peers discovered via mDNS are scoped
+    #[cfg(feature = "mdns")]
+    #[behaviour(ignore)]
+    mdns_service_name: Option<String>,
+    // peers discovered via mDNS that did not advertise their service name yet, with their discovered addresses
+    #[cfg(feature = "mdns")]
+    #[behaviour(ignore)]
+    mdns_candidates: HashMap<PeerId, Vec<Multiaddr>>,
+    // peers discovered via mDNS that advertised the same service name
+    #[cfg(feature = "mdns")]
+    #[behaviour(ignore)]
+    mdns_peers: HashSet<PeerId>,
+    // candidates that still have to be dialed to receive their identify info
+    #[cfg(feature = "mdns")]
+    #[behaviour(ignore)]
+    mdns_dials: Vec<PeerId>,
+    // whether the peers discovered via mDNS advertised the same service name in their identify info, the entries are
+    // removed once the peer expired so that it is checked again on the next discovery
+    #[cfg(feature = "mdns")]
+    #[behaviour(ignore)]
+    identified_services: HashMap<PeerId, bool>,
+    // candidates that advertised a different service name, whose connections have to be closed by the swarm
+    #[cfg(feature = "mdns")]
+    #[behaviour(ignore)]
+    mdns_mismatches: Vec<PeerId>,
 }
 
 impl<Req: MessageEvent, Res: MessageEvent> P2PNetworkBehaviour<Req, Res> {
@@ -206,24 +699,71 @@ impl<Req: MessageEvent, Res: MessageEvent> P2PNetworkBehaviour<Req, Res> {
         config: BehaviourConfig,
     ) -> Result<Swarm<P2PNetworkBehaviour<Req, Res>>, BehaviourError> {
         let local_peer_id = PeerId::from(local_keys.public());
+        let protocols: Vec<_> = config
+            .protocol_names()
+            .into_iter()
+            .map(|name| (MessageProtocol::new(name), ProtocolSupport::Full))
+            .collect();
 
         let noise_keys = noise::Keypair::<noise::X25519Spec>::new()
             .into_authentic(&local_keys)
             .map_err(|e| BehaviourError::NoiseAuthenticError(format!("Could not create authentic keypair: {:?}", e)))?;
         // Use XX handshake pattern
         let noise = NoiseConfig::xx(noise_keys).into_authenticated();
-        // Tcp layer with wrapper to resolve dns addresses
-        let dns_transport = DnsConfig::system(TcpConfig::new())
-            .await
-            .map_err(|e| BehaviourError::TransportError(format!("Could not create transport: {:?}", e)))?;
+        // Tcp layer with optional keepalive on the sockets, and a wrapper to resolve dns addresses
+        let tcp_keepalive = config.tcp_keepalive;
+        let tcp_transport = TcpConfig::new().map(move |stream, _| {
+            if let Some(tcp_keepalive) = tcp_keepalive {
+                tcp_keepalive.apply(stream.get_ref());
+            }
+            stream
+        });
+        let dns_transport = match config.dns_resolver.unwrap_or(DnsResolverConfig::System) {
+            DnsResolverConfig::System => DnsConfig::system(tcp_transport).await,
+            DnsResolverConfig::Custom { nameservers } => {
+                let resolver_config = DnsResolverConfig::custom_resolver(&nameservers);
+                DnsConfig::custom(tcp_transport, resolver_config, ResolverOpts::default()).await
+            }
+        }
+        .map_err(|e| BehaviourError::TransportError(format!("Could not create transport: {:?}", e)))?;
+        // Optional websocket transport on top of tcp, with tls if a certificate for listening on `/wss` was provided
+        let ws_transport = match config.websocket.unwrap_or(WebsocketConfig::Enabled) {
+            WebsocketConfig::Disabled => OptionalTransport::none(),
+            WebsocketConfig::Enabled => OptionalTransport::some(WsConfig::new(dns_transport.clone())),
+            WebsocketConfig::Secure {
+                private_key,
+                certificates,
+            } => {
+                let tls_config = tls::Config::new(
+                    tls::PrivateKey::new(private_key),
+                    certificates.into_iter().map(tls::Certificate::new),
+                )
+                .map_err(|e| BehaviourError::TransportError(format!("Invalid tls configuration: {:?}", e)))?;
+                let mut ws_config = WsConfig::new(dns_transport.clone());
+                ws_config.set_tls_config(tls_config);
+                OptionalTransport::some(ws_config)
+            }
+        };
+        let yamux_config = config.multiplex.unwrap_or_default().yamux_config()?;
+        if let Some((key, value)) = config
+            .identify_metadata
+            .iter()
+            .find(|(key, value)| !is_valid_entry(key, value))
+        {
+            return Err(BehaviourError::InvalidIdentifyMetadata(format!("{}={}", key, value)));
+        }
+        let transport = dns_transport.or_transport(ws_transport);
+        // Unix domain sockets for local-only communication between processes on the same host, via `/unix` addresses
+        #[cfg(all(feature = "uds", unix))]
+        let transport = transport.or_transport(UdsConfig::new());
         // The configured transport establishes connections via tcp with websockets as fallback, and
-        // negotiates authentification and multiplexing on all connections
-        let transport = dns_transport
-            .clone()
-            .or_transport(WsConfig::new(dns_transport))
+        // negotiates authentification and multiplexing on all connections, connections that are not upgraded within
+        // the handshake timeout are dropped
+        let transport = transport
             .upgrade(upgrade::Version::V1)
             .authenticate(noise)
-            .multiplex(YamuxConfig::default())
+            .multiplex(yamux_config)
+            .timeout(config.handshake_timeout.unwrap_or(DEFAULT_HANDSHAKE_TIMEOUT))
             .boxed();
 
         // multicast DNS for peer discovery within a local network
@@ -241,12 +781,37 @@ impl<Req: MessageEvent, Res: MessageEvent> P2PNetworkBehaviour<Req, Res> {
                 .map_err(|e| BehaviourError::MdnsError(e.to_string()))
         }?;
         // Identify protocol to receive identifying information of a remote peer once a connection
-        // was established
+        // was established, the mDNS service name is advertised as part of the metadata
+        let mut identify_metadata = config.identify_metadata;
+        if let Some(service_name) = config.mdns_service_name.as_ref() {
+            if !is_valid_entry(MDNS_SERVICE_KEY, service_name) {
+                return Err(BehaviourError::MdnsError(format!(
+                    "Invalid mDNS service name: `{}`",
+                    service_name
+                )));
+            }
+            identify_metadata.insert(MDNS_SERVICE_KEY.to_string(), service_name.clone());
+        }
+        let agent_version = encode_agent_version(
+            config.agent_version.as_deref().unwrap_or(DEFAULT_AGENT_VERSION),
+            &identify_metadata,
+        );
         let identify = Identify::new(
-            "/identify/0.1.0".into(),
-            "stronghold-communication".into(),
+            config
+                .identify_protocol_version
+                .unwrap_or_else(|| DEFAULT_IDENTIFY_PROTOCOL_VERSION.into()),
+            agent_version,
             local_keys.public(),
         );
+        let wire_format = match config.wire_format {
+            Some(format) => {
+                let format = format
+                    .downcast::<Arc<dyn WireFormat<Req, Res>>>()
+                    .map_err(|_| BehaviourError::WireFormatMismatch)?;
+                Some(Arc::clone(&*format))
+            }
+            None => None,
+        };
         // Enable Request- and Response-Messages with the generic MessageProtocol
         let msg_proto = {
             let mut cfg = RequestResponseConfig::default();
@@ -256,8 +821,20 @@ impl<Req: MessageEvent, Res: MessageEvent> P2PNetworkBehaviour<Req, Res> {
             if let Some(keep_alive) = config.keep_alive {
                 cfg.set_connection_keep_alive(keep_alive);
             }
-            let protocols = iter::once((MessageProtocol(), ProtocolSupport::Full));
-            RequestResponse::new(MessageCodec::<Req, Res>::default(), protocols, cfg)
+            RequestResponse::new(
+                MessageCodec::<Req, Res>::new(wire_format, config.max_message_size),
+                protocols,
+                cfg,
+            )
+        };
+
+        // Optional ping protocol for probes. Failed pings don't close the connection, since the remote peer may not
+        // have enabled probes.
+        let ping = if config.probes {
+            let ping_config = PingConfig::new().with_max_failures(NonZeroU32::new(u32::MAX).unwrap());
+            Some(Ping::new(ping_config))
+        } else {
+            None
         };
 
         // The behaviour describes how the swarm handles events enables interacting with the
@@ -267,14 +844,33 @@ impl<Req: MessageEvent, Res: MessageEvent> P2PNetworkBehaviour<Req, Res> {
             mdns,
             msg_proto,
             identify,
+            ping: Toggle::from(ping),
+            probes: config.probes,
             peers: HashMap::new(),
             events: Vec::new(),
             response_channels: HashMap::new(),
+            #[cfg(feature = "mdns")]
+            mdns_service_name: config.mdns_service_name,
+            #[cfg(feature = "mdns")]
+            mdns_candidates: HashMap::new(),
+            #[cfg(feature = "mdns")]
+            mdns_peers: HashSet::new(),
+            #[cfg(feature = "mdns")]
+            mdns_dials: Vec::new(),
+            #[cfg(feature = "mdns")]
+            identified_services: HashMap::new(),
+            #[cfg(feature = "mdns")]
+            mdns_mismatches: Vec::new(),
         };
 
         // The swarm manages a pool of connections established through the transport and drives the
         // NetworkBehaviour through emitting events triggered by activity on the managed connections.
-        Ok(Swarm::new(transport, behaviour, local_peer_id))
+        // The swarm of libp2p 0.36 dials the known addresses of a peer one after another, the number of addresses
+        // that are dialed concurrently can not be configured on the `SwarmBuilder` yet.
+        let swarm = SwarmBuilder::new(transport, behaviour, local_peer_id)
+            .connection_limits(config.connection_limits.swarm_limits())
+            .build();
+        Ok(swarm)
     }
 
     // Custom function that is called when the swarm is polled
@@ -286,27 +882,50 @@ impl<Req: MessageEvent, Res: MessageEvent> P2PNetworkBehaviour<Req, Res> {
         if !self.events.is_empty() {
             return Poll::Ready(NetworkBehaviourAction::GenerateEvent(self.events.remove(0)));
         }
+        // Dial the mDNS candidates, their addresses are provided by the mDNS behaviour
+        #[cfg(feature = "mdns")]
+        if let Some(peer_id) = self.mdns_dials.pop() {
+            return Poll::Ready(NetworkBehaviourAction::DialPeer {
+                peer_id,
+                condition: DialPeerCondition::Disconnected,
+            });
+        }
         Poll::Pending
     }
 
+    /// Add an address for a peer.
+    /// The known addresses are used when the peer is dialed by its id, e.g. when sending a request.
     pub fn add_peer_addr(&mut self, peer_id: PeerId, addr: Multiaddr) {
         if let Some(addrs) = self.peers.get_mut(&peer_id) {
             if !addrs.contains(&addr) {
-                addrs.push(addr);
+                addrs.push(addr.clone());
             }
         } else {
-            self.peers.insert(peer_id, vec![addr]);
+            self.peers.insert(peer_id, vec![addr.clone()]);
         }
+        self.msg_proto.add_address(&peer_id, addr);
     }
 
     pub fn remove_peer_addr(&mut self, peer_id: &PeerId, addr: &Multiaddr) {
         if let Some(addrs) = self.peers.get_mut(peer_id) {
             addrs.retain(|a| a != addr);
         }
+        self.msg_proto.remove_address(peer_id, addr);
     }
 
     pub fn remove_peer(&mut self, peer_id: &PeerId) -> Option<Vec<Multiaddr>> {
-        self.peers.remove(peer_id)
+        let addrs = self.peers.remove(peer_id);
+        if let Some(addrs) = addrs.as_ref() {
+            for addr in addrs {
+                self.msg_proto.remove_address(peer_id, addr);
+            }
+        }
+        addrs
+    }
+
+    /// Check if probes are enabled, which is configured in the [`BehaviourConfig`].
+    pub fn is_probing_enabled(&self) -> bool {
+        self.probes
     }
 
     pub fn get_peer_addr(&self, peer_id: &PeerId) -> Option<&Vec<Multiaddr>> {
@@ -318,9 +937,32 @@ impl<Req: MessageEvent, Res: MessageEvent> P2PNetworkBehaviour<Req, Res> {
     }
 
     #[cfg(feature = "mdns")]
-    /// Get the peers discovered by mdns
+    /// Get the peers discovered by mdns.
+    /// If a service name was set with [`BehaviourConfig::set_mdns_service_name`], only the peers that advertised the
+    /// same service name are returned.
     pub fn get_active_mdns_peers(&mut self) -> Vec<&PeerId> {
-        self.mdns.discovered_nodes().collect()
+        let mdns_peers = &self.mdns_peers;
+        let scoped = self.mdns_service_name.is_some();
+        self.mdns
+            .discovered_nodes()
+            .filter(|peer_id| !scoped || mdns_peers.contains(peer_id))
+            .collect()
+    }
+
+    #[cfg(feature = "mdns")]
+    /// Take the peers that were discovered via mDNS and dialed, but advertised a different service name than the one
+    /// set with [`BehaviourConfig::set_mdns_service_name`].
+    /// The behaviour can not close connections itself, the connections to these peers should be closed by the owner
+    /// of the swarm, e.g. by banning and directly unbanning the peer.
+    pub fn take_mdns_mismatches(&mut self) -> Vec<PeerId> {
+        self.mdns_mismatches.drain(..).collect()
+    }
+
+    // Check if the agent version that a peer advertised via identify contains the local mDNS service name.
+    #[cfg(feature = "mdns")]
+    fn is_same_mdns_service(&self, agent_version: &str) -> bool {
+        let (_, metadata) = parse_agent_version(agent_version);
+        metadata.get(MDNS_SERVICE_KEY) == self.mdns_service_name.as_ref()
     }
 
     pub fn send_request(&mut self, peer_id: &PeerId, request: Req) -> RequestId {
@@ -332,7 +974,26 @@ impl<Req: MessageEvent, Res: MessageEvent> P2PNetworkBehaviour<Req, Res> {
             .response_channels
             .remove(&request_id)
             .ok_or_else(|| response.clone())?;
-        self.msg_proto.send_response(channel, response)
+        self.msg_proto
+            .send_response(channel, MessageResponse::Response(response))
+            .map_err(|res| match res {
+                MessageResponse::Response(response) => response,
+                MessageResponse::Rejected(_) => unreachable!(),
+            })
+    }
+
+    /// Respond to an inbound request with an explicit rejection, which fails the request on the remote with a
+    /// [`P2POutboundFailure::Rejected`] with the reason.
+    pub fn send_rejection(&mut self, request_id: RequestId, reason: RejectReason) {
+        if let Some(channel) = self.response_channels.remove(&request_id) {
+            let _ = self.msg_proto.send_response(channel, MessageResponse::Rejected(reason));
+        }
+    }
+
+    /// Drop the response channel of an inbound request without responding, so that the stream is closed and the
+    /// remote fails right away instead of waiting for the timeout.
+    pub fn reject_request(&mut self, request_id: RequestId) {
+        self.response_channels.remove(&request_id);
     }
 }
 
@@ -343,23 +1004,55 @@ impl<Req: MessageEvent, Res: MessageEvent> NetworkBehaviourEventProcess<MdnsEven
         match event {
             MdnsEvent::Discovered(list) => {
                 for (peer_id, multiaddr) in list {
-                    self.add_peer_addr(peer_id, multiaddr);
+                    if self.mdns_service_name.is_none() {
+                        self.add_peer_addr(peer_id, multiaddr);
+                        continue;
+                    }
+                    // Peers are only added once they advertised the same service name via identify
+                    match self.identified_services.get(&peer_id) {
+                        Some(true) => {
+                            self.mdns_peers.insert(peer_id);
+                            self.add_peer_addr(peer_id, multiaddr);
+                        }
+                        Some(false) => {}
+                        None => {
+                            // Dialed again on each discovery, in case that a previous dial failed
+                            if !self.mdns_dials.contains(&peer_id) {
+                                self.mdns_dials.push(peer_id);
+                            }
+                            let addrs = self.mdns_candidates.entry(peer_id).or_default();
+                            if !addrs.contains(&multiaddr) {
+                                addrs.push(multiaddr);
+                            }
+                        }
+                    }
                 }
             }
             MdnsEvent::Expired(list) => {
                 for (peer_id, multiaddr) in list {
-                    self.remove_peer_addr(&peer_id, &multiaddr);
+                    if let Some(addrs) = self.mdns_candidates.get_mut(&peer_id) {
+                        addrs.retain(|a| a != &multiaddr);
+                        if addrs.is_empty() {
+                            self.mdns_candidates.remove(&peer_id);
+                        }
+                    } else {
+                        self.remove_peer_addr(&peer_id, &multiaddr);
+                    }
+                    if !self.mdns.has_node(&peer_id) {
+                        self.mdns_peers.remove(&peer_id);
+                        self.identified_services.remove(&peer_id);
+                    }
                 }
             }
         }
     }
 }
 
-impl<Req: MessageEvent, Res: MessageEvent> NetworkBehaviourEventProcess<RequestResponseEvent<Req, Res>>
+impl<Req: MessageEvent, Res: MessageEvent> NetworkBehaviourEventProcess<RequestResponseEvent<Req, MessageResponse<Res>>>
     for P2PNetworkBehaviour<Req, Res>
 {
     // Called when a request or response was received.
-    fn inject_event(&mut self, event: RequestResponseEvent<Req, Res>) {
+    fn inject_event(&mut self, event: RequestResponseEvent<Req, MessageResponse<Res>>) {
         let communication_event = if let RequestResponseEvent::Message {
             peer,
             message:
@@ -383,6 +1076,13 @@ impl<Req: MessageEvent, Res: MessageEvent> NetworkBehaviourEventProcess<RequestR
     }
 }
 
+impl<Req: MessageEvent, Res: MessageEvent> NetworkBehaviourEventProcess<PingEvent> for P2PNetworkBehaviour<Req, Res> {
+    // Called when `ping` produces an event.
+    fn inject_event(&mut self, event: PingEvent) {
+        self.events.push(P2PEvent::from(event));
+    }
+}
+
 impl<Req: MessageEvent, Res: MessageEvent> NetworkBehaviourEventProcess<IdentifyEvent>
     for P2PNetworkBehaviour<Req, Res>
 {
@@ -394,6 +1094,29 @@ impl<Req: MessageEvent, Res: MessageEvent> NetworkBehaviourEventProcess<Identify
             observed_addr: _,
         } = event
         {
+            // Candidates discovered via mDNS are either accepted with their discovered addresses, or ignored and
+            // disconnected if they advertised a different service name
+            #[cfg(feature = "mdns")]
+            if self.mdns_service_name.is_some() {
+                let candidate_addrs = self.mdns_candidates.remove(&peer_id);
+                if candidate_addrs.is_some() || self.mdns.has_node(&peer_id) {
+                    let is_same_service = self.is_same_mdns_service(&info.agent_version);
+                    self.identified_services.insert(peer_id, is_same_service);
+                    if !is_same_service {
+                        self.mdns_peers.remove(&peer_id);
+                        if candidate_addrs.is_some() {
+                            self.mdns_mismatches.push(peer_id);
+                            self.events.push(P2PEvent::from(event));
+                            return;
+                        }
+                    } else if let Some(addrs) = candidate_addrs {
+                        self.mdns_peers.insert(peer_id);
+                        for addr in addrs {
+                            self.add_peer_addr(peer_id, addr);
+                        }
+                    }
+                }
+            }
             if self.get_peer_addr(&peer_id).is_none() {
                 for addr in &info.listen_addrs {
                     self.add_peer_addr(peer_id, addr.clone());
@@ -0,0 +1,246 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use libp2p::core::{multiaddr::Protocol, Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+
+/// Transport that is used for an address, as determined by [`addr_transport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AddrTransport {
+    /// Plain TCP, e.g. `/ip4/127.0.0.1/tcp/8080` or `/dns/example.com/tcp/8080`.
+    Tcp,
+    /// Websocket on top of TCP, e.g. `/ip4/127.0.0.1/tcp/8080/ws`.
+    Websocket,
+    /// Websocket with TLS on top of TCP, e.g. `/dns/example.com/tcp/443/wss`.
+    SecureWebsocket,
+    /// Unix domain socket, e.g. `/unix/stronghold.sock`.
+    Unix,
+}
+
+/// Create the TCP [`Multiaddr`] for a socket address, e.g. `/ip4/127.0.0.1/tcp/8080` for `127.0.0.1:8080`.
+/// A `(IpAddr, u16)` tuple can be converted with `SocketAddr::from`.
+pub fn socket_addr_to_multiaddr(addr: SocketAddr) -> Multiaddr {
+    let ip = match addr.ip() {
+        IpAddr::V4(ip) => Protocol::Ip4(ip),
+        IpAddr::V6(ip) => Protocol::Ip6(ip),
+    };
+    Multiaddr::empty().with(ip).with(Protocol::Tcp(addr.port()))
+}
+
+/// Extract the socket address from a TCP [`Multiaddr`], which may be suffixed with the `/p2p` peer id.
+/// Returns `None` for any other address, e.g. for DNS, websocket or relayed addresses.
+pub fn multiaddr_to_socket_addr(addr: &Multiaddr) -> Option<SocketAddr> {
+    let mut iter = addr.iter();
+    let ip = match iter.next()? {
+        Protocol::Ip4(ip) => IpAddr::V4(ip),
+        Protocol::Ip6(ip) => IpAddr::V6(ip),
+        _ => return None,
+    };
+    let port = match iter.next()? {
+        Protocol::Tcp(port) => port,
+        _ => return None,
+    };
+    match (iter.next(), iter.next()) {
+        (None, _) | (Some(Protocol::P2p(_)), None) => Some(SocketAddr::new(ip, port)),
+        _ => None,
+    }
+}
+
+/// Collect the TCP ports of the addresses, e.g. of the listeners of the swarm, without duplicates and in ascending
+/// order. Websocket addresses are included, since they are served on top of TCP.
+pub fn tcp_ports<'a>(addrs: impl IntoIterator<Item = &'a Multiaddr>) -> Vec<u16> {
+    let mut ports: Vec<u16> = addrs
+        .into_iter()
+        .filter_map(|addr| {
+            addr.iter().find_map(|protocol| match protocol {
+                Protocol::Tcp(port) => Some(port),
+                _ => None,
+            })
+        })
+        .collect();
+    ports.sort_unstable();
+    ports.dedup();
+    ports
+}
+
+/// Determine the transport of the address, which may be suffixed with the `/p2p` peer id.
+/// Returns `None` if the address does not match any of the transports of the swarm, e.g. for UDP or relayed
+/// addresses.
+pub fn addr_transport(addr: &Multiaddr) -> Option<AddrTransport> {
+    let mut iter = addr.iter();
+    let transport = match iter.next()? {
+        Protocol::Unix(_) => AddrTransport::Unix,
+        Protocol::Ip4(_) | Protocol::Ip6(_) | Protocol::Dns(_) | Protocol::Dns4(_) | Protocol::Dns6(_) => {
+            if !matches!(iter.next()?, Protocol::Tcp(_)) {
+                return None;
+            }
+            match addr.iter().nth(2) {
+                Some(Protocol::Ws(_)) => {
+                    iter.next();
+                    AddrTransport::Websocket
+                }
+                Some(Protocol::Wss(_)) => {
+                    iter.next();
+                    AddrTransport::SecureWebsocket
+                }
+                _ => AddrTransport::Tcp,
+            }
+        }
+        _ => return None,
+    };
+    match (iter.next(), iter.next()) {
+        (None, _) | (Some(Protocol::P2p(_)), None) => Some(transport),
+        _ => None,
+    }
+}
+
+/// Extract the peer id of a `/p2p` protocol at the end of the address.
+pub fn addr_peer_id(addr: &Multiaddr) -> Option<PeerId> {
+    match addr.iter().last()? {
+        Protocol::P2p(hash) => PeerId::from_multihash(hash).ok(),
+        _ => None,
+    }
+}
+
+// Transports that are enabled on the swarm, to check if an address can be dialed or listened on.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TransportSupport {
+    pub websocket: bool,
+    pub secure_websocket_listen: bool,
+    pub unix: bool,
+}
+
+impl TransportSupport {
+    pub fn can_dial(&self, addr: &Multiaddr) -> bool {
+        match addr_transport(addr) {
+            Some(AddrTransport::Tcp) => true,
+            Some(AddrTransport::Websocket) | Some(AddrTransport::SecureWebsocket) => self.websocket,
+            Some(AddrTransport::Unix) => self.unix,
+            None => false,
+        }
+    }
+
+    // Listening requires an ip address or a socket path, without a peer id.
+    pub fn can_listen(&self, addr: &Multiaddr) -> bool {
+        let is_resolved = !matches!(
+            addr.iter().next(),
+            Some(Protocol::Dns(_)) | Some(Protocol::Dns4(_)) | Some(Protocol::Dns6(_))
+        );
+        if !is_resolved || addr_peer_id(addr).is_some() {
+            return false;
+        }
+        match addr_transport(addr) {
+            Some(AddrTransport::Tcp) => true,
+            Some(AddrTransport::Websocket) => self.websocket,
+            Some(AddrTransport::SecureWebsocket) => self.secure_websocket_listen,
+            Some(AddrTransport::Unix) => self.unix,
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn convert_socket_addr() {
+        let addr = SocketAddr::from((Ipv4Addr::new(127, 0, 0, 1), 8080));
+        let multiaddr = socket_addr_to_multiaddr(addr);
+        assert_eq!(multiaddr.to_string(), "/ip4/127.0.0.1/tcp/8080");
+        assert_eq!(multiaddr_to_socket_addr(&multiaddr), Some(addr));
+
+        let addr = SocketAddr::from((Ipv6Addr::LOCALHOST, 1));
+        let multiaddr = socket_addr_to_multiaddr(addr).with(Protocol::P2p(PeerId::random().into()));
+        assert_eq!(multiaddr_to_socket_addr(&multiaddr), Some(addr));
+    }
+
+    #[test]
+    fn reject_non_tcp() {
+        let relayed = format!("/ip4/127.0.0.1/tcp/8080/p2p/{}/p2p-circuit", PeerId::random());
+        let addrs = vec![
+            "/ip4/127.0.0.1/udp/8080",
+            "/ip4/127.0.0.1/tcp/8080/ws",
+            "/dns/localhost/tcp/8080",
+            "/ip4/127.0.0.1",
+            relayed.as_str(),
+        ];
+        for addr in addrs {
+            let multiaddr: Multiaddr = addr.parse().expect("Invalid Multiaddress.");
+            assert_eq!(multiaddr_to_socket_addr(&multiaddr), None);
+        }
+    }
+
+    #[test]
+    fn collect_tcp_ports() {
+        let addrs: Vec<Multiaddr> = vec![
+            "/ip4/127.0.0.1/tcp/8080",
+            "/ip4/192.168.0.2/tcp/8080",
+            "/ip6/::1/tcp/443/ws",
+            "/ip4/127.0.0.1/udp/9000",
+        ]
+        .into_iter()
+        .map(|addr| addr.parse().expect("Invalid Multiaddress."))
+        .collect();
+        assert_eq!(tcp_ports(&addrs), vec![443, 8080]);
+    }
+
+    #[test]
+    fn classify_transport() {
+        let peer_id = PeerId::random();
+        let transports = vec![
+            ("/ip4/127.0.0.1/tcp/8080".to_string(), Some(AddrTransport::Tcp)),
+            (
+                format!("/dns/example.com/tcp/8080/p2p/{}", peer_id),
+                Some(AddrTransport::Tcp),
+            ),
+            ("/ip6/::1/tcp/8080/ws".to_string(), Some(AddrTransport::Websocket)),
+            (
+                format!("/dns4/example.com/tcp/443/wss/p2p/{}", peer_id),
+                Some(AddrTransport::SecureWebsocket),
+            ),
+            ("/unix/stronghold.sock".to_string(), Some(AddrTransport::Unix)),
+            ("/ip4/127.0.0.1/udp/8080".to_string(), None),
+            ("/ip4/127.0.0.1/tcp/8080/ws/ws".to_string(), None),
+            (format!("/ip4/127.0.0.1/tcp/8080/p2p/{}/p2p-circuit", peer_id), None),
+        ];
+        for (addr, transport) in transports {
+            let multiaddr: Multiaddr = addr.parse().expect("Invalid Multiaddress.");
+            assert_eq!(addr_transport(&multiaddr), transport, "{}", addr);
+        }
+        let multiaddr: Multiaddr = format!("/ip4/127.0.0.1/tcp/8080/p2p/{}", peer_id).parse().unwrap();
+        assert_eq!(addr_peer_id(&multiaddr), Some(peer_id));
+        assert_eq!(addr_peer_id(&"/ip4/127.0.0.1/tcp/8080".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn check_transport_support() {
+        let support = TransportSupport {
+            websocket: true,
+            secure_websocket_listen: false,
+            unix: false,
+        };
+        let peer_id = PeerId::random();
+        let dns: Multiaddr = "/dns/example.com/tcp/443/wss".parse().unwrap();
+        assert!(support.can_dial(&dns));
+        assert!(!support.can_listen(&dns));
+        let wss: Multiaddr = "/ip4/0.0.0.0/tcp/443/wss".parse().unwrap();
+        assert!(!support.can_listen(&wss));
+        let ws: Multiaddr = "/ip4/0.0.0.0/tcp/0/ws".parse().unwrap();
+        assert!(support.can_listen(&ws));
+        let with_peer: Multiaddr = format!("/ip4/127.0.0.1/tcp/8080/p2p/{}", peer_id).parse().unwrap();
+        assert!(support.can_dial(&with_peer));
+        assert!(!support.can_listen(&with_peer));
+        let unix: Multiaddr = "/unix/stronghold.sock".parse().unwrap();
+        assert!(!support.can_dial(&unix));
+
+        let support = TransportSupport {
+            websocket: false,
+            ..support
+        };
+        assert!(!support.can_dial(&ws));
+        assert!(!support.can_listen(&ws));
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::BTreeMap;
+
+/// Agent version that is advertised via the identify protocol if none is set in the
+/// [`BehaviourConfig`](super::BehaviourConfig).
+pub const DEFAULT_AGENT_VERSION: &str = "stronghold-communication";
+
+/// Version of the protocol family that is advertised via the identify protocol if none is set in the
+/// [`BehaviourConfig`](super::BehaviourConfig).
+pub const DEFAULT_IDENTIFY_PROTOCOL_VERSION: &str = "/identify/0.1.0";
+
+/// Key of the identify metadata entry in which the service name that was set with
+/// [`BehaviourConfig::set_mdns_service_name`](super::BehaviourConfig::set_mdns_service_name) is advertised.
+pub const MDNS_SERVICE_KEY: &str = "mdns-service";
+
+// Characters that delimit the metadata within the agent version.
+const DELIMITERS: [char; 4] = ['(', ')', ';', '='];
+
+pub(crate) fn is_valid_entry(key: &str, value: &str) -> bool {
+    !key.is_empty() && !key.contains(&DELIMITERS[..]) && !value.contains(&DELIMITERS[..])
+}
+
+// Append the metadata to the agent version, e.g. `my-app/1.2.0 (build=42; region=eu)`.
+// Entries with an empty key, or with a key or value that contains one of the delimiters, are skipped.
+pub(crate) fn encode_agent_version(agent_version: &str, metadata: &BTreeMap<String, String>) -> String {
+    let entries: Vec<String> = metadata
+        .iter()
+        .filter(|(key, value)| is_valid_entry(key, value))
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    if entries.is_empty() {
+        agent_version.to_string()
+    } else {
+        format!("{} ({})", agent_version, entries.join("; "))
+    }
+}
+
+/// Split the agent version that a peer advertised via identify into its name and the metadata that the peer set with
+/// [`BehaviourConfig::set_identify_metadata`](super::BehaviourConfig::set_identify_metadata).
+/// If the agent version does not end with a metadata suffix, it is returned unchanged with empty metadata.
+pub fn parse_agent_version(agent_version: &str) -> (String, BTreeMap<String, String>) {
+    let unchanged = || (agent_version.to_string(), BTreeMap::new());
+    let (name, entries) = match agent_version
+        .strip_suffix(')')
+        .and_then(|s| s.rfind(" (").map(|i| (&s[..i], &s[i + 2..])))
+    {
+        Some(split) => split,
+        None => return unchanged(),
+    };
+    let mut metadata = BTreeMap::new();
+    for entry in entries.split("; ") {
+        let mut parts = entry.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) if is_valid_entry(key, value) => {
+                metadata.insert(key.to_string(), value.to_string());
+            }
+            _ => return unchanged(),
+        }
+    }
+    (name.to_string(), metadata)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode_and_parse_metadata() {
+        let mut metadata = BTreeMap::new();
+        metadata.insert("region".to_string(), "eu".to_string());
+        metadata.insert("build".to_string(), "42".to_string());
+        metadata.insert("invalid;".to_string(), "skipped".to_string());
+        let agent_version = encode_agent_version("my-app/1.2.0", &metadata);
+        assert_eq!(agent_version, "my-app/1.2.0 (build=42; region=eu)");
+
+        metadata.remove("invalid;");
+        assert_eq!(
+            parse_agent_version(&agent_version),
+            ("my-app/1.2.0".to_string(), metadata)
+        );
+    }
+
+    #[test]
+    fn parse_without_metadata() {
+        assert_eq!(encode_agent_version("my-app", &BTreeMap::new()), "my-app");
+        for agent_version in &["my-app", "rust-libp2p (linux)", "my-app ()", "my-app (a=b; c)"] {
+            assert_eq!(
+                parse_agent_version(agent_version),
+                (agent_version.to_string(), BTreeMap::new())
+            );
+        }
+    }
+}
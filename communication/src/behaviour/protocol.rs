@@ -2,47 +2,255 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use async_trait::async_trait;
-use core::{fmt::Debug, marker::PhantomData};
-use futures::{prelude::*, AsyncRead, AsyncWrite};
+use core::fmt::Debug;
+use futures::{AsyncRead, AsyncWrite};
 use libp2p::{
     core::{
-        upgrade::{read_one, write_one},
+        upgrade::{read_one, write_one, write_varint, ReadOneError},
         ProtocolName,
     },
     request_response::RequestResponseCodec,
 };
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 // TODO: support no_std
-use std::io::{Error as IOError, ErrorKind as IOErrorKind, Result as IOResult};
+use std::{
+    io::{Error as IOError, ErrorKind as IOErrorKind, Result as IOResult},
+    sync::Arc,
+};
+use thiserror::Error as DeriveError;
 
 /// Trait for the generic Request and Response types
 pub trait MessageEvent: Serialize + DeserializeOwned + Debug + Send + Clone + Sync + 'static {}
 impl<T: Serialize + DeserializeOwned + Debug + Send + Clone + Sync + 'static> MessageEvent for T {}
 
+/// Name of the protocol if no other versions are configured.
+pub const DEFAULT_PROTOCOL: &str = "/stronghold-communication/1.0.0";
+
 /// Custom protocol that extends libp2ps RequestResponseProtocol
 #[derive(Debug, Clone)]
-pub struct MessageProtocol();
+pub struct MessageProtocol(String);
+
+impl MessageProtocol {
+    pub fn new(name: String) -> Self {
+        MessageProtocol(name)
+    }
+}
+
+impl Default for MessageProtocol {
+    fn default() -> Self {
+        MessageProtocol(DEFAULT_PROTOCOL.to_string())
+    }
+}
 
 impl ProtocolName for MessageProtocol {
     fn protocol_name(&self) -> &[u8] {
-        b"/stronghold-communication/1.0.0"
+        self.0.as_bytes()
     }
 }
 
+/// Custom encoding and framing of the requests and responses on the wire, e.g. for the interoperability with peers
+/// that are not implemented in Rust and expect a specific byte layout of the messages, like protobuf.
+/// The format reads and writes the messages directly on the substream and therefore also determines how they are
+/// delimited, the length prefix and the max message size of the default JSON format don't apply.
+/// The requests of the [`CommunicationActor`](crate::actor::CommunicationActor) are wrapped into a
+/// [`RequestEnvelope`](super::RequestEnvelope), which has to be encoded as well. Rejections have to be encoded
+/// distinctly from responses, so that they are read as [`MessageResponse::Rejected`] again.
+///
+/// # Example
+/// ```
+/// use async_trait::async_trait;
+/// use communication::behaviour::{
+///     BehaviourConfig, MessageEvent, MessageResponse, RejectReason, RequestEnvelope, WireFormat,
+/// };
+/// use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+/// use std::{io, sync::Arc};
+///
+/// // JSON messages that are prefixed with their length as 4-byte big-endian integer. The first byte of a response
+/// // frame is 0 for a response, otherwise it is the reason of a rejection.
+/// #[derive(Debug)]
+/// struct FixedLengthJson;
+///
+/// async fn read_frame(io: &mut (dyn AsyncRead + Unpin + Send)) -> io::Result<Vec<u8>> {
+///     let mut len = [0u8; 4];
+///     io.read_exact(&mut len).await?;
+///     let mut frame = vec![0u8; u32::from_be_bytes(len) as usize];
+///     io.read_exact(&mut frame).await?;
+///     Ok(frame)
+/// }
+///
+/// async fn write_frame(io: &mut (dyn AsyncWrite + Unpin + Send), frame: Vec<u8>) -> io::Result<()> {
+///     io.write_all(&(frame.len() as u32).to_be_bytes()).await?;
+///     io.write_all(&frame).await?;
+///     io.close().await
+/// }
+///
+/// fn invalid_data<E: std::error::Error + Send + Sync + 'static>(e: E) -> io::Error {
+///     io::Error::new(io::ErrorKind::InvalidData, e)
+/// }
+///
+/// #[async_trait]
+/// impl<Req: MessageEvent, Res: MessageEvent> WireFormat<Req, Res> for FixedLengthJson {
+///     async fn read_request(&self, io: &mut (dyn AsyncRead + Unpin + Send)) -> io::Result<Req> {
+///         serde_json::from_slice(&read_frame(io).await?).map_err(invalid_data)
+///     }
+///
+///     async fn read_response(&self, io: &mut (dyn AsyncRead + Unpin + Send)) -> io::Result<MessageResponse<Res>> {
+///         let frame = read_frame(io).await?;
+///         match frame.split_first() {
+///             Some((0, json)) => serde_json::from_slice(json).map(MessageResponse::Response).map_err(invalid_data),
+///             Some((2, _)) => Ok(MessageResponse::Rejected(RejectReason::Busy)),
+///             Some((3, _)) => Ok(MessageResponse::Rejected(RejectReason::Paused)),
+///             _ => Ok(MessageResponse::Rejected(RejectReason::Blocked)),
+///         }
+///     }
+///
+///     async fn write_request(&self, io: &mut (dyn AsyncWrite + Unpin + Send), request: Req) -> io::Result<()> {
+///         write_frame(io, serde_json::to_vec(&request).map_err(invalid_data)?).await
+///     }
+///
+///     async fn write_response(
+///         &self,
+///         io: &mut (dyn AsyncWrite + Unpin + Send),
+///         response: MessageResponse<Res>,
+///     ) -> io::Result<()> {
+///         let frame = match response {
+///             MessageResponse::Response(res) => {
+///                 let mut frame = vec![0];
+///                 frame.extend(serde_json::to_vec(&res).map_err(invalid_data)?);
+///                 frame
+///             }
+///             MessageResponse::Rejected(RejectReason::Blocked) => vec![1],
+///             MessageResponse::Rejected(RejectReason::Busy) => vec![2],
+///             MessageResponse::Rejected(RejectReason::Paused) => vec![3],
+///         };
+///         write_frame(io, frame).await
+///     }
+/// }
+///
+/// let mut config = BehaviourConfig::default();
+/// config.set_wire_format::<RequestEnvelope<String>, String>(Arc::new(FixedLengthJson));
+/// ```
+#[async_trait]
+pub trait WireFormat<Req, Res>: Debug + Send + Sync {
+    /// Read a request of a remote peer from the substream.
+    async fn read_request(&self, io: &mut (dyn AsyncRead + Unpin + Send)) -> IOResult<Req>;
+    /// Read the response or the rejection of a remote peer from the substream.
+    async fn read_response(&self, io: &mut (dyn AsyncRead + Unpin + Send)) -> IOResult<MessageResponse<Res>>;
+    /// Write a request to the substream.
+    async fn write_request(&self, io: &mut (dyn AsyncWrite + Unpin + Send), request: Req) -> IOResult<()>;
+    /// Write a response or a rejection to the substream.
+    async fn write_response(
+        &self,
+        io: &mut (dyn AsyncWrite + Unpin + Send),
+        response: MessageResponse<Res>,
+    ) -> IOResult<()>;
+}
+
+/// A remote peer declared a message length that exceeds the configured max message size.
+/// The length prefix is checked before the message is read, so that nothing is allocated for the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DeriveError)]
+#[error("Message of {size} bytes exceeds the max message size of {max} bytes")]
+pub struct MessageTooLarge {
+    /// Length of the message as declared by the remote peer.
+    pub size: usize,
+    /// The configured max message size.
+    pub max: usize,
+}
+
+/// Reason of a remote peer for rejecting a request without handling it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RejectReason {
+    /// The request was blocked by the firewall of the remote peer, or the remote peer did not state a reason.
+    Blocked,
+    /// The remote peer is too busy to handle the request, e.g. because its queue of inbound requests is full.
+    Busy,
+    /// The remote peer paused handling requests.
+    Paused,
+}
+
+impl RejectReason {
+    fn to_byte(self) -> u8 {
+        match self {
+            RejectReason::Blocked => 1,
+            RejectReason::Busy => 2,
+            RejectReason::Paused => 3,
+        }
+    }
+
+    fn from_byte(byte: Option<&u8>) -> Self {
+        match byte {
+            Some(2) => RejectReason::Busy,
+            Some(3) => RejectReason::Paused,
+            _ => RejectReason::Blocked,
+        }
+    }
+}
+
+/// Response on the wire, which is either the response of the remote peer, or the rejection of the request.
+/// A rejection is written as a message of zero bytes, so that it can not be confused with an encoded response, and
+/// the responses of peers that don't send rejections can still be read. The reason of the rejection follows as a
+/// separate message of one byte, rejections of peers that don't send the reason are read as
+/// [`RejectReason::Blocked`]. Custom [`WireFormat`]s determine the encoding of rejections themselves.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MessageResponse<Res> {
+    /// The response to the request.
+    Response(Res),
+    /// The remote peer rejected the request without handling it.
+    Rejected(RejectReason),
+}
+
 /// Describes how messages are read from and written to the io Socket by implementing the RequestResponseCodec
-#[derive(Clone)]
+/// Messages are encoded as JSON with a length prefix, unless a custom [`WireFormat`] is used.
 pub struct MessageCodec<Req, Res> {
-    p: PhantomData<Req>,
-    q: PhantomData<Res>,
+    format: Option<Arc<dyn WireFormat<Req, Res>>>,
+    max_message_size: Option<usize>,
 }
 
-impl<Req, Res> Default for MessageCodec<Req, Res> {
-    fn default() -> Self {
+impl<Req, Res> Clone for MessageCodec<Req, Res> {
+    fn clone(&self) -> Self {
+        MessageCodec {
+            format: self.format.clone(),
+            max_message_size: self.max_message_size,
+        }
+    }
+}
+
+impl<Req, Res> MessageCodec<Req, Res> {
+    pub fn new(format: Option<Arc<dyn WireFormat<Req, Res>>>, max_message_size: Option<usize>) -> Self {
         MessageCodec {
-            p: PhantomData,
-            q: PhantomData,
+            format,
+            max_message_size,
         }
     }
+
+    // Read the length prefix and the message, the message is rejected before reading it if the length exceeds the
+    // max message size.
+    async fn read_message<R>(&self, io: &mut R) -> IOResult<Vec<u8>>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        let max = self.max_message_size.unwrap_or(usize::MAX);
+        read_one(io, max).await.map_err(|e| match e {
+            ReadOneError::TooLarge { requested, max } => {
+                IOError::new(IOErrorKind::InvalidData, MessageTooLarge { size: requested, max })
+            }
+            e => IOError::new(IOErrorKind::InvalidData, e),
+        })
+    }
+
+    fn encode<T: Serialize>(message: &T) -> IOResult<Vec<u8>> {
+        serde_json::to_vec(message).map_err(|e| IOError::new(IOErrorKind::InvalidData, e))
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> IOResult<T> {
+        serde_json::from_slice(bytes).map_err(|e| IOError::new(IOErrorKind::InvalidData, e))
+    }
+}
+
+impl<Req, Res> Default for MessageCodec<Req, Res> {
+    fn default() -> Self {
+        MessageCodec::new(None, None)
+    }
 }
 
 /// Read and write requests and responses, and parse them into the generic structs Req and Res.
@@ -54,21 +262,18 @@ where
 {
     type Protocol = MessageProtocol;
     type Request = Req;
-    type Response = Res;
+    type Response = MessageResponse<Res>;
 
     // read requests from remote peers and parse them into the request struct
     async fn read_request<R>(&mut self, _: &MessageProtocol, io: &mut R) -> IOResult<Self::Request>
     where
         R: AsyncRead + Unpin + Send,
     {
-        read_one(io, usize::MAX)
-            .map(|req| match req {
-                Ok(bytes) => {
-                    serde_json::from_slice(bytes.as_slice()).map_err(|e| IOError::new(IOErrorKind::InvalidData, e))
-                }
-                Err(e) => Err(IOError::new(IOErrorKind::InvalidData, e)),
-            })
-            .await
+        if let Some(format) = self.format.as_ref() {
+            return format.read_request(io).await;
+        }
+        let bytes = self.read_message(io).await?;
+        Self::decode(bytes.as_slice())
     }
 
     // read responses from remote peers and parse them into the request struct
@@ -76,14 +281,18 @@ where
     where
         R: AsyncRead + Unpin + Send,
     {
-        read_one(io, usize::MAX)
-            .map(|res| match res {
-                Ok(bytes) => {
-                    serde_json::from_slice(bytes.as_slice()).map_err(|e| IOError::new(IOErrorKind::InvalidData, e))
-                }
-                Err(e) => Err(IOError::new(IOErrorKind::InvalidData, e)),
-            })
-            .await
+        if let Some(format) = self.format.as_ref() {
+            return format.read_response(io).await;
+        }
+        let bytes = self.read_message(io).await?;
+        if bytes.is_empty() {
+            let reason = match read_one(io, 1).await {
+                Ok(reason) => RejectReason::from_byte(reason.first()),
+                Err(_) => RejectReason::Blocked,
+            };
+            return Ok(MessageResponse::Rejected(reason));
+        }
+        Self::decode(bytes.as_slice()).map(MessageResponse::Response)
     }
 
     // deserialize request and write to the io socket
@@ -91,7 +300,10 @@ where
     where
         R: AsyncWrite + Unpin + Send,
     {
-        let buf = serde_json::to_vec(&req).map_err(|e| IOError::new(IOErrorKind::InvalidData, e))?;
+        if let Some(format) = self.format.as_ref() {
+            return format.write_request(io, req).await;
+        }
+        let buf = Self::encode(&req)?;
         write_one(io, buf).await
     }
 
@@ -100,7 +312,17 @@ where
     where
         R: AsyncWrite + Unpin + Send,
     {
-        let buf = serde_json::to_vec(&res).map_err(|e| IOError::new(IOErrorKind::InvalidData, e))?;
+        if let Some(format) = self.format.as_ref() {
+            return format.write_response(io, res).await;
+        }
+        let buf = match res {
+            MessageResponse::Response(res) => Self::encode(&res)?,
+            MessageResponse::Rejected(reason) => {
+                // The empty message is followed by the reason.
+                write_varint(io, 0).await?;
+                vec![reason.to_byte()]
+            }
+        };
         write_one(io, buf).await
     }
 }
@@ -115,8 +337,64 @@ mod test {
         task,
         task::JoinHandle,
     };
+    use futures::prelude::*;
     use stronghold_utils::test_utils;
 
+    // JSON messages that are terminated by a newline, with rejections written as `!` and the reason byte.
+    #[derive(Debug)]
+    struct LineJson;
+
+    async fn read_line(io: &mut (dyn AsyncRead + Unpin + Send)) -> IOResult<Vec<u8>> {
+        let mut line = Vec::new();
+        let mut byte = [0u8];
+        loop {
+            io.read_exact(&mut byte).await?;
+            if byte[0] == b'\n' {
+                return Ok(line);
+            }
+            line.push(byte[0]);
+        }
+    }
+
+    #[async_trait]
+    impl WireFormat<String, String> for LineJson {
+        async fn read_request(&self, io: &mut (dyn AsyncRead + Unpin + Send)) -> IOResult<String> {
+            let line = read_line(io).await?;
+            serde_json::from_slice(&line).map_err(|e| IOError::new(IOErrorKind::InvalidData, e))
+        }
+
+        async fn read_response(&self, io: &mut (dyn AsyncRead + Unpin + Send)) -> IOResult<MessageResponse<String>> {
+            let line = read_line(io).await?;
+            match line.split_first() {
+                Some((b'!', reason)) => Ok(MessageResponse::Rejected(RejectReason::from_byte(reason.first()))),
+                _ => serde_json::from_slice(&line)
+                    .map(MessageResponse::Response)
+                    .map_err(|e| IOError::new(IOErrorKind::InvalidData, e)),
+            }
+        }
+
+        async fn write_request(&self, io: &mut (dyn AsyncWrite + Unpin + Send), request: String) -> IOResult<()> {
+            let mut line = serde_json::to_vec(&request).map_err(|e| IOError::new(IOErrorKind::InvalidData, e))?;
+            line.push(b'\n');
+            io.write_all(&line).await
+        }
+
+        async fn write_response(
+            &self,
+            io: &mut (dyn AsyncWrite + Unpin + Send),
+            response: MessageResponse<String>,
+        ) -> IOResult<()> {
+            let mut line = match response {
+                MessageResponse::Response(res) => {
+                    serde_json::to_vec(&res).map_err(|e| IOError::new(IOErrorKind::InvalidData, e))?
+                }
+                MessageResponse::Rejected(reason) => vec![b'!', reason.to_byte()],
+            };
+            line.push(b'\n');
+            io.write_all(&line).await
+        }
+    }
+
     fn spawn_listener() -> (SocketAddr, JoinHandle<()>) {
         let listener = task::block_on(async {
             TcpListener::bind("127.0.0.1:0")
@@ -149,7 +427,7 @@ mod test {
         let (addr, listener_handle) = spawn_listener();
 
         let writer_handle = task::spawn(async move {
-            let protocol = MessageProtocol();
+            let protocol = MessageProtocol::default();
             let mut codec = MessageCodec::<Vec<u8>, Vec<u8>>::default();
             let mut socket = TcpStream::connect(addr).await.expect("Failed to connect tcp stream.");
             for bytes in test_vector.iter() {
@@ -182,12 +460,12 @@ mod test {
         let (addr, listener_handle) = spawn_listener();
 
         let writer_handle = task::spawn(async move {
-            let protocol = MessageProtocol();
+            let protocol = MessageProtocol::default();
             let mut codec = MessageCodec::<Vec<u8>, Vec<u8>>::default();
             let mut socket = TcpStream::connect(addr).await.expect("Failed to connect tcp stream.");
             for bytes in test_vector.iter() {
                 codec
-                    .write_response(&protocol, &mut socket, bytes.clone())
+                    .write_response(&protocol, &mut socket, MessageResponse::Response(bytes.clone()))
                     .await
                     .expect("Failed to write response.");
             }
@@ -196,8 +474,139 @@ mod test {
                     .read_response(&protocol, &mut socket)
                     .await
                     .expect("Failed to read response.");
-                assert_eq!(bytes, &received);
+                assert_eq!(MessageResponse::Response(bytes.clone()), received);
+            }
+            socket.shutdown(Shutdown::Both).expect("Failed to shutdown socket.");
+        });
+        task::block_on(async {
+            future::join(listener_handle, writer_handle).await;
+        });
+    }
+
+    #[test]
+    fn custom_wire_format() {
+        let (addr, listener_handle) = spawn_listener();
+
+        let writer_handle = task::spawn(async move {
+            let protocol = MessageProtocol::default();
+            let mut codec = MessageCodec::<String, String>::new(Some(Arc::new(LineJson)), None);
+            let mut socket = TcpStream::connect(addr).await.expect("Failed to connect tcp stream.");
+            codec
+                .write_request(&protocol, &mut socket, "request".into())
+                .await
+                .expect("Failed to write request.");
+            let received = codec
+                .read_request(&protocol, &mut socket)
+                .await
+                .expect("Failed to read request.");
+            assert_eq!(received, "request");
+
+            // the format determines the framing of the messages and the encoding of rejections
+            codec
+                .write_response(&protocol, &mut socket, MessageResponse::Rejected(RejectReason::Busy))
+                .await
+                .expect("Failed to write rejection.");
+            let mut line = [0u8; 3];
+            socket.read_exact(&mut line).await.expect("Failed to read rejection.");
+            assert_eq!(&line, &[b'!', 2, b'\n']);
+            socket.write_all(&line).await.expect("Failed to write rejection.");
+            let received = codec
+                .read_response(&protocol, &mut socket)
+                .await
+                .expect("Failed to read rejection.");
+            assert_eq!(received, MessageResponse::Rejected(RejectReason::Busy));
+
+            // responses are written without the length prefix of the default format
+            codec
+                .write_response(&protocol, &mut socket, MessageResponse::Response("response".into()))
+                .await
+                .expect("Failed to write response.");
+            let mut line = [0u8; 11];
+            socket.read_exact(&mut line).await.expect("Failed to read response.");
+            assert_eq!(&line, b"\"response\"\n");
+            socket.shutdown(Shutdown::Both).expect("Failed to shutdown socket.");
+        });
+        task::block_on(async {
+            future::join(listener_handle, writer_handle).await;
+        });
+    }
+
+    #[test]
+    fn send_rejection() {
+        let (addr, listener_handle) = spawn_listener();
+
+        let writer_handle = task::spawn(async move {
+            let protocol = MessageProtocol::default();
+            let mut codec = MessageCodec::<Vec<u8>, Vec<u8>>::default();
+            let mut socket = TcpStream::connect(addr).await.expect("Failed to connect tcp stream.");
+            for reason in &[RejectReason::Blocked, RejectReason::Paused] {
+                codec
+                    .write_response(&protocol, &mut socket, MessageResponse::Rejected(*reason))
+                    .await
+                    .expect("Failed to write rejection.");
             }
+            // an empty response is still encoded as JSON
+            codec
+                .write_response(&protocol, &mut socket, MessageResponse::Response(Vec::new()))
+                .await
+                .expect("Failed to write response.");
+            for reason in &[RejectReason::Blocked, RejectReason::Paused] {
+                let received = codec
+                    .read_response(&protocol, &mut socket)
+                    .await
+                    .expect("Failed to read rejection.");
+                assert_eq!(received, MessageResponse::Rejected(*reason));
+            }
+            let received = codec
+                .read_response(&protocol, &mut socket)
+                .await
+                .expect("Failed to read response.");
+            assert_eq!(received, MessageResponse::Response(Vec::new()));
+            socket.shutdown(Shutdown::Both).expect("Failed to shutdown socket.");
+        });
+        task::block_on(async {
+            future::join(listener_handle, writer_handle).await;
+        });
+    }
+
+    #[test]
+    fn max_message_size() {
+        let (addr, listener_handle) = spawn_listener();
+
+        let writer_handle = task::spawn(async move {
+            let protocol = MessageProtocol::default();
+            let mut codec = MessageCodec::<Vec<u8>, Vec<u8>>::new(None, Some(1024));
+            let mut socket = TcpStream::connect(addr).await.expect("Failed to connect tcp stream.");
+            codec
+                .write_request(&protocol, &mut socket, vec![1u8; 32])
+                .await
+                .expect("Failed to write request.");
+            let received = codec
+                .read_request(&protocol, &mut socket)
+                .await
+                .expect("Failed to read request.");
+            assert_eq!(received, vec![1u8; 32]);
+
+            // length prefix of 2^30 bytes without the message, which is rejected without waiting for the message
+            socket
+                .write_all(&[0x80, 0x80, 0x80, 0x80, 0x04])
+                .await
+                .expect("Failed to write length prefix.");
+            let err = codec
+                .read_request(&protocol, &mut socket)
+                .await
+                .expect_err("Oversized request was read.");
+            let too_large = err
+                .get_ref()
+                .and_then(|e| e.downcast_ref::<MessageTooLarge>())
+                .expect("Invalid error.");
+            assert_eq!(
+                too_large,
+                &MessageTooLarge {
+                    size: 1 << 30,
+                    max: 1024
+                }
+            );
             socket.shutdown(Shutdown::Both).expect("Failed to shutdown socket.");
         });
         task::block_on(async {
@@ -216,7 +625,7 @@ mod test {
         let (addr, listener_handle) = spawn_listener();
 
         let writer_handle = task::spawn(async move {
-            let protocol = MessageProtocol();
+            let protocol = MessageProtocol::default();
             let mut codec = MessageCodec::<Vec<u8>, Vec<u8>>::default();
             let mut socket = TcpStream::connect(addr).await.expect("Failed to connect tcp stream.");
             for bytes in test_vector.clone().iter_mut() {
@@ -254,13 +663,13 @@ mod test {
         let (addr, listener_handle) = spawn_listener();
 
         let writer_handle = task::spawn(async move {
-            let protocol = MessageProtocol();
+            let protocol = MessageProtocol::default();
             let mut codec = MessageCodec::<Vec<u8>, Vec<u8>>::default();
             let mut socket = TcpStream::connect(addr).await.expect("Failed to connect tcp stream.");
             for bytes in test_vector.clone().iter_mut() {
                 test_utils::corrupt(bytes);
                 codec
-                    .write_response(&protocol, &mut socket, bytes.clone())
+                    .write_response(&protocol, &mut socket, MessageResponse::Response(bytes.clone()))
                     .await
                     .expect("Failed to write response.");
             }
@@ -270,7 +679,7 @@ mod test {
                     .read_response(&protocol, &mut socket)
                     .await
                     .expect("Failed to read response.");
-                results.push(bytes == &received)
+                results.push(MessageResponse::Response(bytes.clone()) == received)
             }
             socket.shutdown(Shutdown::Both).expect("Failed to shutdown socket.");
             results.iter().any(|res| *res)
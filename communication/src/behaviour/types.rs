@@ -6,10 +6,15 @@ use libp2p::{
     core::{Multiaddr, PeerId},
     identify::IdentifyEvent,
     identity::PublicKey,
+    ping::{PingEvent, PingSuccess},
     request_response::{InboundFailure, OutboundFailure, RequestId, RequestResponseEvent, RequestResponseMessage},
     swarm::ProtocolsHandlerUpgrErr,
 };
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error as DeriveError;
+
+use super::protocol::{MessageResponse, RejectReason};
 
 #[cfg(feature = "mdns")]
 use libp2p::mdns::MdnsEvent;
@@ -21,6 +26,65 @@ pub struct RequestEnvelope<Req: Debug + Clone + Serialize + DeserializeOwned> {
     #[serde(bound = "Req: Debug + Clone + Serialize + DeserializeOwned")]
     pub message: Req,
     pub target: String,
+    /// Unix timestamp in milliseconds after which the envelope is expired and dropped by the target.
+    /// This requires that the clocks of source and target are loosely synchronized.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Number of times that the envelope may still be forwarded by a relay.
+    /// A relay should drop the envelope instead of forwarding it once no hops are remaining.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hops_remaining: Option<u8>,
+}
+
+impl<Req: Debug + Clone + Serialize + DeserializeOwned> RequestEnvelope<Req> {
+    /// Set the expiry of the envelope according to the ttl, relative to the current time.
+    pub fn set_ttl(&mut self, ttl: EnvelopeTtl) {
+        match ttl {
+            EnvelopeTtl::Time(duration) => {
+                let expires_at = unix_millis().saturating_add(duration.as_millis() as u64);
+                self.expires_at = Some(expires_at);
+            }
+            EnvelopeTtl::Hops(hops) => self.hops_remaining = Some(hops),
+        }
+    }
+
+    /// Check if the `expires_at` timestamp of the envelope has passed.
+    pub fn is_expired(&self) -> bool {
+        match self.expires_at {
+            Some(expires_at) => unix_millis() > expires_at,
+            None => false,
+        }
+    }
+
+    /// Decrement the remaining hops before the envelope is forwarded by a relay.
+    /// Returns `false` if no hops are remaining and the envelope should be dropped.
+    pub fn decrement_hops(&mut self) -> bool {
+        match self.hops_remaining {
+            Some(0) => false,
+            Some(hops) => {
+                self.hops_remaining = Some(hops - 1);
+                true
+            }
+            None => true,
+        }
+    }
+}
+
+/// Time-to-live of a [`RequestEnvelope`], after which stale envelopes are dropped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EnvelopeTtl {
+    /// Expire the envelope once the duration since it was sent has passed.
+    /// This requires loosely synchronized clocks of the source and target peer.
+    Time(Duration),
+    /// Expire the envelope after it was forwarded by the specified number of relays, independently of the clocks.
+    Hops(u8),
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
 }
 
 /// Event that can be produced by the `Mdns` behaviour.
@@ -50,13 +114,16 @@ pub struct P2PIdentifyInfo {
 }
 
 /// Error that can happen on an outbound substream opening attempt.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, DeriveError)]
 pub enum P2PProtocolsHandlerUpgrErr {
     /// The opening attempt timed out before the negotiation was fully completed.
+    #[error("Timeout while opening the substream")]
     Timeout,
     /// There was an error in the timer used.
+    #[error("Timer error while opening the substream")]
     Timer,
     /// Error while upgrading the substream to the protocol we want.
+    #[error("Upgrading the substream failed")]
     Upgrade,
 }
 
@@ -78,40 +145,64 @@ pub enum P2PIdentifyEvent {
         error: P2PProtocolsHandlerUpgrErr,
     },
 }
+
+/// Event emitted by the `Ping` behaviour, if probes are enabled in the `BehaviourConfig`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum P2PPingEvent {
+    /// A ping was sent to the peer and the pong was received after the round-trip time `rtt`.
+    Ping { peer_id: PeerId, rtt: Duration },
+    /// A ping of the peer was answered.
+    Pong { peer_id: PeerId },
+    /// Sending a ping to the peer failed, e.g. because it timed out or the peer does not support the protocol.
+    Failure { peer_id: PeerId },
+}
+
 /// Possible failures occurring in the context of sending
 /// an outbound request and receiving the response.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, DeriveError, Serialize, Deserialize)]
 pub enum P2POutboundFailure {
     /// The request could not be sent because a dialing attempt failed.
+    #[error("Dialing the remote peer failed")]
     DialFailure,
     /// The request timed out before a response was received.
     ///
     /// It is not known whether the request may have been
     /// received (and processed) by the remote peer.
+    #[error("Timeout before the response was received")]
     Timeout,
     /// The connection closed before a response was received.
     ///
     /// It is not known whether the request may have been
     /// received (and processed) by the remote peer.
+    #[error("Connection closed before the response was received")]
     ConnectionClosed,
     /// The remote supports none of the requested protocols.
+    #[error("The remote peer supports none of the requested protocols")]
     UnsupportedProtocols,
+    /// The remote peer rejected the request with an explicit rejection instead of a response, e.g. because it was
+    /// blocked by its firewall.
+    #[error("The remote peer rejected the request")]
+    Rejected(RejectReason),
 }
 
 /// Possible failures occurring in the context of receiving an
 /// inbound request and sending a response.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, DeriveError, Serialize, Deserialize)]
 pub enum P2PInboundFailure {
     /// The inbound request timed out, either while reading the
     /// incoming request or before a response is sent
+    #[error("Timeout before the response was sent")]
     Timeout,
     /// The local peer supports none of the requested protocols.
+    #[error("The local peer supports none of the requested protocols")]
     UnsupportedProtocols,
     /// The local peer failed to respond to an inbound request
     /// due to the [`ResponseChannel`] being dropped instead of
     /// being passed to [`RequestResponse::send_response`].
+    #[error("No response was sent for the request")]
     ResponseOmission,
     /// The connection closed before a response could be send.
+    #[error("Connection closed before the response was sent")]
     ConnectionClosed,
 }
 
@@ -165,6 +256,8 @@ pub enum P2PEvent<Req, Res> {
     Identify(Box<P2PIdentifyEvent>),
     /// Events from the custom request-response protocol
     RequestResponse(Box<P2PReqResEvent<Req, Res>>),
+    /// Events from the libp2p ping protocol
+    Ping(P2PPingEvent),
 }
 
 #[cfg(feature = "mdns")]
@@ -208,8 +301,20 @@ impl<Req, Res> From<IdentifyEvent> for P2PEvent<Req, Res> {
     }
 }
 
-impl<Req, Res> From<RequestResponseEvent<Req, Res>> for P2PEvent<Req, Res> {
-    fn from(event: RequestResponseEvent<Req, Res>) -> P2PEvent<Req, Res> {
+impl<Req, Res> From<PingEvent> for P2PEvent<Req, Res> {
+    fn from(event: PingEvent) -> P2PEvent<Req, Res> {
+        let peer_id = event.peer;
+        let event = match event.result {
+            Ok(PingSuccess::Ping { rtt }) => P2PPingEvent::Ping { peer_id, rtt },
+            Ok(PingSuccess::Pong) => P2PPingEvent::Pong { peer_id },
+            Err(_) => P2PPingEvent::Failure { peer_id },
+        };
+        P2PEvent::Ping(event)
+    }
+}
+
+impl<Req, Res> From<RequestResponseEvent<Req, MessageResponse<Res>>> for P2PEvent<Req, Res> {
+    fn from(event: RequestResponseEvent<Req, MessageResponse<Res>>) -> P2PEvent<Req, Res> {
         match event {
             RequestResponseEvent::Message { peer, message } => match message {
                 RequestResponseMessage::Request {
@@ -221,13 +326,22 @@ impl<Req, Res> From<RequestResponseEvent<Req, Res>> for P2PEvent<Req, Res> {
                     request_id,
                     request,
                 })),
-                RequestResponseMessage::Response { request_id, response } => {
-                    P2PEvent::RequestResponse(Box::new(P2PReqResEvent::Res {
-                        peer_id: peer,
-                        request_id,
-                        response,
-                    }))
-                }
+                RequestResponseMessage::Response {
+                    request_id,
+                    response: MessageResponse::Response(response),
+                } => P2PEvent::RequestResponse(Box::new(P2PReqResEvent::Res {
+                    peer_id: peer,
+                    request_id,
+                    response,
+                })),
+                RequestResponseMessage::Response {
+                    request_id,
+                    response: MessageResponse::Rejected(reason),
+                } => P2PEvent::RequestResponse(Box::new(P2PReqResEvent::OutboundFailure {
+                    peer_id: peer,
+                    request_id,
+                    error: P2POutboundFailure::Rejected(reason),
+                })),
             },
             RequestResponseEvent::OutboundFailure {
                 peer,
@@ -272,3 +386,47 @@ impl<Req, Res> From<RequestResponseEvent<Req, Res>> for P2PEvent<Req, Res> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn envelope() -> RequestEnvelope<String> {
+        RequestEnvelope {
+            source: String::new(),
+            message: String::from("message"),
+            target: String::new(),
+            expires_at: None,
+            hops_remaining: None,
+        }
+    }
+
+    #[test]
+    fn expire_time_ttl() {
+        let mut request = envelope();
+        assert!(!request.is_expired());
+        request.set_ttl(EnvelopeTtl::Time(Duration::from_secs(60)));
+        assert!(!request.is_expired());
+        request.expires_at = Some(unix_millis() - 1);
+        assert!(request.is_expired());
+    }
+
+    #[test]
+    fn decrement_hops_ttl() {
+        let mut request = envelope();
+        assert!(request.decrement_hops());
+        request.set_ttl(EnvelopeTtl::Hops(1));
+        assert!(request.decrement_hops());
+        assert_eq!(request.hops_remaining, Some(0));
+        assert!(!request.decrement_hops());
+    }
+
+    #[test]
+    fn deserialize_without_ttl() {
+        let bytes = serde_json::to_vec(&envelope()).unwrap();
+        assert!(!String::from_utf8_lossy(&bytes).contains("expires_at"));
+        let request: RequestEnvelope<String> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(request.expires_at, None);
+        assert_eq!(request.hops_remaining, None);
+    }
+}
@@ -69,11 +69,16 @@
 
 pub mod actor;
 pub mod behaviour;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod libp2p {
     //! Re-export [`libp2p`] types.
     pub use libp2p::{
         core::{
-            connection::ConnectionLimit, identity::Keypair, multiaddr::Protocol, ConnectedPoint, Multiaddr, PeerId,
+            connection::{ConnectionLimit, ListenerId},
+            identity::Keypair,
+            multiaddr::Protocol,
+            ConnectedPoint, Multiaddr, PeerId,
         },
         swarm::{Swarm, SwarmEvent},
     };
@@ -0,0 +1,154 @@
+// Copyright 2020-2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! Helpers for tests with multiple interconnected [`CommunicationActor`]s, enabled with the `testing` feature.
+//!
+//! ### Example
+//! ```no_run
+//! use async_std::task;
+//! use communication::{
+//!     actor::{CommunicationActorConfig, FirewallPermission},
+//!     testing::spawn_network,
+//! };
+//! # use communication::actor::{PermissionValue, RequestPermissions, ToPermissionVariants, VariantPermission};
+//! # use riker::actors::*;
+//! # use serde::{Deserialize, Serialize};
+//! #
+//! # #[derive(Debug, Clone, Serialize, Deserialize, RequestPermissions)]
+//! # pub enum Request {
+//! #     Ping,
+//! # }
+//! #
+//! # #[derive(Debug, Clone, Serialize, Deserialize)]
+//! # pub enum Response {
+//! #     Pong,
+//! # }
+//! #
+//! # #[derive(Clone, Debug)]
+//! # struct Client;
+//! #
+//! # impl ActorFactory for Client {
+//! #     fn create() -> Self {
+//! #         Client
+//! #     }
+//! # }
+//! #
+//! # impl Actor for Client {
+//! #     type Msg = Request;
+//! #     fn recv(&mut self, _ctx: &Context<Self::Msg>, _msg: Self::Msg, _sender: Sender) {}
+//! # }
+//!
+//! let system = ActorSystem::new().expect("Failed to create actor system.");
+//! let configs = (0..3)
+//!     .map(|index| {
+//!         let client = system.actor_of::<Client>(&format!("client-{}", index)).unwrap();
+//!         CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all())
+//!     })
+//!     .collect();
+//! let nodes = task::block_on(spawn_network::<Request, Response, Request, RequestPermission>(
+//!     &system, "node", configs,
+//! ))
+//! .expect("Failed to spawn network.");
+//! assert_eq!(nodes.len(), 3);
+//! ```
+
+use crate::{
+    actor::{
+        CommunicationActor, CommunicationActorConfig, CommunicationHandle, CommunicationRequest, ConnectPeerError,
+        KeepAlive, StartListeningError, ToPermissionVariants, VariantPermission,
+    },
+    behaviour::{BehaviourConfig, MessageEvent},
+};
+use libp2p::{
+    identity::{ed25519, Keypair},
+    Multiaddr, PeerId,
+};
+use riker::actors::*;
+use thiserror::Error as DeriveError;
+
+/// Communication actor that was spawned by [`spawn_network`].
+#[derive(Debug, Clone)]
+pub struct TestNode<Req, ClientMsg>
+where
+    Req: Message,
+    ClientMsg: Message,
+{
+    /// Peer id of the node, which is derived with [`deterministic_keypair`] from the name of its actor.
+    pub peer_id: PeerId,
+    /// Address on which the node is listening.
+    pub addr: Multiaddr,
+    /// The communication actor of the node.
+    pub actor: ActorRef<CommunicationRequest<Req, ClientMsg>>,
+}
+
+/// Errors that can occur when spawning the nodes of a test network.
+#[derive(Debug, DeriveError)]
+pub enum TestNetworkError {
+    /// The communication actor of the node at the index could not be created in the actor system.
+    #[error("Spawning communication actor of node {0} failed: {1:?}")]
+    Spawn(usize, CreateError),
+    /// The node at the index could not start listening.
+    #[error("Node {0} failed to start listening: {1}")]
+    Listen(usize, StartListeningError),
+    /// The node at the first index could not connect to the node at the second index.
+    #[error("Node {0} failed to connect node {1}: {2}")]
+    Connect(usize, usize, ConnectPeerError),
+}
+
+/// Create the ed25519 keypair for the seed, so that the peer id of a node is the same in each test run.
+/// The bytes of the seed are folded into the 32 bytes of the secret key. Tests that run at the same time should use
+/// different seeds, e.g. the names of their actors, since peers with the same id could otherwise reach each other's
+/// nodes via the addresses that were discovered with mDNS.
+pub fn deterministic_keypair(seed: &str) -> Keypair {
+    let mut secret = [0u8; 32];
+    for (index, byte) in seed.bytes().enumerate() {
+        secret[index % 32] = secret[index % 32].wrapping_mul(31).wrapping_add(byte);
+    }
+    let secret = ed25519::SecretKey::from_bytes(&mut secret).expect("A 32 bytes secret is always valid.");
+    Keypair::Ed25519(ed25519::Keypair::from(secret))
+}
+
+/// Spawn a communication actor in the system for each config, and connect each pair of nodes with an unlimited
+/// keep-alive. The actors are named `<name>-<index>`, and the keypair of each node is the [`deterministic_keypair`]
+/// for the name of its actor. Each node listens on an OS assigned port on the localhost.
+/// The nodes are returned in the order of the configs.
+pub async fn spawn_network<Req, Res, ClientMsg, P>(
+    system: &ActorSystem,
+    name: &str,
+    configs: Vec<CommunicationActorConfig<Req, Res, ClientMsg>>,
+) -> Result<Vec<TestNode<Req, ClientMsg>>, TestNetworkError>
+where
+    Req: MessageEvent + ToPermissionVariants<P> + Into<ClientMsg>,
+    Res: MessageEvent,
+    ClientMsg: Message,
+    P: Message + VariantPermission,
+{
+    let mut nodes = Vec::with_capacity(configs.len());
+    for (index, config) in configs.into_iter().enumerate() {
+        let actor_name = format!("{}-{}", name, index);
+        let keys = deterministic_keypair(&actor_name);
+        let peer_id = PeerId::from(keys.public());
+        let actor = system
+            .actor_of_args::<CommunicationActor<Req, Res, ClientMsg, P>, _>(
+                &actor_name,
+                (keys, config, BehaviourConfig::default()),
+            )
+            .map_err(|err| TestNetworkError::Spawn(index, err))?;
+        let handle = CommunicationHandle::<Req, Res, ClientMsg>::new(system.clone(), actor.clone());
+        let addr = handle
+            .start_listening(Some("/ip4/127.0.0.1/tcp/0".parse().expect("Valid multiaddr.")))
+            .await
+            .map_err(|err| TestNetworkError::Listen(index, err))?;
+        nodes.push(TestNode { peer_id, addr, actor });
+    }
+    for (index, node) in nodes.iter().enumerate() {
+        let handle = CommunicationHandle::<Req, Res, ClientMsg>::new(system.clone(), node.actor.clone());
+        for (target_index, target) in nodes.iter().enumerate().skip(index + 1) {
+            handle
+                .connect(target.peer_id, target.addr.clone(), KeepAlive::Unlimited)
+                .await
+                .map_err(|err| TestNetworkError::Connect(index, target_index, err))?;
+        }
+    }
+    Ok(nodes)
+}
@@ -4,12 +4,22 @@
 use async_std::task;
 use communication::{
     actor::{
-        CommunicationActor, CommunicationActorConfig, CommunicationRequest, CommunicationResults, ConnectPeerError,
-        FirewallBlocked, FirewallPermission, FirewallRule, KeepAlive, PermissionValue, RequestDirection,
-        RequestMessageError, RequestPermissions, ToPermissionVariants, VariantPermission,
+        AuditConfig, AuditRecord, ClientBreakerConfig, ClientRouter, CommunicationActor, CommunicationActorConfig,
+        CommunicationEvent, CommunicationHandle, CommunicationRequest, CommunicationResults, ConnectPeerError,
+        ConnectionAuthorizer, ConnectionInfo, ConnectionState, FailureBanConfig, FirewallBlocked, FirewallMode,
+        FirewallPermission, FirewallRule, HealthFactor, HealthStatus, InvalidTimeout, KeepAlive, KeepAliveState,
+        ListenerWatchdogConfig, MaintainBackoff, MaintainedState, MatchedRule, OutboundOverflow, PermissionRate,
+        PermissionValue, ProbeError, ProvenanceHook, ReconnectMode, RelayConfig, RequestDirection, RequestHook,
+        RequestMessageError, RequestMsgBuilder, RequestNonce, RequestPermissions, RequestProvenance,
+        ResponseCacheConfig, ResponseHook, StartListeningError, ToPermissionVariants, VariantPermission,
     },
-    behaviour::{BehaviourConfig, P2POutboundFailure},
-    libp2p::{Keypair, Multiaddr, PeerId},
+    behaviour::{
+        multiaddr_to_socket_addr, AddrTransport, BehaviourConfig, ConnectionLimitKind, ConnectionLimitsConfig,
+        P2PEvent, P2PNetworkBehaviour, P2POutboundFailure, P2PReqResEvent, RejectReason, RequestEnvelope,
+        DEFAULT_PROTOCOL,
+    },
+    libp2p::{ConnectedPoint, Keypair, Multiaddr, PeerId, Swarm, SwarmEvent},
+    testing::{deterministic_keypair, spawn_network},
 };
 use riker::actors::*;
 use stronghold_utils::ask;
@@ -17,7 +27,15 @@ use stronghold_utils::ask;
 use core::task::{Context as TaskContext, Poll};
 use futures::{future, prelude::*};
 use serde::{Deserialize, Serialize};
-use std::time::{Duration, Instant};
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, Ipv4Addr, TcpListener},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
+};
 
 fn init_system(
     sys: &ActorSystem,
@@ -27,11 +45,7 @@ fn init_system(
     let keys = Keypair::generate_ed25519();
     let peer_id = PeerId::from(keys.public());
     let behaviour_config = BehaviourConfig::default();
-    let actor_config = CommunicationActorConfig {
-        client,
-        firewall_default_in: FirewallPermission::all(),
-        firewall_default_out: FirewallPermission::all(),
-    };
+    let actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
     let communication_actor = sys
         .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
             "communication",
@@ -41,6 +55,19 @@ fn init_system(
     (peer_id, communication_actor)
 }
 
+// Spawn a communication actor in the system for each config, which are all connected with each other.
+fn spawn_nodes(
+    sys: &ActorSystem,
+    name: &str,
+    configs: Vec<CommunicationActorConfig<Request, Response, Request>>,
+) -> Vec<(PeerId, ActorRef<CommunicationRequest<Request, Request>>)> {
+    task::block_on(spawn_network::<_, Response, _, _>(sys, name, configs))
+        .expect("Failed to spawn network.")
+        .into_iter()
+        .map(|node| (node.peer_id, node.actor))
+        .collect()
+}
+
 // the type of the send request and reponse messages
 #[derive(Debug, Clone, Serialize, Deserialize, RequestPermissions)]
 pub enum Request {
@@ -93,6 +120,85 @@ impl Actor for ReplyActor {
     }
 }
 
+// client that replies to each request after a delay
+#[derive(Clone)]
+struct SlowActor {
+    delay: Duration,
+}
+
+impl ActorFactoryArgs<Duration> for SlowActor {
+    fn create_args(delay: Duration) -> Self {
+        SlowActor { delay }
+    }
+}
+
+impl Actor for SlowActor {
+    type Msg = Request;
+
+    fn recv(&mut self, _ctx: &Context<Self::Msg>, _msg: Self::Msg, sender: Sender) {
+        std::thread::sleep(self.delay);
+        sender
+            .expect("Missing sender.")
+            .try_tell(Response::Pong, None)
+            .expect("Could not tell response.");
+    }
+}
+
+// client that counts the requests that it received, and replies to them
+#[derive(Clone)]
+struct CountingActor {
+    count: Arc<AtomicUsize>,
+}
+
+impl ActorFactoryArgs<Arc<AtomicUsize>> for CountingActor {
+    fn create_args(count: Arc<AtomicUsize>) -> Self {
+        CountingActor { count }
+    }
+}
+
+impl Actor for CountingActor {
+    type Msg = Request;
+
+    fn recv(&mut self, _ctx: &Context<Self::Msg>, _msg: Self::Msg, sender: Sender) {
+        self.count.fetch_add(1, Ordering::SeqCst);
+        sender
+            .expect("Missing sender.")
+            .try_tell(Response::Pong, None)
+            .expect("Could not tell response.");
+    }
+}
+
+// actor that collects the events of the communication actor
+#[derive(Clone)]
+struct ObserverActor {
+    events: Arc<Mutex<Vec<CommunicationEvent>>>,
+}
+
+impl ActorFactoryArgs<Arc<Mutex<Vec<CommunicationEvent>>>> for ObserverActor {
+    fn create_args(events: Arc<Mutex<Vec<CommunicationEvent>>>) -> Self {
+        ObserverActor { events }
+    }
+}
+
+impl Actor for ObserverActor {
+    type Msg = CommunicationEvent;
+
+    fn recv(&mut self, _ctx: &Context<Self::Msg>, msg: Self::Msg, _sender: Sender) {
+        self.events.lock().expect("Failed to lock events.").push(msg);
+    }
+}
+
+// Wait until the observed events match the condition, or panic after 3s.
+fn wait_for_event(events: &Arc<Mutex<Vec<CommunicationEvent>>>, condition: impl Fn(&CommunicationEvent) -> bool) {
+    let start = Instant::now();
+    while !events.lock().expect("Failed to lock events.").iter().any(&condition) {
+        if start.elapsed() > Duration::new(3, 0) {
+            panic!("Event was not emitted.");
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
 #[test]
 fn msg_external_actor() {
     // local actor that receives the results for outgoing requests
@@ -116,10 +222,17 @@ fn msg_external_actor() {
             if let CommunicationResults::RequestMsgResult(Ok(_)) = msg {
                 ctx.stop(&ctx.myself);
             } else if let CommunicationResults::EstablishConnectionResult(result) = msg {
-                let peer_id = result.expect("Panic due to no network connection");
+                let peer_id = result.expect("Panic due to no network connection").peer_id;
                 let req = CommunicationRequest::<Request, Request>::RequestMsg {
                     peer_id,
                     request: Request::Ping,
+                    fallback_addrs: Vec::new(),
+                    bypass_firewall: false,
+                    source_override: None,
+                    cancel_token: None,
+                    deadline: None,
+                    coalesce: None,
+                    hedge: false,
                 };
                 let communication_actor = ctx
                     .select("/user/communication")
@@ -216,7 +329,7 @@ fn establish_connection(
     communication_actor: &ActorRef<CommunicationRequest<Request, Request>>,
     peer_id: PeerId,
     addr: Multiaddr,
-) -> Result<PeerId, ConnectPeerError> {
+) -> Result<ConnectionInfo, ConnectPeerError> {
     match task::block_on(try_ask(
         sys,
         communication_actor,
@@ -254,10 +367,7 @@ fn send_request(
     if let Some(CommunicationResults::RequestMsgResult(res)) = task::block_on(try_ask(
         sys,
         communication_actor,
-        CommunicationRequest::RequestMsg {
-            peer_id,
-            request: Request::Ping,
-        },
+        RequestMsgBuilder::new(peer_id, Request::Ping).build(),
     )) {
         res
     } else {
@@ -293,11 +403,7 @@ fn ask_swarm_info() {
     let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
     let keys = Keypair::generate_ed25519();
     let behaviour_config = BehaviourConfig::default();
-    let actor_config = CommunicationActorConfig {
-        client,
-        firewall_default_in: FirewallPermission::all(),
-        firewall_default_out: FirewallPermission::all(),
-    };
+    let actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
     let communication_actor = sys
         .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
             "communication",
@@ -322,6 +428,20 @@ fn ask_swarm_info() {
         }
         _ => panic!("Unexpected Response"),
     }
+
+    let request = CommunicationRequest::GetSwarmInfoPartial {
+        include_peer_id: false,
+        include_listeners: true,
+        include_connections: false,
+    };
+    match task::block_on(try_ask(&sys, &communication_actor, request)) {
+        Some(CommunicationResults::SwarmInfoPartial {
+            peer_id: None,
+            listeners: Some(listeners),
+            connections: None,
+        }) => assert!(listeners.contains(&addr)),
+        _ => panic!("Unexpected Response"),
+    }
 }
 
 #[test]
@@ -353,20 +473,19 @@ fn ask_request() {
     };
 
     // connect peer A with peer B
-    let connected_peer = establish_connection(
-        &sys_a,
-        &communication_actor_a,
-        peer_b_id,
-        listeners.last().expect("No listeners for peer.").clone(),
-    )
-    .expect("Could not establish connection.");
-    assert_eq!(connected_peer, peer_b_id);
+    let addr_b = listeners.last().expect("No listeners for peer.").clone();
+    let connection = establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b.clone())
+        .expect("Could not establish connection.");
+    assert_eq!(connection.peer_id, peer_b_id);
+    assert_eq!(connection.addr, addr_b);
+    assert!(!connection.is_inbound);
+    assert!(matches!(connection.keep_alive, KeepAlive::Unlimited));
 
     // send message to from A to B
     let res = send_request(&sys_a, &communication_actor_a, peer_b_id);
     assert!(res.is_ok());
     sys_a.stop(&communication_actor_a);
-    sys_b.stop(&communication_actor_b);
+    sys_b.stop(communication_actor_b.clone());
 }
 
 #[test]
@@ -483,56 +602,27 @@ fn manage_connection() {
 
 #[test]
 fn firewall_rules() {
-    // Actor A
-    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
-    let blank_actor = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
-    let keys = Keypair::generate_ed25519();
-    let peer_a_id = PeerId::from(keys.public());
-    let behaviour_config = BehaviourConfig::default();
-    let actor_config = CommunicationActorConfig {
-        client: blank_actor,
-        firewall_default_in: FirewallPermission::none(),
-        firewall_default_out: FirewallPermission::none(),
-    };
-    let communication_actor_a = sys_a
-        .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
-            "communication",
-            (keys, actor_config, behaviour_config.clone()),
-        )
-        .expect("Failed to init actor.");
-
-    // Actor B with firewall that rejects all connections.
-    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
-    let target_actor = sys_b.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
-    let keys = Keypair::generate_ed25519();
-    let peer_b_id = PeerId::from(keys.public());
-    // Set firewall to block all connections per default.
-    let actor_config = CommunicationActorConfig {
-        client: target_actor,
-        firewall_default_in: FirewallPermission::none(),
-        firewall_default_out: FirewallPermission::none(),
-    };
-    let communication_actor_b = sys_b
-        .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
-            "communication",
-            (keys, actor_config, behaviour_config),
-        )
-        .expect("Failed to init actor.");
-
-    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
-
-    let res = establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b);
-    assert!(res.is_ok());
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let blank_actor = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let target_actor = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    // Actor A and actor B with firewalls that reject all requests per default.
+    let configs = vec![
+        CommunicationActorConfig::new(blank_actor, FirewallPermission::none(), FirewallPermission::none()),
+        CommunicationActorConfig::new(target_actor, FirewallPermission::none(), FirewallPermission::none()),
+    ];
+    let nodes = spawn_nodes(&sys, "firewall-rules", configs);
+    let (peer_a_id, communication_actor_a) = nodes[0].clone();
+    let (peer_b_id, communication_actor_b) = nodes[1].clone();
 
     // Outgoing request should be blocked by As firewall
-    match send_request(&sys_a, &communication_actor_a, peer_b_id) {
+    match send_request(&sys, &communication_actor_a, peer_b_id) {
         Err(RequestMessageError::Rejected(FirewallBlocked::Local)) => {}
         _ => panic!("Local firewall should have blocked the request."),
     }
 
     // Set rule for As firewall to allow requests to B
     set_firewall_rule(
-        &sys_a,
+        &sys,
         &communication_actor_a,
         peer_b_id,
         RequestDirection::Out,
@@ -540,7 +630,7 @@ fn firewall_rules() {
     );
 
     // Incoming request should be blocked by Bs firewall
-    match send_request(&sys_a, &communication_actor_a, peer_b_id) {
+    match send_request(&sys, &communication_actor_a, peer_b_id) {
         Err(RequestMessageError::Rejected(FirewallBlocked::Remote))
         | Err(RequestMessageError::Outbound(P2POutboundFailure::Timeout)) => {}
         _ => panic!("Remote firewall should have blocked the request"),
@@ -548,7 +638,7 @@ fn firewall_rules() {
 
     // Set rule for Bs firewall to allow requests from A.
     set_firewall_rule(
-        &sys_b,
+        &sys,
         &communication_actor_b,
         peer_a_id,
         RequestDirection::In,
@@ -556,12 +646,12 @@ fn firewall_rules() {
     );
 
     // Send request
-    let res = send_request(&sys_a, &communication_actor_a, peer_b_id);
+    let res = send_request(&sys, &communication_actor_a, peer_b_id);
     assert!(res.is_ok());
 
     // Forbid requests from A again
     set_firewall_rule(
-        &sys_b,
+        &sys,
         &communication_actor_b,
         peer_a_id,
         RequestDirection::In,
@@ -569,7 +659,7 @@ fn firewall_rules() {
     );
 
     // Requests should be blocked from B again
-    match send_request(&sys_a, &communication_actor_a, peer_b_id) {
+    match send_request(&sys, &communication_actor_a, peer_b_id) {
         Err(RequestMessageError::Rejected(FirewallBlocked::Remote))
         | Err(RequestMessageError::Outbound(P2POutboundFailure::Timeout)) => {}
         _ => panic!("Remote firewall should have blocked the request"),
@@ -578,7 +668,7 @@ fn firewall_rules() {
     // only allow Request::Ping
     let permission = RequestPermission::Ping.permission();
     match task::block_on(try_ask(
-        &sys_b,
+        &sys,
         &communication_actor_b,
         CommunicationRequest::ConfigureFirewall(FirewallRule::AddPermissions {
             peers: vec![peer_a_id],
@@ -592,17 +682,14 @@ fn firewall_rules() {
     }
 
     // Request::Ping should be allowed
-    let res = send_request(&sys_a, &communication_actor_a, peer_b_id);
+    let res = send_request(&sys, &communication_actor_a, peer_b_id);
     assert!(res.is_ok());
 
     // Request::Other should not be allowed
     if let Some(CommunicationResults::RequestMsgResult(res)) = task::block_on(try_ask(
-        &sys_a,
+        &sys,
         &communication_actor_a,
-        CommunicationRequest::RequestMsg {
-            peer_id: peer_b_id,
-            request: Request::Other,
-        },
+        RequestMsgBuilder::new(peer_b_id, Request::Other).build(),
     )) {
         match res {
             Err(RequestMessageError::Rejected(FirewallBlocked::Remote))
@@ -612,4 +699,3509 @@ fn firewall_rules() {
     } else {
         panic!("Unexpected Response");
     }
+
+    // only the permitted requests are counted as inbound requests
+    match task::block_on(try_ask(&sys, &communication_actor_b, CommunicationRequest::GetMetrics)) {
+        Some(CommunicationResults::Metrics(metrics)) => {
+            assert_eq!(metrics.inbound_requests, 2);
+            assert_eq!(metrics.firewall_blocked_in, 3);
+        }
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn request_hooks() {
+    let outgoing = Arc::new(Mutex::new(Vec::new()));
+    let incoming = Arc::new(Mutex::new(Vec::new()));
+
+    // Actor A calls hook on outgoing requests, which replaces `Request::Other` with `Request::Ping`.
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let blank_actor = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let recorded = outgoing.clone();
+    let outgoing_hook: RequestHook<Request> = Arc::new(move |request: &mut Request, peer_id: PeerId| {
+        recorded
+            .lock()
+            .expect("Failed to lock requests.")
+            .push((peer_id, request.clone()));
+        if let Request::Other = request {
+            *request = Request::Ping;
+        }
+    });
+    let mut config_a = CommunicationActorConfig::new(blank_actor, FirewallPermission::all(), FirewallPermission::all());
+    config_a.outgoing_request_hook = Some(outgoing_hook);
+
+    // Actor B calls hook on incoming requests, and only permits `Request::Ping`.
+    let target_actor = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let recorded = incoming.clone();
+    let incoming_hook: RequestHook<Request> = Arc::new(move |request: &mut Request, peer_id: PeerId| {
+        recorded
+            .lock()
+            .expect("Failed to lock requests.")
+            .push((peer_id, request.clone()));
+    });
+    let permission = FirewallPermission::none().add_permission(&RequestPermission::Ping.permission());
+    let mut config_b = CommunicationActorConfig::new(target_actor, permission, FirewallPermission::all());
+    config_b.incoming_request_hook = Some(incoming_hook);
+
+    let nodes = spawn_nodes(&sys, "request-hooks", vec![config_a, config_b]);
+    let (peer_a_id, communication_actor_a) = nodes[0].clone();
+    let peer_b_id = nodes[1].0;
+
+    let res = send_request(&sys, &communication_actor_a, peer_b_id);
+    assert!(res.is_ok());
+    // B's firewall would block `Request::Other`, but the outgoing hook replaced it.
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor_a,
+        RequestMsgBuilder::new(peer_b_id, Request::Other).build(),
+    )) {
+        Some(CommunicationResults::RequestMsgResult(res)) => assert!(res.is_ok()),
+        _ => panic!("Unexpected Response"),
+    }
+
+    let outgoing = outgoing.lock().expect("Failed to lock requests.");
+    assert_eq!(outgoing.len(), 2);
+    assert!(matches!(outgoing[0], (peer_id, Request::Ping) if peer_id == peer_b_id));
+    assert!(matches!(outgoing[1], (peer_id, Request::Other) if peer_id == peer_b_id));
+    // The incoming hook receives the rewritten request, with the source of the envelope.
+    let incoming = incoming.lock().expect("Failed to lock requests.");
+    assert_eq!(incoming.len(), 2);
+    assert!(incoming
+        .iter()
+        .all(|(peer_id, request)| *peer_id == peer_a_id && matches!(request, Request::Ping)));
+}
+
+#[test]
+fn request_provenance() {
+    let provenances = Arc::new(Mutex::new(Vec::new()));
+
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let target_actor = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let received = provenances.clone();
+    let provenance_hook: ProvenanceHook<Request> =
+        Arc::new(move |_request: &mut Request, provenance: RequestProvenance| {
+            received.lock().unwrap().push(provenance);
+        });
+    let mut config_b =
+        CommunicationActorConfig::new(target_actor, FirewallPermission::all(), FirewallPermission::all());
+    config_b.provenance_hook = Some(provenance_hook);
+
+    let nodes = spawn_nodes(&sys, "request-provenance", vec![config_a, config_b]);
+    let (peer_a_id, communication_actor_a) = nodes[0].clone();
+    let peer_b_id = nodes[1].0;
+    let res = send_request(&sys, &communication_actor_a, peer_b_id);
+    assert!(res.is_ok());
+
+    let provenances = provenances.lock().unwrap();
+    assert_eq!(provenances.len(), 1);
+    assert_eq!(provenances[0].source, peer_a_id);
+    assert!(!provenances[0].is_relayed());
+}
+
+#[test]
+fn response_hook() {
+    let sources = Arc::new(Mutex::new(Vec::new()));
+
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let target_actor = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let received = sources.clone();
+    let response_hook: ResponseHook<Response> = Arc::new(move |_response: &mut Response, peer_id: PeerId| {
+        received.lock().unwrap().push(peer_id);
+    });
+    let mut config_b =
+        CommunicationActorConfig::new(target_actor, FirewallPermission::all(), FirewallPermission::all());
+    config_b.response_hook = Some(response_hook);
+
+    let nodes = spawn_nodes(&sys, "response-hook", vec![config_a, config_b]);
+    let (peer_a_id, communication_actor_a) = nodes[0].clone();
+    let peer_b_id = nodes[1].0;
+    let res = send_request(&sys, &communication_actor_a, peer_b_id);
+    assert!(matches!(res, Ok(Response::Pong)));
+
+    assert_eq!(*sources.lock().unwrap(), vec![peer_a_id]);
+}
+
+#[test]
+fn report_responses_sent() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let target_actor = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = sys
+        .actor_of_args::<ObserverActor, _>("observer", events.clone())
+        .expect("Failed to init actor.");
+    let request_ids = Arc::new(Mutex::new(Vec::new()));
+    let received = request_ids.clone();
+    let provenance_hook: ProvenanceHook<Request> =
+        Arc::new(move |_request: &mut Request, provenance: RequestProvenance| {
+            received.lock().unwrap().push(provenance.request_id);
+        });
+    let mut config_b =
+        CommunicationActorConfig::new(target_actor, FirewallPermission::all(), FirewallPermission::all());
+    config_b.observer = Some(observer);
+    config_b.report_responses_sent = true;
+    config_b.send_firewall_rejections = true;
+    config_b.provenance_hook = Some(provenance_hook);
+
+    let nodes = spawn_nodes(&sys, "report-responses-sent", vec![config_a, config_b]);
+    let (peer_a_id, communication_actor_a) = nodes[0].clone();
+    let (peer_b_id, communication_actor_b) = nodes[1].clone();
+    let res = send_request(&sys, &communication_actor_a, peer_b_id);
+    assert!(matches!(res, Ok(Response::Pong)));
+
+    // the event has the id that was passed to the provenance hook
+    let request_id = request_ids.lock().unwrap()[0];
+    wait_for_event(&events, |event| match event {
+        CommunicationEvent::ResponseSent {
+            peer_id,
+            request_id: sent_id,
+        } => *peer_id == peer_a_id && *sent_id == request_id,
+        _ => false,
+    });
+
+    // rejections are not reported
+    set_firewall_rule(
+        &sys,
+        &communication_actor_b,
+        peer_a_id,
+        RequestDirection::In,
+        FirewallPermission::none(),
+    );
+    assert!(matches!(
+        send_request(&sys, &communication_actor_a, peer_b_id),
+        Err(RequestMessageError::Rejected(FirewallBlocked::Remote))
+    ));
+    std::thread::sleep(Duration::from_millis(200));
+    let events = events.lock().unwrap();
+    let sent = events
+        .iter()
+        .filter(|event| matches!(event, CommunicationEvent::ResponseSent { .. }))
+        .count();
+    assert_eq!(sent, 1);
+}
+
+#[test]
+fn check_connection() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_a) = init_system(&sys_a, client);
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b) = init_system(&sys_b, client);
+
+    let check_connection = |peer_id| match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::CheckConnection(peer_id),
+    )) {
+        Some(CommunicationResults::CheckConnectionResult { peer_id: _, state }) => state,
+        _ => panic!("Unexpected Response"),
+    };
+
+    assert_eq!(check_connection(peer_b_id), ConnectionState::Disconnected);
+
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+    let res = establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b);
+    assert!(res.is_ok());
+
+    assert_eq!(check_connection(peer_b_id), ConnectionState::Connected);
+    assert_eq!(check_connection(PeerId::random()), ConnectionState::Disconnected);
+}
+
+#[test]
+fn known_peers() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let client = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let nodes = spawn_nodes(&sys, "known-peers", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let peer_b_id = nodes[1].0;
+
+    // the address of an unreachable peer is added to the address book
+    let peer_c_id = PeerId::random();
+    let addr_c: Multiaddr = "/ip4/127.0.0.1/tcp/1".parse().expect("Invalid Multiaddress.");
+    let _ = task::block_on(try_ask(
+        &sys,
+        &communication_actor_a,
+        RequestMsgBuilder::new(peer_c_id, Request::Ping)
+            .fallback_addr(addr_c.clone())
+            .build(),
+    ));
+
+    let peers = match task::block_on(try_ask(
+        &sys,
+        &communication_actor_a,
+        CommunicationRequest::GetKnownPeers,
+    )) {
+        Some(CommunicationResults::KnownPeers(peers)) => peers,
+        _ => panic!("Unexpected Response"),
+    };
+    assert!(peers
+        .iter()
+        .any(|(peer_id, _, state)| *peer_id == peer_b_id && *state == ConnectionState::Connected));
+    assert!(peers.iter().any(|(peer_id, addrs, state)| *peer_id == peer_c_id
+        && addrs.contains(&addr_c)
+        && *state == ConnectionState::Disconnected));
+}
+
+#[test]
+fn address_book() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let client = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let nodes = spawn_nodes(&sys, "address-book", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let peer_b_id = nodes[1].0;
+
+    let peer_c_id = PeerId::random();
+    let addr_c: Multiaddr = "/ip4/127.0.0.1/tcp/1".parse().expect("Invalid Multiaddress.");
+    let _ = task::block_on(try_ask(
+        &sys,
+        &communication_actor_a,
+        RequestMsgBuilder::new(peer_c_id, Request::Ping)
+            .fallback_addr(addr_c.clone())
+            .build(),
+    ));
+
+    let address_book = || match task::block_on(try_ask(
+        &sys,
+        &communication_actor_a,
+        CommunicationRequest::GetAddressBook,
+    )) {
+        Some(CommunicationResults::AddressBook(address_book)) => address_book,
+        _ => panic!("Unexpected Response"),
+    };
+    let clear = |peer_id| match task::block_on(try_ask(
+        &sys,
+        &communication_actor_a,
+        CommunicationRequest::ClearAddressBook(peer_id),
+    )) {
+        Some(CommunicationResults::ClearAddressBookAck) => {}
+        _ => panic!("Unexpected Response"),
+    };
+    assert_eq!(address_book().get(&peer_c_id), Some(&vec![addr_c]));
+
+    clear(Some(peer_c_id));
+    assert!(!address_book().contains_key(&peer_c_id));
+
+    // clearing the addresses does not close the connections
+    clear(None);
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor_a,
+        CommunicationRequest::CheckConnection(peer_b_id),
+    )) {
+        Some(CommunicationResults::CheckConnectionResult { state, .. }) => {
+            assert_eq!(state, ConnectionState::Connected)
+        }
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn request_source_override() {
+    let sys_proxy = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_proxy
+        .actor_of::<BlankActor>("blank")
+        .expect("Failed to init actor.");
+    let (proxy_id, communication_actor_proxy) = init_system(&sys_proxy, client);
+    let proxy_addr = start_listening(&sys_proxy, &communication_actor_proxy, None);
+
+    let sys_dest = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_dest
+        .actor_of::<ReplyActor>("target")
+        .expect("Failed to init actor.");
+    let (dest_id, communication_actor_dest) = init_system(&sys_dest, client);
+
+    // the destination trusts the proxy as relay for the original requester, but not the proxy itself
+    let source_id = PeerId::random();
+    match task::block_on(try_ask(
+        &sys_dest,
+        &communication_actor_dest,
+        CommunicationRequest::EstablishConnectionViaRelay {
+            peer_id: source_id,
+            relay_peer: proxy_id,
+            relay_addr: proxy_addr,
+        },
+    )) {
+        Some(CommunicationResults::EstablishConnectionResult(res)) => assert!(res.is_ok()),
+        _ => panic!("Unexpected Response"),
+    }
+    set_firewall_rule(
+        &sys_dest,
+        &communication_actor_dest,
+        proxy_id,
+        RequestDirection::In,
+        FirewallPermission::none(),
+    );
+
+    match send_request(&sys_proxy, &communication_actor_proxy, dest_id) {
+        Err(RequestMessageError::Rejected(FirewallBlocked::Remote))
+        | Err(RequestMessageError::Outbound(P2POutboundFailure::Timeout)) => {}
+        _ => panic!("Remote firewall should have blocked the request"),
+    }
+    match task::block_on(try_ask(
+        &sys_proxy,
+        &communication_actor_proxy,
+        RequestMsgBuilder::new(dest_id, Request::Ping)
+            .source_override(source_id)
+            .build(),
+    )) {
+        Some(CommunicationResults::RequestMsgResult(res)) => assert!(matches!(res, Ok(Response::Pong))),
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn relay_permission() {
+    let sys_proxy = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_proxy
+        .actor_of::<BlankActor>("blank")
+        .expect("Failed to init actor.");
+    let (proxy_id, communication_actor_proxy) = init_system(&sys_proxy, client);
+    let proxy_addr = start_listening(&sys_proxy, &communication_actor_proxy, None);
+
+    let sys_dest = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_dest
+        .actor_of::<ReplyActor>("target")
+        .expect("Failed to init actor.");
+    let (dest_id, communication_actor_dest) = init_system(&sys_dest, client);
+
+    // the destination trusts the proxy as relay for the requester, but only to forward `Request::Other`
+    let source_id = PeerId::random();
+    match task::block_on(try_ask(
+        &sys_dest,
+        &communication_actor_dest,
+        CommunicationRequest::EstablishConnectionViaRelay {
+            peer_id: source_id,
+            relay_peer: proxy_id,
+            relay_addr: proxy_addr,
+        },
+    )) {
+        Some(CommunicationResults::EstablishConnectionResult(res)) => assert!(res.is_ok()),
+        _ => panic!("Unexpected Response"),
+    }
+    let permission = FirewallPermission::none().add_permission(&RequestPermission::Other.permission());
+    match task::block_on(try_ask(
+        &sys_dest,
+        &communication_actor_dest,
+        CommunicationRequest::ConfigureFirewall(FirewallRule::SetRelayPermission(Some(permission))),
+    )) {
+        Some(CommunicationResults::ConfigureFirewallAck) => {}
+        _ => panic!("Unexpected Response"),
+    }
+
+    let send_relayed = |request| match task::block_on(try_ask(
+        &sys_proxy,
+        &communication_actor_proxy,
+        RequestMsgBuilder::new(dest_id, request)
+            .source_override(source_id)
+            .build(),
+    )) {
+        Some(CommunicationResults::RequestMsgResult(res)) => res,
+        _ => panic!("Unexpected Response"),
+    };
+    match send_relayed(Request::Ping) {
+        Err(RequestMessageError::Rejected(FirewallBlocked::Remote))
+        | Err(RequestMessageError::Outbound(P2POutboundFailure::Timeout)) => {}
+        _ => panic!("Remote firewall should have blocked the relayed request"),
+    }
+    assert!(matches!(send_relayed(Request::Other), Ok(Response::Pong)));
+}
+
+#[test]
+fn establish_connection_via_relay() {
+    let sys_proxy = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_proxy
+        .actor_of::<BlankActor>("blank")
+        .expect("Failed to init actor.");
+    let (proxy_id, communication_actor_proxy) = init_system(&sys_proxy, client);
+    let proxy_addr = start_listening(&sys_proxy, &communication_actor_proxy, None);
+
+    let sys_dest = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_dest
+        .actor_of::<ReplyActor>("target")
+        .expect("Failed to init actor.");
+    let (dest_id, communication_actor_dest) = init_system(&sys_dest, client);
+
+    // the returned connection is the keep-alive connection to the relay, not to the peer
+    let source_id = PeerId::random();
+    match task::block_on(try_ask(
+        &sys_dest,
+        &communication_actor_dest,
+        CommunicationRequest::EstablishConnectionViaRelay {
+            peer_id: source_id,
+            relay_peer: proxy_id,
+            relay_addr: proxy_addr,
+        },
+    )) {
+        Some(CommunicationResults::EstablishConnectionResult(Ok(info))) => {
+            assert_eq!(info.peer_id, proxy_id);
+            assert!(!info.is_inbound);
+            assert!(matches!(info.keep_alive, KeepAlive::Unlimited));
+        }
+        _ => panic!("Unexpected Response"),
+    }
+
+    // requests of the peer that are forwarded by the relay are accepted
+    match task::block_on(try_ask(
+        &sys_proxy,
+        &communication_actor_proxy,
+        RequestMsgBuilder::new(dest_id, Request::Ping)
+            .source_override(source_id)
+            .build(),
+    )) {
+        Some(CommunicationResults::RequestMsgResult(res)) => assert!(matches!(res, Ok(Response::Pong))),
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn close_relayed_connection() {
+    let sys_proxy = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_proxy
+        .actor_of::<BlankActor>("blank")
+        .expect("Failed to init actor.");
+    let (proxy_id, communication_actor_proxy) = init_system(&sys_proxy, client);
+    let proxy_addr = start_listening(&sys_proxy, &communication_actor_proxy, None);
+
+    let sys_dest = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_dest
+        .actor_of::<ReplyActor>("target")
+        .expect("Failed to init actor.");
+    let (dest_id, communication_actor_dest) = init_system(&sys_dest, client);
+
+    let source_id = PeerId::random();
+    let ask_dest = |request| task::block_on(try_ask(&sys_dest, &communication_actor_dest, request));
+    let via_relay = || match ask_dest(CommunicationRequest::EstablishConnectionViaRelay {
+        peer_id: source_id,
+        relay_peer: proxy_id,
+        relay_addr: proxy_addr.clone(),
+    }) {
+        Some(CommunicationResults::EstablishConnectionResult(res)) => assert!(res.is_ok()),
+        _ => panic!("Unexpected Response"),
+    };
+    let close_relayed = || match ask_dest(CommunicationRequest::CloseRelayedConnection(source_id)) {
+        Some(CommunicationResults::CloseRelayedConnectionAck) => {}
+        _ => panic!("Unexpected Response"),
+    };
+    // forwarded requests of the source are dropped while the relayed path is closed
+    let is_relayed = || {
+        let res = task::block_on(try_ask(
+            &sys_proxy,
+            &communication_actor_proxy,
+            RequestMsgBuilder::new(dest_id, Request::Ping)
+                .source_override(source_id)
+                .build(),
+        ));
+        matches!(res, Some(CommunicationResults::RequestMsgResult(Ok(Response::Pong))))
+    };
+
+    via_relay();
+    assert!(is_relayed());
+    close_relayed();
+    assert!(!is_relayed());
+
+    // setting the relay for the peer again opens the relayed path
+    via_relay();
+    assert!(is_relayed());
+
+    // changing the relay config opens the relayed path
+    close_relayed();
+    assert!(!is_relayed());
+    match ask_dest(CommunicationRequest::SetRelay(RelayConfig::NoRelay)) {
+        Some(CommunicationResults::SetRelayResult(res)) => assert!(res.is_ok()),
+        _ => panic!("Unexpected Response"),
+    }
+    assert!(is_relayed());
+}
+
+#[test]
+fn reconfigure_network() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_a) = init_system(&sys_a, client);
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b) = init_system(&sys_b, client);
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+
+    let reconfigure = |relay| match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::ReconfigureNetwork {
+            relay,
+            firewall: vec![FirewallRule::DenyAll {
+                direction: RequestDirection::In,
+            }],
+        },
+    )) {
+        Some(CommunicationResults::ReconfigureNetworkResult(res)) => res,
+        _ => panic!("Unexpected Response"),
+    };
+    let get_default = || match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::GetFirewallDefault(RequestDirection::In),
+    )) {
+        Some(CommunicationResults::FirewallDefault(permission)) => permission,
+        _ => panic!("Unexpected Response"),
+    };
+
+    // the rules are not applied if the relay could not be connected
+    let unreachable_relay = RelayConfig::RelayAlways {
+        peer_id: PeerId::random(),
+        addr: "/ip4/127.0.0.1/tcp/1".parse().expect("Invalid Multiaddress."),
+    };
+    assert!(reconfigure(Some(unreachable_relay)).is_err());
+    assert!(get_default().is_all());
+
+    let relay = RelayConfig::RelayAlways {
+        peer_id: peer_b_id,
+        addr: addr_b,
+    };
+    assert!(reconfigure(Some(relay)).is_ok());
+    assert!(get_default().is_none());
+    match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::GetSwarmInfo,
+    )) {
+        Some(CommunicationResults::SwarmInfo { connections, .. }) => {
+            assert!(connections.iter().any(|(peer_id, _)| *peer_id == peer_b_id))
+        }
+        _ => panic!("Unexpected Response"),
+    }
+
+    // the rules are applied without changing the relay
+    assert!(reconfigure(None).is_ok());
+}
+
+#[test]
+fn request_fallback_addrs() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_a) = init_system(&sys_a, client);
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b) = init_system(&sys_b, client);
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+
+    // send request without establishing a connection first
+    match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        RequestMsgBuilder::new(peer_b_id, Request::Ping)
+            .fallback_addr(addr_b)
+            .build(),
+    )) {
+        Some(CommunicationResults::RequestMsgResult(res)) => assert!(res.is_ok()),
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn communication_handle() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (peer_a_id, communication_actor_a) = init_system(&sys_a, client);
+    let handle_a = CommunicationHandle::<_, Response, _>::new(sys_a.clone(), communication_actor_a);
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b) = init_system(&sys_b, client);
+    let handle_b = CommunicationHandle::<_, Response, _>::new(sys_b.clone(), communication_actor_b);
+
+    task::block_on(async {
+        let addr_b = handle_b
+            .start_listening(None)
+            .await
+            .expect("Failed to start listening.");
+        let res = handle_a.connect(peer_b_id, addr_b, KeepAlive::None).await;
+        assert_eq!(res.expect("Failed to connect peer.").peer_id, peer_b_id);
+        let res = handle_a.send_request(peer_b_id, Request::Ping).await;
+        assert_eq!(res.expect("Request failed."), Response::Pong);
+
+        let info = handle_a.swarm_info().await;
+        assert_eq!(info.peer_id, peer_a_id);
+        assert!(info.connections.iter().any(|(peer_id, _)| *peer_id == peer_b_id));
+    });
+}
+
+#[test]
+fn protocol_timeouts() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_a) = init_system(&sys_a, client);
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b) = init_system(&sys_b, client);
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+
+    match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::SetProtocolTimeouts {
+            request_timeout: Some(Duration::from_secs(5)),
+            connection_timeout: Some(Duration::from_secs(5)),
+        },
+    )) {
+        Some(CommunicationResults::SetProtocolTimeoutsAck) => {}
+        _ => panic!("Unexpected Response"),
+    }
+    let res = establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b);
+    assert!(res.is_ok());
+    let res = send_request(&sys_a, &communication_actor_a, peer_b_id);
+    assert_eq!(res.expect("Request failed."), Response::Pong);
+}
+
+#[test]
+fn pending_requests() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_a) = init_system(&sys_a, client);
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b) = init_system(&sys_b, client);
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+
+    let pending_requests = |peer_id| match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::GetPendingRequests(peer_id),
+    )) {
+        Some(CommunicationResults::PendingRequests(count)) => count,
+        _ => panic!("Unexpected Response"),
+    };
+
+    let res = establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b);
+    assert!(res.is_ok());
+    assert_eq!(pending_requests(None), 0);
+
+    // answered requests are not pending anymore
+    let res = send_request(&sys_a, &communication_actor_a, peer_b_id);
+    assert!(res.is_ok());
+    assert_eq!(pending_requests(Some(peer_b_id)), 0);
+    assert_eq!(pending_requests(None), 0);
+}
+
+#[test]
+fn dump_state() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (peer_a_id, communication_actor_a) = init_system(&sys_a, client);
+
+    // the client of b never responds
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b) = init_system(&sys_b, client);
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+
+    match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::SetProtocolTimeouts {
+            request_timeout: Some(Duration::from_millis(200)),
+            connection_timeout: None,
+        },
+    )) {
+        Some(CommunicationResults::SetProtocolTimeoutsAck) => {}
+        _ => panic!("Unexpected Response"),
+    }
+    let res = establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b);
+    assert!(res.is_ok());
+    let res = send_request(&sys_a, &communication_actor_a, peer_b_id);
+    assert!(res.is_err());
+
+    // the request timed out locally, but is still awaiting the response of b
+    match task::block_on(try_ask(&sys_a, &communication_actor_a, CommunicationRequest::DumpState)) {
+        Some(CommunicationResults::StateDump(dump)) => {
+            assert!(dump.pending_dials.is_empty());
+            assert_eq!(dump.outbound_requests.len(), 1);
+            assert_eq!(dump.outbound_requests[0].peer_id, peer_b_id);
+            assert!(dump.outbound_requests[0].elapsed >= Duration::from_millis(200));
+            assert!(dump.queued_inbound.is_empty());
+            assert!(dump.inbound_in_flight.is_empty());
+            assert_eq!(dump.queued_outbound, 0);
+            assert_eq!(dump.deferred_actor_requests, 0);
+            assert_eq!(dump.coalesced_requests, 0);
+        }
+        _ => panic!("Unexpected Response"),
+    }
+
+    // the client of b is still servicing the request
+    match task::block_on(try_ask(&sys_b, &communication_actor_b, CommunicationRequest::DumpState)) {
+        Some(CommunicationResults::StateDump(dump)) => {
+            assert_eq!(dump.inbound_in_flight.len(), 1);
+            assert_eq!(dump.inbound_in_flight[0].0, peer_a_id);
+        }
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn cancel_request() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    // the client of b never responds
+    let client = sys.actor_of::<BlankActor>("target").expect("Failed to init actor.");
+    let config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let nodes = spawn_nodes(&sys, "cancel-request", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let peer_b_id = nodes[1].0;
+    let cancel = |token| match task::block_on(try_ask(
+        &sys,
+        &communication_actor_a,
+        CommunicationRequest::CancelRequest(token),
+    )) {
+        Some(CommunicationResults::CancelRequestResult(is_cancelled)) => is_cancelled,
+        _ => panic!("Unexpected Response"),
+    };
+
+    let start = Instant::now();
+    let pending: future::RemoteHandle<CommunicationResults<Response>> = ask(
+        &sys,
+        &communication_actor_a,
+        RequestMsgBuilder::new(peer_b_id, Request::Ping).cancel_token(7).build(),
+    );
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(!cancel(8));
+    assert!(cancel(7));
+    match task::block_on(pending) {
+        CommunicationResults::RequestMsgResult(res) => assert!(matches!(res, Err(RequestMessageError::Cancelled))),
+        _ => panic!("Unexpected Response"),
+    }
+    // the actor stopped waiting before the request timed out
+    assert!(start.elapsed() < Duration::from_secs(3));
+    assert!(!cancel(7));
+}
+
+#[test]
+fn request_deadline() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    // the client of b never responds
+    let client = sys.actor_of::<BlankActor>("target").expect("Failed to init actor.");
+    let config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let nodes = spawn_nodes(&sys, "request-deadline", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let peer_b_id = nodes[1].0;
+    let send_with_deadline = |deadline| match task::block_on(try_ask(
+        &sys,
+        &communication_actor_a,
+        RequestMsgBuilder::new(peer_b_id, Request::Ping)
+            .deadline(deadline)
+            .build(),
+    )) {
+        Some(CommunicationResults::RequestMsgResult(res)) => res,
+        _ => panic!("Unexpected Response"),
+    };
+
+    // the request fails at the deadline, before the request timeout of 3s passed
+    let start = Instant::now();
+    let res = send_with_deadline(start + Duration::from_millis(300));
+    assert!(matches!(res, Err(RequestMessageError::DeadlineExceeded)));
+    assert!(start.elapsed() >= Duration::from_millis(300));
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    // requests whose deadline already passed are not sent
+    let res = send_with_deadline(Instant::now());
+    assert!(matches!(res, Err(RequestMessageError::DeadlineExceeded)));
+}
+
+#[test]
+fn client_ask_timeout() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_a) = init_system(&sys_a, client);
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b) = init_system(&sys_b, client);
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+
+    let set_timeout = |timeout| match task::block_on(try_ask(
+        &sys_b,
+        &communication_actor_b,
+        CommunicationRequest::SetClientAskTimeout(timeout),
+    )) {
+        Some(CommunicationResults::SetClientAskTimeoutResult(res)) => res,
+        _ => panic!("Unexpected Response"),
+    };
+    assert_eq!(
+        set_timeout(Duration::from_secs(0)),
+        Err(InvalidTimeout(Duration::from_secs(0)))
+    );
+    assert_eq!(set_timeout(Duration::from_secs(5)), Ok(Duration::from_secs(3)));
+    assert_eq!(set_timeout(Duration::from_secs(2)), Ok(Duration::from_secs(5)));
+
+    let res = establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b);
+    assert!(res.is_ok());
+    let res = send_request(&sys_a, &communication_actor_a, peer_b_id);
+    assert_eq!(res.expect("Request failed."), Response::Pong);
+}
+
+#[test]
+fn slow_client_timeout() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    // the client of peer b responds after 1s
+    let client = sys
+        .actor_of_args::<SlowActor, _>("slow", Duration::from_secs(1))
+        .expect("Failed to init actor.");
+    let config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let nodes = spawn_nodes(&sys, "slow-client-timeout", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let (peer_b_id, communication_actor_b) = nodes[1].clone();
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor_a,
+        CommunicationRequest::SetProtocolTimeouts {
+            request_timeout: Some(Duration::from_secs(2)),
+            connection_timeout: None,
+        },
+    )) {
+        Some(CommunicationResults::SetProtocolTimeoutsAck) => {}
+        _ => panic!("Unexpected Response"),
+    }
+    let set_timeout = |timeout| match task::block_on(try_ask(
+        &sys,
+        &communication_actor_b,
+        CommunicationRequest::SetClientAskTimeout(timeout),
+    )) {
+        Some(CommunicationResults::SetClientAskTimeoutResult(res)) => assert!(res.is_ok()),
+        _ => panic!("Unexpected Response"),
+    };
+
+    // the request is dropped once the client timed out, without waiting for the late response
+    set_timeout(Duration::from_millis(300));
+    let start = Instant::now();
+    let res = send_request(&sys, &communication_actor_a, peer_b_id);
+    assert!(res.is_err());
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    // peer b is not blocked by the late response of the client
+    match task::block_on(try_ask(&sys, &communication_actor_b, CommunicationRequest::GetMetrics)) {
+        Some(CommunicationResults::Metrics(metrics)) => assert_eq!(metrics.inbound_in_flight, 0),
+        _ => panic!("Unexpected Response"),
+    }
+
+    // wait until the client finished the dropped request
+    std::thread::sleep(Duration::from_secs(1));
+    set_timeout(Duration::from_millis(1500));
+    let res = send_request(&sys, &communication_actor_a, peer_b_id);
+    assert_eq!(res.expect("Request failed."), Response::Pong);
+}
+
+#[test]
+fn websocket_request() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_a) = init_system(&sys_a, client);
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b) = init_system(&sys_b, client);
+    let ws_addr: Multiaddr = "/ip4/127.0.0.1/tcp/0/ws".parse().expect("Invalid Multiaddress.");
+    let addr_b = start_listening(&sys_b, &communication_actor_b, Some(ws_addr));
+    assert!(addr_b.to_string().ends_with("/ws"));
+
+    let res = establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b);
+    assert!(res.is_ok());
+    let res = send_request(&sys_a, &communication_actor_a, peer_b_id);
+    assert_eq!(res.expect("Request over websocket failed."), Response::Pong);
+}
+
+#[test]
+fn listen_local() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_a) = init_system(&sys_a, client);
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b) = init_system(&sys_b, client);
+    let addr_b = match task::block_on(try_ask(
+        &sys_b,
+        &communication_actor_b,
+        CommunicationRequest::StartListeningLocal,
+    )) {
+        Some(CommunicationResults::StartListeningResult(a)) => a.expect("Failed to start listening."),
+        _ => panic!("Unexpected Response"),
+    };
+    assert!(addr_b.to_string().starts_with("/ip4/127.0.0.1/tcp/"));
+
+    let res = establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b);
+    assert!(res.is_ok());
+    let res = send_request(&sys_a, &communication_actor_a, peer_b_id);
+    assert_eq!(res.expect("Request via loopback failed."), Response::Pong);
+
+    match task::block_on(try_ask(
+        &sys_b,
+        &communication_actor_b,
+        CommunicationRequest::RemoveListener,
+    )) {
+        Some(CommunicationResults::RemoveListenerResult(res)) => assert!(res.is_ok()),
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn request_group() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let client = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let nodes = spawn_nodes(&sys, "request-group", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let peer_b_id = nodes[1].0;
+
+    let request_group = || match task::block_on(try_ask(
+        &sys,
+        &communication_actor_a,
+        CommunicationRequest::RequestMsgGroup {
+            group: "group".into(),
+            request: Request::Ping,
+        },
+    )) {
+        Some(CommunicationResults::RequestMsgGroupResult(res)) => res,
+        _ => panic!("Unexpected Response"),
+    };
+    assert!(request_group().is_empty());
+
+    let res = task::block_on(try_ask(
+        &sys,
+        &communication_actor_a,
+        CommunicationRequest::CreateGroup {
+            name: "group".into(),
+            peers: vec![peer_b_id, peer_b_id],
+        },
+    ));
+    assert!(matches!(res, Some(CommunicationResults::CreateGroupAck)));
+    let res = request_group();
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0].0, peer_b_id);
+    assert_eq!(res[0].1.as_ref().expect("Request failed."), &Response::Pong);
+
+    let res = task::block_on(try_ask(
+        &sys,
+        &communication_actor_a,
+        CommunicationRequest::RemoveFromGroup {
+            group: "group".into(),
+            peers: vec![peer_b_id],
+        },
+    ));
+    assert!(matches!(res, Some(CommunicationResults::RemoveFromGroupAck)));
+    assert!(request_group().is_empty());
+}
+
+#[test]
+fn connect_retained_addr() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_a) = init_system(&sys_a, client);
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b) = init_system(&sys_b, client);
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+
+    let connect = |addr: Multiaddr| match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::EstablishConnection {
+            addr,
+            peer_id: peer_b_id,
+            keep_alive: KeepAlive::None,
+        },
+    )) {
+        Some(CommunicationResults::EstablishConnectionResult(res)) => res,
+        _ => panic!("Unexpected Response"),
+    };
+    assert_eq!(connect(addr_b).expect("Failed to connect peer.").peer_id, peer_b_id);
+
+    // close the connection
+    task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::BanPeer(peer_b_id),
+    ));
+    task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::UnbanPeer(peer_b_id),
+    ));
+
+    // the address of the previous connection is dialed together with the invalid target address
+    let invalid_addr: Multiaddr = "/ip4/127.0.0.1/tcp/1".parse().expect("Invalid Multiaddress.");
+    assert_eq!(
+        connect(invalid_addr).expect("Failed to connect peer.").peer_id,
+        peer_b_id
+    );
+}
+
+#[test]
+fn health_summary() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_a) = init_system(&sys_a, client);
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b) = init_system(&sys_b, client);
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+
+    let health = |sys: &ActorSystem, communication_actor: &ActorRef<CommunicationRequest<Request, Request>>| {
+        match task::block_on(try_ask(sys, communication_actor, CommunicationRequest::GetHealth)) {
+            Some(CommunicationResults::Health(health)) => health,
+            _ => panic!("Unexpected Response"),
+        }
+    };
+    let health_a = health(&sys_a, &communication_actor_a);
+    assert_eq!(health_a.status, HealthStatus::Unhealthy);
+    assert!(health_a.factors.contains(&HealthFactor::NotListening));
+    assert_eq!(health_a.relay_connected, None);
+
+    let health_b = health(&sys_b, &communication_actor_b);
+    assert_eq!(health_b.status, HealthStatus::Degraded);
+    assert_eq!(health_b.factors, vec![HealthFactor::NoConnectedPeers]);
+
+    let res = establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b);
+    assert!(res.is_ok());
+    // start a new period for the utilization of the event loop, which was blocked while connecting
+    health(&sys_b, &communication_actor_b);
+    std::thread::sleep(Duration::from_millis(200));
+    let health_b = health(&sys_b, &communication_actor_b);
+    assert_eq!(health_b.status, HealthStatus::Healthy);
+    assert_eq!(health_b.connected_peers, 1);
+    assert!(health_b.loop_utilization < 0.9);
+}
+
+#[test]
+fn disable_relay() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_a) = init_system(&sys_a, client);
+
+    let sys_relay = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_relay
+        .actor_of::<BlankActor>("blank")
+        .expect("Failed to init actor.");
+    let (relay_id, communication_actor_relay) = init_system(&sys_relay, client);
+    let relay_addr = start_listening(&sys_relay, &communication_actor_relay, None);
+
+    let set_relay = |config: RelayConfig| match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::SetRelay(config),
+    )) {
+        Some(CommunicationResults::SetRelayResult(res)) => res,
+        _ => panic!("Unexpected Response"),
+    };
+    let check_connection = |peer_id| match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::CheckConnection(peer_id),
+    )) {
+        Some(CommunicationResults::CheckConnectionResult { peer_id: _, state }) => state,
+        _ => panic!("Unexpected Response"),
+    };
+    let relay_keep_alive = || match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::ExportState,
+    )) {
+        Some(CommunicationResults::State(state)) => state
+            .peers
+            .into_iter()
+            .find(|peer| peer.peer_id == relay_id)
+            .and_then(|peer| peer.keep_alive),
+        _ => panic!("Unexpected Response"),
+    };
+
+    let res = set_relay(RelayConfig::RelayAlways {
+        peer_id: relay_id,
+        addr: relay_addr.clone(),
+    });
+    assert!(res.is_ok());
+    assert_eq!(relay_keep_alive(), Some(KeepAliveState::Unlimited));
+
+    // the relay connection is not kept alive anymore once the relay was disabled
+    assert!(set_relay(RelayConfig::NoRelay).is_ok());
+    assert_eq!(relay_keep_alive(), None);
+    assert_eq!(check_connection(relay_id), ConnectionState::Connected);
+
+    // the relay connection is closed right away if the relay is disabled with disconnect
+    let res = set_relay(RelayConfig::RelayAlways {
+        peer_id: relay_id,
+        addr: relay_addr,
+    });
+    assert!(res.is_ok());
+    match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::SetRelayAndDisconnect(RelayConfig::NoRelay),
+    )) {
+        Some(CommunicationResults::SetRelayResult(res)) => assert!(res.is_ok()),
+        _ => panic!("Unexpected Response"),
+    }
+    assert_eq!(check_connection(relay_id), ConnectionState::Disconnected);
+}
+
+#[test]
+fn direct_upgrade_backoff() {
+    let sys_relay = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_relay
+        .actor_of::<BlankActor>("blank")
+        .expect("Failed to init actor.");
+    let (relay_id, communication_actor_relay) = init_system(&sys_relay, client);
+    let relay_addr = start_listening(&sys_relay, &communication_actor_relay, None);
+
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = sys
+        .actor_of_args::<ObserverActor, _>("observer", events.clone())
+        .expect("Failed to init actor.");
+    let mut actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    actor_config.observer = Some(observer);
+    actor_config.direct_upgrade = true;
+    actor_config.direct_upgrade_backoff = Some(Duration::from_secs(1));
+    let communication_actor = sys
+        .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+            "communication",
+            (Keypair::generate_ed25519(), actor_config, BehaviourConfig::default()),
+        )
+        .expect("Failed to init actor.");
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor,
+        CommunicationRequest::SetRelay(RelayConfig::RelayAlways {
+            peer_id: relay_id,
+            addr: relay_addr,
+        }),
+    )) {
+        Some(CommunicationResults::SetRelayResult(res)) => assert!(res.is_ok()),
+        _ => panic!("Unexpected Response"),
+    }
+    // the relay drops the envelopes for the unknown peer
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor,
+        CommunicationRequest::SetProtocolTimeouts {
+            request_timeout: Some(Duration::from_millis(200)),
+            connection_timeout: None,
+        },
+    )) {
+        Some(CommunicationResults::SetProtocolTimeoutsAck) => {}
+        _ => panic!("Unexpected Response"),
+    }
+
+    // the peer has no known address, so each attempt to upgrade the relayed connection fails
+    let peer_id = PeerId::random();
+    let failed_upgrades = || {
+        events
+            .lock()
+            .expect("Failed to lock events.")
+            .iter()
+            .filter(|event| {
+                matches!(event, CommunicationEvent::DirectConnectionUpgrade { peer_id: peer, result: Err(_) } if *peer == peer_id)
+            })
+            .count()
+    };
+    assert!(send_request(&sys, &communication_actor, peer_id).is_err());
+    wait_for_event(
+        &events,
+        |event| matches!(event, CommunicationEvent::DirectConnectionUpgrade { peer_id: peer, .. } if *peer == peer_id),
+    );
+    assert!(send_request(&sys, &communication_actor, peer_id).is_err());
+    assert_eq!(failed_upgrades(), 1);
+
+    // the upgrade is attempted again once the backoff elapsed
+    std::thread::sleep(Duration::from_secs(1));
+    assert!(send_request(&sys, &communication_actor, peer_id).is_err());
+    let start = Instant::now();
+    while failed_upgrades() < 2 {
+        assert!(
+            start.elapsed() < Duration::from_secs(3),
+            "Upgrade was not attempted again."
+        );
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn misrouted_envelope() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = sys
+        .actor_of_args::<ObserverActor, _>("observer", events.clone())
+        .expect("Failed to init actor.");
+    let keys = Keypair::generate_ed25519();
+    let peer_id = PeerId::from(keys.public());
+    let mut actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    actor_config.observer = Some(observer);
+    let communication_actor = sys
+        .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+            "communication",
+            (keys, actor_config, BehaviourConfig::default()),
+        )
+        .expect("Failed to init actor.");
+    let addr = start_listening(&sys, &communication_actor, None);
+
+    // send an envelope for another target directly via the swarm
+    let mut swarm = task::block_on(P2PNetworkBehaviour::<RequestEnvelope<Request>, Response>::init_swarm(
+        Keypair::generate_ed25519(),
+        BehaviourConfig::default(),
+    ))
+    .expect("Failed to init swarm.");
+    let sender_id = *Swarm::local_peer_id(&swarm);
+    let target = PeerId::random().to_string();
+    let envelope = RequestEnvelope {
+        source: sender_id.to_string(),
+        message: Request::Ping,
+        target: target.clone(),
+        expires_at: None,
+        hops_remaining: None,
+    };
+    swarm.add_peer_addr(peer_id, addr);
+    swarm.send_request(&peer_id, envelope);
+    task::spawn(async move {
+        loop {
+            swarm.next_event().await;
+        }
+    });
+
+    wait_for_event(&events, |event| match event {
+        CommunicationEvent::EnvelopeMisrouted {
+            peer_id: source,
+            target: claimed_target,
+            local_peer_id,
+        } => *source == sender_id && *claimed_target == target && *local_peer_id == peer_id,
+        _ => false,
+    });
+    match task::block_on(try_ask(&sys, &communication_actor, CommunicationRequest::GetMetrics)) {
+        Some(CommunicationResults::Metrics(metrics)) => assert_eq!(metrics.inbound_misrouted, 1),
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn oversized_request() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = sys
+        .actor_of_args::<ObserverActor, _>("observer", events.clone())
+        .expect("Failed to init actor.");
+    let keys = Keypair::generate_ed25519();
+    let peer_id = PeerId::from(keys.public());
+    let mut actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    actor_config.observer = Some(observer);
+    let mut behaviour_config = BehaviourConfig::default();
+    behaviour_config.set_max_message_size(16);
+    let communication_actor = sys
+        .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+            "communication",
+            (keys, actor_config, behaviour_config),
+        )
+        .expect("Failed to init actor.");
+    let addr = start_listening(&sys, &communication_actor, None);
+
+    // the envelope exceeds the max message size of the actor
+    let mut swarm = task::block_on(P2PNetworkBehaviour::<RequestEnvelope<Request>, Response>::init_swarm(
+        Keypair::generate_ed25519(),
+        BehaviourConfig::default(),
+    ))
+    .expect("Failed to init swarm.");
+    let sender_id = *Swarm::local_peer_id(&swarm);
+    let envelope = RequestEnvelope {
+        source: sender_id.to_string(),
+        message: Request::Ping,
+        target: peer_id.to_string(),
+        expires_at: None,
+        hops_remaining: None,
+    };
+    swarm.add_peer_addr(peer_id, addr);
+    swarm.send_request(&peer_id, envelope);
+    task::spawn(async move {
+        loop {
+            swarm.next_event().await;
+        }
+    });
+
+    wait_for_event(&events, |event| match event {
+        CommunicationEvent::MessageTooLarge {
+            peer_id: source,
+            size,
+            max,
+        } => *source == sender_id && *size > 16 && *max == 16,
+        _ => false,
+    });
+}
+
+#[test]
+fn connection_limit_reached() {
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = sys_b
+        .actor_of_args::<ObserverActor, _>("observer", events.clone())
+        .expect("Failed to init actor.");
+    let keys = Keypair::generate_ed25519();
+    let peer_b_id = PeerId::from(keys.public());
+    let mut actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    actor_config.observer = Some(observer);
+    let mut behaviour_config = BehaviourConfig::default();
+    behaviour_config.set_connection_limits(ConnectionLimitsConfig {
+        max_established_incoming: Some(1),
+        ..Default::default()
+    });
+    let communication_actor_b = sys_b
+        .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+            "communication",
+            (keys, actor_config, behaviour_config),
+        )
+        .expect("Failed to init actor.");
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_a) = init_system(&sys_a, client);
+    assert!(establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b.clone()).is_ok());
+
+    // the second inbound connection exceeds the limit of the remote
+    let sys_c = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_c.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_c) = init_system(&sys_c, client);
+    let _ = establish_connection(&sys_c, &communication_actor_c, peer_b_id, addr_b);
+
+    wait_for_event(&events, |event| {
+        matches!(
+            event,
+            CommunicationEvent::ConnectionLimitReached {
+                kind: ConnectionLimitKind::EstablishedIncoming,
+                limit: 1,
+                current: 1,
+            }
+        )
+    });
+    match task::block_on(try_ask(
+        &sys_b,
+        &communication_actor_b,
+        CommunicationRequest::GetMetrics,
+    )) {
+        Some(CommunicationResults::Metrics(metrics)) => assert_eq!(metrics.connections_refused_limit, 1),
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn dial_limit_reached() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = sys_a
+        .actor_of_args::<ObserverActor, _>("observer", events.clone())
+        .expect("Failed to init actor.");
+    let mut actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    actor_config.observer = Some(observer);
+    let mut behaviour_config = BehaviourConfig::default();
+    behaviour_config.set_connection_limits(ConnectionLimitsConfig {
+        max_pending_outgoing: Some(0),
+        ..Default::default()
+    });
+    let communication_actor_a = sys_a
+        .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+            "communication",
+            (Keypair::generate_ed25519(), actor_config, behaviour_config),
+        )
+        .expect("Failed to init actor.");
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b) = init_system(&sys_b, client);
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+
+    // the dial is rejected without being started
+    match establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b) {
+        Err(ConnectPeerError::ConnectionLimit(_)) => {}
+        _ => panic!("Dial should have been rejected."),
+    }
+    wait_for_event(&events, |event| {
+        matches!(
+            event,
+            CommunicationEvent::ConnectionLimitReached {
+                kind: ConnectionLimitKind::PendingOutgoing,
+                limit: 0,
+                current: 0,
+            }
+        )
+    });
+    match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::GetMetrics,
+    )) {
+        Some(CommunicationResults::Metrics(metrics)) => {
+            assert_eq!(metrics.connections_refused_limit, 1);
+            assert_eq!(metrics.dials_in_progress, 0);
+        }
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn maintain_connection() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = sys_a
+        .actor_of_args::<ObserverActor, _>("observer", events.clone())
+        .expect("Failed to init actor.");
+    let mut actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    actor_config.observer = Some(observer);
+    actor_config.maintain_backoff = MaintainBackoff {
+        initial: Duration::from_millis(100),
+        max: Duration::from_millis(200),
+    };
+    let communication_actor_a = sys_a
+        .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+            "communication",
+            (Keypair::generate_ed25519(), actor_config, BehaviourConfig::default()),
+        )
+        .expect("Failed to init actor.");
+
+    let keys_b = Keypair::generate_ed25519();
+    let peer_b_id = PeerId::from(keys_b.public());
+    let init_b = |sys: &ActorSystem| {
+        let client = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+        let actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+        sys.actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+            "communication",
+            (keys_b.clone(), actor_config, BehaviourConfig::default()),
+        )
+        .expect("Failed to init actor.")
+    };
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let communication_actor_b = init_b(&sys_b);
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+
+    // all addresses are tried until one of them is reachable
+    let unreachable_addr: Multiaddr = "/ip4/127.0.0.1/tcp/1".parse().expect("Invalid Multiaddress.");
+    match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::MaintainConnection {
+            peer_id: peer_b_id,
+            addrs: vec![unreachable_addr, addr_b.clone()],
+        },
+    )) {
+        Some(CommunicationResults::MaintainConnectionAck) => {}
+        _ => panic!("Unexpected Response"),
+    }
+    let is_connected = |event: &CommunicationEvent| {
+        matches!(
+            event,
+            CommunicationEvent::MaintainedConnection {
+                peer_id,
+                state: MaintainedState::Connected,
+            } if *peer_id == peer_b_id
+        )
+    };
+    wait_for_event(&events, is_connected);
+
+    // the peer is re-dialed with backoff while it is offline, until it is reachable again
+    sys_b.stop(communication_actor_b.clone());
+    wait_for_event(&events, |event| {
+        matches!(
+            event,
+            CommunicationEvent::MaintainedConnection {
+                state: MaintainedState::Backoff { .. },
+                ..
+            }
+        )
+    });
+    events.lock().expect("Failed to lock events.").clear();
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let communication_actor_b = init_b(&sys_b);
+    start_listening(&sys_b, &communication_actor_b, Some(addr_b));
+    wait_for_event(&events, is_connected);
+
+    match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::StopMaintaining(peer_b_id),
+    )) {
+        Some(CommunicationResults::StopMaintainingResult(is_maintained)) => assert!(is_maintained),
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn audit_records() {
+    #[derive(Clone)]
+    struct AuditActor {
+        records: Arc<Mutex<Vec<AuditRecord<Request>>>>,
+    }
+
+    impl ActorFactoryArgs<Arc<Mutex<Vec<AuditRecord<Request>>>>> for AuditActor {
+        fn create_args(records: Arc<Mutex<Vec<AuditRecord<Request>>>>) -> Self {
+            AuditActor { records }
+        }
+    }
+
+    impl Actor for AuditActor {
+        type Msg = AuditRecord<Request>;
+
+        fn recv(&mut self, _ctx: &Context<Self::Msg>, msg: Self::Msg, _sender: Sender) {
+            self.records.lock().expect("Failed to lock records.").push(msg);
+        }
+    }
+
+    let init_audited = |sys: &ActorSystem, client: ActorRef<Request>, include_payload| {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let actor = sys
+            .actor_of_args::<AuditActor, _>("audit", records.clone())
+            .expect("Failed to init actor.");
+        let keys = Keypair::generate_ed25519();
+        let peer_id = PeerId::from(keys.public());
+        let mut actor_config =
+            CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+        actor_config.audit = Some(AuditConfig { actor, include_payload });
+        let communication_actor = sys
+            .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+                "communication",
+                (keys, actor_config, BehaviourConfig::default()),
+            )
+            .expect("Failed to init actor.");
+        (peer_id, communication_actor, records)
+    };
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (peer_a_id, communication_actor_a, records_a) = init_audited(&sys_a, client, true);
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b, records_b) = init_audited(&sys_b, client, false);
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+    assert!(establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b).is_ok());
+    assert!(matches!(
+        send_request(&sys_a, &communication_actor_a, peer_b_id),
+        Ok(Response::Pong)
+    ));
+    std::thread::sleep(Duration::from_millis(100));
+
+    {
+        let records = records_a.lock().expect("Failed to lock records.");
+        assert_eq!(records.len(), 1);
+        assert!(matches!(records[0].direction, RequestDirection::Out));
+        assert_eq!(records[0].peer_id, peer_b_id);
+        assert_eq!(records[0].rule, MatchedRule::Default(FirewallPermission::all()));
+        assert!(matches!(records[0].request, Some(Request::Ping)));
+        assert!(records[0].would_block.is_none());
+    }
+    {
+        // only the metadata is included
+        let records = records_b.lock().expect("Failed to lock records.");
+        assert_eq!(records.len(), 1);
+        assert!(matches!(records[0].direction, RequestDirection::In));
+        assert_eq!(records[0].peer_id, peer_a_id);
+        assert!(records[0].request.is_none());
+    }
+
+    // in audit mode, requests that the rules would block are recorded with the reason
+    for rule in vec![
+        FirewallRule::SetMode(FirewallMode::Audit),
+        FirewallRule::DenyAll {
+            direction: RequestDirection::Out,
+        },
+    ] {
+        match task::block_on(try_ask(
+            &sys_a,
+            &communication_actor_a,
+            CommunicationRequest::ConfigureFirewall(rule),
+        )) {
+            Some(CommunicationResults::ConfigureFirewallAck) => {}
+            _ => panic!("Unexpected Response"),
+        }
+    }
+    assert!(send_request(&sys_a, &communication_actor_a, peer_b_id).is_ok());
+    std::thread::sleep(Duration::from_millis(100));
+    let records = records_a.lock().expect("Failed to lock records.");
+    assert_eq!(records.len(), 2);
+    assert!(matches!(records[1].would_block, Some(FirewallBlocked::Local)));
+}
+
+#[test]
+fn firewall_rejection() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let client = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let mut config_b = CommunicationActorConfig::new(client, FirewallPermission::none(), FirewallPermission::all());
+    config_b.send_firewall_rejections = true;
+
+    let nodes = spawn_nodes(&sys, "firewall-rejection", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let peer_b_id = nodes[1].0;
+
+    // the request fails right away instead of after the default timeout of 10s
+    let start = Instant::now();
+    assert!(matches!(
+        send_request(&sys, &communication_actor_a, peer_b_id),
+        Err(RequestMessageError::Rejected(FirewallBlocked::Remote))
+    ));
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn coalesce_requests() {
+    // client that takes a while to respond, so that the requests of A overlap
+    #[derive(Clone)]
+    struct SlowReplyActor;
+
+    impl ActorFactory for SlowReplyActor {
+        fn create() -> Self {
+            SlowReplyActor
+        }
+    }
+
+    impl Actor for SlowReplyActor {
+        type Msg = Request;
+
+        fn recv(&mut self, _ctx: &Context<Self::Msg>, _msg: Self::Msg, sender: Sender) {
+            std::thread::sleep(Duration::from_millis(500));
+            sender
+                .expect("Missing sender.")
+                .try_tell(Response::Pong, None)
+                .expect("Could not tell response.");
+        }
+    }
+
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let client = sys.actor_of::<SlowReplyActor>("target").expect("Failed to init actor.");
+    let config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let nodes = spawn_nodes(&sys, "coalesce-requests", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let (peer_b_id, communication_actor_b) = nodes[1].clone();
+
+    let handles: Vec<_> = (0..3)
+        .map(|_| {
+            let sys = sys.clone();
+            let actor = communication_actor_a.clone();
+            std::thread::spawn(move || {
+                let request = RequestMsgBuilder::new(peer_b_id, Request::Ping).coalesce(1).build();
+                let res: CommunicationResults<Response> = task::block_on(ask(&sys, &actor, request));
+                res
+            })
+        })
+        .collect();
+    for handle in handles {
+        match handle.join().expect("Failed to join thread.") {
+            CommunicationResults::RequestMsgResult(Ok(Response::Pong)) => {}
+            _ => panic!("Unexpected Response"),
+        }
+    }
+
+    // only a single request was sent
+    match task::block_on(try_ask(&sys, &communication_actor_a, CommunicationRequest::GetMetrics)) {
+        Some(CommunicationResults::Metrics(metrics)) => {
+            assert_eq!(metrics.outbound_requests, 1);
+            assert_eq!(metrics.outbound_coalesced, 2);
+        }
+        _ => panic!("Unexpected Response"),
+    }
+    match task::block_on(try_ask(&sys, &communication_actor_b, CommunicationRequest::GetMetrics)) {
+        Some(CommunicationResults::Metrics(metrics)) => assert_eq!(metrics.inbound_requests, 1),
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn response_cache() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    // only pings are idempotent
+    let count = Arc::new(AtomicUsize::new(0));
+    let client = sys
+        .actor_of_args::<CountingActor, _>("counting", count.clone())
+        .expect("Failed to init actor.");
+    let mut config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    let nonce: RequestNonce<Request> = Arc::new(|request: &Request| match request {
+        Request::Ping => Some(1),
+        Request::Other => None,
+    });
+    config_b.response_cache = Some(ResponseCacheConfig {
+        capacity: 8,
+        ttl: Duration::from_secs(60),
+        nonce,
+    });
+
+    let nodes = spawn_nodes(&sys, "response-cache", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let peer_b_id = nodes[1].0;
+
+    // the retried ping is answered from the cache
+    for _ in 0..3 {
+        assert!(matches!(
+            send_request(&sys, &communication_actor_a, peer_b_id),
+            Ok(Response::Pong)
+        ));
+    }
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+
+    for _ in 0..2 {
+        let request = RequestMsgBuilder::new(peer_b_id, Request::Other).build();
+        match task::block_on(try_ask(&sys, &communication_actor_a, request)) {
+            Some(CommunicationResults::RequestMsgResult(Ok(Response::Pong))) => {}
+            _ => panic!("Unexpected Response"),
+        }
+    }
+    assert_eq!(count.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn client_router() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let count = Arc::new(AtomicUsize::new(0));
+    let other_client = sys
+        .actor_of_args::<CountingActor, _>("other", count.clone())
+        .expect("Failed to init actor.");
+    let client = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let mut config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    let router: ClientRouter<Request, Request> = Arc::new(move |request: &Request| match request {
+        Request::Other => Some(other_client.clone()),
+        Request::Ping => None,
+    });
+    config_b.client_router = Some(router);
+
+    let nodes = spawn_nodes(&sys, "client-router", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let peer_b_id = nodes[1].0;
+
+    // pings are forwarded to the default client
+    assert!(matches!(
+        send_request(&sys, &communication_actor_a, peer_b_id),
+        Ok(Response::Pong)
+    ));
+    assert_eq!(count.load(Ordering::SeqCst), 0);
+
+    let request = RequestMsgBuilder::new(peer_b_id, Request::Other).build();
+    match task::block_on(try_ask(&sys, &communication_actor_a, request)) {
+        Some(CommunicationResults::RequestMsgResult(Ok(Response::Pong))) => {}
+        _ => panic!("Unexpected Response"),
+    }
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn failure_ban() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = sys
+        .actor_of_args::<ObserverActor, _>("observer", events.clone())
+        .expect("Failed to init actor.");
+    let keys = Keypair::generate_ed25519();
+    let peer_id = PeerId::from(keys.public());
+    let mut actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    actor_config.observer = Some(observer);
+    actor_config.failure_ban = Some(FailureBanConfig {
+        max_failures: 1,
+        window: Duration::from_secs(60),
+        cooldown: Duration::from_secs(60),
+    });
+    let communication_actor = sys
+        .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+            "communication",
+            (keys, actor_config, BehaviourConfig::default()),
+        )
+        .expect("Failed to init actor.");
+    let addr = start_listening(&sys, &communication_actor, None);
+
+    // send envelopes with an invalid source directly via the swarm
+    let mut swarm = task::block_on(P2PNetworkBehaviour::<RequestEnvelope<Request>, Response>::init_swarm(
+        Keypair::generate_ed25519(),
+        BehaviourConfig::default(),
+    ))
+    .expect("Failed to init swarm.");
+    let sender_id = *Swarm::local_peer_id(&swarm);
+    let envelope = RequestEnvelope {
+        source: "invalid".into(),
+        message: Request::Ping,
+        target: peer_id.to_string(),
+        expires_at: None,
+        hops_remaining: None,
+    };
+    swarm.add_peer_addr(peer_id, addr);
+    swarm.send_request(&peer_id, envelope.clone());
+    swarm.send_request(&peer_id, envelope);
+    task::spawn(async move {
+        loop {
+            swarm.next_event().await;
+        }
+    });
+
+    wait_for_event(&events, |event| match event {
+        CommunicationEvent::PeerAutoBanned { peer_id, cooldown } => {
+            *peer_id == sender_id && *cooldown == Duration::from_secs(60)
+        }
+        _ => false,
+    });
+}
+
+#[test]
+fn relay_fallback_delay() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let mut actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    actor_config.relay_fallback_delay = Some(Duration::from_millis(100));
+    let communication_actor = sys
+        .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+            "communication",
+            (Keypair::generate_ed25519(), actor_config, BehaviourConfig::default()),
+        )
+        .expect("Failed to init actor.");
+
+    // relay that answers all requests itself
+    let mut relay = task::block_on(P2PNetworkBehaviour::<RequestEnvelope<Request>, Response>::init_swarm(
+        Keypair::generate_ed25519(),
+        BehaviourConfig::default(),
+    ))
+    .expect("Failed to init swarm.");
+    let relay_id = *Swarm::local_peer_id(&relay);
+    Swarm::listen_on(&mut relay, "/ip4/127.0.0.1/tcp/0".parse().unwrap()).expect("Listening to swarm failed.");
+    let relay_addr = task::block_on(async {
+        loop {
+            if let SwarmEvent::NewListenAddr(addr) = relay.next_event().await {
+                return addr;
+            }
+        }
+    });
+    task::spawn(async move {
+        loop {
+            if let SwarmEvent::Behaviour(P2PEvent::RequestResponse(boxed_event)) = relay.next_event().await {
+                if let P2PReqResEvent::Req { request_id, .. } = *boxed_event {
+                    let _ = relay.send_response(request_id, Response::Pong);
+                }
+            }
+        }
+    });
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor,
+        CommunicationRequest::SetRelay(RelayConfig::RelayBackup {
+            peer_id: relay_id,
+            addr: relay_addr,
+        }),
+    )) {
+        Some(CommunicationResults::SetRelayResult(res)) => assert!(res.is_ok()),
+        _ => panic!("Unexpected Response"),
+    }
+
+    // the direct address accepts the tcp connection, but never completes the handshake
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind listener.");
+    let port = listener.local_addr().unwrap().port();
+    let stalled_addr: Multiaddr = format!("/ip4/127.0.0.1/tcp/{}", port).parse().unwrap();
+
+    // requests without hedging wait for the direct dial before using the relay
+    let start = Instant::now();
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor,
+        RequestMsgBuilder::new(PeerId::random(), Request::Ping)
+            .fallback_addr(stalled_addr.clone())
+            .build(),
+    )) {
+        Some(CommunicationResults::RequestMsgResult(res)) => assert!(res.is_err()),
+        None => {}
+        _ => panic!("Unexpected Response"),
+    }
+    assert!(start.elapsed() >= Duration::from_secs(1));
+
+    let start = Instant::now();
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor,
+        RequestMsgBuilder::new(PeerId::random(), Request::Ping)
+            .fallback_addr(stalled_addr)
+            .hedge(true)
+            .build(),
+    )) {
+        Some(CommunicationResults::RequestMsgResult(res)) => assert!(matches!(res, Ok(Response::Pong))),
+        _ => panic!("Unexpected Response"),
+    }
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn spawn_test_network() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let configs = (0..3)
+        .map(|index| {
+            let client = sys
+                .actor_of::<ReplyActor>(&format!("client-{}", index))
+                .expect("Failed to init actor.");
+            CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all())
+        })
+        .collect();
+    let nodes =
+        task::block_on(spawn_network::<_, Response, _, _>(&sys, "node", configs)).expect("Failed to spawn network.");
+    assert_eq!(nodes[2].peer_id, PeerId::from(deterministic_keypair("node-2").public()));
+
+    // all nodes are connected to each other
+    for node in nodes.iter() {
+        match task::block_on(try_ask(&sys, &node.actor, CommunicationRequest::GetSwarmInfo)) {
+            Some(CommunicationResults::SwarmInfo { connections, .. }) => assert_eq!(connections.len(), 2),
+            _ => panic!("Unexpected Response"),
+        }
+    }
+    let res = send_request(&sys, &nodes[0].actor, nodes[2].peer_id);
+    assert!(matches!(res, Ok(Response::Pong)));
+}
+
+#[test]
+fn listening_ports() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor) = init_system(&sys, client);
+    let handle = CommunicationHandle::<_, Response, _>::new(sys.clone(), communication_actor.clone());
+    assert!(task::block_on(handle.listening_ports()).is_empty());
+
+    let addr = start_listening(&sys, &communication_actor, None);
+    let port = multiaddr_to_socket_addr(&addr).expect("Invalid address.").port();
+    assert_ne!(port, 0);
+    assert_eq!(task::block_on(handle.listening_ports()), vec![port]);
+}
+
+#[test]
+fn validate_multiaddr() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (peer_id, communication_actor) = init_system(&sys, client);
+    let validate = |addr: String| match task::block_on(try_ask(
+        &sys,
+        &communication_actor,
+        CommunicationRequest::ValidateMultiaddr(addr),
+    )) {
+        Some(CommunicationResults::ValidateMultiaddrResult(res)) => res,
+        _ => panic!("Unexpected result."),
+    };
+
+    let info = validate(format!("/dns/example.com/tcp/443/wss/p2p/{}", peer_id)).expect("Invalid Multiaddress.");
+    assert_eq!(info.transport, Some(AddrTransport::SecureWebsocket));
+    assert!(info.is_dialable);
+    assert!(!info.is_listenable);
+    assert_eq!(info.peer_id, Some(peer_id));
+
+    let info = validate("/ip4/0.0.0.0/tcp/0/ws".into()).expect("Invalid Multiaddress.");
+    assert_eq!(info.transport, Some(AddrTransport::Websocket));
+    assert!(info.is_dialable && info.is_listenable);
+    assert_eq!(info.peer_id, None);
+
+    let info = validate("/ip4/127.0.0.1/udp/8080".into()).expect("Invalid Multiaddress.");
+    assert_eq!(info.transport, None);
+    assert!(!info.is_dialable && !info.is_listenable);
+
+    assert!(validate("127.0.0.1:8080".into()).is_err());
+}
+
+#[test]
+fn listener_watchdog_removed_listener() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let keys = Keypair::generate_ed25519();
+    let mut actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    actor_config.listener_watchdog = Some(ListenerWatchdogConfig {
+        interval: Duration::from_millis(100),
+        addrs: vec!["/ip4/127.0.0.1/tcp/0".parse().expect("Invalid Multiaddress.")],
+    });
+    let communication_actor = sys
+        .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+            "communication",
+            (keys, actor_config, BehaviourConfig::default()),
+        )
+        .expect("Failed to init actor.");
+    let handle = CommunicationHandle::<_, Response, _>::new(sys.clone(), communication_actor.clone());
+
+    // the watchdog does not start listening if the node never listened
+    task::block_on(task::sleep(Duration::from_millis(300)));
+    assert!(task::block_on(handle.listening_ports()).is_empty());
+
+    start_listening(&sys, &communication_actor, None);
+    assert_eq!(task::block_on(handle.listening_ports()).len(), 1);
+
+    // listeners that were removed locally are not restored
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor,
+        CommunicationRequest::RemoveListener,
+    )) {
+        Some(CommunicationResults::RemoveListenerResult(res)) => assert!(res.is_ok()),
+        _ => panic!("Unexpected Response"),
+    }
+    task::block_on(task::sleep(Duration::from_millis(300)));
+    assert!(task::block_on(handle.listening_ports()).is_empty());
+}
+
+#[test]
+fn uptime() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let before = SystemTime::now();
+    let (_, communication_actor) = init_system(&sys, client);
+
+    let get_uptime = || match task::block_on(try_ask(&sys, &communication_actor, CommunicationRequest::GetUptime)) {
+        Some(CommunicationResults::Uptime { started_at, elapsed }) => (started_at, elapsed),
+        _ => panic!("Unexpected Response"),
+    };
+    let (started_at, elapsed) = get_uptime();
+    assert!(started_at >= before && started_at <= SystemTime::now());
+    task::block_on(task::sleep(Duration::from_millis(100)));
+    let (started_at_later, elapsed_later) = get_uptime();
+    assert_eq!(started_at, started_at_later);
+    assert!(elapsed_later >= elapsed + Duration::from_millis(100));
+}
+
+#[test]
+fn listen_unsupported() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor) = init_system(&sys, client);
+
+    let addr: Multiaddr = "/ip4/127.0.0.1/udp/0".parse().expect("Invalid Multiaddress.");
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor,
+        CommunicationRequest::StartListening(Some(addr.clone())),
+    )) {
+        Some(CommunicationResults::StartListeningResult(res)) => {
+            assert_eq!(res, Err(StartListeningError::NotSupported(addr)))
+        }
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn listen_in_range() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_a) = init_system(&sys_a, client);
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_b) = init_system(&sys_b, client);
+
+    let taken: Multiaddr = "/ip4/127.0.0.1/tcp/8110".parse().expect("Invalid Multiaddress.");
+    start_listening(&sys_a, &communication_actor_a, Some(taken.clone()));
+
+    let listen_in_range = |range| match task::block_on(try_ask(
+        &sys_b,
+        &communication_actor_b,
+        CommunicationRequest::StartListeningInRange {
+            ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            range,
+        },
+    )) {
+        Some(CommunicationResults::StartListeningResult(res)) => res,
+        _ => panic!("Unexpected Response"),
+    };
+    assert!(listen_in_range(8110..=8110).is_err());
+    let addr = listen_in_range(8110..=8112).expect("No free port in range.");
+    assert_ne!(addr, taken);
+    assert!(addr.to_string().starts_with("/ip4/127.0.0.1/tcp/811"));
+}
+
+#[test]
+fn reconnect_peer() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_a) = init_system(&sys_a, client);
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b) = init_system(&sys_b, client);
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+
+    let reconnect = |peer_id| match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::Reconnect(peer_id),
+    )) {
+        Some(CommunicationResults::ReconnectResult(res)) => res,
+        _ => panic!("Unexpected Response"),
+    };
+
+    // peer without any known address
+    assert!(matches!(
+        reconnect(PeerId::random()),
+        Err(ConnectPeerError::NoAddresses)
+    ));
+
+    let res = establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b);
+    assert!(res.is_ok());
+
+    assert_eq!(reconnect(peer_b_id).expect("Reconnect failed."), peer_b_id);
+    let res = send_request(&sys_a, &communication_actor_a, peer_b_id);
+    assert!(res.is_ok());
+}
+
+#[test]
+fn deferred_reconnect() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = sys
+        .actor_of_args::<ObserverActor, _>("observer", events.clone())
+        .expect("Failed to init actor.");
+    let mut config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    config_a.observer = Some(observer);
+    config_a.reconnect_mode = ReconnectMode::Deferred;
+
+    let client = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let nodes = spawn_nodes(&sys, "deferred-reconnect", vec![config_a, config_b]);
+    let (peer_a_id, communication_actor_a) = nodes[0].clone();
+    let (peer_b_id, communication_actor_b) = nodes[1].clone();
+
+    // peer b closes the connection by temporarily banning peer a
+    for request in vec![
+        CommunicationRequest::BanPeer(peer_a_id),
+        CommunicationRequest::UnbanPeer(peer_a_id),
+    ] {
+        assert!(task::block_on(try_ask(&sys, &communication_actor_b, request)).is_some());
+    }
+
+    // the keep-alive connection is re-established in the background
+    let start = Instant::now();
+    loop {
+        let established = events
+            .lock()
+            .expect("Failed to lock events.")
+            .iter()
+            .filter(|event| matches!(event, CommunicationEvent::ConnectionEstablished { peer_id, .. } if *peer_id == peer_b_id))
+            .count();
+        if established >= 2 {
+            break;
+        }
+        assert!(
+            start.elapsed() < Duration::from_secs(3),
+            "Connection was not re-established."
+        );
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    let res = send_request(&sys, &communication_actor_a, peer_b_id);
+    assert_eq!(res.expect("Request failed."), Response::Pong);
+}
+
+#[test]
+fn firewall_default() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let keys = Keypair::generate_ed25519();
+    let actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::none());
+    let communication_actor = sys
+        .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+            "communication",
+            (keys, actor_config, BehaviourConfig::default()),
+        )
+        .expect("Failed to init actor.");
+
+    let get_default = |direction| match task::block_on(try_ask(
+        &sys,
+        &communication_actor,
+        CommunicationRequest::GetFirewallDefault(direction),
+    )) {
+        Some(CommunicationResults::FirewallDefault(permission)) => permission,
+        _ => panic!("Unexpected Response"),
+    };
+    assert_eq!(get_default(RequestDirection::In), FirewallPermission::all());
+    assert_eq!(get_default(RequestDirection::Out), FirewallPermission::none());
+
+    // a rule for a specific peer does not change the default
+    set_firewall_rule(
+        &sys,
+        &communication_actor,
+        PeerId::random(),
+        RequestDirection::Out,
+        FirewallPermission::all(),
+    );
+    assert_eq!(get_default(RequestDirection::Out), FirewallPermission::none());
+
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor,
+        CommunicationRequest::ConfigureFirewall(FirewallRule::SetRules {
+            direction: RequestDirection::In,
+            peers: Vec::new(),
+            set_default: true,
+            permission: FirewallPermission::none(),
+        }),
+    )) {
+        Some(CommunicationResults::ConfigureFirewallAck) => {}
+        _ => panic!("Unexpected Response"),
+    }
+    assert_eq!(get_default(RequestDirection::In), FirewallPermission::none());
+
+    let configure = |rule| match task::block_on(try_ask(
+        &sys,
+        &communication_actor,
+        CommunicationRequest::ConfigureFirewall(rule),
+    )) {
+        Some(CommunicationResults::ConfigureFirewallAck) => {}
+        _ => panic!("Unexpected Response"),
+    };
+    configure(FirewallRule::AllowAll {
+        direction: RequestDirection::In,
+    });
+    assert!(get_default(RequestDirection::In).is_all());
+    configure(FirewallRule::DenyAll {
+        direction: RequestDirection::In,
+    });
+    assert!(get_default(RequestDirection::In).is_none());
+    assert!(!get_default(RequestDirection::In).is_all());
+
+    // the set permission replaces the prior default instead of being combined with it
+    configure(FirewallRule::AddPermissions {
+        direction: RequestDirection::Out,
+        peers: Vec::new(),
+        change_default: true,
+        permissions: vec![PermissionValue::new(0).unwrap(), PermissionValue::new(1).unwrap()],
+    });
+    assert_eq!(get_default(RequestDirection::Out), 3);
+    configure(FirewallRule::SetRules {
+        direction: RequestDirection::Out,
+        peers: vec![PeerId::random()],
+        set_default: true,
+        permission: FirewallPermission::from(4),
+    });
+    assert_eq!(get_default(RequestDirection::Out), 4);
+}
+
+#[test]
+fn check_permission() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let keys = Keypair::generate_ed25519();
+    let actor_config = CommunicationActorConfig::new(client, FirewallPermission::none(), FirewallPermission::all());
+    let communication_actor = sys
+        .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+            "communication",
+            (keys, actor_config, BehaviourConfig::default()),
+        )
+        .expect("Failed to init actor.");
+
+    let check = |peer_id, direction, request_sample| match task::block_on(try_ask(
+        &sys,
+        &communication_actor,
+        CommunicationRequest::CheckPermission {
+            peer_id,
+            direction,
+            request_sample,
+        },
+    )) {
+        Some(CommunicationResults::CheckPermissionResult(decision)) => decision,
+        _ => panic!("Unexpected Response"),
+    };
+
+    let peer_id = PeerId::random();
+    let decision = check(peer_id, RequestDirection::In, Request::Ping);
+    assert!(!decision.is_permitted);
+    assert_eq!(decision.rule, MatchedRule::Default(FirewallPermission::none()));
+
+    let permission = FirewallPermission::none().add_permission(&RequestPermission::Ping.permission());
+    set_firewall_rule(&sys, &communication_actor, peer_id, RequestDirection::In, permission);
+    let decision = check(peer_id, RequestDirection::In, Request::Ping);
+    assert!(decision.is_permitted);
+    assert_eq!(decision.rule, MatchedRule::Specific(permission));
+    assert!(!check(peer_id, RequestDirection::In, Request::Other).is_permitted);
+
+    // checking a request does not count towards the rate of its permission
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor,
+        CommunicationRequest::ConfigureFirewall(FirewallRule::SetPermissionRate {
+            direction: RequestDirection::Out,
+            permission: RequestPermission::Ping.permission(),
+            rate: Some(PermissionRate {
+                max_requests: 1,
+                window: Duration::from_secs(60),
+            }),
+        }),
+    )) {
+        Some(CommunicationResults::ConfigureFirewallAck) => {}
+        _ => panic!("Unexpected Response"),
+    }
+    for _ in 0..2 {
+        let decision = check(peer_id, RequestDirection::Out, Request::Ping);
+        assert!(decision.is_permitted);
+        assert!(!decision.is_rate_limited);
+    }
+}
+
+#[test]
+fn observe_protocols() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = sys
+        .actor_of_args::<ObserverActor, _>("observer", events.clone())
+        .expect("Failed to init actor.");
+    let mut config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    config_a.observer = Some(observer);
+
+    let client = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let nodes = spawn_nodes(&sys, "observe-protocols", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let peer_b_id = nodes[1].0;
+
+    wait_for_event(
+        &events,
+        |event| matches!(event, CommunicationEvent::ConnectionEstablished { peer_id, .. } if *peer_id == peer_b_id),
+    );
+    // the protocols are emitted once they were received via identify
+    wait_for_event(&events, |event| match event {
+        CommunicationEvent::ProtocolsUpdated { peer_id, protocols } => {
+            *peer_id == peer_b_id && protocols.iter().any(|p| p == DEFAULT_PROTOCOL)
+        }
+        _ => false,
+    });
+}
+
+#[test]
+fn protocol_info() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = sys_a
+        .actor_of_args::<ObserverActor, _>("observer", events.clone())
+        .expect("Failed to init actor.");
+    let keys = Keypair::generate_ed25519();
+    let mut actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    actor_config.observer = Some(observer);
+    let mut behaviour_config = BehaviourConfig::default();
+    behaviour_config.set_protocol_versions(vec!["/stronghold-communication/2.0.0".into(), DEFAULT_PROTOCOL.into()]);
+    let communication_actor_a = sys_a
+        .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+            "communication",
+            (keys, actor_config, behaviour_config),
+        )
+        .expect("Failed to init actor.");
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b) = init_system(&sys_b, client);
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+
+    match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::GetProtocolInfo(peer_b_id),
+    )) {
+        Some(CommunicationResults::ProtocolInfo(info)) => assert!(info.is_none()),
+        _ => panic!("Unexpected Response"),
+    }
+
+    let res = establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b);
+    assert!(res.is_ok());
+    wait_for_event(
+        &events,
+        |event| matches!(event, CommunicationEvent::ProtocolsUpdated { peer_id, .. } if *peer_id == peer_b_id),
+    );
+
+    // peer b only supports the old version
+    match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::GetProtocolInfo(peer_b_id),
+    )) {
+        Some(CommunicationResults::ProtocolInfo(Some(info))) => {
+            assert_eq!(info.agent_version, "stronghold-communication");
+            assert_eq!(info.versions, vec![DEFAULT_PROTOCOL.to_string()]);
+            assert_eq!(info.outbound_version, Some(DEFAULT_PROTOCOL.to_string()));
+        }
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn identify_metadata() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_a) = init_system(&sys_a, client);
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let keys = Keypair::generate_ed25519();
+    let peer_b_id = PeerId::from(keys.public());
+    let actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    let mut metadata = BTreeMap::new();
+    metadata.insert("build".to_string(), "42".to_string());
+    let mut behaviour_config = BehaviourConfig::default();
+    behaviour_config
+        .set_agent_version("my-app/1.2.0".into())
+        .set_identify_protocol_version("my-app/1.0.0".into())
+        .set_identify_metadata(metadata.clone());
+    let communication_actor_b = sys_b
+        .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+            "communication",
+            (keys, actor_config, behaviour_config),
+        )
+        .expect("Failed to init actor.");
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+
+    let res = establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b);
+    assert!(res.is_ok());
+    match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::RefreshIdentify(peer_b_id),
+    )) {
+        Some(CommunicationResults::RefreshIdentifyResult(Ok(info))) => {
+            assert_eq!(info.agent_version, "my-app/1.2.0");
+            assert_eq!(info.protocol_version, "my-app/1.0.0");
+            assert_eq!(info.metadata, metadata);
+        }
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn refresh_identify() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_a) = init_system(&sys_a, client);
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b) = init_system(&sys_b, client);
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+
+    // without any known address the peer can not be dialed
+    match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::RefreshIdentify(peer_b_id),
+    )) {
+        Some(CommunicationResults::RefreshIdentifyResult(res)) => {
+            assert!(matches!(res, Err(ConnectPeerError::NoAddresses)))
+        }
+        _ => panic!("Unexpected Response"),
+    }
+
+    let res = establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b);
+    assert!(res.is_ok());
+    match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::RefreshIdentify(peer_b_id),
+    )) {
+        Some(CommunicationResults::RefreshIdentifyResult(Ok(info))) => {
+            assert_eq!(info.versions, vec![DEFAULT_PROTOCOL.to_string()]);
+        }
+        _ => panic!("Unexpected Response"),
+    }
+    match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::GetProtocolInfo(peer_b_id),
+    )) {
+        Some(CommunicationResults::ProtocolInfo(info)) => assert!(info.is_some()),
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn reuse_connection() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = sys
+        .actor_of_args::<ObserverActor, _>("observer", events.clone())
+        .expect("Failed to init actor.");
+    let mut config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    config_a.observer = Some(observer);
+
+    let client = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let nodes = spawn_nodes(&sys, "reuse-connection", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let peer_b_id = nodes[1].0;
+    let res = send_request(&sys, &communication_actor_a, peer_b_id);
+    assert!(res.is_ok());
+
+    // only the explicit connect dialed the peer, the request used the established connection
+    wait_for_event(
+        &events,
+        |event| matches!(event, CommunicationEvent::Dialing { peer_id } if *peer_id == peer_b_id),
+    );
+    let dials = events
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|event| matches!(event, CommunicationEvent::Dialing { peer_id } if *peer_id == peer_b_id))
+        .count();
+    assert_eq!(dials, 1);
+}
+
+#[test]
+fn observe_dials() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = sys
+        .actor_of_args::<ObserverActor, _>("observer", events.clone())
+        .expect("Failed to init actor.");
+    let keys = Keypair::generate_ed25519();
+    let mut actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    actor_config.observer = Some(observer);
+    let communication_actor = sys
+        .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+            "communication",
+            (keys, actor_config, BehaviourConfig::default()),
+        )
+        .expect("Failed to init actor.");
+
+    // the request implicitly dials the peer on an address that nobody listens on
+    let target_id = PeerId::random();
+    let addr: Multiaddr = "/ip4/127.0.0.1/tcp/1".parse().expect("Invalid Multiaddress.");
+    let res = task::block_on(try_ask(
+        &sys,
+        &communication_actor,
+        RequestMsgBuilder::new(target_id, Request::Ping)
+            .fallback_addr(addr.clone())
+            .build(),
+    ));
+    assert!(matches!(res, Some(CommunicationResults::RequestMsgResult(Err(_)))));
+
+    wait_for_event(
+        &events,
+        |event| matches!(event, CommunicationEvent::Dialing { peer_id } if *peer_id == target_id),
+    );
+    wait_for_event(&events, |event| match event {
+        CommunicationEvent::DialFailure { peer_id, address, .. } => *peer_id == Some(target_id) && *address == addr,
+        _ => false,
+    });
+}
+
+#[test]
+fn prune_failed_addrs() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = sys
+        .actor_of_args::<ObserverActor, _>("observer", events.clone())
+        .expect("Failed to init actor.");
+    let keys = Keypair::generate_ed25519();
+    let mut actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    actor_config.observer = Some(observer);
+    actor_config.prune_failed_addrs = Some(1);
+    let communication_actor = sys
+        .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+            "communication",
+            (keys, actor_config, BehaviourConfig::default()),
+        )
+        .expect("Failed to init actor.");
+
+    let target_id = PeerId::random();
+    let addr: Multiaddr = "/ip4/127.0.0.1/tcp/1".parse().expect("Invalid Multiaddress.");
+    let _ = task::block_on(try_ask(
+        &sys,
+        &communication_actor,
+        RequestMsgBuilder::new(target_id, Request::Ping)
+            .fallback_addr(addr.clone())
+            .build(),
+    ));
+    wait_for_event(&events, |event| match event {
+        CommunicationEvent::AddressPruned { peer_id, address } => *peer_id == target_id && *address == addr,
+        _ => false,
+    });
+
+    match task::block_on(try_ask(&sys, &communication_actor, CommunicationRequest::GetKnownPeers)) {
+        Some(CommunicationResults::KnownPeers(peers)) => {
+            assert!(!peers
+                .iter()
+                .any(|(peer_id, addrs, _)| *peer_id == target_id && addrs.contains(&addr)));
+        }
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn client_breaker() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    // the client of peer B never responds
+    let client = sys.actor_of::<BlankActor>("target").expect("Failed to init actor.");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = sys
+        .actor_of_args::<ObserverActor, _>("observer", events.clone())
+        .expect("Failed to init actor.");
+    let mut config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    config_b.observer = Some(observer);
+    config_b.client_breaker = Some(ClientBreakerConfig {
+        max_timeouts: 1,
+        cooldown: Duration::from_secs(60),
+    });
+
+    let nodes = spawn_nodes(&sys, "client-breaker", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let (peer_b_id, communication_actor_b) = nodes[1].clone();
+    let res = send_request(&sys, &communication_actor_a, peer_b_id);
+    assert!(res.is_err());
+    wait_for_event(&events, |event| {
+        matches!(event, CommunicationEvent::ClientBreakerOpened)
+    });
+
+    // while the breaker is open, the remote is told right away that the peer is busy
+    let res = send_request(&sys, &communication_actor_a, peer_b_id);
+    assert!(matches!(res, Err(RequestMessageError::Unavailable(RejectReason::Busy))));
+    match task::block_on(try_ask(&sys, &communication_actor_b, CommunicationRequest::GetMetrics)) {
+        Some(CommunicationResults::Metrics(metrics)) => assert_eq!(metrics.inbound_rejected_breaker, 1),
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn firewall_permission_rate() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    let client = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let nodes = spawn_nodes(&sys, "firewall-permission-rate", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let peer_b_id = nodes[1].0;
+
+    let window = Duration::from_millis(500);
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor_a,
+        CommunicationRequest::ConfigureFirewall(FirewallRule::SetPermissionRate {
+            direction: RequestDirection::Out,
+            permission: RequestPermission::Ping.permission(),
+            rate: Some(PermissionRate {
+                max_requests: 2,
+                window,
+            }),
+        }),
+    )) {
+        Some(CommunicationResults::ConfigureFirewallAck) => {}
+        _ => panic!("Unexpected Response"),
+    }
+
+    for _ in 0..2 {
+        let res = send_request(&sys, &communication_actor_a, peer_b_id);
+        assert_eq!(res.expect("Request within the rate failed."), Response::Pong);
+    }
+    match send_request(&sys, &communication_actor_a, peer_b_id) {
+        Err(RequestMessageError::Rejected(FirewallBlocked::RateLimited)) => {}
+        _ => panic!("Request should have exceeded the rate."),
+    }
+
+    // requests are permitted again once the earlier ones left the window
+    task::block_on(task::sleep(window));
+    let res = send_request(&sys, &communication_actor_a, peer_b_id);
+    assert_eq!(res.expect("Request after the window failed."), Response::Pong);
+}
+
+#[test]
+fn firewall_audit_mode() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = sys
+        .actor_of_args::<ObserverActor, _>("observer", events.clone())
+        .expect("Failed to init actor.");
+    let mut config_a = CommunicationActorConfig::new(client, FirewallPermission::none(), FirewallPermission::none());
+    config_a.observer = Some(observer);
+
+    let client = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let nodes = spawn_nodes(&sys, "firewall-audit-mode", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let peer_b_id = nodes[1].0;
+
+    match send_request(&sys, &communication_actor_a, peer_b_id) {
+        Err(RequestMessageError::Rejected(FirewallBlocked::Local)) => {}
+        _ => panic!("Local firewall should have blocked the request."),
+    }
+
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor_a,
+        CommunicationRequest::ConfigureFirewall(FirewallRule::SetMode(FirewallMode::Audit)),
+    )) {
+        Some(CommunicationResults::ConfigureFirewallAck) => {}
+        _ => panic!("Unexpected Response"),
+    }
+
+    // the request is allowed, but reported
+    let res = send_request(&sys, &communication_actor_a, peer_b_id);
+    assert!(res.is_ok());
+    wait_for_event(&events, |event| match event {
+        CommunicationEvent::FirewallWouldBlock { peer_id, direction } => {
+            *peer_id == peer_b_id && matches!(direction, RequestDirection::Out)
+        }
+        _ => false,
+    });
+}
+
+#[test]
+fn protocol_versions() {
+    let init_with_versions = |sys: &ActorSystem, client: ActorRef<Request>, versions: Vec<&str>| {
+        let keys = Keypair::generate_ed25519();
+        let peer_id = PeerId::from(keys.public());
+        let mut behaviour_config = BehaviourConfig::default();
+        behaviour_config.set_protocol_versions(versions.into_iter().map(String::from).collect());
+        let actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+        let communication_actor = sys
+            .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+                "communication",
+                (keys, actor_config, behaviour_config),
+            )
+            .expect("Failed to init actor.");
+        (peer_id, communication_actor)
+    };
+
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_a) = init_with_versions(&sys_a, client, vec!["/test/2.0.0", DEFAULT_PROTOCOL]);
+
+    // peer with the old version
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b) = init_system(&sys_b, client);
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+    let res = establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b);
+    assert!(res.is_ok());
+    let res = send_request(&sys_a, &communication_actor_a, peer_b_id);
+    assert!(res.is_ok());
+
+    // peer with an unknown version
+    let sys_c = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_c.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let (peer_c_id, communication_actor_c) = init_with_versions(&sys_c, client, vec!["/test/3.0.0"]);
+    let addr_c = start_listening(&sys_c, &communication_actor_c, None);
+    let res = establish_connection(&sys_a, &communication_actor_a, peer_c_id, addr_c);
+    assert!(res.is_ok());
+    match send_request(&sys_a, &communication_actor_a, peer_c_id) {
+        Err(RequestMessageError::Outbound(P2POutboundFailure::UnsupportedProtocols)) => {}
+        _ => panic!("Request should fail with unsupported protocols."),
+    }
+}
+
+#[test]
+fn firewall_bypass() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let config_a = CommunicationActorConfig::new(client, FirewallPermission::none(), FirewallPermission::none());
+
+    let client = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let nodes = spawn_nodes(&sys, "firewall-bypass", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let peer_b_id = nodes[1].0;
+
+    match send_request(&sys, &communication_actor_a, peer_b_id) {
+        Err(RequestMessageError::Rejected(FirewallBlocked::Local)) => {}
+        _ => panic!("Local firewall should have blocked the request."),
+    }
+
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor_a,
+        RequestMsgBuilder::new(peer_b_id, Request::Ping)
+            .bypass_firewall(true)
+            .build(),
+    )) {
+        Some(CommunicationResults::RequestMsgResult(res)) => assert!(res.is_ok()),
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn connection_grace_period() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = sys
+        .actor_of_args::<ObserverActor, _>("observer", events.clone())
+        .expect("Failed to init actor.");
+    let mut config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    config_a.observer = Some(observer);
+    config_a.connection_grace_period = Some(Duration::from_secs(1));
+
+    let client = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let nodes = spawn_nodes(&sys, "connection-grace-period", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let peer_b_id = nodes[1].0;
+    let is_established = |event: &CommunicationEvent| matches!(event, CommunicationEvent::ConnectionEstablished { peer_id, .. } if *peer_id == peer_b_id);
+    // the event is only emitted once the connection survived the grace period
+    assert!(!events
+        .lock()
+        .expect("Failed to lock events.")
+        .iter()
+        .any(is_established));
+    wait_for_event(&events, is_established);
+}
+
+#[test]
+fn connection_authorizer() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = sys_a
+        .actor_of_args::<ObserverActor, _>("observer", events.clone())
+        .expect("Failed to init actor.");
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client_b = sys_b.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b) = init_system(&sys_b, client_b);
+
+    // deny connections of peer b
+    let authorizer: ConnectionAuthorizer =
+        Arc::new(move |peer_id: PeerId, _: ConnectedPoint| future::ready(peer_id != peer_b_id).boxed());
+    let keys = Keypair::generate_ed25519();
+    let peer_a_id = PeerId::from(keys.public());
+    let mut actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    actor_config.observer = Some(observer);
+    actor_config.connection_authorizer = Some(authorizer);
+    let communication_actor_a = sys_a
+        .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+            "communication",
+            (keys, actor_config, BehaviourConfig::default()),
+        )
+        .expect("Failed to init actor.");
+    let addr_a = start_listening(&sys_a, &communication_actor_a, None);
+
+    let _ = establish_connection(&sys_b, &communication_actor_b, peer_a_id, addr_a);
+    wait_for_event(
+        &events,
+        |event| matches!(event, CommunicationEvent::ConnectionDenied { peer_id } if *peer_id == peer_b_id),
+    );
+    assert!(!events
+        .lock()
+        .expect("Failed to lock events.")
+        .iter()
+        .any(|event| matches!(event, CommunicationEvent::ConnectionEstablished { .. })));
+}
+
+#[test]
+fn observe_disconnect() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = sys
+        .actor_of_args::<ObserverActor, _>("observer", events.clone())
+        .expect("Failed to init actor.");
+    let mut config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    config_a.observer = Some(observer);
+
+    let client = sys.actor_of::<BlankActor>("target").expect("Failed to init actor.");
+    let config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let nodes = spawn_nodes(&sys, "observe-disconnect", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let peer_b_id = nodes[1].0;
+
+    // banning the peer closes the connection
+    let res = task::block_on(try_ask(
+        &sys,
+        &communication_actor_a,
+        CommunicationRequest::BanPeer(peer_b_id),
+    ));
+    assert!(matches!(res, Some(CommunicationResults::BannedPeerAck(_))));
+    wait_for_event(&events, |event| match event {
+        CommunicationEvent::ConnectionClosed {
+            peer_id,
+            num_established,
+            ..
+        } => *peer_id == peer_b_id && *num_established == 0,
+        _ => false,
+    });
+}
+
+#[test]
+fn export_import_state() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_a) = init_system(&sys_a, client);
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b) = init_system(&sys_b, client);
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+
+    let res = establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b);
+    assert!(res.is_ok());
+    set_firewall_rule(
+        &sys_a,
+        &communication_actor_a,
+        peer_b_id,
+        RequestDirection::In,
+        FirewallPermission::none(),
+    );
+
+    let state = match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::ExportState,
+    )) {
+        Some(CommunicationResults::State(state)) => state,
+        _ => panic!("Unexpected Response"),
+    };
+    let peer_b = state
+        .peers
+        .iter()
+        .find(|peer| peer.peer_id == peer_b_id)
+        .expect("Missing state of peer b.");
+    assert_eq!(peer_b.keep_alive, Some(KeepAliveState::Unlimited));
+    assert_eq!(peer_b.firewall_in, Some(FirewallPermission::none()));
+
+    // the standby connects to the keep-alive peer of the imported state
+    let sys_c = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_c.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_c) = init_system(&sys_c, client);
+    match task::block_on(try_ask(
+        &sys_c,
+        &communication_actor_c,
+        CommunicationRequest::ImportState(state),
+    )) {
+        Some(CommunicationResults::ImportStateResult {
+            relay,
+            failed_connections,
+        }) => {
+            assert!(relay.is_ok());
+            assert!(failed_connections.is_empty());
+        }
+        _ => panic!("Unexpected Response"),
+    }
+    match task::block_on(try_ask(
+        &sys_c,
+        &communication_actor_c,
+        CommunicationRequest::CheckConnection(peer_b_id),
+    )) {
+        Some(CommunicationResults::CheckConnectionResult { peer_id: _, state }) => {
+            assert_eq!(state, ConnectionState::Connected)
+        }
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn probe_peer() {
+    let init_probing_system = |sys: &ActorSystem| {
+        let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+        let keys = Keypair::generate_ed25519();
+        let peer_id = PeerId::from(keys.public());
+        let mut behaviour_config = BehaviourConfig::default();
+        behaviour_config.set_probes(true);
+        // the firewall does not affect probes
+        let actor_config =
+            CommunicationActorConfig::new(client, FirewallPermission::none(), FirewallPermission::none());
+        let communication_actor = sys
+            .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+                "communication",
+                (keys, actor_config, behaviour_config),
+            )
+            .expect("Failed to init actor.");
+        (peer_id, communication_actor)
+    };
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let (_, communication_actor_a) = init_probing_system(&sys_a);
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let (peer_b_id, communication_actor_b) = init_probing_system(&sys_b);
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+
+    let probe = |peer_id, addr| match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::Probe { peer_id, addr },
+    )) {
+        Some(CommunicationResults::ProbeResult(res)) => res,
+        _ => panic!("Unexpected Response"),
+    };
+    let timings = probe(peer_b_id, addr_b.clone()).expect("Probe failed.");
+    assert!(timings.connect > Duration::from_secs(0));
+
+    let unreachable: Multiaddr = "/ip4/127.0.0.1/tcp/1".parse().expect("Invalid Multiaddress.");
+    assert!(matches!(probe(peer_b_id, unreachable), Err(ProbeError::Connect(_))));
+
+    // probes are disabled in the default config
+    let sys_c = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_c.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_c) = init_system(&sys_c, client);
+    match task::block_on(try_ask(
+        &sys_c,
+        &communication_actor_c,
+        CommunicationRequest::Probe {
+            peer_id: peer_b_id,
+            addr: addr_b,
+        },
+    )) {
+        Some(CommunicationResults::ProbeResult(res)) => assert!(matches!(res, Err(ProbeError::Disabled))),
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn ping_rtt() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let observer = sys_a
+        .actor_of_args::<ObserverActor, _>("observer", events.clone())
+        .expect("Failed to init actor.");
+    let keys = Keypair::generate_ed25519();
+    let mut behaviour_config = BehaviourConfig::default();
+    behaviour_config.set_probes(true);
+    let mut actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    actor_config.observer = Some(observer);
+    actor_config.rtt_threshold = Some(Duration::from_secs(0));
+    let communication_actor_a = sys_a
+        .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+            "communication",
+            (keys, actor_config, behaviour_config.clone()),
+        )
+        .expect("Failed to init actor.");
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let keys = Keypair::generate_ed25519();
+    let peer_b_id = PeerId::from(keys.public());
+    let actor_config = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    let communication_actor_b = sys_b
+        .actor_of_args::<CommunicationActor<_, Response, _, _>, _>(
+            "communication",
+            (keys, actor_config, behaviour_config),
+        )
+        .expect("Failed to init actor.");
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+    let res = establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b);
+    assert!(res.is_ok());
+
+    // each rtt exceeds the threshold of zero
+    wait_for_event(
+        &events,
+        |event| matches!(event, CommunicationEvent::HighLatency { peer_id, .. } if *peer_id == peer_b_id),
+    );
+    match task::block_on(try_ask(
+        &sys_a,
+        &communication_actor_a,
+        CommunicationRequest::GetSwarmInfo,
+    )) {
+        Some(CommunicationResults::SwarmInfo { connections, .. }) => {
+            let (_, connection) = connections
+                .iter()
+                .find(|(peer_id, _)| *peer_id == peer_b_id)
+                .expect("Missing connection.");
+            assert!(connection.rtt().is_some());
+        }
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn requests_during_connect() {
+    let sys_a = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_a.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor_a) = init_system(&sys_a, client);
+
+    let sys_b = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys_b.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let (peer_b_id, communication_actor_b) = init_system(&sys_b, client);
+    let addr_b = start_listening(&sys_b, &communication_actor_b, None);
+    let res = establish_connection(&sys_a, &communication_actor_a, peer_b_id, addr_b);
+    assert!(res.is_ok());
+    match task::block_on(try_ask(
+        &sys_b,
+        &communication_actor_b,
+        CommunicationRequest::SetProtocolTimeouts {
+            request_timeout: None,
+            connection_timeout: Some(Duration::from_secs(1)),
+        },
+    )) {
+        Some(CommunicationResults::SetProtocolTimeoutsAck) => {}
+        _ => panic!("Unexpected Response"),
+    }
+
+    // peer b is blocked while dialing an address that doesn't respond
+    let connecting = {
+        let sys_b = sys_b.clone();
+        let communication_actor_b = communication_actor_b.clone();
+        std::thread::spawn(move || {
+            let addr: Multiaddr = "/ip4/10.255.255.1/tcp/1".parse().expect("Invalid Multiaddress.");
+            establish_connection(&sys_b, &communication_actor_b, PeerId::random(), addr)
+        })
+    };
+    std::thread::sleep(Duration::from_millis(100));
+
+    // the request is answered once the connect completed
+    let res = send_request(&sys_a, &communication_actor_a, peer_b_id);
+    assert_eq!(res.expect("Request failed."), Response::Pong);
+    assert!(connecting.join().expect("Failed to join thread.").is_err());
+}
+
+#[test]
+fn inbound_queue_limit() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let client = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let mut config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    config_b.inbound_queue_limit = Some(0);
+    let nodes = spawn_nodes(&sys, "inbound-queue-limit", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let (peer_b_id, communication_actor_b) = nodes[1].clone();
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor_b,
+        CommunicationRequest::SetProtocolTimeouts {
+            request_timeout: None,
+            connection_timeout: Some(Duration::from_secs(1)),
+        },
+    )) {
+        Some(CommunicationResults::SetProtocolTimeoutsAck) => {}
+        _ => panic!("Unexpected Response"),
+    }
+
+    // peer b is blocked while dialing an address that doesn't respond
+    let connecting = {
+        let sys = sys.clone();
+        let communication_actor_b = communication_actor_b.clone();
+        std::thread::spawn(move || {
+            let addr: Multiaddr = "/ip4/10.255.255.1/tcp/1".parse().expect("Invalid Multiaddress.");
+            establish_connection(&sys, &communication_actor_b, PeerId::random(), addr)
+        })
+    };
+    std::thread::sleep(Duration::from_millis(100));
+
+    // the request is rejected since no requests may be queued
+    let res = send_request(&sys, &communication_actor_a, peer_b_id);
+    assert!(res.is_err());
+    assert!(connecting.join().expect("Failed to join thread.").is_err());
+
+    match task::block_on(try_ask(&sys, &communication_actor_b, CommunicationRequest::GetMetrics)) {
+        Some(CommunicationResults::Metrics(metrics)) => {
+            assert_eq!(metrics.inbound_rejected_busy, 1);
+            assert_eq!(metrics.inbound_requests, 0);
+        }
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn max_inbound_in_flight() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    // the client of peer b never responds, and no further requests may be queued while it handles one
+    let client = sys.actor_of::<BlankActor>("target").expect("Failed to init actor.");
+    let mut config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    config_b.max_inbound_in_flight = Some(1);
+    config_b.inbound_queue_limit = Some(0);
+    let nodes = spawn_nodes(&sys, "max-inbound-in-flight", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let (peer_b_id, communication_actor_b) = nodes[1].clone();
+    let get_metrics = || match task::block_on(try_ask(&sys, &communication_actor_b, CommunicationRequest::GetMetrics)) {
+        Some(CommunicationResults::Metrics(metrics)) => metrics,
+        _ => panic!("Unexpected Response"),
+    };
+
+    let pending = {
+        let sys = sys.clone();
+        let communication_actor_a = communication_actor_a.clone();
+        std::thread::spawn(move || {
+            task::block_on(try_ask(
+                &sys,
+                &communication_actor_a,
+                RequestMsgBuilder::new(peer_b_id, Request::Ping).build(),
+            ))
+        })
+    };
+    std::thread::sleep(Duration::from_millis(200));
+    // peer b is not blocked while the client handles the request
+    assert_eq!(get_metrics().inbound_in_flight, 1);
+
+    // the second request is rejected as busy right away
+    let start = Instant::now();
+    let res = send_request(&sys, &communication_actor_a, peer_b_id);
+    assert!(matches!(res, Err(RequestMessageError::Unavailable(RejectReason::Busy))));
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(get_metrics().inbound_rejected_busy, 1);
+
+    // the request is not in flight anymore once the client timed out
+    let _ = pending.join().expect("Failed to join thread.");
+    let start = Instant::now();
+    while get_metrics().inbound_in_flight > 0 {
+        assert!(start.elapsed() < Duration::from_secs(3), "Request is still in flight.");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn pause_requests() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let client = sys.actor_of::<ReplyActor>("target").expect("Failed to init actor.");
+    let config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let nodes = spawn_nodes(&sys, "pause-requests", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let (peer_b_id, communication_actor_b) = nodes[1].clone();
+
+    let pause = |sys, actor, queue_outbound| match task::block_on(try_ask(
+        sys,
+        actor,
+        CommunicationRequest::Pause { queue_outbound },
+    )) {
+        Some(CommunicationResults::PauseAck) => {}
+        _ => panic!("Unexpected Response"),
+    };
+    let resume = |sys, actor| match task::block_on(try_ask(sys, actor, CommunicationRequest::Resume)) {
+        Some(CommunicationResults::ResumeAck) => {}
+        _ => panic!("Unexpected Response"),
+    };
+
+    // outbound requests are rejected
+    pause(&sys, &communication_actor_a, false);
+    let res = send_request(&sys, &communication_actor_a, peer_b_id);
+    assert!(matches!(res, Err(RequestMessageError::Paused)));
+    resume(&sys, &communication_actor_a);
+
+    // inbound requests are rejected, while the connection remains
+    pause(&sys, &communication_actor_b, false);
+    let res = send_request(&sys, &communication_actor_a, peer_b_id);
+    assert!(matches!(
+        res,
+        Err(RequestMessageError::Unavailable(RejectReason::Paused))
+    ));
+    resume(&sys, &communication_actor_b);
+    let res = send_request(&sys, &communication_actor_a, peer_b_id);
+    assert_eq!(res.expect("Request failed."), Response::Pong);
+}
+
+#[test]
+fn listener_errors() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor) = init_system(&sys, client);
+    start_listening(&sys, &communication_actor, None);
+
+    // listeners that are removed locally are not reported as failure
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor,
+        CommunicationRequest::RemoveListener,
+    )) {
+        Some(CommunicationResults::RemoveListenerResult(res)) => assert!(res.is_ok()),
+        _ => panic!("Unexpected Response"),
+    }
+    std::thread::sleep(Duration::from_millis(100));
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor,
+        CommunicationRequest::GetListenerErrors,
+    )) {
+        Some(CommunicationResults::ListenerErrors(errors)) => assert!(errors.is_empty()),
+        _ => panic!("Unexpected Response"),
+    }
+}
+
+#[test]
+fn max_outbound_in_flight() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let mut config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    config_a.max_outbound_in_flight = Some(1);
+
+    // the client of peer b responds after 1s
+    let client = sys
+        .actor_of_args::<SlowActor, _>("slow", Duration::from_secs(1))
+        .expect("Failed to init actor.");
+    let config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let nodes = spawn_nodes(&sys, "max-outbound-in-flight", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let peer_b_id = nodes[1].0;
+    let set_timeout = |timeout| match task::block_on(try_ask(
+        &sys,
+        &communication_actor_a,
+        CommunicationRequest::SetProtocolTimeouts {
+            request_timeout: Some(timeout),
+            connection_timeout: None,
+        },
+    )) {
+        Some(CommunicationResults::SetProtocolTimeoutsAck) => {}
+        _ => panic!("Unexpected Response"),
+    };
+
+    // the request that timed out locally stays in flight until the late response is received
+    set_timeout(Duration::from_millis(300));
+    assert!(send_request(&sys, &communication_actor_a, peer_b_id).is_err());
+    assert!(matches!(
+        send_request(&sys, &communication_actor_a, peer_b_id),
+        Err(RequestMessageError::TooManyInFlight)
+    ));
+
+    std::thread::sleep(Duration::from_millis(1500));
+    match task::block_on(try_ask(&sys, &communication_actor_a, CommunicationRequest::GetMetrics)) {
+        Some(CommunicationResults::Metrics(metrics)) => assert_eq!(metrics.outbound_in_flight, 0),
+        _ => panic!("Unexpected Response"),
+    }
+    set_timeout(Duration::from_secs(2));
+    let res = send_request(&sys, &communication_actor_a, peer_b_id);
+    assert_eq!(res.expect("Request failed."), Response::Pong);
+}
+
+#[test]
+fn cancel_outbound_overflow_wait() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let mut config_a = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+    config_a.max_outbound_in_flight = Some(1);
+    config_a.outbound_overflow = OutboundOverflow::Wait;
+
+    // the client of b never responds
+    let client = sys.actor_of::<BlankActor>("target").expect("Failed to init actor.");
+    let config_b = CommunicationActorConfig::new(client, FirewallPermission::all(), FirewallPermission::all());
+
+    let nodes = spawn_nodes(&sys, "cancel-outbound-overflow-wait", vec![config_a, config_b]);
+    let (_, communication_actor_a) = nodes[0].clone();
+    let peer_b_id = nodes[1].0;
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor_a,
+        CommunicationRequest::SetProtocolTimeouts {
+            request_timeout: Some(Duration::from_millis(300)),
+            connection_timeout: None,
+        },
+    )) {
+        Some(CommunicationResults::SetProtocolTimeoutsAck) => {}
+        _ => panic!("Unexpected Response"),
+    }
+    // the request that timed out locally stays in flight
+    assert!(send_request(&sys, &communication_actor_a, peer_b_id).is_err());
+
+    let pending: future::RemoteHandle<CommunicationResults<Response>> = ask(
+        &sys,
+        &communication_actor_a,
+        RequestMsgBuilder::new(peer_b_id, Request::Ping)
+            .cancel_token(7)
+            .deadline(Instant::now() + Duration::from_secs(5))
+            .build(),
+    );
+    std::thread::sleep(Duration::from_millis(200));
+
+    // queries are answered while the request waits for capacity
+    match task::block_on(try_ask(&sys, &communication_actor_a, CommunicationRequest::GetMetrics)) {
+        Some(CommunicationResults::Metrics(metrics)) => assert_eq!(metrics.outbound_in_flight, 1),
+        _ => panic!("Unexpected Response"),
+    }
+    let start = Instant::now();
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor_a,
+        CommunicationRequest::CancelRequest(7),
+    )) {
+        Some(CommunicationResults::CancelRequestResult(is_cancelled)) => assert!(is_cancelled),
+        _ => panic!("Unexpected Response"),
+    }
+    match task::block_on(pending) {
+        CommunicationResults::RequestMsgResult(res) => assert!(matches!(res, Err(RequestMessageError::Cancelled))),
+        _ => panic!("Unexpected Response"),
+    }
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn stale_pending_dial() {
+    let sys = ActorSystem::new().expect("Failed to create actor system.");
+    let client = sys.actor_of::<BlankActor>("blank").expect("Failed to init actor.");
+    let (_, communication_actor) = init_system(&sys, client);
+    match task::block_on(try_ask(
+        &sys,
+        &communication_actor,
+        CommunicationRequest::SetProtocolTimeouts {
+            request_timeout: None,
+            connection_timeout: Some(Duration::from_millis(300)),
+        },
+    )) {
+        Some(CommunicationResults::SetProtocolTimeoutsAck) => {}
+        _ => panic!("Unexpected Response"),
+    }
+
+    // the address accepts the tcp connection, but never completes the handshake
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind listener.");
+    let port = listener.local_addr().unwrap().port();
+    let stalled_addr: Multiaddr = format!("/ip4/127.0.0.1/tcp/{}", port).parse().unwrap();
+    let res = establish_connection(&sys, &communication_actor, PeerId::random(), stalled_addr);
+    assert!(matches!(res, Err(ConnectPeerError::Timeout)));
+
+    // the dial that timed out is not awaited by later requests
+    match task::block_on(try_ask(&sys, &communication_actor, CommunicationRequest::DumpState)) {
+        Some(CommunicationResults::StateDump(dump)) => assert!(dump.pending_dials.is_empty()),
+        _ => panic!("Unexpected Response"),
+    }
 }
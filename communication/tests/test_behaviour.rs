@@ -4,14 +4,21 @@
 use async_std::task;
 use communication::{
     behaviour::{
-        BehaviourConfig, MessageEvent, P2PEvent, P2PIdentifyEvent, P2PNetworkBehaviour, P2PReqResEvent, RequestEnvelope,
+        multiaddr_to_socket_addr, BehaviourConfig, BehaviourError, DnsResolverConfig, MessageEvent, MultiplexConfig,
+        P2PEvent, P2PIdentifyEvent, P2PNetworkBehaviour, P2PReqResEvent, RequestEnvelope, TcpKeepaliveConfig,
+        WebsocketConfig, MIN_RECEIVE_WINDOW_SIZE,
     },
     libp2p::{Keypair, Multiaddr, PeerId, Protocol, Swarm, SwarmEvent},
 };
 use core::{ops::Deref, str::FromStr, time::Duration};
 use futures::future;
 use serde::{Deserialize, Serialize};
-use std::net::Ipv4Addr;
+use std::{
+    collections::BTreeMap,
+    net::{Ipv4Addr, TcpStream, UdpSocket},
+    thread,
+    time::Instant,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Request {
@@ -108,6 +115,172 @@ fn new_behaviour() {
     assert!(swarm.get_all_peers().is_empty());
 }
 
+#[test]
+fn websocket_disabled() {
+    let local_keys = Keypair::generate_ed25519();
+    let mut config = BehaviourConfig::default();
+    config.set_websocket(WebsocketConfig::Disabled);
+    let mut swarm = task::block_on(P2PNetworkBehaviour::<Empty, Empty>::init_swarm(local_keys, config))
+        .expect("Failed to init swarm.");
+    let ws_addr: Multiaddr = "/ip4/127.0.0.1/tcp/0/ws".parse().expect("Invalid Multiaddress.");
+    assert!(Swarm::listen_on(&mut swarm, ws_addr).is_err());
+    assert!(Swarm::listen_on(&mut swarm, mock_addr()).is_ok());
+}
+
+#[test]
+fn multiplex_limits() {
+    let mut swarm_a = mock_swarm::<Empty, Empty>();
+    let peer_a_id = *Swarm::local_peer_id(&swarm_a);
+    Swarm::listen_on(&mut swarm_a, mock_addr()).expect("Listening to swarm failed.");
+    let addr_a = start_listening(&mut swarm_a).expect("Start listening failed.");
+    task::spawn(async move {
+        loop {
+            swarm_a.next_event().await;
+        }
+    });
+
+    let mut config = BehaviourConfig::default();
+    config.set_multiplex(MultiplexConfig {
+        max_substreams: Some(16),
+        receive_window_size: Some(1024 * 1024),
+        max_buffer_size: Some(2 * 1024 * 1024),
+    });
+    let mut swarm_b = task::block_on(P2PNetworkBehaviour::<Empty, Empty>::init_swarm(
+        Keypair::generate_ed25519(),
+        config,
+    ))
+    .expect("Failed to init swarm.");
+    establish_connection(peer_a_id, addr_a, &mut swarm_b).expect("Failed to establish a connection.");
+
+    // a receive window below the minimum of yamux is rejected
+    let mut config = BehaviourConfig::default();
+    config.set_multiplex(MultiplexConfig {
+        receive_window_size: Some(MIN_RECEIVE_WINDOW_SIZE - 1),
+        ..Default::default()
+    });
+    let res = task::block_on(P2PNetworkBehaviour::<Empty, Empty>::init_swarm(
+        Keypair::generate_ed25519(),
+        config,
+    ));
+    assert!(matches!(res, Err(BehaviourError::InvalidMultiplexConfig(_))));
+}
+
+// Minimal dns server that answers each query for an A record with the address 127.0.0.1.
+fn stub_nameserver() -> UdpSocket {
+    let socket = UdpSocket::bind("127.0.0.1:0").expect("Failed to bind socket.");
+    let server = socket.try_clone().expect("Failed to clone socket.");
+    thread::spawn(move || {
+        let mut buf = [0u8; 512];
+        while let Ok((len, src)) = server.recv_from(&mut buf) {
+            // skip the labels of the queried name, followed by the type and class of the question
+            let mut end = 12;
+            while end < len && buf[end] != 0 {
+                end += buf[end] as usize + 1;
+            }
+            end += 5;
+            if end > len {
+                continue;
+            }
+            let is_a_record = buf[end - 4..end - 2] == [0, 1];
+            let mut res = buf[..end].to_vec();
+            // response flags, one question, and one answer if an A record was queried
+            res[2..12].copy_from_slice(&[0x81, 0x80, 0, 1, 0, is_a_record as u8, 0, 0, 0, 0]);
+            if is_a_record {
+                // pointer to the queried name, type A, class IN, ttl of 60s and the address
+                res.extend_from_slice(&[0xc0, 0x0c, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 127, 0, 0, 1]);
+            }
+            let _ = server.send_to(&res, src);
+        }
+    });
+    socket
+}
+
+#[test]
+fn custom_dns_resolver() {
+    let mut swarm_a = mock_swarm::<Empty, Empty>();
+    let peer_a_id = *Swarm::local_peer_id(&swarm_a);
+    Swarm::listen_on(&mut swarm_a, mock_addr()).expect("Listening to swarm failed.");
+    let addr_a = start_listening(&mut swarm_a).expect("Start listening failed.");
+    let port = multiaddr_to_socket_addr(&addr_a).expect("Invalid address.").port();
+    task::spawn(async move {
+        loop {
+            swarm_a.next_event().await;
+        }
+    });
+
+    let nameserver = stub_nameserver();
+    let mut config = BehaviourConfig::default();
+    config.set_dns_resolver(DnsResolverConfig::Custom {
+        nameservers: vec![nameserver.local_addr().unwrap()],
+    });
+    let mut swarm_b = task::block_on(P2PNetworkBehaviour::<Empty, Empty>::init_swarm(
+        Keypair::generate_ed25519(),
+        config,
+    ))
+    .expect("Failed to init swarm.");
+    let dns_addr: Multiaddr = format!("/dns4/peer-a.stronghold.test/tcp/{}", port)
+        .parse()
+        .expect("Invalid Multiaddress.");
+    establish_connection(peer_a_id, dns_addr, &mut swarm_b).expect("Failed to establish a connection.");
+}
+
+#[test]
+fn tcp_keepalive() {
+    let mut config = BehaviourConfig::default();
+    config.set_tcp_keepalive(TcpKeepaliveConfig {
+        idle: Duration::from_secs(30),
+        interval: Some(Duration::from_secs(5)),
+        count: Some(3),
+    });
+    let mut swarm_a = task::block_on(P2PNetworkBehaviour::<Empty, Empty>::init_swarm(
+        Keypair::generate_ed25519(),
+        config.clone(),
+    ))
+    .expect("Failed to init swarm.");
+    let peer_a_id = *Swarm::local_peer_id(&swarm_a);
+    Swarm::listen_on(&mut swarm_a, mock_addr()).expect("Listening to swarm failed.");
+    let addr_a = start_listening(&mut swarm_a).expect("Start listening failed.");
+    task::spawn(async move {
+        loop {
+            swarm_a.next_event().await;
+        }
+    });
+
+    let mut swarm_b = task::block_on(P2PNetworkBehaviour::<Empty, Empty>::init_swarm(
+        Keypair::generate_ed25519(),
+        config,
+    ))
+    .expect("Failed to init swarm.");
+    establish_connection(peer_a_id, addr_a, &mut swarm_b).expect("Failed to establish a connection.");
+}
+
+#[test]
+fn handshake_timeout() {
+    let mut config = BehaviourConfig::default();
+    config.set_handshake_timeout(Duration::from_millis(500));
+    let mut swarm = task::block_on(P2PNetworkBehaviour::<Empty, Empty>::init_swarm(
+        Keypair::generate_ed25519(),
+        config,
+    ))
+    .expect("Failed to init swarm.");
+    Swarm::listen_on(&mut swarm, mock_addr()).expect("Listening to swarm failed.");
+    let addr = start_listening(&mut swarm).expect("Start listening failed.");
+
+    // open a plain tcp connection that never starts the handshake
+    let socket_addr = multiaddr_to_socket_addr(&addr).expect("Invalid address.");
+    let start = Instant::now();
+    let _stream = TcpStream::connect(socket_addr).expect("Failed to connect.");
+    let res = task::block_on(async_std::future::timeout(Duration::from_secs(5), async {
+        loop {
+            if let SwarmEvent::IncomingConnectionError { .. } = swarm.next_event().await {
+                return;
+            }
+        }
+    }));
+    assert!(res.is_ok(), "The stalled connection was not dropped.");
+    assert!(start.elapsed() >= Duration::from_millis(500));
+}
+
 #[test]
 fn add_peer() {
     let mut swarm = mock_swarm::<Empty, Empty>();
@@ -343,6 +516,112 @@ fn identify_event() {
     a.and(b).expect("Invalid event received from swarm.");
 }
 
+#[test]
+fn identify_metadata() {
+    let mut metadata = BTreeMap::new();
+    metadata.insert("build".to_string(), "42".to_string());
+    let mut config = BehaviourConfig::default();
+    config.set_identify_metadata(metadata);
+    let res = task::block_on(P2PNetworkBehaviour::<Empty, Empty>::init_swarm(
+        Keypair::generate_ed25519(),
+        config,
+    ));
+    assert!(res.is_ok());
+
+    // entries that can not be encoded in the agent version are rejected
+    for (key, value) in [("", "42"), ("build=id", "42"), ("build", "4;2")].iter() {
+        let mut metadata = BTreeMap::new();
+        metadata.insert(key.to_string(), value.to_string());
+        let mut config = BehaviourConfig::default();
+        config.set_identify_metadata(metadata);
+        let res = task::block_on(P2PNetworkBehaviour::<Empty, Empty>::init_swarm(
+            Keypair::generate_ed25519(),
+            config,
+        ));
+        assert!(matches!(res, Err(BehaviourError::InvalidIdentifyMetadata(_))));
+    }
+}
+
+fn mdns_swarm(service_name: &str) -> Swarm<P2PNetworkBehaviour<Empty, Empty>> {
+    let local_keys = Keypair::generate_ed25519();
+    let mut config = BehaviourConfig::default();
+    config
+        .set_mdns_query_interval(Duration::from_secs(1))
+        .set_mdns_service_name(service_name.to_string());
+    let mut swarm = task::block_on(P2PNetworkBehaviour::init_swarm(local_keys, config)).expect("Failed to init swarm.");
+    Swarm::listen_on(&mut swarm, mock_addr()).expect("Listening to swarm failed.");
+    start_listening(&mut swarm).expect("Start listening failed.");
+    swarm
+}
+
+// Drive the swarms for the duration, or until the condition is met. Connections to peers of a different mDNS service
+// are closed the same way as in the communication actor.
+fn drive_swarms(
+    swarms: &mut [&mut Swarm<P2PNetworkBehaviour<Empty, Empty>>; 3],
+    duration: Duration,
+    condition: impl Fn(&mut [&mut Swarm<P2PNetworkBehaviour<Empty, Empty>>; 3]) -> bool,
+) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < duration {
+        if condition(swarms) {
+            return true;
+        }
+        let [a, b, c] = swarms;
+        let _ = task::block_on(async_std::future::timeout(
+            Duration::from_millis(50),
+            future::join3(a.next_event(), b.next_event(), c.next_event()),
+        ));
+        for swarm in swarms.iter_mut() {
+            for peer_id in swarm.take_mdns_mismatches() {
+                if Swarm::is_connected(swarm, &peer_id) {
+                    Swarm::ban_peer_id(swarm, peer_id);
+                    Swarm::unban_peer_id(swarm, peer_id);
+                }
+            }
+        }
+    }
+    condition(swarms)
+}
+
+#[test]
+fn mdns_service_name() {
+    let mut swarm_a = mdns_swarm("mdns-service-name-test-a");
+    let peer_a_id = *Swarm::local_peer_id(&swarm_a);
+    let mut swarm_b = mdns_swarm("mdns-service-name-test-a");
+    let peer_b_id = *Swarm::local_peer_id(&swarm_b);
+    let mut swarm_c = mdns_swarm("mdns-service-name-test-c");
+    let peer_c_id = *Swarm::local_peer_id(&swarm_c);
+    let mut swarms = [&mut swarm_a, &mut swarm_b, &mut swarm_c];
+
+    // nodes with the same service name discover each other
+    let discovered = drive_swarms(&mut swarms, Duration::from_secs(20), |[a, b, _]| {
+        a.get_active_mdns_peers().contains(&&peer_b_id) && b.get_active_mdns_peers().contains(&&peer_a_id)
+    });
+    assert!(discovered);
+    assert!(swarm_a.get_peer_addr(&peer_b_id).is_some());
+
+    // nodes with a different service name are ignored
+    let mut swarms = [&mut swarm_a, &mut swarm_b, &mut swarm_c];
+    let discovered = drive_swarms(&mut swarms, Duration::from_secs(3), |[a, b, c]| {
+        a.get_active_mdns_peers().contains(&&peer_c_id)
+            || b.get_active_mdns_peers().contains(&&peer_c_id)
+            || c.get_active_mdns_peers().contains(&&peer_a_id)
+            || c.get_active_mdns_peers().contains(&&peer_b_id)
+    });
+    assert!(!discovered);
+    assert!(swarm_a.get_peer_addr(&peer_c_id).is_none());
+
+    // the connections to the node with a different service name are closed
+    let mut swarms = [&mut swarm_a, &mut swarm_b, &mut swarm_c];
+    let disconnected = drive_swarms(&mut swarms, Duration::from_secs(10), |[a, b, c]| {
+        !Swarm::is_connected(a, &peer_c_id)
+            && !Swarm::is_connected(b, &peer_c_id)
+            && !Swarm::is_connected(c, &peer_a_id)
+            && !Swarm::is_connected(c, &peer_b_id)
+    });
+    assert!(disconnected);
+}
+
 #[test]
 fn relay() {
     let mut swarm = mock_swarm::<RequestEnvelope<Request>, Response>();
@@ -410,6 +689,8 @@ fn relay() {
             source: peer_a_id.to_string(),
             message: Request::Ping,
             target: peer_b_id.to_string(),
+            expires_at: None,
+            hops_remaining: None,
         };
         swarm_a.send_request(&relay_peer_id, envelope);
         loop {
@@ -443,6 +724,7 @@ fn relay() {
                                 source,
                                 message: _,
                                 target,
+                                ..
                             },
                     } => {
                         assert_eq!(peer_id, relay_peer_id);